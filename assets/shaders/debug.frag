@@ -0,0 +1,10 @@
+#version 430 core
+
+in vec4 v4Color;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    Out_v4Color = v4Color;
+}
@@ -0,0 +1,15 @@
+#version 430 core
+
+uniform mat4 View;
+uniform mat4 Projection;
+
+layout (location = 0) in vec3 In_v3Pos;
+layout (location = 1) in vec4 In_v4Color;
+
+out vec4 v4Color;
+
+void main()
+{
+    gl_Position = Projection * View * vec4(In_v3Pos, 1);
+    v4Color = In_v4Color;
+}
@@ -0,0 +1,54 @@
+#version 430 core
+
+#extension GL_ARB_shader_storage_buffer_object : require
+
+struct Particle {
+    vec4 PositionAge;      // xyz position, w age in seconds
+    vec4 VelocityLifetime; // xyz velocity, w lifetime in seconds
+};
+
+layout (std430, binding = 4) readonly buffer Particles
+{
+    Particle Ps[];
+};
+
+uniform mat4 View;
+uniform mat4 Projection;
+// Curves over life baked into evenly spaced samples, sizes packed four per vector
+const int LIFE_SAMPLES = 16;
+uniform vec4 ColorOverLife[LIFE_SAMPLES];
+uniform vec4 SizeOverLife[LIFE_SAMPLES / 4];
+
+out block {
+    vec4 v4Color;
+    vec2 v2Local;
+} Out;
+
+float sizeAt(int i)
+{
+    return SizeOverLife[i / 4][i % 4];
+}
+
+void main()
+{
+    // Two triangles per particle, no vertex buffer required
+    const vec2 corners[6] = vec2[6](vec2(-0.5, -0.5), vec2(0.5, -0.5), vec2(0.5, 0.5),
+                                    vec2(0.5, 0.5), vec2(-0.5, 0.5), vec2(-0.5, -0.5));
+    vec2 corner = corners[gl_VertexID];
+    Particle particle = Ps[gl_InstanceID];
+
+    float life = clamp(particle.PositionAge.w / particle.VelocityLifetime.w, 0.0, 1.0) * float(LIFE_SAMPLES - 1);
+    int below = min(int(life), LIFE_SAMPLES - 2);
+    float t = life - float(below);
+    vec4 color = mix(ColorOverLife[below], ColorOverLife[below + 1], t);
+    float size = mix(sizeAt(below), sizeAt(below + 1), t);
+
+    // The view matrix rows are the camera axes in world space
+    vec3 right = vec3(View[0][0], View[1][0], View[2][0]);
+    vec3 up = vec3(View[0][1], View[1][1], View[2][1]);
+    vec3 position = particle.PositionAge.xyz + (right * corner.x + up * corner.y) * size;
+
+    gl_Position = Projection * View * vec4(position, 1);
+    Out.v4Color = color;
+    Out.v2Local = corner * 2.0;
+}
@@ -0,0 +1,22 @@
+// Cube map helpers for compute shaders writing cube maps as layered images.
+#ifndef CUBE_GLSL
+#define CUBE_GLSL
+
+// Direction through the center of `texel` on `face` of a cube map `size` texels wide, faces in the order of
+// GL_TEXTURE_CUBE_MAP_POSITIVE_X onwards.
+vec3 cubeDirection(ivec2 texel, int face, int size)
+{
+    vec2 st = (vec2(texel) + 0.5) / float(size) * 2.0 - 1.0;
+    vec3 direction;
+    switch (face) {
+        case 0: direction = vec3(1.0, -st.y, -st.x); break;
+        case 1: direction = vec3(-1.0, -st.y, st.x); break;
+        case 2: direction = vec3(st.x, 1.0, st.y); break;
+        case 3: direction = vec3(st.x, -1.0, -st.y); break;
+        case 4: direction = vec3(st.x, -st.y, 1.0); break;
+        default: direction = vec3(-st.x, -st.y, -1.0); break;
+    }
+    return normalize(direction);
+}
+
+#endif
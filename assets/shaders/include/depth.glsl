@@ -0,0 +1,33 @@
+// Depth buffer helpers matching the projections built by gfx::Camera, with and without reverse-Z.
+// Mirrored on the CPU by gfx::depth, keep the two in sync.
+
+// Depth buffer value -> NDC z. Standard depth maps NDC [-1, 1] to [0, 1], reverse-Z stores NDC as is.
+float depthToNdc(float depth, bool reverseZ)
+{
+    return reverseZ ? depth : depth * 2.0 - 1.0;
+}
+
+// NDC z -> depth buffer value, e.g. to compare against a depth texture.
+float ndcToDepth(float ndc, bool reverseZ)
+{
+    return reverseZ ? ndc : ndc * 0.5 + 0.5;
+}
+
+// Depth buffer value -> positive view space distance along the view axis.
+// `far` is ignored with reverse-Z, which always uses an infinite far plane.
+float linearizeDepth(float depth, float near, float far, bool reverseZ)
+{
+    float ndc = depthToNdc(depth, reverseZ);
+    if (reverseZ) {
+        return near / max(ndc, 1e-7);
+    }
+    return (far * near) / (far - ndc * (far - near));
+}
+
+// Screen uv in [0, 1] and depth buffer value -> view space position.
+vec3 viewPositionFromDepth(vec2 uv, float depth, mat4 inverseProjection, bool reverseZ)
+{
+    vec4 ndc = vec4(uv * 2.0 - 1.0, depthToNdc(depth, reverseZ), 1.0);
+    vec4 view = inverseProjection * ndc;
+    return view.xyz / view.w;
+}
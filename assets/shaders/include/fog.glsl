@@ -0,0 +1,31 @@
+// Fog with the parameters of the FrameBlock. Mirrored on the CPU by gfx::Fog, keep the two in sync.
+#ifndef FOG_GLSL
+#define FOG_GLSL
+
+#include "frame.glsl"
+
+// Fraction of a surface `distance` away still visible through the fog, 1 without fog.
+float fogFactor(float distance)
+{
+    int mode = int(FogParams.x);
+    if (mode == 1) {
+        return clamp((FogParams.w - distance) / max(FogParams.w - FogParams.z, 0.0001), 0.0, 1.0);
+    }
+    if (mode == 2) {
+        return exp(-FogParams.y * distance);
+    }
+    if (mode == 3) {
+        float d = FogParams.y * distance;
+        return exp(-d * d);
+    }
+    return 1.0;
+}
+
+// Fade `color` of a surface at `worldPos` towards the fog color by its distance from the camera.
+vec3 applyFog(vec3 color, vec3 worldPos)
+{
+    float distance = length(worldPos - CameraPosition.xyz);
+    return mix(FogColor.rgb, color, fogFactor(distance));
+}
+
+#endif
@@ -0,0 +1,13 @@
+// Values shared by every program for the view being drawn, uploaded by gfx::FrameUniforms.
+// Keep in sync with GpuFrame in gfx/frame.rs.
+#ifndef FRAME_GLSL
+#define FRAME_GLSL
+
+layout (std140, binding = 0) uniform FrameBlock
+{
+    vec4 CameraPosition; // xyz world position, w unused
+    vec4 FogColor;       // rgb linear color, a unused
+    vec4 FogParams;      // x mode (0 none, 1 linear, 2 exp, 3 exp2), y density, z linear start, w linear end
+};
+
+#endif
@@ -0,0 +1,43 @@
+// GGX importance sampling, shared by the environment prefiltering passes of gfx::EnvironmentMap.
+#ifndef GGX_GLSL
+#define GGX_GLSL
+
+const float PI = 3.14159265359;
+
+// Point `i` of an `n` point Hammersley set, evenly spread over the unit square.
+vec2 hammersley(uint i, uint n)
+{
+    uint bits = bitfieldReverse(i);
+    return vec2(float(i) / float(n), float(bits) * 2.3283064365386963e-10);
+}
+
+// Half vector around `normal` for the point `xi` on the unit square, distributed like GGX with `roughness`.
+vec3 importanceSampleGgx(vec2 xi, vec3 normal, float roughness)
+{
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+    vec3 h = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + normal * h.z);
+}
+
+float distributionGgx(float nDotH, float roughness)
+{
+    float a2 = roughness * roughness * roughness * roughness;
+    float d = nDotH * nDotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith shadowing-masking with Schlick-GGX, remapped for image-based lighting.
+float geometrySmith(float nDotV, float nDotL, float roughness)
+{
+    float k = roughness * roughness / 2.0;
+    return nDotV / (nDotV * (1.0 - k) + k) * nDotL / (nDotL * (1.0 - k) + k);
+}
+
+#endif
@@ -0,0 +1,35 @@
+// Image-based lighting from a gfx::EnvironmentMap, whose `apply` binds its maps and sets the uniforms. EnvIntensity
+// stays 0 in programs it was never applied to, so the environment adds nothing.
+#ifndef IBL_GLSL
+#define IBL_GLSL
+
+#include "frame.glsl"
+
+// Units fixed to gfx::environment's IRRADIANCE_UNIT, SPECULAR_UNIT and BRDF_UNIT
+layout (binding = 4) uniform samplerCube EnvIrradiance;
+layout (binding = 5) uniform samplerCube EnvSpecular;
+layout (binding = 6) uniform sampler2D EnvBrdf;
+uniform float EnvSpecularMaxLevel;
+uniform float EnvIntensity;
+
+// Light the environment reflects towards the camera off a surface at `worldPos`, diffuse and specular, with the
+// split-sum approximation.
+vec3 environmentLighting(vec3 albedo, vec3 worldPos, vec3 normal, float roughness, float metallic)
+{
+    vec3 toCamera = normalize(CameraPosition.xyz - worldPos);
+    float nDotV = max(dot(normal, toCamera), 0.0001);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+
+    // Fresnel-Schlick, damped on rough surfaces so they don't get bright rims
+    vec3 fresnel = f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - nDotV, 5.0);
+    vec3 diffuse = texture(EnvIrradiance, normal).rgb * albedo * (1.0 - fresnel) * (1.0 - metallic);
+
+    vec3 reflected = reflect(-toCamera, normal);
+    vec3 prefiltered = textureLod(EnvSpecular, reflected, roughness * EnvSpecularMaxLevel).rgb;
+    vec2 brdf = texture(EnvBrdf, vec2(nDotV, roughness)).rg;
+    vec3 specular = prefiltered * (f0 * brdf.x + brdf.y);
+
+    return (diffuse + specular) * EnvIntensity;
+}
+
+#endif
@@ -0,0 +1,19 @@
+#version 430 core
+
+// What the mirror sees, rendered from the reflected camera into a target the size of the viewport
+layout (binding = 2) uniform sampler2D SecondaryView;
+// Bottom left corner of the viewport being drawn, in framebuffer pixels
+uniform vec2 ViewOrigin;
+
+in block {
+    vec4 v4Color;
+} In;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    // The reflection lines up with the mirror on screen, so it's sampled where the fragment is
+    vec2 uv = (gl_FragCoord.xy - ViewOrigin) / vec2(textureSize(SecondaryView, 0));
+    Out_v4Color = vec4(texture(SecondaryView, uv).rgb * In.v4Color.rgb, 1.0);
+}
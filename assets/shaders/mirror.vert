@@ -0,0 +1,25 @@
+#version 430 core
+
+#extension GL_ARB_shader_storage_buffer_object : require
+
+layout (std140, binding = 0) buffer CB0
+{
+    mat4 Transforms[];
+};
+
+uniform mat4 View;
+uniform mat4 Projection;
+
+layout (location = 0) in vec3 In_v3Pos;
+layout (location = 1) in vec4 In_v4Color;
+layout (location = 2) in uint In_iDrawID;
+
+out block {
+    vec4 v4Color;
+} Out;
+
+void main()
+{
+    gl_Position = Projection * View * Transforms[In_iDrawID] * vec4(In_v3Pos, 1);
+    Out.v4Color = In_v4Color;
+}
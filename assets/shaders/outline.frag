@@ -0,0 +1,10 @@
+#version 430 core
+
+uniform vec4 OutlineColor;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    Out_v4Color = OutlineColor;
+}
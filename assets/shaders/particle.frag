@@ -0,0 +1,19 @@
+#version 430 core
+
+in block {
+    vec4 v4Color;
+    vec2 v2Local;
+} In;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    // Round, soft-edged sprite from the quad's corners at distance sqrt(2)
+    float falloff = 1.0 - smoothstep(0.5, 1.0, length(In.v2Local));
+    if (falloff <= 0.0) {
+        discard;
+    }
+
+    Out_v4Color = vec4(In.v4Color.rgb, In.v4Color.a * falloff);
+}
@@ -0,0 +1,34 @@
+#version 430 core
+
+#extension GL_ARB_shader_storage_buffer_object : require
+
+layout (std140, binding = 0) buffer CB0
+{
+    mat4 Transforms[];
+};
+
+uniform mat4 View;
+uniform mat4 Projection;
+
+layout (location = 0) in vec3 In_v3Pos;
+layout (location = 2) in uint In_iDrawID;
+
+out block {
+    vec4 v4Color;
+    vec2 v2Local;
+} Out;
+
+void main()
+{
+    mat4 World = Transforms[In_iDrawID];
+
+    // The bottom row carries the particle's color instead of projective terms, see `gfx::particle`
+    Out.v4Color = vec4(World[0][3], World[1][3], World[2][3], World[3][3]);
+    World[0][3] = 0;
+    World[1][3] = 0;
+    World[2][3] = 0;
+    World[3][3] = 1;
+
+    gl_Position = Projection * View * World * vec4(In_v3Pos, 1);
+    Out.v2Local = In_v3Pos.xy * 2.0;
+}
@@ -0,0 +1,14 @@
+#version 430 core
+
+in block {
+    vec2 v2TexCoord;
+} In;
+
+uniform sampler2D Scene;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    Out_v4Color = texture(Scene, In.v2TexCoord);
+}
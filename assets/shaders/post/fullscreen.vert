@@ -0,0 +1,14 @@
+#version 430 core
+
+out block {
+    vec2 v2TexCoord;
+} Out;
+
+// Oversized triangle covering the whole screen, no vertex buffer required
+void main()
+{
+    vec2 pos = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+
+    Out.v2TexCoord = pos;
+}
@@ -0,0 +1,60 @@
+#version 430 core
+
+in block {
+    vec2 v2TexCoord;
+} In;
+
+uniform sampler2D Scene;
+uniform vec2 InverseScreenSize;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_SPAN_MAX   8.0
+
+void main()
+{
+    vec2 uv = In.v2TexCoord;
+
+    vec3 rgbNW = texture(Scene, uv + vec2(-1.0, -1.0) * InverseScreenSize).rgb;
+    vec3 rgbNE = texture(Scene, uv + vec2( 1.0, -1.0) * InverseScreenSize).rgb;
+    vec3 rgbSW = texture(Scene, uv + vec2(-1.0,  1.0) * InverseScreenSize).rgb;
+    vec3 rgbSE = texture(Scene, uv + vec2( 1.0,  1.0) * InverseScreenSize).rgb;
+    vec4 rgbaM = texture(Scene, uv);
+
+    const vec3 luma = vec3(0.299, 0.587, 0.114);
+    float lumaNW = dot(rgbNW, luma);
+    float lumaNE = dot(rgbNE, luma);
+    float lumaSW = dot(rgbSW, luma);
+    float lumaSE = dot(rgbSE, luma);
+    float lumaM  = dot(rgbaM.rgb, luma);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // Blur direction runs along the edge, perpendicular to the luma gradient
+    vec2 dir = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+         ((lumaNW + lumaSW) - (lumaNE + lumaSE))
+    );
+
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * (0.25 * FXAA_REDUCE_MUL), FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * InverseScreenSize;
+
+    vec3 rgbA = 0.5 * (
+        texture(Scene, uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(Scene, uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(Scene, uv + dir * -0.5).rgb +
+        texture(Scene, uv + dir *  0.5).rgb);
+
+    // The wider sample may have crossed onto another edge, fall back to the narrow one
+    float lumaB = dot(rgbB, luma);
+    if (lumaB < lumaMin || lumaB > lumaMax) {
+        Out_v4Color = vec4(rgbA, rgbaM.a);
+    } else {
+        Out_v4Color = vec4(rgbB, rgbaM.a);
+    }
+}
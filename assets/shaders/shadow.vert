@@ -0,0 +1,19 @@
+#version 430 core
+
+#extension GL_ARB_shader_storage_buffer_object : require
+
+layout (std140, binding = 0) buffer CB0
+{
+    mat4 Transforms[];
+};
+
+uniform mat4 LightSpace;
+
+layout (location = 0) in vec3 In_v3Pos;
+layout (location = 2) in uint In_iDrawID;
+
+void main()
+{
+    mat4 World = Transforms[In_iDrawID];
+    gl_Position = LightSpace * World * vec4(In_v3Pos, 1);
+}
@@ -1,12 +1,131 @@
 #version 430 core
 
+#extension GL_ARB_shader_storage_buffer_object : require
+
+#include "include/depth.glsl"
+#include "include/fog.glsl"
+#include "include/ibl.glsl"
+
+struct Light {
+    vec4 PositionType;   // xyz position, w type (0 directional, 1 point, 2 spot)
+    vec4 DirectionRange; // xyz direction, w range
+    vec4 ColorIntensity; // rgb color, a intensity
+    vec4 Cone;           // x cos(inner angle), y cos(outer angle)
+};
+
+layout (std430, binding = 1) readonly buffer LightBlock
+{
+    vec4 Ambient;
+    uvec4 LightCount;
+    Light Lights[];
+};
+
+uniform sampler2DShadow ShadowMap;
+uniform float ShadowBias;
+uniform int ShadowPcfRadius;
+uniform int ShadowReverseZ;
+
+// Surface response to the environment, see include/ibl.glsl
+uniform float Roughness;
+uniform float Metallic;
+
 in block {
-    vec3 v3Color;
+    vec4 v4Color;
+    vec3 v3WorldPos;
+    vec3 v3Normal;
+    vec4 v4LightSpacePos;
 } In;
 
 layout (location = 0) out vec4 Out_v4Color;
 
+// Fraction of the PCF kernel around the fragment that the shadow map sees as lit
+float shadowFactor(vec3 normal, vec3 toLight)
+{
+    vec3 coords = In.v4LightSpacePos.xyz / In.v4LightSpacePos.w;
+    coords.xy = coords.xy * 0.5 + 0.5;
+    coords.z = ndcToDepth(coords.z, ShadowReverseZ != 0);
+    if (coords.z > 1.0 || coords.z < 0.0) {
+        return 1.0;
+    }
+
+    float bias = max(ShadowBias * (1.0 - dot(normal, toLight)), ShadowBias * 0.1);
+    if (ShadowReverseZ != 0) {
+        bias = -bias;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(ShadowMap, 0));
+
+    float lit = 0.0;
+    for (int x = -ShadowPcfRadius; x <= ShadowPcfRadius; x++) {
+        for (int y = -ShadowPcfRadius; y <= ShadowPcfRadius; y++) {
+            lit += texture(ShadowMap, vec3(coords.xy + vec2(x, y) * texel, coords.z - bias));
+        }
+    }
+
+    float taps = float((2 * ShadowPcfRadius + 1) * (2 * ShadowPcfRadius + 1));
+    return lit / taps;
+}
+
+vec3 shadeLight(Light light, vec3 pos, vec3 normal)
+{
+    int type = int(light.PositionType.w);
+    vec3 toLight;
+    float attenuation = 1.0;
+
+    if (type == 0) {
+        toLight = -light.DirectionRange.xyz;
+        attenuation = shadowFactor(normal, toLight);
+    } else {
+        vec3 delta = light.PositionType.xyz - pos;
+        float dist = length(delta);
+        toLight = delta / max(dist, 0.0001);
+
+        // Smooth inverse-square falloff reaching exactly zero at the light's range
+        float ratio = clamp(dist / light.DirectionRange.w, 0.0, 1.0);
+        float window = 1.0 - ratio * ratio;
+        attenuation = (window * window) / (1.0 + dist * dist);
+
+        if (type == 2) {
+            float cosAngle = dot(-toLight, light.DirectionRange.xyz);
+            attenuation *= smoothstep(light.Cone.y, light.Cone.x, cosAngle);
+        }
+    }
+
+    float diffuse = max(dot(normal, toLight), 0.0);
+    return light.ColorIntensity.rgb * light.ColorIntensity.a * diffuse * attenuation;
+}
+
+// Alpha for the material's `AlphaMode` variant, see `gfx::material`. Discards cut out fragments.
+float cutoutAlpha(float alpha)
+{
+#if defined(ALPHA_COVERAGE)
+    if (gl_NumSamples > 1) {
+#if defined(ALPHA_SHARPEN)
+        // About one pixel of falloff around the cutoff, whatever the alpha gradient's scale on screen
+        alpha = clamp((alpha - ALPHA_CUTOFF) / max(fwidth(alpha), 0.0001) + 0.5, 0.0, 1.0);
+#endif
+        return alpha;
+    }
+#endif
+#if defined(ALPHA_MASK) || defined(ALPHA_COVERAGE)
+    if (alpha < ALPHA_CUTOFF) {
+        discard;
+    }
+    return 1.0;
+#else
+    return alpha;
+#endif
+}
+
 void main()
 {
-    Out_v4Color = vec4(In.v3Color, 1.0f);
-}
\ No newline at end of file
+    vec3 normal = normalize(In.v3Normal);
+    vec3 lighting = Ambient.rgb;
+
+    for (uint i = 0; i < LightCount.x; i++) {
+        lighting += shadeLight(Lights[i], In.v3WorldPos, normal);
+    }
+
+    vec3 albedo = In.v4Color.rgb;
+    vec3 color = albedo * lighting + environmentLighting(albedo, In.v3WorldPos, normal, Roughness, Metallic);
+    Out_v4Color = vec4(applyFog(color, In.v3WorldPos), cutoutAlpha(In.v4Color.a));
+}
@@ -9,13 +9,18 @@ layout (std140, binding = 0) buffer CB0
 
 uniform mat4 View;
 uniform mat4 Projection;
+uniform mat4 LightSpace;
 
 layout (location = 0) in vec3 In_v3Pos;
-layout (location = 1) in vec3 In_v3Color;
+layout (location = 1) in vec4 In_v4Color;
 layout (location = 2) in uint In_iDrawID;
+layout (location = 3) in vec3 In_v3Normal;
 
 out block {
-    vec3 v3Color;
+    vec4 v4Color;
+    vec3 v3WorldPos;
+    vec3 v3Normal;
+    vec4 v4LightSpacePos;
 } Out;
 
 void main()
@@ -24,5 +29,8 @@ void main()
     vec3 worldPos = vec3(World * vec4(In_v3Pos, 1));
     gl_Position = Projection * View * vec4(worldPos, 1);
     
-    Out.v3Color = In_v3Color;
-}
\ No newline at end of file
+    Out.v4Color = In_v4Color;
+    Out.v3WorldPos = worldPos;
+    Out.v3Normal = transpose(inverse(mat3(World))) * In_v3Normal;
+    Out.v4LightSpacePos = LightSpace * vec4(worldPos, 1);
+}
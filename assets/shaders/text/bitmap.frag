@@ -0,0 +1,15 @@
+#version 430 core
+
+uniform sampler2D Atlas;
+
+in vec2 v2Uv;
+in vec4 v4Color;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    // The atlas is white glyphs on black, any channel is the glyph coverage
+    float coverage = texture(Atlas, v2Uv).r;
+    Out_v4Color = vec4(v4Color.rgb, v4Color.a * coverage);
+}
@@ -0,0 +1,18 @@
+#version 430 core
+
+uniform sampler2D Atlas;
+
+in vec2 v2Uv;
+in vec4 v4Color;
+
+layout (location = 0) out vec4 Out_v4Color;
+
+void main()
+{
+    // 0.5 is the glyph edge, larger is further inside. Smoothing over one screen pixel keeps edges sharp but
+    // anti-aliased at any scale
+    float distance = texture(Atlas, v2Uv).r;
+    float width = max(fwidth(distance) * 0.5, 1e-4);
+    float coverage = smoothstep(0.5 - width, 0.5 + width, distance);
+    Out_v4Color = vec4(v4Color.rgb, v4Color.a * coverage);
+}
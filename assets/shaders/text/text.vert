@@ -0,0 +1,18 @@
+#version 430 core
+
+// Pixels from the top left corner for screen-space text, world space otherwise
+uniform mat4 Transform;
+
+layout (location = 0) in vec3 In_v3Pos;
+layout (location = 1) in vec2 In_v2Uv;
+layout (location = 2) in vec4 In_v4Color;
+
+out vec2 v2Uv;
+out vec4 v4Color;
+
+void main()
+{
+    gl_Position = Transform * vec4(In_v3Pos, 1);
+    v2Uv = In_v2Uv;
+    v4Color = In_v4Color;
+}
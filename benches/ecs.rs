@@ -0,0 +1,157 @@
+//! ECS and math benchmarks, as a baseline for performance work.
+//!
+//! Run with `cargo bench --bench ecs`, optionally with a filter like `cargo bench --bench ecs -- query`. Every case
+//! runs a few times and reports the fastest, along with nanoseconds per entity to compare cases of different sizes.
+
+use std::time::{Duration, Instant};
+
+use rusttest::logic::{hierarchy, Entity, QueryIter, World};
+use rusttest::math::isometry::Transform3;
+
+const ENTITIES: usize = 1_000_000;
+const CHURN_ENTITIES: usize = 10_000;
+const RUNS: usize = 5;
+/// Children of every root in the hierarchy case, each parented to the one before it.
+const CHAIN_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+struct Position(glam::Vec3);
+#[derive(Debug, Clone, Copy)]
+struct Velocity(glam::Vec3);
+#[derive(Debug, Clone, Copy)]
+struct Frozen;
+#[derive(Debug, Clone, Copy)]
+struct WorldMatrix(glam::Mat4);
+
+/// Marker components, each entity gets the ones matching the bits of its index to spread it over archetypes.
+#[derive(Debug, Clone, Copy)]
+struct Tag<const N: usize>;
+
+struct Row {
+    name: String,
+    entities: usize,
+    best: Duration,
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|a| !a.starts_with('-'));
+    let mut rows: Vec<Row> = Vec::new();
+
+    let mut bench = |name: &str, entities: usize, run: &mut dyn FnMut() -> Duration| {
+        if filter.as_ref().map_or(false, |f| !name.contains(f.as_str())) {
+            return;
+        }
+
+        let best = (0..RUNS).map(|_| run()).min().unwrap();
+        rows.push(Row { name: name.to_owned(), entities, best });
+    };
+
+    bench("spawn", ENTITIES, &mut || {
+        let mut world = World::new();
+        let start = Instant::now();
+        for i in 0..ENTITIES {
+            world.spawn((Position(glam::Vec3::splat(i as f32)), Velocity(glam::Vec3::X)));
+        }
+        start.elapsed()
+    });
+
+    for archetypes in [1, 16, 256] {
+        let world = fragmented_world(ENTITIES, archetypes);
+        bench(&format!("query, {} archetypes", archetypes), ENTITIES, &mut || {
+            let start = Instant::now();
+            let mut query = world.query::<(&mut Position, &Velocity)>().unwrap();
+            for (mut position, velocity) in query.iter() {
+                position.0 += velocity.0 * (1.0 / 60.0);
+            }
+            start.elapsed()
+        });
+    }
+
+    bench("add/remove component", CHURN_ENTITIES, &mut || {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..CHURN_ENTITIES)
+            .map(|_| world.spawn((Position(glam::Vec3::ZERO), Velocity(glam::Vec3::X))))
+            .collect();
+
+        let start = Instant::now();
+        for entity in &entities {
+            world.add_component(*entity, Frozen).unwrap();
+        }
+        for entity in &entities {
+            world.remove_component::<Frozen>(*entity).unwrap();
+        }
+        start.elapsed()
+    });
+
+    // The flat case, every local transform to a world matrix with no parents to compose
+    let mut world = World::new();
+    for i in 0..ENTITIES {
+        let transform = Transform3::new(glam::Vec3::splat(i as f32), glam::Quat::from_rotation_y(i as f32), glam::Vec3::ONE);
+        world.spawn((transform, WorldMatrix(glam::Mat4::IDENTITY)));
+    }
+    bench("transform propagation", ENTITIES, &mut || {
+        let start = Instant::now();
+        let mut query = world.query::<(&Transform3, &mut WorldMatrix)>().unwrap();
+        for (transform, mut matrix) in query.iter() {
+            matrix.0 = transform.matrix();
+        }
+        start.elapsed()
+    });
+
+    // Chains of parented entities, through `propagate_transforms()`. Only the first run writes `GlobalTransform`s,
+    // the others measure walking the hierarchy when nothing moved
+    let mut world = World::new();
+    for i in 0..ENTITIES / CHAIN_LENGTH {
+        let root = Transform3::new(glam::Vec3::splat(i as f32), glam::Quat::IDENTITY, glam::Vec3::ONE);
+        let mut parent = world.spawn_single(root);
+        for _ in 1..CHAIN_LENGTH {
+            let child = world.spawn_single(Transform3::new(glam::Vec3::Y, glam::Quat::IDENTITY, glam::Vec3::ONE));
+            world.set_parent(child, parent).unwrap();
+            parent = child;
+        }
+    }
+    bench("hierarchy propagation", ENTITIES, &mut || {
+        let start = Instant::now();
+        hierarchy::propagate_transforms(&mut world);
+        start.elapsed()
+    });
+
+    print_table(&rows);
+}
+
+/// `entities` entities with `Position` and `Velocity`, spread evenly over `archetypes` archetypes (a power of two
+/// up to 256) by adding tag components.
+fn fragmented_world(entities: usize, archetypes: usize) -> World {
+    let mut world = World::new();
+    let bits = archetypes.trailing_zeros();
+
+    for i in 0..entities {
+        let entity = world.spawn((Position(glam::Vec3::ZERO), Velocity(glam::Vec3::ONE)));
+        let combination = i % archetypes;
+
+        macro_rules! tag {
+            ($($bit:literal),*) => {
+                $(
+                    if $bit < bits && combination & (1 << $bit) != 0 {
+                        world.add_component(entity, Tag::<$bit>).unwrap();
+                    }
+                )*
+            };
+        }
+        tag!(0, 1, 2, 3, 4, 5, 6, 7);
+    }
+
+    world
+}
+
+fn print_table(rows: &[Row]) {
+    let name_width = rows.iter().map(|r| r.name.len()).max().unwrap_or(0).max("case".len());
+
+    println!("{:<name_width$}  {:>10}  {:>12}  {:>10}", "case", "entities", "best (ms)", "ns/entity");
+    println!("{}", "-".repeat(name_width + 2 + 10 + 2 + 12 + 2 + 10));
+    for row in rows {
+        let ms = row.best.as_secs_f64() * 1000.0;
+        let ns_per_entity = row.best.as_secs_f64() * 1e9 / row.entities as f64;
+        println!("{:<name_width$}  {:>10}  {:>12.3}  {:>10.2}", row.name, row.entities, ms, ns_per_entity);
+    }
+}
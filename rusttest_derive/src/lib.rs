@@ -0,0 +1,76 @@
+//! Derive macros for `rusttest`, re-exported from the crates they're for.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, Index};
+
+/// Implement `logic::ComponentBundle` for a struct, every field of which is a component, or a nested bundle when
+/// marked `#[bundle]`.
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match bundle_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn bundle_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(syn::Error::new(input.span(), "`Bundle` can only be derived for structs")),
+    };
+
+    let mut stores = Vec::new();
+    let mut adds = Vec::new();
+    let members: Vec<TokenStream2> = match fields {
+        Fields::Named(fields) => fields.named.iter().map(|f| {
+            let ident = f.ident.as_ref().unwrap();
+            quote!(#ident)
+        }).collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len()).map(|i| {
+            let index = Index::from(i);
+            quote!(#index)
+        }).collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    for (field, member) in fields.iter().zip(members) {
+        let ty = &field.ty;
+        let nested = field.attrs.iter().any(|attr| attr.path.is_ident("bundle"));
+
+        if nested {
+            stores.push(quote! {
+                <#ty as ::rusttest::logic::ComponentBundle>::component_stores(stores);
+            });
+            adds.push(quote! {
+                ::rusttest::logic::ComponentBundle::add_to_builder(self.#member, builder);
+            });
+        } else {
+            stores.push(quote! {
+                stores.push(::rusttest::logic::ComponentStore::new::<#ty>());
+            });
+            adds.push(quote! {
+                builder.add(self.#member);
+            });
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rusttest::logic::ComponentBundle for #name #type_generics #where_clause {
+            fn component_stores(stores: &mut ::std::vec::Vec<::rusttest::logic::ComponentStore>) {
+                #(#stores)*
+            }
+
+            fn add_to_builder(self, builder: &mut ::rusttest::logic::EntityBuilder) {
+                #(#adds)*
+            }
+        }
+    })
+}
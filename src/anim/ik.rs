@@ -0,0 +1,169 @@
+//! Inverse kinematics constraints, meant to run after animation sampling so they can correct the sampled pose.
+//!
+//! There's no transform hierarchy yet, so every `Transform3` involved is treated as a world-space transform and the
+//! constraints write positions back to child joints themselves.
+//! ## Example
+//! ```ignore
+//! let shoulder = world.spawn_single(Transform3::identity());
+//! let elbow = world.spawn_single(Transform3::identity());
+//! let hand = world.spawn_single(Transform3::identity());
+//! world.spawn_single(anim::TwoBoneIk::new(shoulder, elbow, hand, target, pole));
+//!
+//! // Every frame, after animation sampling
+//! anim::ik::apply(&mut world);
+//! ```
+
+use crate::log::LOGGER;
+use crate::logic::{ComponentError, Entity, QueryIter, World};
+use crate::math::isometry::Transform3;
+
+/// Bends a three-joint chain (e.g. shoulder, elbow, hand) so that `end` reaches `target`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoBoneIk {
+    pub upper: Entity,
+    pub lower: Entity,
+    pub end: Entity,
+    pub target: glam::Vec3,
+    /// Point the middle joint bends towards, e.g. in front of the knee.
+    pub pole: glam::Vec3,
+    /// How much of the solved pose is blended over the sampled one, from `0.0` to `1.0`.
+    pub weight: f32,
+}
+
+impl TwoBoneIk {
+    pub fn new(upper: Entity, lower: Entity, end: Entity, target: glam::Vec3, pole: glam::Vec3) -> Self {
+        TwoBoneIk { upper, lower, end, target, pole, weight: 1.0 }
+    }
+}
+
+/// Rotates the entity's own `Transform3` so its local `forward` axis points at `target`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookAt {
+    pub target: glam::Vec3,
+    /// Local axis that should end up facing the target.
+    pub forward: glam::Vec3,
+    /// How much of the solved rotation is blended over the sampled one, from `0.0` to `1.0`.
+    pub weight: f32,
+}
+
+impl LookAt {
+    pub fn new(target: glam::Vec3) -> Self {
+        LookAt { target, forward: glam::Vec3::Z, weight: 1.0 }
+    }
+}
+
+/// World-space rotations of the upper and lower joints that place the end of the chain on `target`.
+///
+/// `upper`, `lower` and `end` are joint positions, `upper_rotation` and `lower_rotation` their current world rotations.
+/// Targets out of reach leave the chain fully stretched towards them.
+/// Based on Daniel Holden's "Simple Two Joint IK".
+pub fn solve_two_bone(
+    upper: glam::Vec3,
+    lower: glam::Vec3,
+    end: glam::Vec3,
+    upper_rotation: glam::Quat,
+    lower_rotation: glam::Quat,
+    target: glam::Vec3,
+    pole: glam::Vec3,
+) -> (glam::Quat, glam::Quat) {
+    const EPSILON: f32 = 1e-4;
+
+    let upper_len = (lower - upper).length();
+    let lower_len = (end - lower).length();
+    let target_len = (target - upper).length().clamp(EPSILON, upper_len + lower_len - EPSILON);
+
+    let angle = |a: glam::Vec3, b: glam::Vec3| a.normalize_or_zero().dot(b.normalize_or_zero()).clamp(-1.0, 1.0).acos();
+    let law_of_cosines = |a: f32, b: f32, opposite: f32| ((a * a + b * b - opposite * opposite) / (2.0 * a * b)).clamp(-1.0, 1.0).acos();
+
+    // Current and desired interior angles at the upper and lower joints
+    let upper_angle = angle(end - upper, lower - upper);
+    let lower_angle = angle(upper - lower, end - lower);
+    let upper_angle_solved = law_of_cosines(upper_len, target_len, lower_len);
+    let lower_angle_solved = law_of_cosines(upper_len, lower_len, target_len);
+
+    let bend_axis = (end - upper).cross(pole - upper).normalize_or_zero();
+    let bend_axis = if bend_axis == glam::Vec3::ZERO { glam::Vec3::X } else { bend_axis };
+
+    let upper_bend = glam::Quat::from_axis_angle(bend_axis, upper_angle_solved - upper_angle);
+    let lower_bend = glam::Quat::from_axis_angle(bend_axis, lower_angle_solved - lower_angle);
+    let swing = glam::Quat::from_rotation_arc(
+        (end - upper).normalize_or_zero(),
+        (target - upper).normalize_or_zero(),
+    );
+
+    let upper_delta = swing * upper_bend;
+    (upper_delta * upper_rotation, upper_delta * lower_bend * lower_rotation)
+}
+
+/// World rotation that turns `rotation`'s local `forward` axis towards `target`.
+pub fn solve_look_at(position: glam::Vec3, rotation: glam::Quat, forward: glam::Vec3, target: glam::Vec3) -> glam::Quat {
+    let to_target = (target - position).normalize_or_zero();
+    if to_target == glam::Vec3::ZERO {
+        return rotation;
+    }
+
+    let current = (rotation * forward).normalize();
+    glam::Quat::from_rotation_arc(current, to_target) * rotation
+}
+
+/// Apply every `TwoBoneIk` and `LookAt` constraint in `world`.
+pub fn apply(world: &mut World) {
+    let chains: Vec<TwoBoneIk> = match world.query::<(&TwoBoneIk,)>() {
+        Ok(mut query) => query.iter().copied().collect(),
+        Err(e) => {
+            LOGGER().a.error(format!("failed to query two-bone IK chains: {:?}", e).as_str());
+            Vec::new()
+        },
+    };
+
+    for chain in chains {
+        if let Err(e) = apply_two_bone(world, &chain) {
+            LOGGER().a.error(format!("failed to apply two-bone IK to {:?}: {:?}", chain.upper, e).as_str());
+        }
+    }
+
+    match world.query::<(&mut Transform3, &LookAt)>() {
+        Ok(mut query) => {
+            for (mut transform, look_at) in query.iter() {
+                let solved = solve_look_at(transform.position, transform.rotation, look_at.forward, look_at.target);
+                transform.rotation = transform.rotation.slerp(solved, look_at.weight.clamp(0.0, 1.0));
+            }
+        },
+        Err(e) => {
+            LOGGER().a.error(format!("failed to query look-at constraints: {:?}", e).as_str());
+        },
+    }
+}
+
+fn apply_two_bone(world: &mut World, chain: &TwoBoneIk) -> Result<(), ComponentError> {
+    let upper = world.get_component_mut::<Transform3>(chain.upper)?.clone();
+    let lower = world.get_component_mut::<Transform3>(chain.lower)?.clone();
+    let end = world.get_component_mut::<Transform3>(chain.end)?.clone();
+
+    let (upper_solved, lower_solved) = solve_two_bone(
+        upper.position, lower.position, end.position,
+        upper.rotation, lower.rotation,
+        chain.target, chain.pole,
+    );
+
+    let weight = chain.weight.clamp(0.0, 1.0);
+    let upper_rotation = upper.rotation.slerp(upper_solved, weight);
+    let lower_rotation = lower.rotation.slerp(lower_solved, weight);
+
+    // Carry the child joints along, keeping their offsets in their parent's rotated frame
+    let lower_position = upper.position + upper_rotation * (upper.rotation.inverse() * (lower.position - upper.position));
+    let end_position = lower_position + lower_rotation * (lower.rotation.inverse() * (end.position - lower.position));
+    let end_rotation = lower_rotation * lower.rotation.inverse() * end.rotation;
+
+    world.get_component_mut::<Transform3>(chain.upper)?.rotation = upper_rotation;
+
+    let lower = world.get_component_mut::<Transform3>(chain.lower)?;
+    lower.position = lower_position;
+    lower.rotation = lower_rotation;
+
+    let end = world.get_component_mut::<Transform3>(chain.end)?;
+    end.position = end_position;
+    end.rotation = end_rotation;
+
+    Ok(())
+}
@@ -0,0 +1,3 @@
+pub mod ik;
+
+pub use self::ik::{TwoBoneIk, LookAt};
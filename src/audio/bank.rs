@@ -0,0 +1,197 @@
+//! Sound banks, data files mapping event names to the sounds they play.
+//!
+//! Gameplay code only ever names an event like `"footstep_grass"`; which samples it picks from, how likely each one
+//! is, how much volume and pitch vary between plays and which bus it goes through all live in the bank, so sound
+//! content can be iterated on without touching code. Banks are RON files:
+//! ```ron
+//! (
+//!     events: {
+//!         "footstep_grass": (
+//!             // Either a path, or a path with a weight (1 if left out)
+//!             samples: ["sounds/grass_1.wav", (path: "sounds/grass_2.wav", weight: 0.5)],
+//!             volume: (0.8, 1.0), // Random range each play, 1 if left out
+//!             pitch: (0.95, 1.05),
+//!             bus: "sfx",         // "master" if left out
+//!         ),
+//!     },
+//! )
+//! ```
+//! ## Example
+//! ```ignore
+//! let bank = audio::SoundBank::from_res(&res, "sounds/footsteps.ron")?;
+//! let event = bank.event("footstep_grass").unwrap();
+//! ```
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::resource::{self, Resource};
+
+/// Bus events go through when their definition doesn't name one.
+pub const MASTER_BUS: &str = "master";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load sound bank: {0}")]
+    Load(#[from] resource::Error),
+    #[error("sound bank isn't UTF-8")]
+    Encoding,
+    #[error("invalid sound bank: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("sound event `{event}` {message}")]
+    Invalid {
+        event: String,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedSample {
+    /// Resource name of a WAV file.
+    pub path: String,
+    /// Relative chance of being picked over the other samples of the event.
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundEvent {
+    pub samples: Vec<WeightedSample>,
+    /// Range each play picks a volume multiplier from.
+    pub volume: (f32, f32),
+    /// Range each play picks a playback speed from, 2 being an octave up.
+    pub pitch: (f32, f32),
+    pub bus: String,
+}
+
+impl SoundEvent {
+    /// Pick a sample by weight, with `roll` uniform in [0, 1).
+    pub fn pick(&self, roll: f32) -> &WeightedSample {
+        let total: f32 = self.samples.iter().map(|s| s.weight).sum();
+        let mut target = roll * total;
+        for sample in &self.samples {
+            if target < sample.weight {
+                return sample;
+            }
+            target -= sample.weight;
+        }
+
+        // Only reachable through rounding
+        self.samples.last().unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SoundBank {
+    events: HashMap<String, SoundEvent>,
+}
+
+impl SoundBank {
+    pub fn from_res(res: &Resource, name: &str) -> Result<Self, Error> {
+        let bytes = res.load_bytes(name)?;
+        let text = std::str::from_utf8(&bytes).map_err(|_| Error::Encoding)?;
+        Self::parse(text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let file: BankFile = ron::from_str(text)?;
+        let mut events = HashMap::new();
+
+        for (name, definition) in file.events {
+            let event = SoundEvent::from(definition);
+
+            if event.samples.is_empty() {
+                return Err(Error::Invalid { event: name, message: "has no samples".to_owned() });
+            }
+            if event.samples.iter().any(|s| s.weight.is_nan() || s.weight <= 0.0) {
+                return Err(Error::Invalid { event: name, message: "has a sample with a weight that isn't positive".to_owned() });
+            }
+            if event.pitch.0 <= 0.0 {
+                return Err(Error::Invalid { event: name, message: "has a pitch that isn't positive".to_owned() });
+            }
+
+            events.insert(name, event);
+        }
+
+        Ok(SoundBank { events })
+    }
+
+    pub fn event(&self, name: &str) -> Option<&SoundEvent> {
+        self.events.get(name)
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = (&str, &SoundEvent)> {
+        self.events.iter().map(|(name, event)| (name.as_str(), event))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// A bank file as written, before defaults are filled in.
+#[derive(Deserialize)]
+struct BankFile {
+    events: HashMap<String, EventDefinition>,
+}
+
+#[derive(Deserialize)]
+struct EventDefinition {
+    samples: Vec<SampleDefinition>,
+    #[serde(default = "unit_range")]
+    volume: (f32, f32),
+    #[serde(default = "unit_range")]
+    pitch: (f32, f32),
+    #[serde(default = "master_bus")]
+    bus: String,
+}
+
+/// Either a path, or a path with a weight.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SampleDefinition {
+    Path(String),
+    Weighted {
+        path: String,
+        #[serde(default = "unit_weight")]
+        weight: f32,
+    },
+}
+
+fn unit_range() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+fn unit_weight() -> f32 {
+    1.0
+}
+
+fn master_bus() -> String {
+    MASTER_BUS.to_owned()
+}
+
+impl From<EventDefinition> for SoundEvent {
+    fn from(definition: EventDefinition) -> Self {
+        let samples = definition.samples
+            .into_iter()
+            .map(|sample| match sample {
+                SampleDefinition::Path(path) => WeightedSample { path, weight: 1.0 },
+                SampleDefinition::Weighted { path, weight } => WeightedSample { path, weight },
+            })
+            .collect();
+
+        // Ranges can be written either way around
+        let ordered = |(min, max): (f32, f32)| (min.min(max), min.max(max));
+
+        SoundEvent {
+            samples,
+            volume: ordered(definition.volume),
+            pitch: ordered(definition.pitch),
+            bus: definition.bus,
+        }
+    }
+}
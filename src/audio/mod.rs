@@ -0,0 +1,5 @@
+pub mod bank;
+pub mod player;
+
+pub use self::bank::{SoundBank, SoundEvent};
+pub use self::player::Audio;
@@ -0,0 +1,237 @@
+//! Playing sound events through a software mixer on SDL's audio thread.
+//!
+//! `Audio` owns the output device and the events of every loaded bank. Posting an event picks one of its samples and
+//! a volume and pitch in its ranges, then starts a voice the mixer plays to the end. Voices are grouped into buses,
+//! each with its own volume, like "music" and "sfx" sliders in the options.
+//!
+//! Samples are decoded once when their bank is loaded, converted to the device's format, and shared by every voice
+//! playing them. Voices aren't positioned yet, the entity an event is posted for only lets `stop_entity()` silence
+//! it.
+//! ## Example
+//! ```ignore
+//! let mut audio = audio::Audio::new(&sdl)?;
+//! audio.load_bank(&res, "sounds/footsteps.ron")?;
+//! audio.set_bus_volume("sfx", 0.5);
+//!
+//! // When a foot lands
+//! audio.post_event("footstep_grass", player)?;
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sdl2::audio::{AudioCVT, AudioCallback, AudioDevice, AudioFormat, AudioSpecDesired, AudioSpecWAV};
+
+use crate::log::LOGGER;
+use crate::logic::Entity;
+use crate::resource::{self, Resource};
+
+use super::bank::{self, SoundBank, SoundEvent};
+
+/// Voices mixed at once. Posting past this steals the oldest voice.
+pub const MAX_VOICES: usize = 32;
+const SAMPLE_RATE: i32 = 48000;
+const CHANNELS: u8 = 2;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to open audio device: {0}")]
+    Device(String),
+    #[error(transparent)]
+    Bank(#[from] bank::Error),
+    #[error("failed to load sample {path}: {source}")]
+    Load {
+        path: String,
+        source: resource::Error,
+    },
+    #[error("failed to decode sample {path}: {message}")]
+    Decode {
+        path: String,
+        message: String,
+    },
+    #[error("no sound event named `{0}`")]
+    UnknownEvent(String),
+}
+
+/// Interleaved samples in the device's format.
+type Samples = Arc<[f32]>;
+
+struct Voice {
+    samples: Samples,
+    /// Playback position in frames, fractional since pitch resamples.
+    position: f64,
+    pitch: f64,
+    volume: f32,
+    bus: String,
+    entity: Entity,
+}
+
+/// State shared with the audio thread, only touched through `AudioDevice::lock()`.
+struct Mixer {
+    voices: Vec<Voice>,
+    bus_volumes: HashMap<String, f32>,
+    channels: usize,
+}
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|s| *s = 0.0);
+        let channels = self.channels;
+
+        for voice in &mut self.voices {
+            let frames = voice.samples.len() / channels;
+            let volume = voice.volume * self.bus_volumes.get(&voice.bus).copied().unwrap_or(1.0);
+
+            for frame in out.chunks_mut(channels) {
+                let index = voice.position as usize;
+                if index + 1 >= frames {
+                    voice.position = frames as f64;
+                    break;
+                }
+
+                // Linear interpolation between the frames around the position
+                let t = (voice.position - index as f64) as f32;
+                for (channel, out) in frame.iter_mut().enumerate() {
+                    let a = voice.samples[index * channels + channel];
+                    let b = voice.samples[(index + 1) * channels + channel];
+                    *out += (a + (b - a) * t) * volume;
+                }
+                voice.position += voice.pitch;
+            }
+        }
+
+        self.voices.retain(|v| (v.position as usize) + 1 < v.samples.len() / channels);
+        out.iter_mut().for_each(|s| *s = s.clamp(-1.0, 1.0));
+    }
+}
+
+pub struct Audio {
+    device: AudioDevice<Mixer>,
+    events: HashMap<String, SoundEvent>,
+    /// Decoded samples by resource name.
+    samples: HashMap<String, Samples>,
+    rng: u64,
+}
+
+impl Audio {
+    /// Open the default output device and start playing silence.
+    pub fn new(sdl: &sdl2::Sdl) -> Result<Self, Error> {
+        let subsystem = sdl.audio().map_err(Error::Device)?;
+        let desired = AudioSpecDesired { freq: Some(SAMPLE_RATE), channels: Some(CHANNELS), samples: Some(1024) };
+        let device = subsystem.open_playback(None, &desired, |spec| Mixer {
+            voices: Vec::with_capacity(MAX_VOICES),
+            bus_volumes: HashMap::new(),
+            channels: spec.channels as usize,
+        }).map_err(Error::Device)?;
+        device.resume();
+
+        let spec = device.spec();
+        LOGGER().a.info(format!("audio output: {} Hz, {} channels", spec.freq, spec.channels).as_str());
+
+        let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Ok(Audio { device, events: HashMap::new(), samples: HashMap::new(), rng: seed | 1 })
+    }
+
+    /// Load a bank and decode every sample its events use. Events already loaded from another bank are replaced by
+    /// the ones in this bank with the same name.
+    pub fn load_bank(&mut self, res: &Resource, name: &str) -> Result<(), Error> {
+        let bank = SoundBank::from_res(res, name)?;
+
+        for (event_name, event) in bank.events() {
+            for sample in &event.samples {
+                if !self.samples.contains_key(&sample.path) {
+                    let samples = self.decode(res, &sample.path)?;
+                    self.samples.insert(sample.path.clone(), samples);
+                }
+            }
+
+            if self.events.insert(event_name.to_owned(), event.clone()).is_some() {
+                LOGGER().a.info(format!("sound event {} replaced by {}", event_name, name).as_str());
+            }
+        }
+
+        LOGGER().a.info(format!("loaded sound bank {} with {} events", name, bank.len()).as_str());
+        Ok(())
+    }
+
+    /// Decode the WAV resource `path` into the device's format.
+    fn decode(&self, res: &Resource, path: &str) -> Result<Samples, Error> {
+        let decode_error = |message: String| Error::Decode { path: path.to_owned(), message };
+
+        let bytes = res.load_bytes(path).map_err(|source| Error::Load { path: path.to_owned(), source })?;
+        let mut rwops = sdl2::rwops::RWops::from_bytes(&bytes).map_err(decode_error)?;
+        let wav = AudioSpecWAV::load_wav_rw(&mut rwops).map_err(decode_error)?;
+
+        let spec = self.device.spec();
+        let cvt = AudioCVT::new(wav.format, wav.channels, wav.freq, AudioFormat::f32_sys(), spec.channels, spec.freq)
+            .map_err(decode_error)?;
+        let bytes = cvt.convert(wav.buffer().to_vec());
+
+        Ok(bytes.chunks_exact(4).map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])).collect())
+    }
+
+    /// Play the event `name`, on behalf of `entity`.
+    pub fn post_event(&mut self, name: &str, entity: Entity) -> Result<(), Error> {
+        let (pick, volume, pitch) = (self.random(), self.random(), self.random());
+        let event = self.events.get(name).ok_or_else(|| Error::UnknownEvent(name.to_owned()))?;
+        let sample = event.pick(pick);
+        let voice = Voice {
+            samples: self.samples[&sample.path].clone(),
+            position: 0.0,
+            pitch: lerp(event.pitch, pitch) as f64,
+            volume: lerp(event.volume, volume),
+            bus: event.bus.clone(),
+            entity,
+        };
+
+        let mut mixer = self.device.lock();
+        if mixer.voices.len() >= MAX_VOICES {
+            mixer.voices.remove(0);
+        }
+        mixer.voices.push(voice);
+
+        Ok(())
+    }
+
+    /// Stop every voice posted for `entity`, e.g. when it's despawned.
+    pub fn stop_entity(&mut self, entity: Entity) {
+        self.device.lock().voices.retain(|v| v.entity != entity);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.device.lock().voices.clear();
+    }
+
+    /// Volume multiplier of `bus`, 1 until set.
+    pub fn bus_volume(&mut self, bus: &str) -> f32 {
+        self.device.lock().bus_volumes.get(bus).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_bus_volume(&mut self, bus: &str, volume: f32) {
+        self.device.lock().bus_volumes.insert(bus.to_owned(), volume.max(0.0));
+    }
+
+    /// Voices playing right now.
+    pub fn voice_count(&mut self) -> usize {
+        self.device.lock().voices.len()
+    }
+
+    pub fn has_event(&self, name: &str) -> bool {
+        self.events.contains_key(name)
+    }
+
+    /// Uniform in [0, 1), from xorshift64*. Variation in sounds doesn't need anything better.
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        bits as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn lerp((min, max): (f32, f32), t: f32) -> f32 {
+    min + (max - min) * t
+}
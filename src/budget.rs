@@ -0,0 +1,161 @@
+//! Budgets for what content is allowed to use.
+//!
+//! Each budget has an optional limit. `Budgets::check()` compares the current `Usage` against them every frame and
+//! reports a `BudgetAlert` (also logged) whenever one crosses its warning threshold or its limit, in either direction,
+//! so content that outgrows the target hardware gets noticed while it's being made.
+//! ## Example
+//! ```ignore
+//! let mut budgets = Budgets::new();
+//! budgets.set_limit(Budget::Entities, Some(10_000));
+//! budgets.set_limit(Budget::Vram, Some(512 << 20));
+//!
+//! // Every frame
+//! for alert in budgets.check(&Usage::measure(&world)) {
+//!     // Show it in the editor
+//! }
+//! ```
+
+use crate::gfx;
+use crate::log::LOGGER;
+use crate::logic::World;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Budget {
+    Entities,
+    /// Bytes of video memory, as estimated by `gfx::memory`.
+    Vram,
+    AudioVoices,
+}
+
+impl Budget {
+    pub const ALL: [Budget; 3] = [Budget::Entities, Budget::Vram, Budget::AudioVoices];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Budget::Entities => "entities",
+            Budget::Vram => "VRAM",
+            Budget::AudioVoices => "audio voices",
+        }
+    }
+}
+
+/// What's in use right now, in the units of each `Budget`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub entities: usize,
+    pub vram: usize,
+    pub audio_voices: usize,
+}
+
+impl Usage {
+    /// Measure every subsystem the world knows about. Voices are left at 0 for the caller to fill in from its
+    /// `audio::Audio`.
+    pub fn measure(world: &World) -> Self {
+        Usage {
+            entities: world.entity_count(),
+            vram: gfx::memory::total(),
+            audio_voices: 0,
+        }
+    }
+
+    pub fn get(&self, budget: Budget) -> usize {
+        match budget {
+            Budget::Entities => self.entities,
+            Budget::Vram => self.vram,
+            Budget::AudioVoices => self.audio_voices,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Normal,
+    /// Past the warning threshold, but within the limit.
+    Warning,
+    Exceeded,
+}
+
+/// A budget changed level, up or down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetAlert {
+    pub budget: Budget,
+    pub level: Level,
+    pub previous: Level,
+    pub usage: usize,
+    pub limit: usize,
+}
+
+pub struct Budgets {
+    limits: [Option<usize>; 3],
+    levels: [Level; 3],
+    warning_fraction: f32,
+}
+
+impl Budgets {
+    /// No limits, warning at 80% of a limit once one is set.
+    pub fn new() -> Self {
+        Budgets {
+            limits: [None; 3],
+            levels: [Level::Normal; 3],
+            warning_fraction: 0.8,
+        }
+    }
+
+    pub fn limit(&self, budget: Budget) -> Option<usize> {
+        self.limits[budget as usize]
+    }
+
+    /// `None` removes the limit.
+    pub fn set_limit(&mut self, budget: Budget, limit: Option<usize>) {
+        self.limits[budget as usize] = limit;
+    }
+
+    pub fn warning_fraction(&self) -> f32 {
+        self.warning_fraction
+    }
+
+    /// Warn once usage reaches `fraction` of a limit.
+    pub fn set_warning_fraction(&mut self, fraction: f32) {
+        self.warning_fraction = fraction.clamp(0.0, 1.0);
+    }
+
+    /// Level of `budget` as of the last check.
+    pub fn level(&self, budget: Budget) -> Level {
+        self.levels[budget as usize]
+    }
+
+    /// Compare `usage` against every limit, returning and logging the budgets that changed level since the
+    /// last check.
+    pub fn check(&mut self, usage: &Usage) -> Vec<BudgetAlert> {
+        let mut alerts = Vec::new();
+
+        for budget in Budget::ALL {
+            let index = budget as usize;
+            let used = usage.get(budget);
+
+            let (level, limit) = match self.limits[index] {
+                Some(limit) if used > limit => (Level::Exceeded, limit),
+                Some(limit) if used as f64 >= limit as f64 * self.warning_fraction as f64 => (Level::Warning, limit),
+                Some(limit) => (Level::Normal, limit),
+                None => (Level::Normal, 0),
+            };
+
+            let previous = self.levels[index];
+            if level == previous {
+                continue;
+            }
+            self.levels[index] = level;
+
+            let message = format!("{} budget: {} of {} used ({:?} -> {:?})", budget.name(), used, limit, previous, level);
+            match level {
+                Level::Exceeded => LOGGER().a.error(message.as_str()),
+                Level::Warning if previous < level => LOGGER().a.warn(message.as_str()),
+                _ => LOGGER().a.info(message.as_str()),
+            }
+
+            alerts.push(BudgetAlert { budget, level, previous, usage: used, limit });
+        }
+
+        alerts
+    }
+}
@@ -0,0 +1,209 @@
+//! A graphics API abstraction, so renderers can be written once for OpenGL today and wgpu or Vulkan later.
+//!
+//! The layer is shaped after the explicit APIs rather than OpenGL: a `Device` creates buffers, textures and
+//! pipelines, which bundle a program with its fixed-function state and vertex layout. Draws are recorded into an
+//! `Encoder` and only run when the encoder is submitted, which OpenGL does by replaying the recording.
+//!
+//! Only `opengl::GlDevice` exists for now. Batches, programs and the passes built on them still call OpenGL
+//! directly and are moved over one at a time, new code should go through a `Device`.
+//! ## Example
+//! ```ignore
+//! let mut device = gfx::backend::GlDevice::new();
+//! let vertices = device.create_buffer(&BufferDesc::new(BufferUsage::Vertex, "quad vertices"), bytes_of(&quad))?;
+//! let pipeline = device.create_pipeline(&res, &PipelineDesc {
+//!     shader: "shaders/debug",
+//!     render_state: gfx::RenderState::default(),
+//!     vertex_layout: &[VertexAttribute::float(0, 0, 3, 0, 12)],
+//!     label: "debug lines",
+//! })?;
+//!
+//! let mut encoder = device.create_encoder();
+//! encoder.set_pipeline(&pipeline);
+//! encoder.set_vertex_buffer(0, &vertices);
+//! encoder.draw(0..6, 0..1);
+//! device.submit(encoder);
+//! ```
+
+pub mod opengl;
+
+pub use self::opengl::GlDevice;
+
+use std::ops::Range;
+
+use crate::resource::Resource;
+
+use crate::gfx::caps::Capabilities;
+use crate::gfx::color::ColorSpace;
+use crate::gfx::shader;
+use crate::gfx::state::RenderState;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to create pipeline: {0}")]
+    Pipeline(#[from] shader::Error),
+    #[error("{what}: expected {size} bytes, got {given}")]
+    DataSize {
+        what: &'static str,
+        size: usize,
+        given: usize,
+    },
+    #[error("not supported by this device: {0}")]
+    Unsupported(&'static str),
+}
+
+/// What a buffer is bound as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferUsage {
+    Vertex,
+    /// 32-bit indices.
+    Index,
+    /// Read and written by shaders, like per-instance transforms.
+    Storage,
+    /// Draw commands for `Encoder::draw_indexed_indirect`.
+    Indirect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferDesc<'a> {
+    pub usage: BufferUsage,
+    /// Whether the buffer is rewritten often, like every frame, rather than once.
+    pub dynamic: bool,
+    /// Name shown in graphics debuggers.
+    pub label: &'a str,
+}
+
+impl<'a> BufferDesc<'a> {
+    pub fn new(usage: BufferUsage, label: &'a str) -> Self {
+        BufferDesc { usage, dynamic: false, label }
+    }
+
+    pub fn dynamic(usage: BufferUsage, label: &'a str) -> Self {
+        BufferDesc { usage, dynamic: true, label }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8(ColorSpace),
+    /// Depth in whatever format `state::depth_format()` picks.
+    Depth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureDesc<'a> {
+    pub width: i32,
+    pub height: i32,
+    pub format: TextureFormat,
+    pub label: &'a str,
+}
+
+/// Type of a vertex attribute's components as the shader sees them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttributeKind {
+    Float,
+    UnsignedInt,
+}
+
+/// Where one shader input comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    /// `layout (location = ...)` in the shader.
+    pub location: u32,
+    /// Vertex buffer slot the attribute is read from, see `Encoder::set_vertex_buffer`.
+    pub slot: u32,
+    pub kind: AttributeKind,
+    /// 1 to 4.
+    pub components: i32,
+    /// Bytes from the start of a vertex.
+    pub offset: usize,
+    /// Bytes between vertices.
+    pub stride: usize,
+    /// Advance once per instance instead of once per vertex.
+    pub per_instance: bool,
+}
+
+impl VertexAttribute {
+    pub fn float(location: u32, slot: u32, components: i32, offset: usize, stride: usize) -> Self {
+        VertexAttribute { location, slot, kind: AttributeKind::Float, components, offset, stride, per_instance: false }
+    }
+
+    /// A `uint` advancing once per instance, like the draw IDs batches index their transforms with.
+    pub fn instance_uint(location: u32, slot: u32) -> Self {
+        VertexAttribute {
+            location,
+            slot,
+            kind: AttributeKind::UnsignedInt,
+            components: 1,
+            offset: 0,
+            stride: std::mem::size_of::<u32>(),
+            per_instance: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineDesc<'a> {
+    /// Resource name of the shaders, without the `.vert`/`.frag` extension.
+    pub shader: &'a str,
+    pub render_state: RenderState,
+    pub vertex_layout: &'a [VertexAttribute],
+    pub label: &'a str,
+}
+
+/// One `DrawElementsIndirectCommand`, as laid out in an indirect buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(C)]
+pub struct DrawIndexedIndirect {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+pub trait Device {
+    type Buffer;
+    type Texture;
+    type Pipeline;
+    type Encoder: Encoder<Self>;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Create a buffer holding `data`, sized to fit it.
+    fn create_buffer(&mut self, desc: &BufferDesc, data: &[u8]) -> Result<Self::Buffer, Error>;
+
+    /// Overwrite part of `buffer` starting `offset` bytes in. Ordered before draws submitted afterwards.
+    fn write_buffer(&mut self, buffer: &Self::Buffer, offset: usize, data: &[u8]) -> Result<(), Error>;
+
+    /// Create a texture, filled with tightly packed RGBA8 `pixels` (top row first) if given.
+    fn create_texture(&mut self, desc: &TextureDesc, pixels: Option<&[u8]>) -> Result<Self::Texture, Error>;
+
+    fn create_pipeline(&mut self, res: &Resource, desc: &PipelineDesc) -> Result<Self::Pipeline, Error>;
+
+    fn create_encoder(&mut self) -> Self::Encoder;
+
+    /// Run everything recorded in `encoder`, in order.
+    fn submit(&mut self, encoder: Self::Encoder);
+}
+
+/// Records draws for a `Device` to run on `submit()`. Bindings carry over between draws until replaced.
+pub trait Encoder<D: Device + ?Sized> {
+    fn set_pipeline(&mut self, pipeline: &D::Pipeline);
+
+    fn set_vertex_buffer(&mut self, slot: u32, buffer: &D::Buffer);
+
+    fn set_index_buffer(&mut self, buffer: &D::Buffer);
+
+    fn set_storage_buffer(&mut self, binding: u32, buffer: &D::Buffer);
+
+    fn set_texture(&mut self, unit: u32, texture: &D::Texture);
+
+    /// Draw non-indexed `vertices` for each of `instances`.
+    fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>);
+
+    /// Draw `indices` of the index buffer, offset by `base_vertex`, for each of `instances`.
+    fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>);
+
+    /// Draw `count` `DrawIndexedIndirect` commands from `buffer`, starting `offset` bytes in.
+    fn draw_indexed_indirect(&mut self, buffer: &D::Buffer, offset: usize, count: u32);
+}
@@ -0,0 +1,375 @@
+//! The OpenGL implementation of the backend traits.
+//!
+//! Encoders record commands into a list and `submit()` replays them against the current context. Vertex layouts are
+//! applied when a vertex buffer is bound, with `glVertexAttribPointer`, so 4.2 contexts without separate attribute
+//! formats work too. Indirect draws without multidraw indirect read the commands back and draw them one at a time.
+
+use std::ops::Range;
+
+use crate::gfx::caps::{self, Capabilities};
+use crate::gfx::debug_group;
+use crate::gfx::memory::{Allocation, Category};
+use crate::gfx::shader::Program;
+use crate::gfx::state::{self, RenderState};
+use crate::gfx::texture::Texture;
+use crate::resource::Resource;
+
+use super::{
+    AttributeKind, BufferDesc, BufferUsage, Device, DrawIndexedIndirect, Encoder, Error, PipelineDesc, TextureDesc,
+    TextureFormat, VertexAttribute,
+};
+
+impl BufferUsage {
+    fn gl_target(&self) -> gl::types::GLenum {
+        match self {
+            BufferUsage::Vertex => gl::ARRAY_BUFFER,
+            BufferUsage::Index => gl::ELEMENT_ARRAY_BUFFER,
+            BufferUsage::Storage => gl::SHADER_STORAGE_BUFFER,
+            BufferUsage::Indirect => gl::DRAW_INDIRECT_BUFFER,
+        }
+    }
+}
+
+pub struct GlBuffer {
+    id: gl::types::GLuint,
+    target: gl::types::GLenum,
+    size: usize,
+    _memory: Allocation,
+}
+
+impl GlBuffer {
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for GlBuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &mut self.id); }
+    }
+}
+
+/// A program with the fixed-function state and vertex layout it's drawn with.
+pub struct GlPipeline {
+    program: Program,
+    render_state: RenderState,
+    vertex_layout: Vec<VertexAttribute>,
+    /// Holds the attribute setup of whatever vertex buffers were last bound for this pipeline.
+    vao: gl::types::GLuint,
+}
+
+impl GlPipeline {
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn render_state(&self) -> &RenderState {
+        &self.render_state
+    }
+}
+
+impl Drop for GlPipeline {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &mut self.vao); }
+    }
+}
+
+enum Command {
+    SetPipeline {
+        program: gl::types::GLuint,
+        render_state: RenderState,
+        vertex_layout: Vec<VertexAttribute>,
+        vao: gl::types::GLuint,
+    },
+    SetVertexBuffer {
+        slot: u32,
+        buffer: gl::types::GLuint,
+    },
+    SetIndexBuffer(gl::types::GLuint),
+    SetStorageBuffer {
+        binding: u32,
+        buffer: gl::types::GLuint,
+    },
+    SetTexture {
+        unit: u32,
+        texture: gl::types::GLuint,
+    },
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+    DrawIndexed {
+        indices: Range<u32>,
+        base_vertex: i32,
+        instances: Range<u32>,
+    },
+    DrawIndexedIndirect {
+        buffer: gl::types::GLuint,
+        offset: usize,
+        count: u32,
+    },
+}
+
+/// Commands recorded for `GlDevice::submit()`. Buffers, textures and pipelines used have to outlive the submit.
+#[derive(Default)]
+pub struct GlEncoder {
+    commands: Vec<Command>,
+}
+
+impl Encoder<GlDevice> for GlEncoder {
+    fn set_pipeline(&mut self, pipeline: &GlPipeline) {
+        self.commands.push(Command::SetPipeline {
+            program: pipeline.program.id(),
+            render_state: pipeline.render_state,
+            vertex_layout: pipeline.vertex_layout.clone(),
+            vao: pipeline.vao,
+        });
+    }
+
+    fn set_vertex_buffer(&mut self, slot: u32, buffer: &GlBuffer) {
+        self.commands.push(Command::SetVertexBuffer { slot, buffer: buffer.id });
+    }
+
+    fn set_index_buffer(&mut self, buffer: &GlBuffer) {
+        self.commands.push(Command::SetIndexBuffer(buffer.id));
+    }
+
+    fn set_storage_buffer(&mut self, binding: u32, buffer: &GlBuffer) {
+        self.commands.push(Command::SetStorageBuffer { binding, buffer: buffer.id });
+    }
+
+    fn set_texture(&mut self, unit: u32, texture: &Texture) {
+        self.commands.push(Command::SetTexture { unit, texture: texture.id() });
+    }
+
+    fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.commands.push(Command::Draw { vertices, instances });
+    }
+
+    fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.commands.push(Command::DrawIndexed { indices, base_vertex, instances });
+    }
+
+    fn draw_indexed_indirect(&mut self, buffer: &GlBuffer, offset: usize, count: u32) {
+        self.commands.push(Command::DrawIndexedIndirect { buffer: buffer.id, offset, count });
+    }
+}
+
+/// The current OpenGL context, as a `Device`.
+pub struct GlDevice {
+    capabilities: Capabilities,
+}
+
+impl GlDevice {
+    /// Wrap the current context. OpenGL functions have to be loaded already, see `caps::create_context`.
+    pub fn new() -> Self {
+        GlDevice { capabilities: caps::capabilities() }
+    }
+}
+
+impl Device for GlDevice {
+    type Buffer = GlBuffer;
+    type Texture = Texture;
+    type Pipeline = GlPipeline;
+    type Encoder = GlEncoder;
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn create_buffer(&mut self, desc: &BufferDesc, data: &[u8]) -> Result<GlBuffer, Error> {
+        if desc.usage == BufferUsage::Storage && !self.capabilities.storage_buffers {
+            return Err(Error::Unsupported("storage buffers"));
+        }
+
+        let target = desc.usage.gl_target();
+        let mut id: gl::types::GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(target, id);
+            gl::BufferData(
+                target,
+                data.len() as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+                if desc.dynamic { gl::DYNAMIC_DRAW } else { gl::STATIC_DRAW },
+            );
+        }
+        debug_group::label(gl::BUFFER, id, desc.label);
+
+        let category = if desc.dynamic { Category::Streaming } else { Category::Meshes };
+        Ok(GlBuffer { id, target, size: data.len(), _memory: Allocation::new(category, data.len()) })
+    }
+
+    fn write_buffer(&mut self, buffer: &GlBuffer, offset: usize, data: &[u8]) -> Result<(), Error> {
+        if offset + data.len() > buffer.size {
+            let given = offset + data.len();
+            return Err(Error::DataSize { what: "writing past the end of a buffer", size: buffer.size, given });
+        }
+
+        unsafe {
+            gl::BindBuffer(buffer.target, buffer.id);
+            gl::BufferSubData(
+                buffer.target,
+                offset as gl::types::GLintptr,
+                data.len() as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn create_texture(&mut self, desc: &TextureDesc, pixels: Option<&[u8]>) -> Result<Texture, Error> {
+        let texture = match (desc.format, pixels) {
+            (TextureFormat::Rgba8(color_space), Some(pixels)) => {
+                let size = (desc.width * desc.height * 4) as usize;
+                if pixels.len() != size {
+                    return Err(Error::DataSize { what: "RGBA8 texture", size, given: pixels.len() });
+                }
+                Texture::from_rgba8(desc.width, desc.height, pixels, color_space)
+            },
+            (TextureFormat::Rgba8(color_space), None) => Texture::new_color(desc.width, desc.height, color_space),
+            (TextureFormat::Depth, None) => Texture::new_empty(desc.width, desc.height, state::depth_format()),
+            (TextureFormat::Depth, Some(_)) => return Err(Error::Unsupported("uploading depth textures")),
+        };
+        debug_group::label(gl::TEXTURE, texture.id(), desc.label);
+
+        Ok(texture)
+    }
+
+    fn create_pipeline(&mut self, res: &Resource, desc: &PipelineDesc) -> Result<GlPipeline, Error> {
+        let program = Program::from_res(res, desc.shader)?;
+
+        let mut vao: gl::types::GLuint = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao); }
+        debug_group::label(gl::VERTEX_ARRAY, vao, desc.label);
+
+        Ok(GlPipeline {
+            program,
+            render_state: desc.render_state,
+            vertex_layout: desc.vertex_layout.to_vec(),
+            vao,
+        })
+    }
+
+    fn create_encoder(&mut self) -> GlEncoder {
+        GlEncoder::default()
+    }
+
+    fn submit(&mut self, encoder: GlEncoder) {
+        let mut layout: &[VertexAttribute] = &[];
+
+        for command in &encoder.commands {
+            match command {
+                Command::SetPipeline { program, render_state, vertex_layout, vao } => unsafe {
+                    render_state.apply();
+                    gl::UseProgram(*program);
+                    gl::BindVertexArray(*vao);
+                    layout = vertex_layout.as_slice();
+                },
+                Command::SetVertexBuffer { slot, buffer } => unsafe {
+                    gl::BindBuffer(gl::ARRAY_BUFFER, *buffer);
+                    for attribute in layout.iter().filter(|a| a.slot == *slot) {
+                        set_attribute(attribute);
+                    }
+                },
+                Command::SetIndexBuffer(buffer) => unsafe {
+                    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, *buffer);
+                },
+                Command::SetStorageBuffer { binding, buffer } => unsafe {
+                    gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, *binding, *buffer);
+                },
+                Command::SetTexture { unit, texture } => unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                    gl::BindTexture(gl::TEXTURE_2D, *texture);
+                },
+                Command::Draw { vertices, instances } => unsafe {
+                    gl::DrawArraysInstancedBaseInstance(
+                        gl::TRIANGLES,
+                        vertices.start as gl::types::GLint,
+                        vertices.len() as gl::types::GLsizei,
+                        instances.len() as gl::types::GLsizei,
+                        instances.start,
+                    );
+                },
+                Command::DrawIndexed { indices, base_vertex, instances } => unsafe {
+                    draw_elements(indices.clone(), *base_vertex, instances.clone());
+                },
+                Command::DrawIndexedIndirect { buffer, offset, count } => {
+                    self.draw_indexed_indirect(*buffer, *offset, *count);
+                },
+            }
+        }
+    }
+}
+
+impl GlDevice {
+    fn draw_indexed_indirect(&self, buffer: gl::types::GLuint, offset: usize, count: u32) {
+        unsafe { gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer); }
+
+        if self.capabilities.multi_draw_indirect {
+            unsafe {
+                gl::MultiDrawElementsIndirect(
+                    gl::TRIANGLES,
+                    gl::UNSIGNED_INT,
+                    offset as *const gl::types::GLvoid,
+                    count as gl::types::GLsizei,
+                    0,
+                );
+            }
+            return;
+        }
+
+        let mut commands = vec![DrawIndexedIndirect::default(); count as usize];
+        unsafe {
+            gl::GetBufferSubData(
+                gl::DRAW_INDIRECT_BUFFER,
+                offset as gl::types::GLintptr,
+                (commands.len() * std::mem::size_of::<DrawIndexedIndirect>()) as gl::types::GLsizeiptr,
+                commands.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+        }
+
+        for cmd in commands.iter().filter(|cmd| cmd.instance_count > 0) {
+            unsafe {
+                draw_elements(
+                    cmd.first_index..cmd.first_index + cmd.count,
+                    cmd.base_vertex,
+                    cmd.base_instance..cmd.base_instance + cmd.instance_count,
+                );
+            }
+        }
+    }
+}
+
+unsafe fn set_attribute(attribute: &VertexAttribute) {
+    let offset = attribute.offset as *const gl::types::GLvoid;
+    let stride = attribute.stride as gl::types::GLsizei;
+
+    gl::EnableVertexAttribArray(attribute.location);
+    match attribute.kind {
+        AttributeKind::Float => {
+            gl::VertexAttribPointer(attribute.location, attribute.components, gl::FLOAT, gl::FALSE, stride, offset);
+        },
+        AttributeKind::UnsignedInt => {
+            gl::VertexAttribIPointer(attribute.location, attribute.components, gl::UNSIGNED_INT, stride, offset);
+        },
+    }
+    gl::VertexAttribDivisor(attribute.location, attribute.per_instance as gl::types::GLuint);
+}
+
+unsafe fn draw_elements(indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+    gl::DrawElementsInstancedBaseVertexBaseInstance(
+        gl::TRIANGLES,
+        indices.len() as gl::types::GLsizei,
+        gl::UNSIGNED_INT,
+        (indices.start as usize * std::mem::size_of::<u32>()) as *const gl::types::GLvoid,
+        instances.len() as gl::types::GLsizei,
+        base_vertex,
+        instances.start,
+    );
+}
@@ -1,4 +1,15 @@
-use crate::log::LOGGER;
+use std::cmp::Ordering;
+
+use crate::math::frustum::Frustum;
+
+use super::camera::Camera;
+use super::caps;
+use super::debug_group;
+use super::gl_error;
+use super::layer::RenderLayers;
+use super::memory::{Allocation, Category};
+use super::queue::{RenderQueue, SortKey};
+use super::state::{BlendMode, RenderState};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -6,6 +17,8 @@ pub enum Error {
     OpenGLError {
         flag: u32
     },
+    #[error("invalid mesh LODs: {0}")]
+    InvalidLods(&'static str),
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -28,25 +41,256 @@ impl From<(f32, f32, f32)> for f32_f32_f32 {
     }
 }
 
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct f32_f32_f32_f32 {
+    pub d0: f32,
+    pub d1: f32,
+    pub d2: f32,
+    pub d3: f32,
+}
+
+impl f32_f32_f32_f32 {
+    pub fn new(d0: f32, d1: f32, d2: f32, d3: f32) -> Self {
+        f32_f32_f32_f32{ d0, d1, d2, d3 }
+    }
+}
+
+impl From<(f32, f32, f32, f32)> for f32_f32_f32_f32 {
+    fn from(other: (f32, f32, f32, f32)) -> Self {
+        f32_f32_f32_f32::new(other.0, other.1, other.2, other.3)
+    }
+}
+
+/// Colors given without alpha are fully opaque.
+impl From<(f32, f32, f32)> for f32_f32_f32_f32 {
+    fn from(other: (f32, f32, f32)) -> Self {
+        f32_f32_f32_f32::new(other.0, other.1, other.2, 1.0)
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct Vertex {
     pub pos: f32_f32_f32,
-    pub color: f32_f32_f32,
+    pub color: f32_f32_f32_f32,
+    pub normal: f32_f32_f32,
+}
+
+/// Most levels of detail a mesh can have, must match `MAX_LODS` in `cull.comp`.
+pub const MAX_LODS: usize = 8;
+
+/// One level of detail of a mesh: a range of its indices, drawn for instances at most `max_distance` from the eye.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lod {
+    pub first_index: u32,
+    pub count: u32,
+    pub max_distance: f32,
+}
+
+/// The indices of a merged mesh that came from one of its parts, see `Mesh::merge_with_submeshes()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Submesh {
+    pub first_index: u32,
+    pub count: u32,
+}
+
+/// A mesh's index buffer. 16-bit indices take half the memory and bandwidth, but only reach 65536 vertices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// 16-bit if every index fits, 32-bit otherwise.
+    pub fn compact(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|&i| i <= u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<u32> {
+        match self {
+            Indices::U16(indices) => indices.get(i).map(|&i| i as u32),
+            Indices::U32(indices) => indices.get(i).copied(),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = u32> + '_> {
+        match self {
+            Indices::U16(indices) => Box::new(indices.iter().map(|&i| i as u32)),
+            Indices::U32(indices) => Box::new(indices.iter().copied()),
+        }
+    }
+
+    /// Type of the indices for `glDrawElements*`.
+    pub fn gl_type(&self) -> gl::types::GLenum {
+        match self {
+            Indices::U16(_) => gl::UNSIGNED_SHORT,
+            Indices::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+
+    /// Size of one index in bytes.
+    pub fn index_size(&self) -> usize {
+        match self {
+            Indices::U16(_) => std::mem::size_of::<u16>(),
+            Indices::U32(_) => std::mem::size_of::<u32>(),
+        }
+    }
+
+    fn as_ptr(&self) -> *const gl::types::GLvoid {
+        match self {
+            Indices::U16(indices) => indices.as_ptr() as *const gl::types::GLvoid,
+            Indices::U32(indices) => indices.as_ptr() as *const gl::types::GLvoid,
+        }
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        Indices::U32(indices)
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Mesh {
     vertices: Vec<Vertex>,
-    indices: Vec<u32>,
+    indices: Indices,
+    /// Ordered from most to least detailed. The last one is drawn at any distance past the others.
+    lods: Vec<Lod>,
 }
 
 impl Mesh {
+    /// Indices are stored as 16-bit when they all fit, see `Indices::compact()`.
     pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Mesh::with_indices(vertices, Indices::compact(indices))
+    }
+
+    /// A mesh keeping `indices` in the type they're given as.
+    pub fn with_indices(vertices: Vec<Vertex>, indices: Indices) -> Self {
+        let lods = vec![Lod { first_index: 0, count: indices.len() as u32, max_distance: f32::INFINITY }];
         Mesh{
             vertices: vertices,
             indices: indices,
+            lods: lods,
+        }
+    }
+
+    /// Replace the single level of detail covering every index with `lods`, e.g. LOD0..N concatenated into one
+    /// index buffer. They have to be ordered by increasing `max_distance`, and fit in the index buffer.
+    pub fn set_lods(&mut self, lods: Vec<Lod>) -> Result<(), Error> {
+        if lods.is_empty() || lods.len() > MAX_LODS {
+            return Err(Error::InvalidLods("there has to be at least one and at most MAX_LODS"));
+        }
+        if lods.iter().any(|lod| lod.first_index as usize + lod.count as usize > self.indices.len()) {
+            return Err(Error::InvalidLods("a range is past the end of the indices"));
+        }
+        let increasing = |pair: &[Lod]| pair[0].max_distance.partial_cmp(&pair[1].max_distance) == Some(Ordering::Less);
+        if !lods.windows(2).all(increasing) {
+            return Err(Error::InvalidLods("distances have to increase"));
         }
+
+        self.lods = lods;
+        Ok(())
+    }
+
+    /// Transform every part by its matrix and concatenate them into one mesh, so static geometry like level pieces
+    /// sharing a material takes a single draw command instead of one per part. Only the most detailed LOD of each
+    /// part is kept, and the merged mesh has one LOD covering everything.
+    pub fn merge(parts: &[(Mesh, glam::Mat4)]) -> Mesh {
+        Mesh::merge_with_submeshes(parts).0
+    }
+
+    /// `merge()`, also returning where each part's indices ended up, in the order of `parts`. Parts meant for
+    /// different materials can be drawn separately from these ranges.
+    pub fn merge_with_submeshes(parts: &[(Mesh, glam::Mat4)]) -> (Mesh, Vec<Submesh>) {
+        let mut vertices = Vec::with_capacity(parts.iter().map(|(mesh, _)| mesh.vertices.len()).sum());
+        let mut indices = Vec::with_capacity(parts.iter().map(|(mesh, _)| mesh.lods[0].count as usize).sum());
+        let mut submeshes = Vec::with_capacity(parts.len());
+
+        for (mesh, transform) in parts {
+            let base = vertices.len() as u32;
+            let normal_matrix = glam::Mat3::from_mat4(*transform).inverse().transpose();
+
+            vertices.extend(mesh.vertices.iter().map(|vertex| {
+                let pos = transform.transform_point3(glam::vec3(vertex.pos.d0, vertex.pos.d1, vertex.pos.d2));
+                let normal = (normal_matrix * glam::vec3(vertex.normal.d0, vertex.normal.d1, vertex.normal.d2))
+                    .normalize_or_zero();
+                Vertex {
+                    pos: (pos.x, pos.y, pos.z).into(),
+                    color: vertex.color,
+                    normal: (normal.x, normal.y, normal.z).into(),
+                }
+            }));
+
+            let lod = &mesh.lods[0];
+            let first = indices.len();
+            let range = mesh.indices.iter().skip(lod.first_index as usize).take(lod.count as usize);
+            indices.extend(range.map(|i| base + i));
+            submeshes.push(Submesh { first_index: first as u32, count: lod.count });
+            // Mirroring transforms flip triangles inside out, so flip their winding back
+            if transform.determinant() < 0.0 {
+                for triangle in indices[first..].chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+        }
+
+        (Mesh::new(vertices, indices), submeshes)
+    }
+
+    pub fn lods(&self) -> &[Lod] {
+        &self.lods
+    }
+
+    /// Level of detail to draw at `distance`.
+    pub fn lod_for(&self, distance: f32) -> &Lod {
+        self.lods.iter().find(|lod| distance <= lod.max_distance).unwrap_or(&self.lods[self.lods.len() - 1])
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &Indices {
+        &self.indices
+    }
+
+    /// Sphere around the center of the mesh's bounding box enclosing every vertex, as `(center, radius)`.
+    pub fn bounding_sphere(&self) -> (glam::Vec3, f32) {
+        let positions = || self.vertices.iter().map(|v| glam::vec3(v.pos.d0, v.pos.d1, v.pos.d2));
+
+        let min = positions().fold(glam::Vec3::splat(f32::MAX), |acc, p| acc.min(p));
+        let max = positions().fold(glam::Vec3::splat(f32::MIN), |acc, p| acc.max(p));
+        if min.x > max.x {
+            return (glam::Vec3::ZERO, 0.0);
+        }
+
+        let center = (min + max) * 0.5;
+        let radius = positions().fold(0.0f32, |acc, p| acc.max(p.distance(center)));
+
+        (center, radius)
     }
 }
 
@@ -68,11 +312,8 @@ struct DrawElementsIndirectCmd {
     base_vertex: gl::types::GLint,     // indices[i] + baseVertex
     base_instance: gl::types::GLuint,  // used in calculating instance = [gl_InstanceID / divisor] + baseInstance
     
-    // TODO: When getting around to compute shaders, note that GLSL layout std140 rules dictate 16-byte alignment
-    // Since padding would need to be used here, glMultiDraw...Indirect commands must specify a stride of 16 bytes!
-    // padding0: gl::types::GLuint,
-    // padding1: gl::types::GLuint,
-    // padding2: gl::types::GLuint,
+    // Compute shaders writing these must declare the command buffer std430, where a struct of five uints is
+    // tightly packed. Under std140 it would be padded to 32 bytes and glMultiDraw...Indirect would need that stride.
 }
 
 /// Struct encapsulating all meshes, transforms, and buffers required for an OpenGL indirect multidraw call.
@@ -91,8 +332,16 @@ struct DrawElementsIndirectCmd {
 pub struct Batch {
     program_id: gl::types::GLuint,
     mesh: Mesh,
+    /// Local space bounding sphere of `mesh` as `(center, radius)`, used for culling instances.
+    bounds: (glam::Vec3, f32),
+    render_state: Option<RenderState>,
+    layers: RenderLayers,
+    /// Eye the last `select_lods()` picked levels of detail for.
+    lod_eye: Option<glam::Vec3>,
 
     draw_commands: Vec<DrawElementsIndirectCmd>,
+    /// Set when something other than `draw_commands` wrote the indirect buffer, e.g. GPU culling.
+    draw_commands_stale: bool,
     transforms: Vec<glam::Mat4>,
 
     vao: gl::types::GLuint,         // vertex array object
@@ -101,6 +350,7 @@ pub struct Batch {
     idbo: gl::types::GLuint,        // indirect draw buffer object
     drawidbo: gl::types::GLuint,    // draw ID buffer object
     transformbo: gl::types::GLuint, // transforms SSBO
+    _memory: Allocation,
 }
 
 impl Batch {
@@ -126,9 +376,9 @@ impl Batch {
         for i in 0..transforms.len() {
             draw_commands.push(
                 DrawElementsIndirectCmd {
-                    count: mesh.indices.len() as u32,
+                    count: mesh.lods[0].count,
                     instance_count: 1,
-                    first_index: 0,
+                    first_index: mesh.lods[0].first_index,
                     base_vertex: 0,
                     base_instance: i as u32,
                 }
@@ -159,17 +409,27 @@ impl Batch {
                 3,
                 gl::FLOAT,
                 gl::FALSE,
-                (6 * std::mem::size_of::<f32>()) as gl::types::GLsizei,
+                std::mem::size_of::<Vertex>() as gl::types::GLsizei,
                 std::ptr::null(),
             );
             gl::VertexAttribPointer(
                 1,
-                3,
+                4,
                 gl::FLOAT,
                 gl::FALSE,
-                (6 * std::mem::size_of::<f32>()) as gl::types::GLsizei,
+                std::mem::size_of::<Vertex>() as gl::types::GLsizei,
                 (3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
             );
+            // Location 2 is taken by the draw ID below
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribPointer(
+                3,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                std::mem::size_of::<Vertex>() as gl::types::GLsizei,
+                (7 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+            );
 
             gl::GenBuffers(1, &mut drawidbo);
             gl::BindBuffer(gl::ARRAY_BUFFER, drawidbo);
@@ -194,8 +454,8 @@ impl Batch {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, idxbo);
             gl::BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
-                (mesh.indices.len() * std::mem::size_of::<gl::types::GLuint>()) as gl::types::GLsizeiptr,
-                mesh.indices.as_ptr() as *const gl::types::GLvoid,
+                (mesh.indices.len() * mesh.indices.index_size()) as gl::types::GLsizeiptr,
+                mesh.indices.as_ptr(),
                 gl::STATIC_DRAW,
             );
 
@@ -218,43 +478,247 @@ impl Batch {
                 gl::DYNAMIC_DRAW,
             );
             
-            let error = gl::GetError();
-            if error != gl::NO_ERROR {
-                LOGGER().a.error(format!("OpenGL error {}", error).as_str());
-            }
+            debug_group::label(gl::VERTEX_ARRAY, vao, &format!("batch {}", vao));
+            debug_group::label(gl::BUFFER, vbo, &format!("batch {} vertices", vao));
+            debug_group::label(gl::BUFFER, drawidbo, &format!("batch {} draw IDs", vao));
+            debug_group::label(gl::BUFFER, idxbo, &format!("batch {} indices", vao));
+            debug_group::label(gl::BUFFER, transformbo, &format!("batch {} transforms", vao));
+            debug_group::label(gl::BUFFER, idbo, &format!("batch {} draw commands", vao));
+
         }
+        gl_error::check("creating a batch");
         
+        let bounds = mesh.bounding_sphere();
+        let bytes = mesh.vertices.len() * std::mem::size_of::<Vertex>()
+            + mesh.indices.len() * mesh.indices.index_size()
+            + drawids.len() * std::mem::size_of::<gl::types::GLuint>()
+            + transforms.len() * std::mem::size_of::<glam::Mat4>()
+            + draw_commands.len() * std::mem::size_of::<DrawElementsIndirectCmd>();
+
         Ok(Batch {
             program_id: program,
             mesh: mesh,
+            bounds: bounds,
+            render_state: None,
+            layers: RenderLayers::DEFAULT,
+            lod_eye: None,
             transforms: transforms.to_vec(),
 
             draw_commands: draw_commands,
+            draw_commands_stale: false,
             vao: vao,
             vbo: vbo,
             idxbo: idxbo,
             idbo: idbo,
             drawidbo: drawidbo,
             transformbo: transformbo,
+            _memory: Allocation::new(Category::Meshes, bytes),
         })
     }
     
-    pub fn draw(&self) {
+    /// Number of instances in the batch.
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Opt into setting fixed-function state before every draw. With `None`, the batch draws with
+    /// whatever state is currently bound.
+    pub fn set_render_state(&mut self, render_state: Option<RenderState>) {
+        self.render_state = render_state;
+    }
+
+    pub fn render_state(&self) -> Option<&RenderState> {
+        self.render_state.as_ref()
+    }
+
+    /// Layers every instance is on. Only checked by whatever decides which batches a camera draws, like
+    /// `BatchExtractor::draw()`, drawing a batch directly ignores them.
+    pub fn set_layers(&mut self, layers: RenderLayers) {
+        self.layers = layers;
+    }
+
+    pub fn layers(&self) -> RenderLayers {
+        self.layers
+    }
+
+    pub fn program_id(&self) -> gl::types::GLuint {
+        self.program_id
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.render_state.map(|s| s.blend).unwrap_or(BlendMode::Opaque)
+    }
+
+    /// Set the blend mode, opting into a default `RenderState` if the batch didn't have one.
+    /// Transparent modes also disable depth writes, so they don't occlude anything drawn after them.
+    pub fn set_blend_mode(&mut self, blend: BlendMode) {
+        let mut render_state = self.render_state.unwrap_or_default();
+        render_state.blend = blend;
+        render_state.depth_write = !blend.is_transparent();
+        self.render_state = Some(render_state);
+    }
+
+    /// Average position of all instances in the batch.
+    pub fn center(&self) -> glam::Vec3 {
+        if self.transforms.is_empty() {
+            return glam::Vec3::ZERO;
+        }
+
+        let sum = self.transforms
+            .iter()
+            .fold(glam::Vec3::ZERO, |acc, t| acc + t.w_axis.truncate());
+
+        sum / self.transforms.len() as f32
+    }
+
+    /// Reorder the indirect draw commands so the instance farthest from `eye` is drawn first.
+    /// Needed for blended instances to composite correctly with each other.
+    pub fn sort_back_to_front(&mut self, eye: glam::Vec3) {
+        let transforms = &self.transforms;
+        let distance = |cmd: &DrawElementsIndirectCmd| {
+            let base_instance = cmd.base_instance;
+            transforms[base_instance as usize].w_axis.truncate().distance_squared(eye)
+        };
+
+        self.draw_commands.sort_by(|a, b| {
+            distance(b).partial_cmp(&distance(a)).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        self.upload_draw_commands();
+    }
+
+    /// Skip drawing instances whose bounding sphere is entirely outside `frustum`.
+    /// Stays in effect for every following draw until the next `cull()`.
+    pub fn cull(&mut self, frustum: &Frustum) {
+        let (local_center, local_radius) = self.bounds;
+        let mut changed = false;
+
+        for cmd in self.draw_commands.iter_mut() {
+            let transform = &self.transforms[cmd.base_instance as usize];
+            let center = transform.transform_point3(local_center);
+            let scale = transform.x_axis.truncate().length()
+                .max(transform.y_axis.truncate().length())
+                .max(transform.z_axis.truncate().length());
+
+            let instance_count = frustum.intersects_sphere(center, local_radius * scale) as gl::types::GLuint;
+            if cmd.instance_count != instance_count {
+                cmd.instance_count = instance_count;
+                changed = true;
+            }
+        }
+
+        if changed || self.draw_commands_stale {
+            self.upload_draw_commands();
+        }
+    }
+
+    /// Pick each instance's level of detail by the distance from its bounding sphere's center to `eye`. Uploaded by
+    /// the next `cull()`, GPU culling picks them itself for the same eye.
+    pub fn select_lods(&mut self, eye: glam::Vec3) {
+        self.lod_eye = Some(eye);
+        if self.mesh.lods.len() < 2 {
+            return;
+        }
+
+        let local_center = self.bounds.0;
+        for cmd in self.draw_commands.iter_mut() {
+            let center = self.transforms[cmd.base_instance as usize].transform_point3(local_center);
+            let lod = self.mesh.lod_for(center.distance(eye));
+
+            if cmd.first_index != lod.first_index || cmd.count != lod.count {
+                cmd.first_index = lod.first_index;
+                cmd.count = lod.count;
+                self.draw_commands_stale = true;
+            }
+        }
+    }
+
+    /// Number of instances that survived the last `cull()`. Unknown on the CPU for GPU culled batches.
+    pub fn visible_len(&self) -> usize {
+        self.draw_commands.iter().filter(|cmd| cmd.instance_count > 0).count()
+    }
+
+    fn upload_draw_commands(&mut self) {
+        self.draw_commands_stale = false;
         unsafe {
-            gl::UseProgram(self.program_id);
-            gl::BindVertexArray(self.vao);
-            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.transformbo);
             gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.idbo);
-            gl::MultiDrawElementsIndirect(
-                gl::TRIANGLES,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-                self.draw_commands.len() as gl::types::GLsizei,
+            gl::BufferSubData(
+                gl::DRAW_INDIRECT_BUFFER,
                 0,
+                (self.draw_commands.len() * std::mem::size_of::<DrawElementsIndirectCmd>()) as gl::types::GLsizeiptr,
+                self.draw_commands.as_ptr() as *const gl::types::GLvoid,
             );
         }
     }
 
+    pub fn draw(&self) {
+        if let Some(render_state) = &self.render_state {
+            render_state.apply();
+        }
+
+        self.draw_with_program(self.program_id);
+    }
+
+    /// Draw the batch's geometry using a different program, e.g. for outline or depth-only passes.
+    /// Fixed-function state is left untouched.
+    ///
+    /// Without multidraw indirect, every visible command becomes its own instanced draw. The commands on the CPU are
+    /// used for that, so GPU culling has no effect there.
+    pub fn draw_with_program(&self, program: gl::types::GLuint) {
+        unsafe {
+            gl::UseProgram(program);
+            gl::BindVertexArray(self.vao);
+            // Binding 0 is shared by every batch, so it has to be pointed at this batch's transforms each draw
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, self.transformbo);
+
+            if caps::capabilities().multi_draw_indirect {
+                gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.idbo);
+                gl::MultiDrawElementsIndirect(
+                    gl::TRIANGLES,
+                    self.mesh.indices.gl_type(),
+                    std::ptr::null(),
+                    self.draw_commands.len() as gl::types::GLsizei,
+                    0,
+                );
+                return;
+            }
+
+            for cmd in self.draw_commands.iter().filter(|cmd| cmd.instance_count > 0) {
+                gl::DrawElementsInstancedBaseVertexBaseInstance(
+                    gl::TRIANGLES,
+                    cmd.count as gl::types::GLsizei,
+                    self.mesh.indices.gl_type(),
+                    (cmd.first_index as usize * self.mesh.indices.index_size()) as *const gl::types::GLvoid,
+                    cmd.instance_count as gl::types::GLsizei,
+                    cmd.base_vertex,
+                    cmd.base_instance,
+                );
+            }
+        }
+    }
+
+    pub(super) fn bounds(&self) -> (glam::Vec3, f32) {
+        self.bounds
+    }
+
+    pub(super) fn lods(&self) -> &[Lod] {
+        &self.mesh.lods
+    }
+
+    pub(super) fn lod_eye(&self) -> Option<glam::Vec3> {
+        self.lod_eye
+    }
+
+    pub(super) fn transform_buffer(&self) -> gl::types::GLuint {
+        self.transformbo
+    }
+
+    /// Hand out the indirect buffer for writing on the GPU. The next `cull()` or sort re-uploads the CPU commands.
+    pub(super) fn indirect_buffer_mut(&mut self) -> gl::types::GLuint {
+        self.draw_commands_stale = true;
+        self.idbo
+    }
+
     pub fn set_transform(&mut self, index: usize, transform: glam::Mat4) {
         self.transforms[index] = transform;
         unsafe {
@@ -282,6 +746,23 @@ impl Batch {
     }
 }
 
+/// Draw all opaque batches, then all transparent batches ordered back-to-front relative to `camera`.
+/// Instances within each transparent batch are sorted as well. Culling is left to the caller, see `Batch::cull`.
+pub fn draw_sorted(batches: &mut [&mut Batch], camera: &Camera) {
+    let eye = camera.transform.position;
+
+    for batch in batches.iter_mut().filter(|b| b.blend_mode().is_transparent()) {
+        batch.sort_back_to_front(eye);
+    }
+
+    let mut queue = RenderQueue::new();
+    for batch in batches.iter().map(|b| &**b) {
+        let depth = batch.center().distance(eye);
+        queue.push(SortKey::new(0, batch.blend_mode().is_transparent(), batch.program_id(), 0, depth), batch);
+    }
+    queue.execute();
+}
+
 impl Drop for Batch {
     fn drop(&mut self) {
         unsafe {
@@ -1,14 +1,57 @@
-use crate::math::isometry::TransformEuler;
+use crate::math::frustum::Frustum;
+use crate::math::isometry::{Transform3, TransformEuler};
+use crate::math::ext::{wrap_angle_positive, QuatExt};
+use crate::math::units::Radians;
 
+use super::layer::RenderLayers;
+use super::viewport::Viewport;
+
+/// How a `Camera` stores and applies its orientation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Pitch and yaw in `transform.euler_rotation`. Pitch is clamped short of straight up or down, and there's
+    /// no roll, which is what first-person controls want.
+    Euler,
+    /// A quaternion in `rotation`, with +Z forward and +Y up. Free to pitch over the top and roll, without
+    /// gimbal lock, e.g. for flight or spectator cameras.
+    Quaternion,
+}
+
+/// How quickly a smoothed camera catches up with its target, as exponential decay rates per second.
+/// At rate `k`, the remaining distance shrinks to `exp(-k)` of itself every second, so 0 never moves and
+/// `f32::INFINITY` snaps straight to the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothing {
+    pub position: f32,
+    pub rotation: f32,
+}
+
+impl Default for Smoothing {
+    /// Snap to the target, i.e. no smoothing.
+    fn default() -> Self {
+        Smoothing { position: f32::INFINITY, rotation: f32::INFINITY }
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
     pub view: glam::Mat4,
     pub projection: glam::Mat4,
+    /// Position in both modes, the rotation only in `CameraMode::Euler`.
     pub transform: TransformEuler,
+    /// Orientation in `CameraMode::Quaternion`.
+    pub rotation: glam::Quat,
+    /// Layers drawn from this camera, see `RenderLayers`.
+    pub cull_mask: RenderLayers,
+    mode: CameraMode,
+    smoothing: Smoothing,
+    /// Position and orientation `update()` moves towards.
+    target: Option<(glam::Vec3, glam::Quat)>,
     // TODO: specific program variable for rendering?
 
     /// 3D camera vectors used for calculating the current 
     /// view matrix in conjunction with camera rotation.
-    /// ```
+    /// ```text
     ///             ^              ^                    
     ///          up |              | worldup (immutable)
     ///          ___|______        |                    
@@ -47,6 +90,11 @@ impl Camera {
             view: view_,
             projection: projection_,
             transform: transform_,
+            rotation: glam::Quat::look_rotation(front_, worldup_),
+            cull_mask: RenderLayers::ALL,
+            mode: CameraMode::Euler,
+            smoothing: Smoothing::default(),
+            target: None,
             front: front_,
             right: right_,
             up: up_,
@@ -54,6 +102,61 @@ impl Camera {
         }
     }
     
+    /// Camera in `CameraMode::Quaternion` placed and oriented like `transform`, whose scale is ignored.
+    pub fn from_transform3(projection: glam::Mat4, transform: &Transform3, worldup: glam::Vec3) -> Self {
+        let mut camera = Camera::new(
+            glam::Mat4::IDENTITY,
+            projection,
+            TransformEuler::new(transform.position, glam::Vec3::ZERO),
+            worldup,
+        );
+        camera.rotation = transform.rotation.normalize();
+        camera.mode = CameraMode::Quaternion;
+        camera.update_view();
+        camera
+    }
+
+    /// The camera placement as a `Transform3`, in either mode.
+    pub fn transform3(&self) -> Transform3 {
+        Transform3::new(self.transform.position, self.orientation(), glam::Vec3::ONE)
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switch how the orientation is stored, keeping the current view direction. Switching to `CameraMode::Euler`
+    /// drops any roll, and clamps the pitch like `rotate()` does.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+
+        match mode {
+            CameraMode::Quaternion => self.rotation = self.orientation(),
+            CameraMode::Euler => {
+                let front = self.front;
+                self.transform.euler_rotation = glam::vec3(
+                    front.y.clamp(-1.0, 1.0).asin(),
+                    wrap_angle_positive(front.z.atan2(front.x)),
+                    0.0,
+                );
+                self.rotate(Radians(0.0), Radians(0.0));
+            },
+        }
+
+        self.mode = mode;
+        self.update_camera_vectors();
+    }
+
+    /// Current orientation as a quaternion, in either mode.
+    pub fn orientation(&self) -> glam::Quat {
+        match self.mode {
+            CameraMode::Euler => glam::Quat::look_rotation(self.front, self.up),
+            CameraMode::Quaternion => self.rotation,
+        }
+    }
+
     /// Update camera's view matrix. Then, update camera's front-right-up vectors.
     pub fn update_view(&mut self) {
         let target = self.transform.position + self.front;
@@ -62,6 +165,13 @@ impl Camera {
     }
     
     fn update_camera_vectors(&mut self) {
+        if self.mode == CameraMode::Quaternion {
+            self.front = self.rotation * glam::Vec3::Z;
+            self.up = self.rotation * glam::Vec3::Y;
+            self.right = self.front.cross(self.up);
+            return;
+        }
+
         let updated_vec = glam::vec3(
             f32::cos(self.transform.euler_rotation.y) * f32::cos(self.transform.euler_rotation.x),
             f32::sin(self.transform.euler_rotation.x),
@@ -84,10 +194,98 @@ impl Camera {
         self.transform.position += self.up * dist;
     }
 
-    /// Adds an euler rotation to current transform rotation.
+    /// Where `point` ends up on screen, in pixels from the top left corner of `viewport`.
+    /// `None` if it's behind the camera.
+    pub fn project(&self, point: glam::Vec3, viewport: &Viewport) -> Option<glam::Vec2> {
+        let clip = self.projection * self.view * point.extend(1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+
+        let ndc = clip.truncate() / clip.w;
+        Some(glam::vec2(
+            (ndc.x * 0.5 + 0.5) * viewport.width as f32,
+            (0.5 - ndc.y * 0.5) * viewport.height as f32,
+        ))
+    }
+
+    /// World-space ray from the camera through `point`, in pixels from the top left corner of `viewport`,
+    /// as `(origin, normalized direction)`. Assumes a perspective projection.
+    pub fn screen_ray(&self, point: glam::Vec2, viewport: &Viewport) -> (glam::Vec3, glam::Vec3) {
+        let ndc = glam::vec2(
+            point.x / viewport.width as f32 * 2.0 - 1.0,
+            1.0 - point.y / viewport.height as f32 * 2.0,
+        );
+
+        // Undoing only the perspective scale works for any depth mapping, reversed or infinite included
+        let view_direction = glam::vec3(ndc.x / self.projection.x_axis.x, ndc.y / self.projection.y_axis.y, 1.0);
+        let inverse_view = self.view.inverse();
+
+        (
+            inverse_view.transform_point3(glam::Vec3::ZERO),
+            inverse_view.transform_vector3(view_direction).normalize(),
+        )
+    }
+
+    /// Clip planes of the current view and projection.
+    /// This camera seen in a mirror on the plane through `point` with normal `normal`, for rendering reflections.
+    /// Only the view matrix and position are mirrored, so the result shouldn't be moved or `update_view()`-ed.
+    /// Mirroring flips triangle winding, so reflections should be drawn without face culling.
+    pub fn reflected(&self, point: glam::Vec3, normal: glam::Vec3) -> Camera {
+        let normal = normal.normalize();
+        let d = -normal.dot(point);
+        // Householder reflection across the plane n.x + d = 0
+        let reflection = glam::Mat4::from_cols(
+            glam::vec4(1.0 - 2.0 * normal.x * normal.x, -2.0 * normal.x * normal.y, -2.0 * normal.x * normal.z, 0.0),
+            glam::vec4(-2.0 * normal.y * normal.x, 1.0 - 2.0 * normal.y * normal.y, -2.0 * normal.y * normal.z, 0.0),
+            glam::vec4(-2.0 * normal.z * normal.x, -2.0 * normal.z * normal.y, 1.0 - 2.0 * normal.z * normal.z, 0.0),
+            glam::vec4(-2.0 * d * normal.x, -2.0 * d * normal.y, -2.0 * d * normal.z, 1.0),
+        );
+
+        let mut camera = self.clone();
+        camera.view = self.view * reflection;
+        camera.transform.position = reflection.transform_point3(self.transform.position);
+        camera.target = None;
+        camera
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.projection * self.view)
+    }
+
+    /// Builds a left-handed perspective projection matrix. `fov_y` is the vertical field of view, pass
+    /// `Degrees(..).into()` if that's what you have.
+    pub fn perspective(fov_y: Radians, aspect_ratio: f32, z_near: f32, z_far: f32) -> glam::Mat4 {
+        glam::Mat4::perspective_lh(fov_y.0, aspect_ratio, z_near, z_far)
+    }
+
+    /// Builds a left-handed perspective projection with no far plane, mapping `z_near` to depth 1 and infinity to 0.
+    /// Only for use with `state::set_reverse_z(true)`.
+    pub fn perspective_infinite_reverse(fov_y: Radians, aspect_ratio: f32, z_near: f32) -> glam::Mat4 {
+        glam::Mat4::perspective_infinite_reverse_lh(fov_y.0, aspect_ratio, z_near)
+    }
+
+    /// Change the aspect ratio of the current projection, keeping its vertical field of view or height.
+    /// Works for any symmetric perspective or orthographic projection, reverse-Z or not.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.projection.x_axis.x = self.projection.y_axis.y / aspect_ratio;
+    }
+
+    /// Adds pitch and yaw to current transform rotation.
     /// This should be used instead of accessing `transform.euler_rotation` because it also prevents overflow.
-    pub fn rotate(&mut self, euler: glam::Vec3) {
-        self.transform.euler_rotation += euler;
+    /// In `CameraMode::Quaternion`, yaw turns around the world up axis and pitch around the camera's own sideways
+    /// axis, and neither is clamped.
+    pub fn rotate(&mut self, pitch: Radians, yaw: Radians) {
+        if self.mode == CameraMode::Quaternion {
+            // Positive angles turn +Z towards +Y and +X towards +Z, like the Euler angles do
+            let yaw = glam::Quat::from_axis_angle(self.worldup, -yaw.0);
+            let pitch = glam::Quat::from_axis_angle(glam::Vec3::X, -pitch.0);
+            self.rotation = (yaw * self.rotation * pitch).normalize();
+            return;
+        }
+
+        self.transform.euler_rotation.x += pitch.0;
+        self.transform.euler_rotation.y += yaw.0;
         
         // Constrain pitch to (-π/2, π/2)
         // ε needed to remove weirdness since `front` can be flipped at ±π/2 pitch
@@ -98,10 +296,91 @@ impl Camera {
         }
         
         // Smooth wrap current yaw to [0, 2π)
-        // Use fmodulus trick so we can support rotations greater than 2π without snapping to 0 in constant time
-        self.transform.euler_rotation.y = 
-            (std::f32::consts::PI * 2.0 + 
-                (self.transform.euler_rotation.y % (std::f32::consts::PI * 2.0))
-            ) % (std::f32::consts::PI * 2.0);
+        self.transform.euler_rotation.y = wrap_angle_positive(self.transform.euler_rotation.y);
+    }
+
+    /// Roll around the view direction. Only `CameraMode::Quaternion` can roll, Euler cameras ignore this.
+    pub fn roll(&mut self, angle: Radians) {
+        if self.mode == CameraMode::Quaternion {
+            self.rotation = (self.rotation * glam::Quat::from_axis_angle(glam::Vec3::Z, angle.0)).normalize();
+        }
     }
-}
\ No newline at end of file
+
+    /// Turn towards `target` by spherically interpolating from the current orientation, `amount` being 0 for
+    /// no change and 1 to face it fully. Keeps the camera upright relative to the world up axis.
+    pub fn look_at(&mut self, target: glam::Vec3, amount: f32) {
+        let direction = target - self.transform.position;
+        if direction.length_squared() < f32::EPSILON {
+            return;
+        }
+
+        // Looking straight along the world up axis leaves no sideways axis, keep the current up instead
+        let direction = direction.normalize();
+        let up = if direction.cross(self.worldup).length_squared() < 1e-6 { self.up } else { self.worldup };
+        let rotation = self.orientation().slerp(glam::Quat::look_rotation(direction, up), amount.clamp(0.0, 1.0));
+        self.set_orientation(rotation);
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+    }
+
+    /// Have `update()` move the camera towards `position` and `rotation`.
+    pub fn set_target(&mut self, position: glam::Vec3, rotation: glam::Quat) {
+        self.target = Some((position, rotation.normalize()));
+    }
+
+    /// Stop moving towards the target, leaving the camera where it is.
+    pub fn clear_target(&mut self) {
+        self.target = None;
+    }
+
+    /// Third-person follow: target a spot at `offset` in `followed`'s local space, e.g. `(0, 2, -5)` for above
+    /// and behind, looking at it. Call every frame before `update()` as the followed entity moves.
+    pub fn follow(&mut self, followed: &Transform3, offset: glam::Vec3) {
+        let position = followed.position + followed.rotation * offset;
+        let direction = followed.position - position;
+        let rotation = if direction.cross(self.worldup).length_squared() < 1e-6 {
+            // Directly above or below, there's no sideways axis to keep upright with
+            self.orientation()
+        } else {
+            glam::Quat::look_rotation(direction, self.worldup)
+        };
+
+        self.set_target(position, rotation);
+    }
+
+    /// Move towards the target by the smoothing rates, `dt` seconds after the last update. Doesn't touch the
+    /// view matrix, call `update_view()` after.
+    pub fn update(&mut self, dt: f32) {
+        let (position, rotation) = match self.target {
+            Some(target) => target,
+            None => return,
+        };
+
+        // Exponential decay is frame rate independent, unlike lerping by a fixed amount per frame
+        let blend = |rate: f32| if rate.is_infinite() { 1.0 } else { 1.0 - (-rate * dt).exp() };
+
+        self.transform.position = self.transform.position.lerp(position, blend(self.smoothing.position));
+        let rotation = self.orientation().slerp(rotation, blend(self.smoothing.rotation));
+        self.set_orientation(rotation);
+    }
+
+    /// Point the camera the way `rotation` does, as far as the current mode can represent.
+    fn set_orientation(&mut self, rotation: glam::Quat) {
+        match self.mode {
+            CameraMode::Quaternion => self.rotation = rotation.normalize(),
+            CameraMode::Euler => {
+                let front = rotation * glam::Vec3::Z;
+                self.transform.euler_rotation.x = front.y.clamp(-1.0, 1.0).asin();
+                self.transform.euler_rotation.y = front.z.atan2(front.x);
+                self.rotate(Radians(0.0), Radians(0.0));
+            },
+        }
+        self.update_camera_vectors();
+    }
+}
@@ -0,0 +1,193 @@
+//! What the current OpenGL context can do.
+//!
+//! `create_context()` asks for the newest core context the driver will give, stepping down from 4.6 to 4.2, and
+//! records what it got. Subsystems check `capabilities()` instead of assuming 4.3, and fall back where they can:
+//! batches issue one instanced draw per instance instead of a multidraw, GPU culling is left to the CPU, reverse-Z
+//! keeps standard depth, and debug groups and labels do nothing.
+//!
+//! 4.2 is the floor since instanced draws need a base instance. Every shader still needs storage buffers, which a
+//! 4.2 driver has to provide through `ARB_shader_storage_buffer_object`, and they're compiled as the context's GLSL
+//! version instead of the `#version 430` they're written for.
+//! ## Example
+//! ```ignore
+//! let gl_context = gfx::caps::create_context(&video_subsys, &window)?;
+//! if gfx::capabilities().compute_shaders {
+//!     extractor.set_gpu_culling(Some(gfx::GpuCulling::new(&res, 1024)?));
+//! }
+//! ```
+
+use std::sync::Mutex;
+
+use crate::log::LOGGER;
+
+/// Context versions to try, newest first.
+pub const VERSIONS: [(u8, u8); 4] = [(4, 6), (4, 5), (4, 3), (4, 2)];
+/// Oldest version anything can be drawn with.
+pub const MIN_VERSION: (u8, u8) = (4, 2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// `(major, minor)` of the context.
+    pub version: (u8, u8),
+    /// `glMultiDrawElementsIndirect`, OpenGL 4.3 or `ARB_multi_draw_indirect`.
+    pub multi_draw_indirect: bool,
+    /// Compute shaders, OpenGL 4.3. The extension isn't enough, since compute shaders aren't rewritten for it.
+    pub compute_shaders: bool,
+    /// Shader storage buffers, OpenGL 4.3 or `ARB_shader_storage_buffer_object`.
+    pub storage_buffers: bool,
+    /// Debug output, groups and labels, OpenGL 4.3 or `KHR_debug`.
+    pub debug_output: bool,
+    /// `glClipControl`, OpenGL 4.5 or `ARB_clip_control`.
+    pub clip_control: bool,
+}
+
+impl Capabilities {
+    /// What a 4.3 context has, assumed until a context is created through `create_context()`.
+    pub const BASELINE: Capabilities = Capabilities {
+        version: (4, 3),
+        multi_draw_indirect: true,
+        compute_shaders: true,
+        storage_buffers: true,
+        debug_output: true,
+        clip_control: false,
+    };
+
+    /// Query the current context. OpenGL functions have to be loaded already.
+    pub fn detect() -> Self {
+        let version = (integer(gl::MAJOR_VERSION) as u8, integer(gl::MINOR_VERSION) as u8);
+        let extensions = extensions();
+        let has = |name: &str| extensions.iter().any(|e| e == name);
+        let core_43 = version >= (4, 3);
+
+        Capabilities {
+            version,
+            multi_draw_indirect: (core_43 || has("GL_ARB_multi_draw_indirect"))
+                && gl::MultiDrawElementsIndirect::is_loaded(),
+            compute_shaders: core_43 && gl::DispatchCompute::is_loaded(),
+            storage_buffers: core_43 || has("GL_ARB_shader_storage_buffer_object"),
+            debug_output: (core_43 || has("GL_KHR_debug")) && gl::DebugMessageCallback::is_loaded(),
+            clip_control: (version >= (4, 5) || has("GL_ARB_clip_control")) && gl::ClipControl::is_loaded(),
+        }
+    }
+
+    /// The `#version` number shaders are compiled as, e.g. 420 for a 4.2 context.
+    pub fn glsl_version(&self) -> u32 {
+        self.version.0 as u32 * 100 + self.version.1 as u32 * 10
+    }
+
+    /// Why nothing can be drawn with these capabilities, if that's the case.
+    pub fn unsupported_reason(&self) -> Option<String> {
+        if self.version < MIN_VERSION {
+            Some(format!("OpenGL {}.{} is older than {}.{}", self.version.0, self.version.1, MIN_VERSION.0, MIN_VERSION.1))
+        } else if !self.storage_buffers {
+            Some(format!("OpenGL {}.{} has no shader storage buffers", self.version.0, self.version.1))
+        } else {
+            None
+        }
+    }
+
+    /// Log the version and every missing feature along with what's done without it.
+    pub fn log(&self) {
+        LOGGER().a.info(format!("OpenGL {}.{} core context", self.version.0, self.version.1).as_str());
+
+        let fallbacks = [
+            (self.multi_draw_indirect, "multidraw indirect", "drawing instances one at a time"),
+            (self.compute_shaders, "compute shaders", "culling on the CPU"),
+            (self.debug_output, "debug output", "OpenGL errors are only caught by glGetError"),
+            (self.clip_control, "glClipControl", "reverse-Z depth is disabled"),
+        ];
+        for (supported, feature, fallback) in fallbacks {
+            if !supported {
+                LOGGER().a.warn(format!("no {}, {}", feature, fallback).as_str());
+            }
+        }
+    }
+}
+
+static CURRENT: Mutex<Capabilities> = Mutex::new(Capabilities::BASELINE);
+
+/// Capabilities of the context created last.
+pub fn capabilities() -> Capabilities {
+    *CURRENT.lock().unwrap()
+}
+
+/// Replace the capabilities everything else checks, e.g. to force a fallback path for testing it.
+pub fn set_capabilities(capabilities: Capabilities) {
+    *CURRENT.lock().unwrap() = capabilities;
+}
+
+/// Create the newest core context from `VERSIONS` that `window` supports, load the OpenGL functions for it and
+/// record its capabilities. Context attributes other than the version and profile are left as set.
+pub fn create_context(
+    video: &sdl2::VideoSubsystem,
+    window: &sdl2::video::Window,
+) -> Result<sdl2::video::GLContext, String> {
+    let gl_attr = video.gl_attr();
+    gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+
+    let mut last_error = String::from("no versions to try");
+    for &(major, minor) in VERSIONS.iter() {
+        gl_attr.set_context_version(major, minor);
+        match window.gl_create_context() {
+            Ok(gl_context) => {
+                gl::load_with(|s| video.gl_get_proc_address(s) as *const _);
+
+                let capabilities = Capabilities::detect();
+                set_capabilities(capabilities);
+                if let Some(reason) = capabilities.unsupported_reason() {
+                    return Err(reason);
+                }
+                return Ok(gl_context);
+            },
+            Err(e) => {
+                LOGGER().a.debug(format!("no OpenGL {}.{} core context: {}", major, minor, e).as_str());
+                last_error = e;
+            },
+        }
+    }
+
+    Err(format!("failed to create an OpenGL {}.{} or newer core context: {}", MIN_VERSION.0, MIN_VERSION.1, last_error))
+}
+
+/// Point `#version` at the context's GLSL version if it's older than the one `source` is written for, so a 4.2
+/// driver with the right extensions doesn't refuse a 430 shader outright.
+pub fn rewrite_glsl_version(source: &str) -> String {
+    let glsl_version = capabilities().glsl_version();
+    let mut rewritten = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        let written = line.trim_start()
+            .strip_prefix("#version")
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|number| number.parse::<u32>().ok());
+
+        match written {
+            Some(written) if written > glsl_version => {
+                rewritten.push_str(&line.replacen(&written.to_string(), &glsl_version.to_string(), 1));
+            },
+            _ => rewritten.push_str(line),
+        }
+        rewritten.push('\n');
+    }
+
+    rewritten
+}
+
+fn integer(name: gl::types::GLenum) -> i32 {
+    let mut value: gl::types::GLint = 0;
+    unsafe { gl::GetIntegerv(name, &mut value); }
+    value
+}
+
+fn extensions() -> Vec<String> {
+    (0..integer(gl::NUM_EXTENSIONS) as gl::types::GLuint)
+        .filter_map(|i| {
+            let name = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+            if name.is_null() {
+                return None;
+            }
+            let name = unsafe { std::ffi::CStr::from_ptr(name as *const std::os::raw::c_char) };
+            Some(name.to_string_lossy().into_owned())
+        })
+        .collect()
+}
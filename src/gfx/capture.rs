@@ -0,0 +1,324 @@
+//! Recording gameplay clips, frame by frame.
+//!
+//! Reading back the window every frame with `glReadPixels` would stall until the GPU caught up, so `FrameCapture`
+//! reads each frame into one of a ring of pixel pack buffers instead, and only maps a buffer once its fence says
+//! the copy is done, a couple of frames later. Mapped frames go to a writer thread, which saves them as numbered
+//! PNGs or pipes them into `ffmpeg`, encoding to whatever the output's extension says, `.mp4` or `.gif` alike.
+//!
+//! Every frame captured while recording is written, and `ffmpeg` is told they're evenly spaced at the clip's frame
+//! rate, so clips play back at the right speed when the game runs at that rate. Resizing the window ends a recording.
+//! ## Example
+//! ```ignore
+//! let mut capture = gfx::FrameCapture::new(gfx::CaptureOutput::Ffmpeg { path: "clip.mp4".into(), fps: 60 });
+//!
+//! // After everything is drawn to the window, before swapping
+//! if input.is_key_pressed(&sdl2::keyboard::Keycode::R) {
+//!     capture.toggle()?;
+//! }
+//! capture.capture();
+//! window.gl_swap_window();
+//! ```
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use crate::log::LOGGER;
+use crate::system::thread;
+
+use super::memory::{Allocation, Category};
+use super::screenshot;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to write captured frames: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to encode captured frame: {0}")]
+    Encode(#[from] screenshot::Error),
+    #[error("ffmpeg exited with {0}")]
+    Ffmpeg(std::process::ExitStatus),
+    #[error("nothing to capture, the viewport is {}x{}", width, height)]
+    EmptyImage {
+        width: i32,
+        height: i32,
+    },
+}
+
+/// Frames being read back at once. Each is mapped about this many frames after being captured.
+const RING_SIZE: usize = 3;
+/// Frames waiting for the writer thread before capturing blocks on it, so a slow encoder can't take up unbounded
+/// memory.
+const QUEUE_SIZE: usize = 8;
+
+/// Where a recording's frames go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureOutput {
+    /// Numbered PNGs, `frame-00000.png` onwards, in `directory`, which is created if missing.
+    Images { directory: PathBuf },
+    /// Raw frames piped to `ffmpeg`, which has to be on the path, encoding them to `path` at `fps` frames per second.
+    /// GIFs get a palette generated from the whole clip.
+    Ffmpeg { path: PathBuf, fps: u32 },
+}
+
+struct Recording {
+    width: i32,
+    height: i32,
+    /// Captured frames, bottom row first like OpenGL reads them.
+    sender: mpsc::SyncSender<Vec<u8>>,
+    writer: JoinHandle<Result<(), Error>>,
+}
+
+pub struct FrameCapture {
+    output: CaptureOutput,
+    pbos: [gl::types::GLuint; RING_SIZE],
+    /// Slots with a read in flight, oldest first, with the fence signaled once it's done.
+    pending: VecDeque<(usize, gl::types::GLsync)>,
+    next: usize,
+    recording: Option<Recording>,
+    memory: Allocation,
+}
+
+impl FrameCapture {
+    pub fn new(output: CaptureOutput) -> Self {
+        let mut pbos = [0; RING_SIZE];
+        unsafe { gl::GenBuffers(RING_SIZE as gl::types::GLsizei, pbos.as_mut_ptr()); }
+
+        FrameCapture {
+            output,
+            pbos,
+            pending: VecDeque::with_capacity(RING_SIZE),
+            next: 0,
+            recording: None,
+            memory: Allocation::new(Category::Streaming, 0),
+        }
+    }
+
+    pub fn output(&self) -> &CaptureOutput {
+        &self.output
+    }
+
+    /// Takes effect from the next recording.
+    pub fn set_output(&mut self, output: CaptureOutput) {
+        self.output = output;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Start recording the current viewport of the window's default framebuffer, stopping first if already
+    /// recording.
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.stop();
+
+        let [_, _, width, height] = viewport();
+        if width <= 0 || height <= 0 {
+            return Err(Error::EmptyImage { width, height });
+        }
+
+        let bytes = width as usize * height as usize * 4;
+        unsafe {
+            for pbo in &self.pbos {
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, *pbo);
+                let size = bytes as gl::types::GLsizeiptr;
+                gl::BufferData(gl::PIXEL_PACK_BUFFER, size, std::ptr::null(), gl::STREAM_READ);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        self.memory.resize(bytes * RING_SIZE);
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let writer = match &self.output {
+            CaptureOutput::Images { directory } => {
+                std::fs::create_dir_all(directory)?;
+                let directory = directory.clone();
+                thread::spawn_named("capture writer", move || write_images(&directory, width, height, receiver))?
+            },
+            CaptureOutput::Ffmpeg { path, fps } => {
+                // Spawned here rather than on the writer thread, so a missing ffmpeg is reported right away
+                let child = spawn_ffmpeg(path, *fps, width, height)?;
+                thread::spawn_named("capture writer", move || pipe_to_ffmpeg(child, receiver))?
+            },
+        };
+
+        LOGGER().a.info(format!("recording {}x{} to {:?}", width, height, self.output).as_str());
+        self.recording = Some(Recording { width, height, sender, writer });
+        Ok(())
+    }
+
+    /// Stop recording, once the frames still being read back are written. The returned thread finishes writing
+    /// them, and encoding if it's `ffmpeg`. `None` if there was no recording.
+    pub fn stop(&mut self) -> Option<JoinHandle<Result<(), Error>>> {
+        while !self.pending.is_empty() {
+            self.collect(true);
+        }
+
+        let recording = self.recording.take()?;
+        // Dropping the sender ends the writer's loop
+        drop(recording.sender);
+        Some(recording.writer)
+    }
+
+    /// Start recording if stopped and stop if recording, for hotkeys.
+    pub fn toggle(&mut self) -> Result<(), Error> {
+        if self.is_recording() {
+            self.stop();
+            Ok(())
+        } else {
+            self.start()
+        }
+    }
+
+    /// Capture the current viewport of the window's default framebuffer if recording, every frame after it's drawn.
+    pub fn capture(&mut self) {
+        let (width, height) = match &self.recording {
+            Some(recording) => (recording.width, recording.height),
+            None => return,
+        };
+
+        let [x, y, viewport_width, viewport_height] = viewport();
+        if (viewport_width, viewport_height) != (width, height) {
+            LOGGER().a.info("window resized, stopping the recording");
+            self.stop();
+            return;
+        }
+
+        if self.pending.len() == RING_SIZE {
+            self.collect(true);
+        }
+
+        let slot = self.next;
+        self.next = (self.next + 1) % RING_SIZE;
+        unsafe {
+            let mut previous: gl::types::GLint = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[slot]);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            // Into the buffer, so this returns without waiting for the frame to finish
+            gl::ReadPixels(x, y, width, height, gl::RGBA, gl::UNSIGNED_BYTE, std::ptr::null_mut());
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as gl::types::GLuint);
+
+            self.pending.push_back((slot, gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)));
+        }
+
+        // Hand over whatever earlier frames are done by now
+        while self.collect(false) {}
+    }
+
+    /// Send the oldest pending frame to the writer if its read is done, or once it is when `wait`. False if there
+    /// was none to send.
+    fn collect(&mut self, wait: bool) -> bool {
+        let (slot, fence) = match self.pending.front() {
+            Some(&pending) => pending,
+            None => return false,
+        };
+
+        let status = unsafe {
+            if wait {
+                gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, u64::MAX)
+            } else {
+                gl::ClientWaitSync(fence, 0, 0)
+            }
+        };
+        if status != gl::ALREADY_SIGNALED && status != gl::CONDITION_SATISFIED {
+            return false;
+        }
+
+        self.pending.pop_front();
+        unsafe { gl::DeleteSync(fence); }
+
+        let recording = match &self.recording {
+            Some(recording) => recording,
+            None => return true,
+        };
+        let bytes = recording.width as usize * recording.height as usize * 4;
+
+        let mut pixels = vec![0u8; bytes];
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[slot]);
+            let mapped = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, bytes as gl::types::GLsizeiptr, gl::MAP_READ_BIT);
+            if !mapped.is_null() {
+                std::ptr::copy_nonoverlapping(mapped as *const u8, pixels.as_mut_ptr(), bytes);
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        // Only fails if the writer gave up, which it logs itself
+        let _ = recording.sender.send(pixels);
+        true
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        self.stop();
+        unsafe { gl::DeleteBuffers(RING_SIZE as gl::types::GLsizei, self.pbos.as_ptr()); }
+    }
+}
+
+fn viewport() -> [gl::types::GLint; 4] {
+    let mut viewport = [0; 4];
+    unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()); }
+    viewport
+}
+
+fn write_images(directory: &Path, width: i32, height: i32, frames: mpsc::Receiver<Vec<u8>>) -> Result<(), Error> {
+    let mut count = 0;
+    for pixels in frames {
+        let path = directory.join(format!("frame-{:05}.png", count));
+        if let Err(e) = screenshot::write_png(&path, width as u32, height as u32, &pixels) {
+            LOGGER().a.error(format!("failed to save captured frame {}: {}", path.display(), e).as_str());
+            return Err(e.into());
+        }
+        count += 1;
+    }
+
+    LOGGER().a.info(format!("saved {} captured frames to {}", count, directory.display()).as_str());
+    Ok(())
+}
+
+fn spawn_ffmpeg(path: &Path, fps: u32, width: i32, height: i32) -> Result<std::process::Child, Error> {
+    // Frames come bottom row first
+    let is_gif = path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("gif"));
+    let filter = if is_gif { "vflip,split[a][b];[a]palettegen[p];[b][p]paletteuse" } else { "vflip" };
+
+    let size = format!("{}x{}", width, height);
+    let fps = fps.to_string();
+    let child = std::process::Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", size.as_str(), "-r", fps.as_str(), "-i", "-"])
+        .args(["-vf", filter])
+        .arg(path)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+
+    Ok(child)
+}
+
+fn pipe_to_ffmpeg(mut child: std::process::Child, frames: mpsc::Receiver<Vec<u8>>) -> Result<(), Error> {
+    let mut stdin = child.stdin.take().expect("ffmpeg was spawned with a piped stdin");
+    for pixels in frames {
+        if let Err(e) = stdin.write_all(&pixels) {
+            LOGGER().a.error(format!("failed to pipe captured frame to ffmpeg: {}", e).as_str());
+            let _ = child.kill();
+            return Err(e.into());
+        }
+    }
+
+    // Closing stdin tells ffmpeg the clip is over
+    drop(stdin);
+    let status = child.wait()?;
+    if !status.success() {
+        LOGGER().a.error(format!("ffmpeg exited with {}", status).as_str());
+        return Err(Error::Ffmpeg(status));
+    }
+
+    LOGGER().a.info("finished encoding captured clip");
+    Ok(())
+}
@@ -0,0 +1,54 @@
+/// How the values stored in a texture or framebuffer should be interpreted.
+///
+/// Lighting and blending math is only correct on linear values, but 8 bits per channel isn't enough precision to
+/// store dark linear colors without banding, so color data authored by artists is usually stored sRGB encoded.
+/// OpenGL decodes sRGB textures to linear when sampled and, with `GL_FRAMEBUFFER_SRGB` enabled, encodes linear
+/// shader output when writing to an sRGB framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Values are stored as-is. Use for data textures (normals, roughness, etc.) and HDR targets.
+    Linear,
+    /// Values are gamma encoded. Use for albedo/color textures and 8-bit display targets.
+    Srgb,
+}
+
+impl ColorSpace {
+    /// The 8-bit RGBA internal format for this color space.
+    pub fn rgba8_format(&self) -> gl::types::GLenum {
+        match self {
+            ColorSpace::Linear => gl::RGBA8,
+            ColorSpace::Srgb => gl::SRGB8_ALPHA8,
+        }
+    }
+
+    /// Color space OpenGL will use for a texture with the given internal format.
+    pub fn of_format(internal_format: gl::types::GLenum) -> Self {
+        match internal_format {
+            gl::SRGB8 | gl::SRGB8_ALPHA8 | gl::SRGB | gl::SRGB_ALPHA => ColorSpace::Srgb,
+            _ => ColorSpace::Linear,
+        }
+    }
+}
+
+/// Decode a single sRGB channel value in [0, 1] to linear.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a single linear channel value in [0, 1] to sRGB.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decode an sRGB color to linear, leaving alpha untouched.
+pub fn srgb_to_linear_rgba(c: glam::Vec4) -> glam::Vec4 {
+    glam::vec4(srgb_to_linear(c.x), srgb_to_linear(c.y), srgb_to_linear(c.z), c.w)
+}
@@ -0,0 +1,132 @@
+//! Frustum culling on the GPU for batches too large to cull on the CPU every frame.
+//!
+//! A compute pass tests every instance's bounding sphere against the frustum and appends a draw command for each
+//! visible one to the front of the batch's indirect buffer. The rest of the buffer is zeroed beforehand, so the
+//! leftover commands draw nothing and the draw count can stay fixed on the CPU. Each command gets the level of detail
+//! for the instance's distance from the eye of the batch's last `select_lods()`. With an `OcclusionCulling` pyramid,
+//! instances hidden behind the occluders it was built from are dropped as well.
+
+use crate::math::frustum::Frustum;
+use crate::resource::Resource;
+
+use super::batch::Batch;
+use super::caps;
+use super::occlusion::OcclusionCulling;
+use super::shader::{self, Program};
+
+/// Shader storage binding the compute pass writes draw commands to.
+const COMMANDS_BINDING: u32 = 2;
+/// Shader storage binding of the visible instance counter.
+const COUNTER_BINDING: u32 = 3;
+/// Must match `local_size_x` in `cull.comp`.
+const WORKGROUP_SIZE: usize = 64;
+
+pub struct GpuCulling {
+    program: Program,
+    counterbo: gl::types::GLuint,
+    min_instances: usize,
+}
+
+impl GpuCulling {
+    /// Only batches with at least `min_instances` instances are worth a dispatch, smaller ones should be culled
+    /// with `Batch::cull`.
+    pub fn new(res: &Resource, min_instances: usize) -> Result<Self, shader::Error> {
+        let program = Program::from_res_compute(res, "shaders/cull")?;
+
+        let mut counterbo: gl::types::GLuint = 0;
+        let zero: u32 = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut counterbo);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, counterbo);
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                std::mem::size_of::<u32>() as gl::types::GLsizeiptr,
+                &zero as *const u32 as *const gl::types::GLvoid,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+
+        Ok(GpuCulling { program, counterbo, min_instances })
+    }
+
+    pub fn min_instances(&self) -> usize {
+        self.min_instances
+    }
+
+    pub fn set_min_instances(&mut self, min_instances: usize) {
+        self.min_instances = min_instances;
+    }
+
+    /// Whether `batch` should be culled here rather than on the CPU. Transparent batches are always left to the
+    /// CPU, since they get their commands re-sorted (and re-uploaded) every frame anyway. So is everything without
+    /// multidraw indirect, which draws from the CPU's commands.
+    pub fn applies_to(&self, batch: &Batch) -> bool {
+        batch.len() >= self.min_instances
+            && !batch.blend_mode().is_transparent()
+            && caps::capabilities().multi_draw_indirect
+    }
+
+    /// Rewrite `batch`'s indirect buffer on the GPU so only instances intersecting `frustum` are drawn.
+    pub fn cull(&self, batch: &mut Batch, frustum: &Frustum) {
+        self.dispatch(batch, frustum, None);
+    }
+
+    /// `cull()`, also dropping instances `occlusion`'s last pyramid has hidden. `frustum` has to be of the camera
+    /// the pyramid was built from.
+    pub fn cull_occluded(&self, batch: &mut Batch, frustum: &Frustum, occlusion: &OcclusionCulling) {
+        self.dispatch(batch, frustum, Some(occlusion));
+    }
+
+    fn dispatch(&self, batch: &mut Batch, frustum: &Frustum, occlusion: Option<&OcclusionCulling>) {
+        let (center, radius) = batch.bounds();
+
+        self.program.set_vec4f_array("FrustumPlanes", &frustum.planes);
+        self.program.set_vec4f("Bounds", center.extend(radius));
+        self.program.set_i32("InstanceCount", batch.len() as i32);
+        // Without an eye to measure distances from, every instance gets the most detailed level
+        let lods: Vec<glam::Vec4> = batch.lods().iter()
+            .map(|lod| glam::vec4(lod.first_index as f32, lod.count as f32, lod.max_distance.min(f32::MAX), 0.0))
+            .collect();
+        let lod_count = if batch.lod_eye().is_some() { lods.len() } else { 1 };
+        self.program.set_vec4f_array("Lods", &lods);
+        self.program.set_i32("LodCount", lod_count as i32);
+        self.program.set_vec3f("Eye", batch.lod_eye().unwrap_or(glam::Vec3::ZERO));
+        self.program.set_i32("OcclusionEnabled", occlusion.is_some() as i32);
+        if let Some(occlusion) = occlusion {
+            occlusion.apply(&self.program);
+        }
+
+        let transformbo = batch.transform_buffer();
+        let idbo = batch.indirect_buffer_mut();
+        let zero: u32 = 0;
+
+        unsafe {
+            for buffer in [idbo, self.counterbo] {
+                gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, buffer);
+                gl::ClearBufferData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    gl::R32UI,
+                    gl::RED_INTEGER,
+                    gl::UNSIGNED_INT,
+                    &zero as *const u32 as *const gl::types::GLvoid,
+                );
+            }
+
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, transformbo);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, COMMANDS_BINDING, idbo);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, COUNTER_BINDING, self.counterbo);
+
+            gl::UseProgram(self.program.id());
+            gl::DispatchCompute(((batch.len() + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as gl::types::GLuint, 1, 1);
+
+            // Commands are read by the following indirect draw
+            gl::MemoryBarrier(gl::COMMAND_BARRIER_BIT | gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+    }
+}
+
+impl Drop for GpuCulling {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &mut self.counterbo); }
+    }
+}
@@ -0,0 +1,220 @@
+//! Immediate-style debug shapes, e.g. for visualizing physics or camera math.
+//!
+//! Shapes are accumulated as line segments over the frame and drawn in one call by `flush()`, which also
+//! clears them, so anything that should stay visible has to be submitted again every frame.
+//! ## Example
+//! ```ignore
+//! debug_draw.line(glam::Vec3::ZERO, glam::Vec3::X, glam::vec4(1.0, 0.0, 0.0, 1.0));
+//! debug_draw.sphere(center, 0.5, glam::vec4(0.0, 1.0, 0.0, 1.0));
+//!
+//! // After the scene is drawn
+//! debug_draw.flush(&camera);
+//! ```
+//!
+//! Lines can also be drawn in screen space with `line_2d()`, e.g. for graphs, and are drawn by `flush_screen()`.
+
+use crate::resource::Resource;
+
+use super::camera::Camera;
+use super::memory::{Allocation, Category};
+use super::shader::{self, Program};
+use super::state::RenderState;
+use super::viewport::Viewport;
+
+/// Line segments used to approximate each circle of a sphere.
+const SPHERE_SEGMENTS: usize = 24;
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct DebugVertex {
+    pos: [f32; 3],
+    color: [f32; 4],
+}
+
+pub struct DebugDraw {
+    program: Program,
+    vertices: Vec<DebugVertex>,
+    screen_vertices: Vec<DebugVertex>,
+    /// Whether shapes are hidden behind scene geometry.
+    depth_test: bool,
+
+    vao: gl::types::GLuint,
+    vbo: gl::types::GLuint,
+    /// Size in vertices the vertex buffer was last allocated with.
+    capacity: usize,
+    memory: Allocation,
+}
+
+impl DebugDraw {
+    pub fn new(res: &Resource) -> Result<Self, shader::Error> {
+        let program = Program::from_res(res, "shaders/debug")?;
+
+        let mut vao: gl::types::GLuint = 0;
+        let mut vbo: gl::types::GLuint = 0;
+        let stride = std::mem::size_of::<DebugVertex>() as gl::types::GLsizei;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+            );
+
+            gl::BindVertexArray(0);
+        }
+
+        Ok(DebugDraw {
+            program,
+            vertices: Vec::new(),
+            screen_vertices: Vec::new(),
+            depth_test: true,
+            vao,
+            vbo,
+            capacity: 0,
+            memory: Allocation::new(Category::Streaming, 0),
+        })
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    /// With depth testing off, shapes are drawn on top of everything.
+    pub fn set_depth_test(&mut self, depth_test: bool) {
+        self.depth_test = depth_test;
+    }
+
+    pub fn line(&mut self, a: glam::Vec3, b: glam::Vec3, color: glam::Vec4) {
+        let color = color.to_array();
+        self.vertices.push(DebugVertex { pos: a.to_array(), color });
+        self.vertices.push(DebugVertex { pos: b.to_array(), color });
+    }
+
+    /// Screen-space line between `a` and `b`, in pixels from the top left of the viewport.
+    pub fn line_2d(&mut self, a: glam::Vec2, b: glam::Vec2, color: glam::Vec4) {
+        let color = color.to_array();
+        self.screen_vertices.push(DebugVertex { pos: [a.x, a.y, 0.0], color });
+        self.screen_vertices.push(DebugVertex { pos: [b.x, b.y, 0.0], color });
+    }
+
+    /// Axis aligned box spanning `min` to `max`.
+    pub fn aabb(&mut self, min: glam::Vec3, max: glam::Vec3, color: glam::Vec4) {
+        let corner = |i: usize| glam::vec3(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+
+        // Every edge connects two corners differing in exactly one axis bit
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+
+    /// Wireframe sphere made of one circle around each axis.
+    pub fn sphere(&mut self, center: glam::Vec3, radius: f32, color: glam::Vec4) {
+        let circle_point = |i: usize, u: glam::Vec3, v: glam::Vec3| {
+            let angle = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+
+        for (u, v) in [(glam::Vec3::X, glam::Vec3::Y), (glam::Vec3::Y, glam::Vec3::Z), (glam::Vec3::Z, glam::Vec3::X)] {
+            for i in 0..SPHERE_SEGMENTS {
+                self.line(circle_point(i, u, v), circle_point(i + 1, u, v), color);
+            }
+        }
+    }
+
+    /// Draw everything submitted since the last flush as seen from `camera`, then forget it.
+    pub fn flush(&mut self, camera: &Camera) {
+        let state = RenderState {
+            depth_test: self.depth_test,
+            depth_write: false,
+            ..RenderState::default()
+        };
+
+        // Taken out only for the draw, the allocation is reused next frame
+        let mut vertices = std::mem::take(&mut self.vertices);
+        self.draw_vertices(&vertices, camera.view, camera.projection, state);
+        vertices.clear();
+        self.vertices = vertices;
+    }
+
+    /// Draw the screen-space lines submitted since the last flush over `viewport`, then forget them.
+    pub fn flush_screen(&mut self, viewport: &Viewport) {
+        let projection = glam::Mat4::orthographic_rh_gl(0.0, viewport.width as f32, viewport.height as f32, 0.0, -1.0, 1.0);
+
+        let mut vertices = std::mem::take(&mut self.screen_vertices);
+        self.draw_vertices(&vertices, glam::Mat4::IDENTITY, projection, RenderState::fullscreen());
+        vertices.clear();
+        self.screen_vertices = vertices;
+    }
+
+    fn draw_vertices(&mut self, vertices: &[DebugVertex], view: glam::Mat4, projection: glam::Mat4, state: RenderState) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        let size = vertices.len() * std::mem::size_of::<DebugVertex>();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+            // Grow (and orphan) the buffer only when the vertices no longer fit
+            if vertices.len() > self.capacity {
+                self.capacity = vertices.len().next_power_of_two();
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (self.capacity * std::mem::size_of::<DebugVertex>()) as gl::types::GLsizeiptr,
+                    std::ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+                self.memory.resize(self.capacity * std::mem::size_of::<DebugVertex>());
+            }
+
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                size as gl::types::GLsizeiptr,
+                vertices.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+
+        state.apply();
+
+        self.program.set_mat4fv("View", view, 0);
+        self.program.set_mat4fv("Projection", projection, 0);
+
+        unsafe {
+            gl::UseProgram(self.program.id());
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::LINES, 0, vertices.len() as gl::types::GLsizei);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for DebugDraw {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &mut self.vbo);
+            gl::DeleteVertexArrays(1, &mut self.vao);
+        }
+    }
+}
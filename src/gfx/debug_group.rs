@@ -0,0 +1,64 @@
+//! Annotations for graphics debuggers like RenderDoc and Nsight.
+//!
+//! `debug_group()` nests the draw calls made inside it under a named marker in a capture, and `label()` names
+//! OpenGL objects, so captures show "shaders/test" instead of "Program 7". Both are core in OpenGL 4.3 and are
+//! ignored by drivers when no debugger is attached. On older contexts without `KHR_debug` they do nothing.
+//! ## Example
+//! ```ignore
+//! gfx::debug_group("shadow pass", || {
+//!     shadow.begin(sun_direction, glam::Vec3::ZERO, 10.0);
+//!     extractor.draw_with_program(shadow.program_id(), &shadow.frustum());
+//!     shadow.end(&viewport);
+//! });
+//! ```
+
+use super::caps;
+
+/// Longest label every implementation has to accept, `GL_MAX_LABEL_LENGTH` is at least this, terminator included.
+const MAX_LABEL_LENGTH: usize = 256;
+
+/// Run `f` inside a debug group called `name`, returning what it returns.
+pub fn debug_group<R, F: FnOnce() -> R>(name: &str, f: F) -> R {
+    if !caps::capabilities().debug_output {
+        return f();
+    }
+
+    let name = truncate(name);
+    unsafe {
+        gl::PushDebugGroup(
+            gl::DEBUG_SOURCE_APPLICATION,
+            0,
+            name.len() as gl::types::GLsizei,
+            name.as_ptr() as *const gl::types::GLchar,
+        );
+    }
+
+    let result = f();
+
+    unsafe { gl::PopDebugGroup(); }
+    result
+}
+
+/// Name the object `id` of type `identifier`, e.g. `gl::BUFFER` or `gl::PROGRAM`.
+pub fn label(identifier: gl::types::GLenum, id: gl::types::GLuint, name: &str) {
+    if !caps::capabilities().debug_output {
+        return;
+    }
+
+    let name = truncate(name);
+    unsafe {
+        gl::ObjectLabel(identifier, id, name.len() as gl::types::GLsizei, name.as_ptr() as *const gl::types::GLchar);
+    }
+}
+
+fn truncate(name: &str) -> &str {
+    if name.len() < MAX_LABEL_LENGTH {
+        return name;
+    }
+
+    let mut end = MAX_LABEL_LENGTH - 1;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
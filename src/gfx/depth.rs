@@ -0,0 +1,45 @@
+//! CPU mirror of `shaders/include/depth.glsl`, for reconstructing positions from depth buffer reads,
+//! e.g. when picking. Keep the two in sync.
+//!
+//! All functions follow the depth mapping currently set with `state::set_reverse_z`.
+
+use super::state;
+
+/// Depth buffer value to NDC z.
+pub fn depth_to_ndc(depth: f32) -> f32 {
+    if state::is_reverse_z() { depth } else { depth * 2.0 - 1.0 }
+}
+
+/// NDC z to depth buffer value.
+pub fn ndc_to_depth(ndc: f32) -> f32 {
+    if state::is_reverse_z() { ndc } else { ndc * 0.5 + 0.5 }
+}
+
+/// Depth buffer value to positive view space distance along the view axis.
+/// `far` is ignored with reverse-Z, which always uses an infinite far plane.
+pub fn linearize_depth(depth: f32, near: f32, far: f32) -> f32 {
+    let ndc = depth_to_ndc(depth);
+    if state::is_reverse_z() {
+        return near / ndc.max(1e-7);
+    }
+
+    (far * near) / (far - ndc * (far - near))
+}
+
+/// Screen uv in [0, 1] (origin at the bottom left) and depth buffer value to view space position.
+pub fn view_position_from_depth(uv: glam::Vec2, depth: f32, inverse_projection: glam::Mat4) -> glam::Vec3 {
+    let ndc = (uv * 2.0 - glam::Vec2::ONE).extend(depth_to_ndc(depth)).extend(1.0);
+    let view = inverse_projection * ndc;
+
+    view.truncate() / view.w
+}
+
+/// Depth buffer value at `(x, y)` in window coordinates of the bound framebuffer. Stalls until everything drawn
+/// into it so far is done, so only for a handful of reads a frame.
+pub fn read_depth(x: i32, y: i32) -> f32 {
+    let mut depth: f32 = 1.0;
+    unsafe {
+        gl::ReadPixels(x, y, 1, 1, gl::DEPTH_COMPONENT, gl::FLOAT, &mut depth as *mut f32 as *mut gl::types::GLvoid);
+    }
+    depth
+}
@@ -0,0 +1,354 @@
+//! Image-based lighting from HDR environment maps.
+//!
+//! An `EnvironmentMap` is loaded from an equirectangular Radiance `.hdr` panorama and prefiltered on the GPU into:
+//! * a small irradiance cube map, the diffuse light reaching a surface facing each direction,
+//! * a specular cube map whose mips are the environment blurred for increasing roughness, reflections sampling the
+//!   mip for their surface's roughness,
+//! * a lookup table of the scale and bias the specular BRDF applies to a surface's F0, by n.v and roughness.
+//!
+//! That's the split-sum approximation: environment lighting costs a handful of texture reads per fragment, with all
+//! of the integration done once at load time. The standard lit program (`test.frag`) picks it up through
+//! `include/ibl.glsl`, reflecting the environment according to its `Roughness` and `Metallic` uniforms, and adds it
+//! to the light from `Lights`. Lower the ambient there when lighting with an environment, it already covers it.
+//! ## Example
+//! ```ignore
+//! let environment = gfx::EnvironmentMap::from_res(&res, "environments/sky.hdr", 128)?;
+//!
+//! program.set_f32("Roughness", 0.4);
+//! program.set_f32("Metallic", 0.0);
+//! environment.apply(&program);
+//! extractor.draw(&camera);
+//! ```
+
+use crate::resource::{self, Resource};
+
+use super::caps;
+use super::memory::{Allocation, Category};
+use super::shader::{self, Program};
+use super::texture::Texture;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load environment map: {0}")]
+    Load(#[from] resource::Error),
+    #[error("failed to decode environment map: {0}")]
+    Decode(String),
+    #[error("failed to load environment prefiltering programs: {0}")]
+    Program(#[from] shader::Error),
+    #[error("environment maps need compute shaders")]
+    Unsupported,
+}
+
+/// Texture units the maps are bound to, fixed in `include/ibl.glsl`. Units 0 to 3 are taken by materials, shadows,
+/// secondary views and the occlusion pyramid.
+pub const IRRADIANCE_UNIT: u32 = 4;
+pub const SPECULAR_UNIT: u32 = 5;
+pub const BRDF_UNIT: u32 = 6;
+
+/// Width and height of the irradiance map's faces. Irradiance has no detail left to need more.
+const IRRADIANCE_SIZE: i32 = 32;
+/// Width and height of the BRDF lookup table.
+const BRDF_SIZE: i32 = 128;
+/// Mips of the specular map, the last one being fully rough.
+const MAX_SPECULAR_LEVELS: i32 = 6;
+/// GGX samples per texel when prefiltering the specular map and integrating the BRDF.
+const SAMPLE_COUNT: i32 = 512;
+/// Must match `local_size_x` and `local_size_y` of the shaders in `shaders/ibl`.
+const WORKGROUP_SIZE: i32 = 8;
+
+/// A decoded HDR image in linear RGB, three floats per pixel, the first row being the top of the image.
+pub struct HdrImage {
+    pub width: i32,
+    pub height: i32,
+    pub pixels: Vec<f32>,
+}
+
+pub struct EnvironmentMap {
+    irradiance: gl::types::GLuint,
+    /// Roughness 0 at level 0, up to 1 at the last level.
+    specular: gl::types::GLuint,
+    specular_levels: i32,
+    brdf: Texture,
+    intensity: f32,
+    _memory: Allocation,
+}
+
+impl EnvironmentMap {
+    /// Load the Radiance HDR resource `name`, an equirectangular panorama, and prefilter it. `size` is the width and
+    /// height of the specular map's faces, which sharp reflections show at full resolution.
+    pub fn from_res(res: &Resource, name: &str, size: i32) -> Result<Self, Error> {
+        let image = decode_hdr(&res.load_bytes(name)?)?;
+        EnvironmentMap::from_equirect(res, &image, size)
+    }
+
+    /// Prefilter an equirectangular panorama already in memory, e.g. one rendered or generated at runtime.
+    pub fn from_equirect(res: &Resource, image: &HdrImage, size: i32) -> Result<Self, Error> {
+        if !caps::capabilities().compute_shaders {
+            return Err(Error::Unsupported);
+        }
+        let texels = (image.width * image.height) as usize;
+        assert_eq!(image.pixels.len(), texels * 3, "pixel data doesn't match image size");
+
+        let equirect_program = Program::from_res_compute(res, "shaders/ibl/equirect")?;
+        let irradiance_program = Program::from_res_compute(res, "shaders/ibl/irradiance")?;
+        let prefilter_program = Program::from_res_compute(res, "shaders/ibl/prefilter")?;
+        let brdf_program = Program::from_res_compute(res, "shaders/ibl/brdf")?;
+
+        // Only needed while prefiltering, 32-bit so bright spots like the sun don't overflow half floats
+        let equirect = Texture::new_empty(image.width, image.height, gl::RGBA32F);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, equirect.id());
+            // Wraps around horizontally, the left and right edges meet
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as gl::types::GLint);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                image.width,
+                image.height,
+                gl::RGB,
+                gl::FLOAT,
+                image.pixels.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            // Filtering across cube faces is global state, and harmless for every other cube map
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+
+        // The panorama as a mipmapped cube map, which prefiltering samples blurrier mips of against aliasing
+        let source_levels = mip_levels(size);
+        let source = new_cube(size, source_levels);
+        equirect.bind(0);
+        equirect_program.set_i32("Equirect", 0);
+        dispatch(&equirect_program, source, 0, size);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, source);
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+        }
+
+        let irradiance = new_cube(IRRADIANCE_SIZE, 1);
+        // The mip about as wide as the irradiance map, which the convolution's step is fine enough for
+        let source_level = (source_levels - mip_levels(IRRADIANCE_SIZE)).max(0);
+        irradiance_program.set_i32("Environment", 0);
+        irradiance_program.set_f32("SourceLevel", source_level as f32);
+        dispatch(&irradiance_program, irradiance, 0, IRRADIANCE_SIZE);
+
+        let specular_levels = source_levels.min(MAX_SPECULAR_LEVELS);
+        let specular = new_cube(size, specular_levels);
+        prefilter_program.set_i32("Environment", 0);
+        prefilter_program.set_i32("SampleCount", SAMPLE_COUNT);
+        for level in 0..specular_levels {
+            let roughness = if specular_levels > 1 { level as f32 / (specular_levels - 1) as f32 } else { 0.0 };
+            prefilter_program.set_f32("Roughness", roughness);
+            dispatch(&prefilter_program, specular, level, (size >> level).max(1));
+        }
+
+        let brdf = Texture::new_empty(BRDF_SIZE, BRDF_SIZE, gl::RG16F);
+        brdf_program.set_i32("SampleCount", SAMPLE_COUNT);
+        unsafe {
+            gl::UseProgram(brdf_program.id());
+            gl::BindImageTexture(0, brdf.id(), 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RG16F);
+            let groups = ((BRDF_SIZE + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as gl::types::GLuint;
+            gl::DispatchCompute(groups, groups, 1);
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+            gl::DeleteTextures(1, &source);
+        }
+
+        let bytes = cube_bytes(IRRADIANCE_SIZE, 1) + cube_bytes(size, specular_levels);
+
+        Ok(EnvironmentMap {
+            irradiance,
+            specular,
+            specular_levels,
+            brdf,
+            intensity: 1.0,
+            _memory: Allocation::new(Category::Textures, bytes),
+        })
+    }
+
+    /// Scale of the light from the environment, 1 by default.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.max(0.0);
+    }
+
+    /// Bind the maps to their units and set the environment uniforms of `include/ibl.glsl` on `program`.
+    pub fn apply(&self, program: &Program) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + IRRADIANCE_UNIT);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.irradiance);
+            gl::ActiveTexture(gl::TEXTURE0 + SPECULAR_UNIT);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.specular);
+        }
+        self.brdf.bind(BRDF_UNIT);
+
+        program.set_f32("EnvSpecularMaxLevel", (self.specular_levels - 1) as f32);
+        program.set_f32("EnvIntensity", self.intensity);
+    }
+}
+
+impl Drop for EnvironmentMap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &mut self.irradiance);
+            gl::DeleteTextures(1, &mut self.specular);
+        }
+    }
+}
+
+/// Decode a Radiance HDR (RGBE) image, the format most HDR panoramas come in. Supports the usual `-Y <height> +X
+/// <width>` orientation, with flat or run-length encoded scanlines.
+pub fn decode_hdr(bytes: &[u8]) -> Result<HdrImage, Error> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    let magic = reader.line()?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return Err(Error::Decode("not a Radiance HDR file".into()));
+    }
+    // Header variables end at the first empty line
+    loop {
+        let line = reader.line()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(Error::Decode(format!("unsupported pixel format {}", format)));
+            }
+        }
+    }
+
+    let resolution = reader.line()?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (width.parse::<usize>().ok(), height.parse::<usize>().ok()),
+        _ => (None, None),
+    };
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => (width, height),
+        _ => return Err(Error::Decode(format!("unsupported resolution line '{}'", resolution))),
+    };
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    let mut scanline = vec![0u8; width * 4];
+    for _ in 0..height {
+        reader.scanline(&mut scanline)?;
+
+        for rgbe in scanline.chunks_exact(4) {
+            // The exponent is shared by all three channels, each being a fraction of it over 256
+            let scale = if rgbe[3] == 0 { 0.0 } else { 2f32.powi(rgbe[3] as i32 - 136) };
+            pixels.extend(rgbe[..3].iter().map(|&c| c as f32 * scale));
+        }
+    }
+
+    Ok(HdrImage { width: width as i32, height: height as i32, pixels })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, Error> {
+        let byte = *self.bytes.get(self.position).ok_or_else(|| Error::Decode("unexpected end of file".into()))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn line(&mut self) -> Result<String, Error> {
+        let mut line = Vec::new();
+        loop {
+            match self.byte()? {
+                b'\n' => return Ok(String::from_utf8_lossy(&line).trim_end().to_owned()),
+                byte => line.push(byte),
+            }
+        }
+    }
+
+    /// Read one scanline of RGBE pixels into `scanline`, decoding run-length encoding if it has any.
+    fn scanline(&mut self, scanline: &mut [u8]) -> Result<(), Error> {
+        let width = scanline.len() / 4;
+        let header = self.bytes.get(self.position..self.position + 4);
+
+        // Encoded scanlines start with 2, 2 and the width, with each channel encoded separately after
+        let encoded = (8..0x8000).contains(&width)
+            && matches!(header, Some(&[2, 2, high, low]) if (high as usize) << 8 | low as usize == width);
+        if !encoded {
+            for byte in scanline.iter_mut() {
+                *byte = self.byte()?;
+            }
+            return Ok(());
+        }
+
+        self.position += 4;
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                // Above 128 is a run of one value, otherwise that many literal values
+                let count = self.byte()? as usize;
+                let (count, run) = if count > 128 { (count - 128, true) } else { (count, false) };
+                if count == 0 || x + count > width {
+                    return Err(Error::Decode("bad run length in scanline".into()));
+                }
+
+                let value = if run { self.byte()? } else { 0 };
+                for _ in 0..count {
+                    scanline[x * 4 + channel] = if run { value } else { self.byte()? };
+                    x += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Levels of a full mip chain for a texture `size` wide.
+fn mip_levels(size: i32) -> i32 {
+    32 - (size.max(1) as u32).leading_zeros() as i32
+}
+
+fn new_cube(size: i32, levels: i32) -> gl::types::GLuint {
+    let mut id: gl::types::GLuint = 0;
+    let min_filter = if levels > 1 { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+
+    unsafe {
+        gl::GenTextures(1, &mut id);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+        gl::TexStorage2D(gl::TEXTURE_CUBE_MAP, levels, gl::RGBA16F, size, size);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter as gl::types::GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as gl::types::GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as gl::types::GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as gl::types::GLint);
+        gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as gl::types::GLint);
+        gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+    }
+
+    id
+}
+
+/// Bytes of an RGBA16F cube map with `levels` mips.
+fn cube_bytes(size: i32, levels: i32) -> usize {
+    (0..levels).map(|level| ((size >> level).max(1) as usize).pow(2) * 6 * 8).sum()
+}
+
+/// Run `program` over every face of `level` of `cube`, `size` being that level's width. Whatever cube map it reads
+/// has to be bound to unit 0 beforehand.
+fn dispatch(program: &Program, cube: gl::types::GLuint, level: i32, size: i32) {
+    let groups = ((size + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as gl::types::GLuint;
+
+    unsafe {
+        gl::UseProgram(program.id());
+        gl::BindImageTexture(0, cube, level, gl::TRUE, 0, gl::WRITE_ONLY, gl::RGBA16F);
+        gl::DispatchCompute(groups, groups, 6);
+        // Mipmaps are generated from it, or later passes sample it
+        gl::MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
+    }
+}
@@ -0,0 +1,274 @@
+//! Render extraction: turns entities in the `World` into GPU batches so nobody has to manage `Batch`es by hand.
+//!
+//! Every entity with a `MeshHandle`, `MaterialHandle`, `Mobility` and `Transform3` is grouped by
+//! `(mesh, material, mobility, outlined, render layers)` and drawn as one instance of that group's `Batch`. Batches
+//! are created when the first entity of a group appears, rebuilt when the group's size changes, and destroyed once
+//! it's empty. Entities in a hierarchy are drawn with their `GlobalTransform`.
+//! ## Example
+//! ```ignore
+//! let mut extractor = gfx::BatchExtractor::new();
+//! let mesh = extractor.add_mesh(mesh);
+//! let material = extractor.add_material(gfx::Material::new(program.id(), gfx::RenderState::default()));
+//!
+//! world.spawn((mesh, material, gfx::Mobility::Static, Transform3::identity()));
+//!
+//! // Every frame
+//! extractor.extract(&world);
+//! extractor.draw(&camera);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::log::LOGGER;
+use crate::logic::{QueryIter, World};
+use crate::logic::hierarchy::GlobalTransform;
+use crate::logic::query::Has;
+use crate::math::frustum::Frustum;
+use crate::math::isometry::Transform3;
+
+use super::batch::{Batch, Mesh};
+use super::camera::Camera;
+use super::cull::GpuCulling;
+use super::layer::RenderLayers;
+use super::material::Material;
+use super::occlusion::OcclusionCulling;
+use super::outline::{Outline, Outlined};
+use super::queue::{RenderQueue, SortKey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MeshHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialHandle(usize);
+
+/// Whether an entity is expected to move. Decides how often its batch's transforms are uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Mobility {
+    /// Transforms are only re-uploaded when they differ from the last upload.
+    Static,
+    /// Transforms are re-uploaded every frame.
+    Dynamic,
+}
+
+/// `(mesh, material, mobility, has Outlined, RenderLayers or the default)`
+type BatchKey = (MeshHandle, MaterialHandle, Mobility, bool, RenderLayers);
+
+pub struct BatchExtractor {
+    meshes: Vec<Mesh>,
+    materials: Vec<Material>,
+    batches: HashMap<BatchKey, Batch>,
+    /// Transforms last uploaded for each static batch.
+    static_transforms: HashMap<BatchKey, Vec<glam::Mat4>>,
+    outline: Option<Outline>,
+    gpu_culling: Option<GpuCulling>,
+    occlusion: Option<OcclusionCulling>,
+}
+
+impl BatchExtractor {
+    pub fn new() -> Self {
+        BatchExtractor {
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            batches: HashMap::new(),
+            static_transforms: HashMap::new(),
+            outline: None,
+            gpu_culling: None,
+            occlusion: None,
+        }
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh) -> MeshHandle {
+        self.meshes.push(mesh);
+        MeshHandle(self.meshes.len() - 1)
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialHandle {
+        self.materials.push(material);
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    pub fn mesh(&self, handle: MeshHandle) -> &Mesh {
+        &self.meshes[handle.0]
+    }
+
+    pub fn material(&self, handle: MaterialHandle) -> &Material {
+        &self.materials[handle.0]
+    }
+
+    /// Outline pass used for entities marked `Outlined`. Without one, they're drawn like any other entity.
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
+        self.outline = outline;
+    }
+
+    /// Compute pass used to cull large batches. Without one, every batch is culled on the CPU.
+    pub fn set_gpu_culling(&mut self, gpu_culling: Option<GpuCulling>) {
+        self.gpu_culling = gpu_culling;
+    }
+
+    /// Occluder pass run before each `draw()`, hiding instances of GPU culled batches behind other geometry.
+    /// Without GPU culling, it's never run.
+    pub fn set_occlusion_culling(&mut self, occlusion: Option<OcclusionCulling>) {
+        self.occlusion = occlusion;
+    }
+
+    /// Sync GPU batches with the renderable entities currently in `world`.
+    pub fn extract(&mut self, world: &World) {
+        let mut groups: HashMap<BatchKey, Vec<glam::Mat4>> = HashMap::new();
+
+        match world.query::<(
+            &MeshHandle,
+            &MaterialHandle,
+            &Mobility,
+            &Transform3,
+            Option<&GlobalTransform>,
+            Has<Outlined>,
+            Option<&RenderLayers>,
+        )>() {
+            Ok(mut query) => {
+                for (mesh, material, mobility, transform, global, outlined, layers) in query.iter() {
+                    let layers = layers.copied().unwrap_or_default();
+                    groups.entry((*mesh, *material, *mobility, outlined, layers))
+                          .or_insert_with(Vec::new)
+                          .push(global.map_or_else(|| transform.matrix(), |global| global.0));
+                }
+            },
+            Err(e) => {
+                LOGGER().a.error(format!("failed to query renderable entities: {:?}", e).as_str());
+                return;
+            },
+        }
+
+        // Destroy batches whose entities are all gone
+        self.batches.retain(|key, _| groups.contains_key(key));
+        self.static_transforms.retain(|key, _| groups.contains_key(key));
+
+        for (key, transforms) in groups {
+            let (mesh, material, mobility, _, layers) = key;
+
+            let rebuild = match self.batches.get(&key) {
+                Some(batch) => batch.len() != transforms.len(),
+                None => true,
+            };
+
+            if rebuild {
+                let material = self.materials[material.0];
+                match Batch::new(material.program_id, self.meshes[mesh.0].clone(), &transforms) {
+                    Ok(mut batch) => {
+                        batch.set_render_state(Some(material.render_state));
+                        batch.set_layers(layers);
+                        self.batches.insert(key, batch);
+                    },
+                    Err(e) => {
+                        LOGGER().a.error(format!("failed to create batch for {:?}: {}", key, e).as_str());
+                        continue;
+                    },
+                }
+            } else if mobility == Mobility::Dynamic || self.static_transforms.get(&key) != Some(&transforms) {
+                self.batches.get_mut(&key).unwrap().set_all_transforms(&transforms);
+            }
+
+            if mobility == Mobility::Static {
+                self.static_transforms.insert(key, transforms);
+            }
+        }
+    }
+
+    /// Draw every extracted batch visible from `camera` through a `RenderQueue`, transparent ones back-to-front,
+    /// and outlined ones last. Batches on none of the layers in the camera's `cull_mask` are skipped.
+    pub fn draw(&mut self, camera: &Camera) {
+        let eye = camera.transform.position;
+        let frustum = camera.frustum();
+
+        let occluded = self.render_occluders(camera, &frustum);
+        let occlusion = self.occlusion.as_ref().filter(|_| occluded);
+
+        let cull_mask = camera.cull_mask;
+        for batch in self.batches.values_mut().filter(|b| b.layers().intersects(cull_mask)) {
+            batch.select_lods(eye);
+            match &self.gpu_culling {
+                Some(gpu_culling) if gpu_culling.applies_to(batch) => match occlusion {
+                    Some(occlusion) => gpu_culling.cull_occluded(batch, &frustum, occlusion),
+                    None => gpu_culling.cull(batch, &frustum),
+                },
+                _ => batch.cull(&frustum),
+            }
+
+            if batch.blend_mode().is_transparent() {
+                batch.sort_back_to_front(eye);
+            }
+        }
+
+        let mut queue = RenderQueue::new();
+        let mut outlined: Vec<&Batch> = Vec::new();
+
+        for (key, batch) in self.batches.iter().filter(|(_, b)| b.layers().intersects(cull_mask)) {
+            if key.3 && self.outline.is_some() {
+                outlined.push(batch);
+                continue;
+            }
+
+            let transparent = batch.blend_mode().is_transparent();
+            let depth = batch.center().distance(eye);
+            queue.push(SortKey::new(0, transparent, batch.program_id(), key.1.0 as u32, depth), batch);
+        }
+
+        queue.execute();
+
+        if let Some(outline) = &self.outline {
+            for batch in outlined {
+                outline.draw(batch, camera);
+            }
+        }
+    }
+
+    /// Draw every opaque batch depth-only into the occlusion culling pyramid for `camera`, if any batch is going to
+    /// be tested against it. True if the pyramid was built.
+    fn render_occluders(&mut self, camera: &Camera, frustum: &Frustum) -> bool {
+        let gpu_culled = match &self.gpu_culling {
+            Some(gpu_culling) => self.batches.values().any(|batch| gpu_culling.applies_to(batch)),
+            None => false,
+        };
+        let mut occlusion = match self.occlusion.take() {
+            Some(occlusion) if gpu_culled => occlusion,
+            occlusion => {
+                self.occlusion = occlusion;
+                return false;
+            },
+        };
+
+        occlusion.begin(camera);
+        self.draw_layers_with_program(occlusion.program_id(), frustum, camera.cull_mask);
+        occlusion.end();
+
+        self.occlusion = Some(occlusion);
+        true
+    }
+
+    /// Draw every opaque batch with `program` and the current fixed-function state, e.g. for depth-only passes.
+    /// Instances outside `frustum` are skipped.
+    pub fn draw_with_program(&mut self, program: gl::types::GLuint, frustum: &Frustum) {
+        self.draw_layers_with_program(program, frustum, RenderLayers::ALL);
+    }
+
+    /// `draw_with_program()`, only for batches on any of `layers`.
+    pub fn draw_layers_with_program(&mut self, program: gl::types::GLuint, frustum: &Frustum, layers: RenderLayers) {
+        let drawn = |batch: &Batch| !batch.blend_mode().is_transparent() && batch.layers().intersects(layers);
+
+        for batch in self.batches.values_mut().filter(|b| drawn(b)) {
+            match &self.gpu_culling {
+                Some(gpu_culling) if gpu_culling.applies_to(batch) => gpu_culling.cull(batch, frustum),
+                _ => batch.cull(frustum),
+            }
+        }
+
+        let mut queue = RenderQueue::new();
+        for (key, batch) in self.batches.iter().filter(|(_, b)| drawn(b)) {
+            queue.push_with(SortKey::new(0, false, program, key.1.0 as u32, 0.0), batch, program, None);
+        }
+        queue.execute();
+    }
+
+    pub fn batch_count(&self) -> usize {
+        self.batches.len()
+    }
+}
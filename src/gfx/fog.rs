@@ -0,0 +1,73 @@
+//! Distance fog, applied by the standard material through `shaders/include/fog.glsl`.
+//!
+//! `Fog` is uploaded with the rest of the per-view values by `FrameUniforms`, so every program including
+//! `fog.glsl` fades with the same parameters. `Fog::factor` is the CPU mirror of `fogFactor()`, for fading things
+//! that aren't drawn by a fogged program, like labels. Keep the two in sync.
+//! ## Example
+//! ```ignore
+//! frame_uniforms.set_fog(Fog::exponential_squared(sky.fog_color, sky.fog_density));
+//! ```
+//! ```text
+//! #include "include/fog.glsl"
+//!
+//! Out_v4Color = vec4(applyFog(color, In.v3WorldPos), alpha);
+//! ```
+
+/// How fog thickens with distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    None,
+    /// No fog up to `start`, rising linearly to full fog at `end`, in world units from the camera.
+    Linear { start: f32, end: f32 },
+    /// Visibility falls off as `e^-(density * distance)`.
+    Exponential { density: f32 },
+    /// Visibility falls off as `e^-(density * distance)^2`, clearer up close and thicker in the distance.
+    ExponentialSquared { density: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    /// Linear RGB.
+    pub color: glam::Vec3,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog { mode: FogMode::None, color: glam::Vec3::ZERO }
+    }
+}
+
+impl Fog {
+    pub fn linear(color: glam::Vec3, start: f32, end: f32) -> Self {
+        Fog { mode: FogMode::Linear { start, end }, color }
+    }
+
+    pub fn exponential(color: glam::Vec3, density: f32) -> Self {
+        Fog { mode: FogMode::Exponential { density }, color }
+    }
+
+    pub fn exponential_squared(color: glam::Vec3, density: f32) -> Self {
+        Fog { mode: FogMode::ExponentialSquared { density }, color }
+    }
+
+    /// Fraction of a surface `distance` away still visible through the fog, 1 without fog.
+    pub fn factor(&self, distance: f32) -> f32 {
+        match self.mode {
+            FogMode::None => 1.0,
+            FogMode::Linear { start, end } => ((end - distance) / (end - start).max(0.0001)).clamp(0.0, 1.0),
+            FogMode::Exponential { density } => (-density * distance).exp(),
+            FogMode::ExponentialSquared { density } => (-(density * distance).powi(2)).exp(),
+        }
+    }
+
+    /// `FogParams` of `frame.glsl`: mode, density, linear start and end.
+    pub(super) fn params(&self) -> [f32; 4] {
+        match self.mode {
+            FogMode::None => [0.0; 4],
+            FogMode::Linear { start, end } => [1.0, 0.0, start, end],
+            FogMode::Exponential { density } => [2.0, density, 0.0, 0.0],
+            FogMode::ExponentialSquared { density } => [3.0, density, 0.0, 0.0],
+        }
+    }
+}
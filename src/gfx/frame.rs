@@ -0,0 +1,106 @@
+//! The uniform buffer of values shared by every program for the view being drawn.
+//!
+//! `FrameUniforms` owns one small uniform buffer bound to `FRAME_BINDING`, holding the camera position and the
+//! `Fog` parameters. Programs get at it by including `shaders/include/frame.glsl`, or `fog.glsl` which includes
+//! it, instead of each one being handed the same uniforms. `upload()` rewrites it for the camera about to be drawn
+//! from, so views like the minimap and mirror get their own camera position.
+//! The GLSL side of the buffer must match `GpuFrame`:
+//! ```text
+//! layout (std140, binding = 0) uniform FrameBlock
+//! {
+//!     vec4 CameraPosition; // xyz world position, w unused
+//!     vec4 FogColor;       // rgb linear color, a unused
+//!     vec4 FogParams;      // x mode (0 none, 1 linear, 2 exp, 3 exp2), y density, z linear start, w linear end
+//! };
+//! ```
+//! ## Example
+//! ```ignore
+//! let mut frame_uniforms = gfx::FrameUniforms::new();
+//!
+//! // Every frame
+//! frame_uniforms.set_fog(gfx::Fog::exponential(sky.fog_color, sky.fog_density));
+//! // Before drawing from each camera
+//! frame_uniforms.upload(&camera);
+//! extractor.draw(&camera);
+//! ```
+
+use super::camera::Camera;
+use super::fog::Fog;
+use super::memory::{Allocation, Category};
+
+/// Uniform buffer binding point the frame buffer is bound to.
+pub const FRAME_BINDING: u32 = 0;
+
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+struct GpuFrame {
+    camera_position: [f32; 4],
+    fog_color: [f32; 4],
+    fog_params: [f32; 4],
+}
+
+/// Owns the frame uniform buffer.
+pub struct FrameUniforms {
+    fog: Fog,
+    ubo: gl::types::GLuint,
+    _memory: Allocation,
+}
+
+impl FrameUniforms {
+    pub fn new() -> Self {
+        let size = std::mem::size_of::<GpuFrame>();
+        let mut ubo: gl::types::GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, ubo);
+            gl::BufferData(gl::UNIFORM_BUFFER, size as gl::types::GLsizeiptr, std::ptr::null(), gl::DYNAMIC_DRAW);
+        }
+
+        FrameUniforms {
+            fog: Fog::default(),
+            ubo,
+            _memory: Allocation::new(Category::Streaming, size),
+        }
+    }
+
+    pub fn fog(&self) -> Fog {
+        self.fog
+    }
+
+    /// Takes effect from the next `upload()`.
+    pub fn set_fog(&mut self, fog: Fog) {
+        self.fog = fog;
+    }
+
+    /// Upload the values for drawing from `camera` and bind the buffer to `FRAME_BINDING` for subsequent draws.
+    pub fn upload(&self, camera: &Camera) {
+        let frame = GpuFrame {
+            camera_position: camera.transform.position.extend(1.0).to_array(),
+            fog_color: self.fog.color.extend(1.0).to_array(),
+            fog_params: self.fog.params(),
+        };
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                std::mem::size_of::<GpuFrame>() as gl::types::GLsizeiptr,
+                &frame as *const GpuFrame as *const gl::types::GLvoid,
+            );
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, FRAME_BINDING, self.ubo);
+        }
+    }
+}
+
+impl Default for FrameUniforms {
+    fn default() -> Self {
+        FrameUniforms::new()
+    }
+}
+
+impl Drop for FrameUniforms {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &mut self.ubo); }
+    }
+}
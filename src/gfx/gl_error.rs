@@ -0,0 +1,195 @@
+//! Central handling of OpenGL errors and debug messages.
+//!
+//! Errors come from two places: the debug output callback, which reports everything the driver notices with a
+//! severity, and `glGetError`, which `check()` drains after code that wants to know right away. Both go through the
+//! same policy, which decides per severity whether to ignore, log or panic. `glGetError` errors count as high
+//! severity.
+//!
+//! `install()` enables synchronous debug output, so a message is reported from inside the call that caused it, and a
+//! backtrace from a panic points right at it. Panicking can't unwind back through the driver, so a panic from the
+//! callback aborts the process after printing its message.
+//! ## Example
+//! ```ignore
+//! gfx::gl_error::install();
+//! gfx::gl_error::set_strict(true); // Panic on anything that would be logged, in debug builds
+//!
+//! gl::BufferData(...);
+//! gfx::gl_error::check("uploading vertices");
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::log::LOGGER;
+
+use super::caps;
+
+/// How bad a message is, as the driver reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    High,
+    Medium,
+    Low,
+    Notification,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [Severity::High, Severity::Medium, Severity::Low, Severity::Notification];
+
+    fn from_gl(severity: gl::types::GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => Severity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Severity::Medium,
+            gl::DEBUG_SEVERITY_LOW => Severity::Low,
+            _ => Severity::Notification,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::High => "high",
+            Severity::Medium => "medium",
+            Severity::Low => "low",
+            Severity::Notification => "notification",
+        }
+    }
+}
+
+/// What to do with a message of some severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Ignore,
+    Log,
+    Panic,
+}
+
+impl Action {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Action::Ignore,
+            1 => Action::Log,
+            _ => Action::Panic,
+        }
+    }
+}
+
+// Indexed by Severity, defaults log everything but notifications
+static ACTIONS: [AtomicU8; 4] = [
+    AtomicU8::new(Action::Log as u8),
+    AtomicU8::new(Action::Log as u8),
+    AtomicU8::new(Action::Log as u8),
+    AtomicU8::new(Action::Ignore as u8),
+];
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+pub fn action(severity: Severity) -> Action {
+    Action::from_u8(ACTIONS[severity as usize].load(Ordering::Relaxed))
+}
+
+pub fn set_action(severity: Severity, action: Action) {
+    ACTIONS[severity as usize].store(action as u8, Ordering::Relaxed);
+}
+
+/// In debug builds, panic on every message that would otherwise be logged. Has no effect in release builds.
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+pub fn is_strict() -> bool {
+    cfg!(debug_assertions) && STRICT.load(Ordering::Relaxed)
+}
+
+/// The action actually taken for `severity`, with strict mode applied.
+fn effective_action(severity: Severity) -> Action {
+    match action(severity) {
+        Action::Log if is_strict() => Action::Panic,
+        action => action,
+    }
+}
+
+/// Apply the policy to a message.
+pub fn report(severity: Severity, message: &str) {
+    match effective_action(severity) {
+        Action::Ignore => {},
+        Action::Log => {
+            let message = format!("OpenGL ({} severity): {}", severity.name(), message);
+            match severity {
+                Severity::High => LOGGER().a.error(message.as_str()),
+                Severity::Medium | Severity::Low => LOGGER().a.warn(message.as_str()),
+                Severity::Notification => LOGGER().a.debug(message.as_str()),
+            }
+        },
+        Action::Panic => panic!("OpenGL ({} severity): {}", severity.name(), message),
+    }
+}
+
+/// Enable synchronous debug output, reporting every message through the policy. Does nothing without debug output,
+/// leaving only `check()`.
+pub fn install() {
+    if !caps::capabilities().debug_output {
+        return;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+        gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, std::ptr::null(), gl::TRUE);
+    }
+}
+
+extern "system" fn debug_message_callback(
+    source: u32, ty: u32, id: u32, severity: u32, length: i32,
+    message: *const std::os::raw::c_char, user_param: *mut std::os::raw::c_void)
+{
+    let _ = (source, ty, user_param);
+    let severity = Severity::from_gl(severity);
+    // Skip the conversion for messages that go nowhere, notifications can come every frame
+    if effective_action(severity) == Action::Ignore {
+        return;
+    }
+
+    let message = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+    match std::str::from_utf8(message) {
+        Ok(m) => report(severity, &format!("{} (id {})", m, id)),
+        Err(e) => LOGGER().a.error(format!("received invalid OpenGL callback message: {}", e).as_str()),
+    }
+}
+
+/// Drain the `glGetError` queue, oldest first.
+pub fn drain() -> Vec<gl::types::GLenum> {
+    let mut errors = Vec::new();
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+        errors.push(error);
+    }
+
+    errors
+}
+
+/// Drain the `glGetError` queue and report each error as high severity, with `context` saying what was being done.
+/// Returns whether there were any.
+pub fn check(context: &str) -> bool {
+    let errors = drain();
+    for error in &errors {
+        report(Severity::High, &format!("{} while {}", error_name(*error), context));
+    }
+
+    !errors.is_empty()
+}
+
+/// Name of a `glGetError` code.
+pub fn error_name(error: gl::types::GLenum) -> String {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM".to_owned(),
+        gl::INVALID_VALUE => "GL_INVALID_VALUE".to_owned(),
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION".to_owned(),
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION".to_owned(),
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY".to_owned(),
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW".to_owned(),
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW".to_owned(),
+        _ => format!("{:#06x}", error),
+    }
+}
@@ -0,0 +1,278 @@
+//! Particles simulated in a compute shader, for counts too large to update and upload from the CPU every frame.
+//!
+//! Particles live in two storage buffers used in turns. Every `update()` dispatches `gpu_particle.comp`, which ages
+//! and moves the particles of one buffer and spawns new ones, compacting survivors and new particles into the other.
+//! Each buffer comes with a `DrawArraysIndirect` command whose instance count the compute pass writes, so the alive
+//! count never travels back to the CPU: the next dispatch reads it to know how many particles there are, and `draw()`
+//! draws that many camera-facing quads straight from the buffer.
+//!
+//! Emitters take the same `EmitterConfig` as the CPU `ParticleSystem`, with color and size over life baked into
+//! `LIFE_SAMPLES` samples. Particles aren't sorted, so additive blending looks best.
+//! ## Example
+//! ```ignore
+//! let mut sparks = gfx::GpuParticles::new(&res, EmitterConfig {
+//!     rate: 5000.0,
+//!     max_particles: 20000,
+//!     blend: gfx::BlendMode::Additive,
+//!     ..EmitterConfig::default()
+//! }, glam::vec3(0.0, 1.0, 0.0))?;
+//!
+//! // Every frame
+//! sparks.update(frame_time);
+//! // After the opaque scene, with its depth still bound
+//! sparks.draw(&camera);
+//! ```
+
+use std::time::Duration;
+
+use crate::resource::Resource;
+
+use super::camera::Camera;
+use super::caps;
+use super::memory::{Allocation, Category};
+use super::particle::EmitterConfig;
+use super::shader::{self, Program, Shader};
+use super::state::RenderState;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load GPU particle programs: {0}")]
+    Program(#[from] shader::Error),
+    #[error("GPU particles need compute shaders and shader storage buffers")]
+    Unsupported,
+    #[error("emitter needs room for at least one particle")]
+    NoCapacity,
+}
+
+/// Shader storage bindings of `gpu_particle.comp`. The particles being drawn are read from `SRC_PARTICLES_BINDING`.
+const SRC_PARTICLES_BINDING: u32 = 4;
+const DST_PARTICLES_BINDING: u32 = 5;
+const SRC_COMMAND_BINDING: u32 = 6;
+const DST_COMMAND_BINDING: u32 = 7;
+/// Must match `local_size_x` in `gpu_particle.comp`.
+const WORKGROUP_SIZE: usize = 64;
+/// Samples color and size curves are baked into, must match `LIFE_SAMPLES` in `gpu_particle.vert`.
+pub const LIFE_SAMPLES: usize = 16;
+/// `vec4 PositionAge, VelocityLifetime` per particle.
+const PARTICLE_SIZE: usize = std::mem::size_of::<f32>() * 8;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct DrawArraysIndirectCmd {
+    count: u32,
+    instance_count: u32,
+    first: u32,
+    base_instance: u32,
+}
+
+pub struct GpuParticles {
+    config: EmitterConfig,
+    pub position: glam::Vec3,
+    /// Stops spawning while false, particles already alive live out their lifetime.
+    pub enabled: bool,
+    simulate_program: Program,
+    draw_program: Program,
+    color_over_life: Vec<glam::Vec4>,
+    size_over_life: Vec<glam::Vec4>,
+    /// Fraction of a particle owed from previous updates.
+    accumulator: f32,
+    /// Spawned by the next update on top of the rate, from `burst()`.
+    burst: usize,
+    rng: u32,
+    particlebos: [gl::types::GLuint; 2],
+    commandbos: [gl::types::GLuint; 2],
+    /// Which of the buffers holds the latest particles.
+    current: usize,
+    vao: gl::types::GLuint, // empty, quads are generated from gl_VertexID
+    _memory: Allocation,
+}
+
+impl GpuParticles {
+    pub fn new(res: &Resource, config: EmitterConfig, position: glam::Vec3) -> Result<Self, Error> {
+        let capabilities = caps::capabilities();
+        if !capabilities.compute_shaders || !capabilities.storage_buffers {
+            return Err(Error::Unsupported);
+        }
+        if config.max_particles == 0 {
+            return Err(Error::NoCapacity);
+        }
+
+        let simulate_program = Program::from_res_compute(res, "shaders/gpu_particle")?;
+        // Shares the CPU particles' fragment shader, the quads are built differently but shade the same
+        let shaders = [
+            Shader::from_res(res, "shaders/gpu_particle.vert")?,
+            Shader::from_res(res, "shaders/particle.frag")?,
+        ];
+        let draw_program = Program::from_shaders(&shaders).map_err(|message| shader::Error::LinkError {
+            name: "shaders/gpu_particle".into(),
+            message,
+        })?;
+
+        let life = |i: usize| i as f32 / (LIFE_SAMPLES - 1) as f32;
+        let color_over_life = (0..LIFE_SAMPLES).map(|i| config.color.sample(life(i))).collect();
+        let size_at = |i: usize| config.size.sample(life(i));
+        let size_over_life = (0..LIFE_SAMPLES / 4)
+            .map(|i| glam::vec4(size_at(i * 4), size_at(i * 4 + 1), size_at(i * 4 + 2), size_at(i * 4 + 3)))
+            .collect();
+
+        let mut particlebos: [gl::types::GLuint; 2] = [0; 2];
+        let mut commandbos: [gl::types::GLuint; 2] = [0; 2];
+        let mut vao: gl::types::GLuint = 0;
+        let empty = DrawArraysIndirectCmd { count: 6, instance_count: 0, first: 0, base_instance: 0 };
+        let bytes = config.max_particles * PARTICLE_SIZE;
+
+        unsafe {
+            gl::GenBuffers(2, particlebos.as_mut_ptr());
+            gl::GenBuffers(2, commandbos.as_mut_ptr());
+            for (particlebo, commandbo) in particlebos.iter().zip(commandbos.iter()) {
+                gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, *particlebo);
+                gl::BufferData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    bytes as gl::types::GLsizeiptr,
+                    std::ptr::null(),
+                    gl::DYNAMIC_COPY,
+                );
+
+                gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, *commandbo);
+                gl::BufferData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    std::mem::size_of::<DrawArraysIndirectCmd>() as gl::types::GLsizeiptr,
+                    &empty as *const DrawArraysIndirectCmd as *const gl::types::GLvoid,
+                    gl::DYNAMIC_COPY,
+                );
+            }
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+
+        Ok(GpuParticles {
+            config,
+            position,
+            enabled: true,
+            simulate_program,
+            draw_program,
+            color_over_life,
+            size_over_life,
+            accumulator: 0.0,
+            burst: 0,
+            rng: seed | 1,
+            particlebos,
+            commandbos,
+            current: 0,
+            vao,
+            _memory: Allocation::new(Category::Meshes, bytes * 2),
+        })
+    }
+
+    pub fn config(&self) -> &EmitterConfig {
+        &self.config
+    }
+
+    /// Spawn `count` more particles on the next `update()`, as far as `max_particles` allows, enabled or not.
+    pub fn burst(&mut self, count: usize) {
+        self.burst += count;
+    }
+
+    /// Age, move and spawn particles by `dt` on the GPU.
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        if self.enabled {
+            self.accumulator += self.config.rate.max(0.0) * dt;
+        } else {
+            self.accumulator = 0.0;
+        }
+        let due = self.accumulator.floor();
+        self.accumulator -= due;
+        // Anything past capacity is dropped by the compute pass anyway
+        let spawn = (due as usize + std::mem::take(&mut self.burst)).min(self.config.max_particles);
+
+        // xorshift32, only has to differ between updates
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+
+        let (min_lifetime, max_lifetime) = self.config.lifetime;
+        let program = &self.simulate_program;
+        program.set_f32("Dt", dt);
+        program.set_i32("Capacity", self.config.max_particles as i32);
+        program.set_i32("SpawnCount", spawn as i32);
+        program.set_u32("Seed", self.rng);
+        program.set_vec3f("Position", self.position);
+        program.set_vec3f("SpawnExtents", self.config.spawn_extents);
+        program.set_vec3f("Velocity", self.config.velocity);
+        program.set_f32("VelocitySpread", self.config.velocity_spread);
+        program.set_vec3f("Acceleration", self.config.acceleration);
+        program.set_vec2f("Lifetime", glam::vec2(min_lifetime, max_lifetime));
+
+        let (src, dst) = (self.current, 1 - self.current);
+        let zero: u32 = 0;
+
+        unsafe {
+            // Only the instance count, the rest of the command stays as created
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.commandbos[dst]);
+            gl::ClearBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                gl::R32UI,
+                std::mem::size_of::<u32>() as gl::types::GLintptr,
+                std::mem::size_of::<u32>() as gl::types::GLsizeiptr,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                &zero as *const u32 as *const gl::types::GLvoid,
+            );
+
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, SRC_PARTICLES_BINDING, self.particlebos[src]);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, DST_PARTICLES_BINDING, self.particlebos[dst]);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, SRC_COMMAND_BINDING, self.commandbos[src]);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, DST_COMMAND_BINDING, self.commandbos[dst]);
+
+            gl::UseProgram(program.id());
+            // Every thread either simulates a live particle or spawns one, at most capacity of them
+            let threads = self.config.max_particles + spawn;
+            gl::DispatchCompute(((threads + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as gl::types::GLuint, 1, 1);
+
+            // Particles are read by the draw's vertex shader and the command by the draw itself
+            gl::MemoryBarrier(gl::COMMAND_BARRIER_BIT | gl::SHADER_STORAGE_BARRIER_BIT);
+        }
+
+        self.current = dst;
+    }
+
+    /// Draw the particles facing `camera`, over the bound framebuffer. Depth is tested but not written, so this
+    /// belongs after opaque geometry.
+    pub fn draw(&self, camera: &Camera) {
+        let render_state = RenderState {
+            blend: self.config.blend,
+            depth_write: !self.config.blend.is_transparent(),
+            ..RenderState::default()
+        };
+        render_state.apply();
+
+        let program = &self.draw_program;
+        program.use_program();
+        program.set_mat4fv("View", camera.view, 0);
+        program.set_mat4fv("Projection", camera.projection, 0);
+        program.set_vec4f_array("ColorOverLife", &self.color_over_life);
+        program.set_vec4f_array("SizeOverLife", &self.size_over_life);
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, SRC_PARTICLES_BINDING, self.particlebos[self.current]);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.commandbos[self.current]);
+            gl::DrawArraysIndirect(gl::TRIANGLES, std::ptr::null());
+        }
+    }
+}
+
+impl Drop for GpuParticles {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(2, self.particlebos.as_ptr());
+            gl::DeleteBuffers(2, self.commandbos.as_ptr());
+            gl::DeleteVertexArrays(1, &mut self.vao);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Render graph: passes declared with the targets they read and write, run in whatever order that implies.
+//!
+//! Each frame, passes are added to a `RenderGraph` along with the resources they read and write, and a closure doing
+//! the actual drawing. `execute()` then:
+//! - drops passes nothing visible depends on, keeping those writing the backbuffer or an imported resource,
+//! - orders the rest so every resource is written before it's read, keeping the order passes were added in where
+//!   it doesn't matter, and fails on cycles,
+//! - allocates transient targets from a `TargetPool` right before their first writer and hands them back after
+//!   their last reader, so targets with lifetimes that don't overlap share memory, even across frames,
+//! - binds the target a pass writes, with a viewport covering it, and applies the `RenderState` the pass declared,
+//!   if any, before running the pass,
+//! - in debug builds, checks the pass left that target, viewport and state bound, see `validate`.
+//!
+//! Resources are either transient render targets owned by the graph, the window's backbuffer, or imported ones the
+//! graph only orders passes by, like a `ShadowMap` binding its own depth target. A pass writes at most one target.
+//!
+//! Pass closures all get the same `&mut C`, the state passes share, like the extractor and programs, since each one
+//! can't borrow it for itself while the others are waiting to run.
+//! ## Example
+//! ```ignore
+//! let mut graph = RenderGraph::new();
+//! let shadows = graph.import("shadow map");
+//! let scene = graph.create_target("scene", TargetDesc::new(Size::Viewport, gfx::ColorSpace::Srgb));
+//! let backbuffer = graph.backbuffer();
+//!
+//! graph.add_pass("post", &[scene], &[backbuffer], |_, pass| {
+//!     pass.texture(scene).unwrap().bind(0);
+//!     // Draw the fullscreen triangle
+//! });
+//! graph.add_pass("shadow", &[], &[shadows], |frame: &mut Frame, _| {
+//!     frame.shadow.begin(sun, glam::Vec3::ZERO, 10.0);
+//!     frame.extractor.draw_with_program(frame.shadow.program_id(), &frame.shadow.frustum());
+//!     frame.shadow.end(&frame.viewport);
+//! });
+//! graph.add_pass("scene", &[shadows], &[scene], |frame, pass| {
+//!     gfx::state::clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+//!     frame.extractor.draw(&frame.camera);
+//! });
+//!
+//! // Runs shadow, scene, post
+//! graph.execute(&mut pool, &viewport, &mut frame)?;
+//! ```
+
+use std::collections::HashSet;
+
+use super::color::ColorSpace;
+use super::state::RenderState;
+use super::target::{self, RenderTarget};
+use super::texture::Texture;
+use super::validate;
+use super::viewport::Viewport;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to allocate render graph target: {0}")]
+    Target(#[from] target::Error),
+    #[error("render graph has a cycle through pass `{0}`")]
+    Cycle(String),
+    #[error("render graph pass `{0}` writes more than one target")]
+    MultipleTargets(String),
+    #[error("render graph resource `{0}` is read but never written")]
+    NeverWritten(String),
+}
+
+/// Size of a transient target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Size {
+    /// Same as the viewport the graph is executed with.
+    Viewport,
+    /// The viewport scaled by a factor, e.g. 0.5 for half resolution.
+    Scaled(f32),
+    Fixed {
+        width: i32,
+        height: i32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetDesc {
+    pub size: Size,
+    pub color_space: ColorSpace,
+}
+
+impl TargetDesc {
+    pub fn new(size: Size, color_space: ColorSpace) -> Self {
+        TargetDesc { size, color_space }
+    }
+
+    fn resolve(&self, viewport: &Viewport) -> (i32, i32) {
+        match self.size {
+            Size::Viewport => (viewport.width, viewport.height),
+            Size::Scaled(scale) => (
+                ((viewport.width as f32 * scale).round() as i32).max(1),
+                ((viewport.height as f32 * scale).round() as i32).max(1),
+            ),
+            Size::Fixed { width, height } => (width, height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ResourceKind {
+    Transient(TargetDesc),
+    Backbuffer,
+    Imported,
+}
+
+struct Resource {
+    name: String,
+    kind: ResourceKind,
+}
+
+struct Pass<'a, C> {
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    /// Applied before the pass runs, and expected to still be set once it returns.
+    render_state: Option<RenderState>,
+    run: Box<dyn FnOnce(&mut C, &PassContext) + 'a>,
+}
+
+/// What a pass gets to see while it runs.
+pub struct PassContext<'g> {
+    /// Covers the bound target, or is the graph's viewport for passes writing no target.
+    pub viewport: Viewport,
+    targets: &'g [Option<RenderTarget>],
+}
+
+impl<'g> PassContext<'g> {
+    /// Color texture of transient target `id`, while it's allocated.
+    pub fn texture(&self, id: ResourceId) -> Option<&'g Texture> {
+        self.targets.get(id.0)?.as_ref().map(RenderTarget::color)
+    }
+}
+
+/// Render targets kept between frames for graphs to reuse. Targets unused for a few frames are freed.
+#[derive(Default)]
+pub struct TargetPool {
+    /// `(target, frames since it was last used)`
+    free: Vec<(RenderTarget, u32)>,
+}
+
+/// Frames a pooled target may go unused before being freed.
+const POOL_FRAMES: u32 = 3;
+
+impl TargetPool {
+    pub fn new() -> Self {
+        TargetPool::default()
+    }
+
+    fn take(&mut self, width: i32, height: i32, color_space: ColorSpace) -> Result<RenderTarget, target::Error> {
+        let found = self.free.iter().position(|(target, _)| {
+            target.width() == width && target.height() == height && target.color().color_space() == color_space
+        });
+
+        match found {
+            Some(i) => Ok(self.free.swap_remove(i).0),
+            None => RenderTarget::new(width, height, color_space),
+        }
+    }
+
+    fn give(&mut self, target: RenderTarget) {
+        self.free.push((target, 0));
+    }
+
+    fn end_frame(&mut self) {
+        for (_, unused) in self.free.iter_mut() {
+            *unused += 1;
+        }
+        self.free.retain(|(_, unused)| *unused <= POOL_FRAMES);
+    }
+
+    /// Targets waiting to be reused.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+pub struct RenderGraph<'a, C> {
+    resources: Vec<Resource>,
+    passes: Vec<Pass<'a, C>>,
+    backbuffer: Option<ResourceId>,
+}
+
+impl<'a, C> Default for RenderGraph<'a, C> {
+    fn default() -> Self {
+        RenderGraph { resources: Vec::new(), passes: Vec::new(), backbuffer: None }
+    }
+}
+
+impl<'a, C> RenderGraph<'a, C> {
+    pub fn new() -> Self {
+        RenderGraph::default()
+    }
+
+    /// A render target the graph allocates for as long as passes use it.
+    pub fn create_target(&mut self, name: &str, desc: TargetDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Transient(desc))
+    }
+
+    /// A resource living outside the graph. Passes writing one are never dropped.
+    pub fn import(&mut self, name: &str) -> ResourceId {
+        self.add_resource(name, ResourceKind::Imported)
+    }
+
+    /// The window's default framebuffer.
+    pub fn backbuffer(&mut self) -> ResourceId {
+        match self.backbuffer {
+            Some(id) => id,
+            None => {
+                let id = self.add_resource("backbuffer", ResourceKind::Backbuffer);
+                self.backbuffer = Some(id);
+                id
+            },
+        }
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind) -> ResourceId {
+        self.resources.push(Resource { name: name.to_owned(), kind });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn add_pass<F>(&mut self, name: &str, reads: &[ResourceId], writes: &[ResourceId], run: F)
+        where F: FnOnce(&mut C, &PassContext) + 'a
+    {
+        self.push_pass(name, reads, writes, None, Box::new(run));
+    }
+
+    /// A pass drawing with `render_state` throughout. The graph applies it before the pass runs, and in debug builds
+    /// panics if the pass returns with different depth, blend or cull state.
+    pub fn add_pass_with_state<F>(
+        &mut self,
+        name: &str,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+        render_state: RenderState,
+        run: F,
+    ) where F: FnOnce(&mut C, &PassContext) + 'a
+    {
+        self.push_pass(name, reads, writes, Some(render_state), Box::new(run));
+    }
+
+    fn push_pass(
+        &mut self,
+        name: &str,
+        reads: &[ResourceId],
+        writes: &[ResourceId],
+        render_state: Option<RenderState>,
+        run: Box<dyn FnOnce(&mut C, &PassContext) + 'a>,
+    ) {
+        self.passes.push(Pass {
+            name: name.to_owned(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            render_state,
+            run,
+        });
+    }
+
+    /// Run the passes that contribute to the backbuffer or an imported resource, see the module documentation.
+    /// Nothing runs if the graph is invalid.
+    pub fn execute(self, pool: &mut TargetPool, viewport: &Viewport, context: &mut C) -> Result<(), Error> {
+        let order = self.schedule()?;
+
+        // Position in `order` of the first and last pass using each transient target
+        let mut first_use = vec![usize::MAX; self.resources.len()];
+        let mut last_use = vec![0; self.resources.len()];
+        for (position, &pass) in order.iter().enumerate() {
+            let pass = &self.passes[pass];
+            for id in pass.reads.iter().chain(pass.writes.iter()) {
+                first_use[id.0] = first_use[id.0].min(position);
+                last_use[id.0] = last_use[id.0].max(position);
+            }
+
+            let targets = pass.writes.iter().filter(|id| self.resources[id.0].kind != ResourceKind::Imported).count();
+            if targets > 1 {
+                return Err(Error::MultipleTargets(pass.name.clone()));
+            }
+        }
+
+        let mut targets: Vec<Option<RenderTarget>> = (0..self.resources.len()).map(|_| None).collect();
+        let mut passes: Vec<Option<Pass<C>>> = self.passes.into_iter().map(Some).collect();
+
+        for (position, &index) in order.iter().enumerate() {
+            let pass = passes[index].take().unwrap();
+
+            for id in &pass.writes {
+                if let ResourceKind::Transient(desc) = self.resources[id.0].kind {
+                    if first_use[id.0] == position {
+                        let (width, height) = desc.resolve(viewport);
+                        targets[id.0] = Some(pool.take(width, height, desc.color_space)?);
+                    }
+                }
+            }
+
+            let mut pass_viewport = *viewport;
+            for id in &pass.writes {
+                match self.resources[id.0].kind {
+                    ResourceKind::Transient(_) => {
+                        let target = targets[id.0].as_ref().unwrap();
+                        target.bind();
+                        pass_viewport = Viewport::make_viewport(target.width(), target.height());
+                    },
+                    ResourceKind::Backbuffer => RenderTarget::bind_default(),
+                    ResourceKind::Imported => {},
+                }
+            }
+            pass_viewport.use_viewport();
+            if let Some(render_state) = &pass.render_state {
+                render_state.apply();
+            }
+
+            // Passes writing imported resources bind those themselves, so only their state can be checked
+            let expected = validate::is_enabled().then(|| {
+                let binds_own_target = pass.writes.iter().any(|id| self.resources[id.0].kind == ResourceKind::Imported);
+                validate::Expected::current(binds_own_target, pass.render_state)
+            });
+
+            (pass.run)(context, &PassContext { viewport: pass_viewport, targets: &targets });
+
+            if let Some(expected) = expected {
+                expected.check(&pass.name);
+            }
+
+            for (id, resource) in self.resources.iter().enumerate() {
+                if matches!(resource.kind, ResourceKind::Transient(_)) && last_use[id] == position {
+                    if let Some(target) = targets[id].take() {
+                        pool.give(target);
+                    }
+                }
+            }
+        }
+
+        RenderTarget::bind_default();
+        viewport.use_viewport();
+        pool.end_frame();
+
+        Ok(())
+    }
+
+    /// Indices of the passes to run, in order.
+    fn schedule(&self) -> Result<Vec<usize>, Error> {
+        let writers = |id: ResourceId| -> Vec<usize> {
+            self.passes.iter().enumerate().filter(|(_, p)| p.writes.contains(&id)).map(|(i, _)| i).collect()
+        };
+
+        // Walk back from the passes with visible results to everything they read
+        let mut live: HashSet<usize> = self.passes.iter().enumerate()
+            .filter(|(_, pass)| {
+                pass.writes.iter().any(|id| !matches!(self.resources[id.0].kind, ResourceKind::Transient(_)))
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let mut stack: Vec<usize> = live.iter().copied().collect();
+        while let Some(pass) = stack.pop() {
+            for &id in &self.passes[pass].reads {
+                let writers = writers(id);
+                if writers.is_empty() && matches!(self.resources[id.0].kind, ResourceKind::Transient(_)) {
+                    return Err(Error::NeverWritten(self.resources[id.0].name.clone()));
+                }
+                for writer in writers {
+                    if live.insert(writer) {
+                        stack.push(writer);
+                    }
+                }
+            }
+        }
+
+        // Edges from every writer of a resource to its readers, and between its writers in the order they were added
+        let mut dependencies: Vec<HashSet<usize>> = vec![HashSet::new(); self.passes.len()];
+        for id in (0..self.resources.len()).map(ResourceId) {
+            let writers: Vec<usize> = writers(id).into_iter().filter(|w| live.contains(w)).collect();
+            for pair in writers.windows(2) {
+                dependencies[pair[1]].insert(pair[0]);
+            }
+            for (reader, pass) in self.passes.iter().enumerate() {
+                if live.contains(&reader) && pass.reads.contains(&id) {
+                    dependencies[reader].extend(writers.iter().filter(|&&w| w != reader));
+                }
+            }
+        }
+
+        // Kahn's algorithm, always taking the earliest added pass that's ready
+        let mut order = Vec::with_capacity(live.len());
+        let mut done = vec![false; self.passes.len()];
+        while order.len() < live.len() {
+            let ready = (0..self.passes.len())
+                .find(|&i| live.contains(&i) && !done[i] && dependencies[i].iter().all(|&d| done[d]));
+
+            match ready {
+                Some(pass) => {
+                    done[pass] = true;
+                    order.push(pass);
+                },
+                None => {
+                    let stuck = (0..self.passes.len()).find(|&i| live.contains(&i) && !done[i]).unwrap();
+                    return Err(Error::Cycle(self.passes[stuck].name.clone()));
+                },
+            }
+        }
+
+        Ok(order)
+    }
+}
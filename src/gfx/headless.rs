@@ -0,0 +1,99 @@
+//! Rendering without a visible window.
+//!
+//! OpenGL always needs a context, so this creates a hidden window for it, and when there's no display at all
+//! (like on CI machines) falls back to SDL's `offscreen` video driver, which makes an EGL context with no window system.
+//! Everything is drawn into a RenderTarget that can be read back afterwards, for rendering tests or thumbnails.
+//! ## Example
+//! ```ignore
+//! let headless = gfx::HeadlessContext::new(256, 256)?;
+//! headless.target().bind();
+//! // ... draw
+//! let rgba = headless.read_pixels();
+//! ```
+
+use crate::log::LOGGER;
+
+use super::caps;
+use super::color::ColorSpace;
+use super::target::{self, RenderTarget};
+use super::viewport::Viewport;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to create a headless OpenGL context: {}", message)]
+    Context {
+        message: String,
+    },
+
+    #[error("failed to create render target")]
+    Target(#[from] target::Error),
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Context { message }
+    }
+}
+
+/// The newest OpenGL core context `caps::create_context()` can get, with nothing on screen, and a RenderTarget to draw into.
+pub struct HeadlessContext {
+    // Fields drop in order, and the target has to go while the context is still alive
+    target: RenderTarget,
+    _gl_context: sdl2::video::GLContext,
+    _window: sdl2::video::Window,
+    video: sdl2::VideoSubsystem,
+    _sdl: sdl2::Sdl,
+}
+
+impl HeadlessContext {
+    /// Create the context and load the OpenGL functions for it, with a `width` x `height` linear color target.
+    pub fn new(width: i32, height: i32) -> Result<Self, Error> {
+        let sdl = sdl2::init()?;
+        let video = match sdl.video() {
+            Ok(video) => video,
+            Err(e) => {
+                LOGGER().a.warn(format!("no display for a hidden window ({}), trying the offscreen driver", e).as_str());
+                sdl2::hint::set("SDL_VIDEODRIVER", "offscreen");
+                sdl.video()?
+            },
+        };
+
+        let gl_attr = video.gl_attr();
+        gl_attr.set_depth_size(24);
+        gl_attr.set_stencil_size(8);
+
+        let window = video.window("headless", width as u32, height as u32)
+            .opengl()
+            .hidden()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let gl_context = caps::create_context(&video, &window)?;
+
+        let target = RenderTarget::new(width, height, ColorSpace::Linear)?;
+
+        Ok(HeadlessContext { target, _gl_context: gl_context, _window: window, video, _sdl: sdl })
+    }
+
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    pub fn target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.target
+    }
+
+    /// A viewport covering the whole target.
+    pub fn viewport(&self) -> Viewport {
+        Viewport::make_viewport(self.target.width(), self.target.height())
+    }
+
+    pub fn video(&self) -> &sdl2::VideoSubsystem {
+        &self.video
+    }
+
+    /// Wait for rendering to finish and read the target back as RGBA8, top row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        unsafe { gl::Finish(); }
+        self.target.read_pixels()
+    }
+}
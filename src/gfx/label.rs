@@ -0,0 +1,237 @@
+//! Screen-space text pinned to places in the world: entity names, and damage numbers floating up and fading out.
+//!
+//! Unlike `TextRenderer::draw_world()`, the text stays the same size on screen at any distance. Entities get a label
+//! from a `WorldLabel` next to their `Transform3`, short-lived text like damage numbers is spawned on `WorldLabels`
+//! directly. Labels behind scene geometry fade out, which is found by comparing their depth against the depth
+//! buffer, so `update()` has to run while the scene's target is still bound and its depth is complete. Floating text
+//! moves separately, in `advance()`, which the `advance_world_labels` system calls on the world's `WorldLabels`.
+//! ## Example
+//! ```ignore
+//! world.spawn((mesh, material, gfx::Mobility::Dynamic, transform, WorldLabel::new("Goblin")));
+//! world.insert_resource(WorldLabels::new());
+//! schedule.add_system(Stage::Update, "world labels", advance_world_labels);
+//! world.resource_mut::<WorldLabels>().unwrap().damage_number(hit.point, 12.0);
+//!
+//! // Every frame, right after drawing the scene
+//! let mut labels = world.resource_mut::<WorldLabels>().unwrap();
+//! labels.update(&world, frame_time, &camera, &viewport);
+//! // After resolving the scene to the window
+//! labels.draw(&world, &mut text, &camera, &viewport, &screen);
+//! text.flush(&screen);
+//! ```
+
+use std::time::Duration;
+
+use crate::log::LOGGER;
+use crate::logic::system::FrameTime;
+use crate::logic::{QueryIter, Res, ResMut, World};
+use crate::math::isometry::Transform3;
+
+use super::camera::Camera;
+use super::depth;
+use super::state;
+use super::text::TextRenderer;
+use super::viewport::Viewport;
+
+/// How much of fully visible a label gets per second while fading in or out of occlusion.
+const OCCLUSION_FADE_SPEED: f32 = 6.0;
+/// Slack for depth comparisons, so labels resting on a surface aren't hidden by it.
+const DEPTH_EPSILON: f32 = 1e-4;
+
+/// Component labeling an entity with `text`, centered above its position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldLabel {
+    pub text: String,
+    pub color: glam::Vec4,
+    /// From the entity's position to where the label is anchored, in world space.
+    pub offset: glam::Vec3,
+    pub scale: f32,
+    /// Keep drawing the label over whatever is in front of it.
+    pub always_visible: bool,
+    /// In [0, 1], how far the label has faded in from being occluded.
+    visibility: f32,
+}
+
+impl WorldLabel {
+    pub fn new(text: &str) -> Self {
+        WorldLabel {
+            text: text.to_owned(),
+            color: glam::Vec4::ONE,
+            offset: glam::vec3(0.0, 0.5, 0.0),
+            scale: 1.0,
+            always_visible: false,
+            visibility: 1.0,
+        }
+    }
+}
+
+/// Text floating up from where it was spawned, fading out over its lifetime.
+#[derive(Debug, Clone, PartialEq)]
+struct Floating {
+    text: String,
+    position: glam::Vec3,
+    velocity: glam::Vec3,
+    color: glam::Vec4,
+    scale: f32,
+    age: f32,
+    lifetime: f32,
+    visibility: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct WorldLabels {
+    floating: Vec<Floating>,
+}
+
+impl WorldLabels {
+    pub fn new() -> Self {
+        WorldLabels::default()
+    }
+
+    /// Spawn `text` at `position`, rising at `velocity` for `lifetime`.
+    pub fn float(
+        &mut self,
+        text: &str,
+        position: glam::Vec3,
+        velocity: glam::Vec3,
+        color: glam::Vec4,
+        lifetime: Duration,
+    ) {
+        self.floating.push(Floating {
+            text: text.to_owned(),
+            position,
+            velocity,
+            color,
+            scale: 1.0,
+            age: 0.0,
+            lifetime: lifetime.as_secs_f32().max(f32::EPSILON),
+            visibility: 1.0,
+        });
+    }
+
+    /// Spawn `amount` of damage as a red number floating up from `position`.
+    pub fn damage_number(&mut self, position: glam::Vec3, amount: f32) {
+        self.float(
+            &format!("{:.0}", amount),
+            position,
+            glam::vec3(0.0, 0.8, 0.0),
+            glam::vec4(1.0, 0.25, 0.2, 1.0),
+            Duration::from_millis(1200),
+        );
+    }
+
+    /// Floating text still alive.
+    pub fn floating_len(&self) -> usize {
+        self.floating.len()
+    }
+
+    /// Move floating text along by `dt`, dropping what's outlived its lifetime.
+    pub fn advance(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        self.floating.retain_mut(|floating| {
+            floating.age += dt;
+            floating.position += floating.velocity * dt;
+            floating.velocity *= (1.0 - 2.0 * dt).max(0.0);
+            floating.age < floating.lifetime
+        });
+    }
+
+    /// Fade labels in or out by `dt` depending on whether the depth buffer of the bound framebuffer has something in
+    /// front of them. `viewport` is where `camera` was drawn in that framebuffer.
+    pub fn update(&mut self, world: &World, dt: Duration, camera: &Camera, viewport: &Viewport) {
+        let fade = OCCLUSION_FADE_SPEED * dt.as_secs_f32();
+
+        for floating in self.floating.iter_mut() {
+            approach(&mut floating.visibility, visible(floating.position, camera, viewport), fade);
+        }
+
+        match world.query::<(&mut WorldLabel, &Transform3)>() {
+            Ok(mut query) => {
+                for (mut label, transform) in query.iter() {
+                    let target = label.always_visible || visible(transform.position + label.offset, camera, viewport);
+                    approach(&mut label.visibility, target, fade);
+                }
+            },
+            Err(e) => {
+                LOGGER().a.error(format!("failed to query world labels: {:?}", e).as_str());
+            },
+        }
+    }
+
+    /// Queue every label on `text`, centered on its anchor as seen by `camera` in `viewport`, a region of `screen`.
+    /// `text` still has to be flushed over `screen`.
+    pub fn draw(
+        &self,
+        world: &World,
+        text: &mut TextRenderer,
+        camera: &Camera,
+        viewport: &Viewport,
+        screen: &Viewport,
+    ) {
+        // Text is positioned from the top left, window coordinates count from the bottom left
+        let origin = glam::vec2(
+            (viewport.x - screen.x) as f32,
+            (screen.y + screen.height - viewport.y - viewport.height) as f32,
+        );
+        let mut draw = |label: &str, anchor: glam::Vec3, scale: f32, color: glam::Vec4| {
+            if color.w <= 0.0 {
+                return;
+            }
+            if let Some(position) = camera.project(anchor, viewport) {
+                let size = text.measure(label, scale);
+                text.draw(label, origin + position - glam::vec2(size.x * 0.5, size.y), scale, color);
+            }
+        };
+
+        for floating in &self.floating {
+            let life = floating.age / floating.lifetime;
+            // Pops in slightly larger, then fades over the second half of its life
+            let scale = floating.scale * (1.0 + 0.5 * (1.0 - life * 4.0).max(0.0));
+            let alpha = floating.color.w * floating.visibility * (2.0 - life * 2.0).min(1.0);
+            draw(&floating.text, floating.position, scale, floating.color.truncate().extend(alpha));
+        }
+
+        match world.query::<(&WorldLabel, &Transform3)>() {
+            Ok(mut query) => {
+                for (label, transform) in query.iter() {
+                    let color = label.color.truncate().extend(label.color.w * label.visibility);
+                    draw(&label.text, transform.position + label.offset, label.scale, color);
+                }
+            },
+            Err(e) => {
+                LOGGER().a.error(format!("failed to query world labels: {:?}", e).as_str());
+            },
+        }
+    }
+}
+
+/// System moving the floating text of the `WorldLabels` resource along by the frame's length.
+pub fn advance_world_labels(frame: Res<FrameTime>, mut labels: ResMut<WorldLabels>) {
+    labels.advance(frame.0);
+}
+
+fn approach(value: &mut f32, visible: bool, step: f32) {
+    let target = if visible { 1.0 } else { 0.0 };
+    *value += (target - *value).clamp(-step, step);
+}
+
+/// Whether nothing in the bound depth buffer is in front of `point`. Points off screen count as hidden.
+fn visible(point: glam::Vec3, camera: &Camera, viewport: &Viewport) -> bool {
+    let clip = camera.projection * camera.view * point.extend(1.0);
+    if clip.w <= f32::EPSILON {
+        return false;
+    }
+
+    let ndc = clip.truncate() / clip.w;
+    if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+        return false;
+    }
+
+    let x = viewport.x + ((ndc.x * 0.5 + 0.5) * viewport.width as f32) as i32;
+    let y = viewport.y + ((ndc.y * 0.5 + 0.5) * viewport.height as f32) as i32;
+    let scene = depth::read_depth(x.min(viewport.x + viewport.width - 1), y.min(viewport.y + viewport.height - 1));
+    let label = depth::ndc_to_depth(ndc.z);
+
+    if state::is_reverse_z() { label >= scene - DEPTH_EPSILON } else { label <= scene + DEPTH_EPSILON }
+}
@@ -0,0 +1,53 @@
+//! Render layers, for cameras that draw different subsets of the scene.
+//!
+//! Entities are on the layers of their `RenderLayers` component, or `RenderLayers::DEFAULT` without one, and
+//! cameras only draw entities on at least one of the layers in their `cull_mask`. A first-person weapon can be kept
+//! out of reflections, or 3D UI only drawn by its own camera, without a separate world for each.
+//! ## Example
+//! ```ignore
+//! const VIEWMODEL: u32 = 1;
+//! world.spawn((mesh, material, gfx::Mobility::Dynamic, transform, gfx::RenderLayers::layer(VIEWMODEL)));
+//!
+//! // Mirrors show everything but the weapon
+//! let mut reflection = camera.reflected(mirror_point, mirror_normal);
+//! reflection.cull_mask = gfx::RenderLayers::ALL.without(VIEWMODEL);
+//! ```
+
+/// A set of up to 32 layers, as a bit mask. Used as a component for the layers an entity is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RenderLayers(pub u32);
+
+impl RenderLayers {
+    /// Layer 0, which everything is on unless told otherwise.
+    pub const DEFAULT: RenderLayers = RenderLayers(1);
+    pub const ALL: RenderLayers = RenderLayers(u32::MAX);
+    pub const NONE: RenderLayers = RenderLayers(0);
+
+    /// Only layer `index`, from 0 to 31.
+    pub fn layer(index: u32) -> Self {
+        RenderLayers(1 << index)
+    }
+
+    pub fn with(self, index: u32) -> Self {
+        RenderLayers(self.0 | 1 << index)
+    }
+
+    pub fn without(self, index: u32) -> Self {
+        RenderLayers(self.0 & !(1 << index))
+    }
+
+    pub fn contains(self, index: u32) -> bool {
+        self.0 & 1 << index != 0
+    }
+
+    /// Whether any layer is in both sets, i.e. whether a camera with cull mask `other` draws these layers.
+    pub fn intersects(self, other: RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers::DEFAULT
+    }
+}
@@ -0,0 +1,205 @@
+//! Light components and the collection step that makes them visible to shaders.
+//!
+//! Every frame, `Lights::collect` gathers all `Light` components in the world into a shader storage buffer bound
+//! to `LIGHTS_BINDING`. Shaders loop over all of them (forward rendering), so there's no fixed light limit.
+//! The GLSL side of the buffer must match `GpuLight` and `GpuLightHeader`:
+//! ```text
+//! struct Light {
+//!     vec4 PositionType;   // xyz position, w type (0 directional, 1 point, 2 spot)
+//!     vec4 DirectionRange; // xyz direction, w range
+//!     vec4 ColorIntensity; // rgb color, a intensity
+//!     vec4 Cone;           // x cos(inner angle), y cos(outer angle)
+//! };
+//!
+//! layout (std430, binding = 1) readonly buffer LightBlock
+//! {
+//!     vec4 Ambient;
+//!     uvec4 LightCount;
+//!     Light Lights[];
+//! };
+//! ```
+
+use crate::log::LOGGER;
+use crate::logic::{QueryIter, World};
+use crate::math::units::Radians;
+
+use super::memory::{Allocation, Category};
+
+/// Shader storage buffer binding point the light buffer is bound to.
+pub const LIGHTS_BINDING: u32 = 1;
+
+/// A light source component. Color is linear RGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    /// Infinitely far away light (e.g. the sun) shining along `direction`.
+    Directional {
+        direction: glam::Vec3,
+        color: glam::Vec3,
+        intensity: f32,
+    },
+    /// Light shining in all directions from `position`, fading out completely at `range`.
+    Point {
+        position: glam::Vec3,
+        color: glam::Vec3,
+        intensity: f32,
+        range: f32,
+    },
+    /// Cone of light from `position` along `direction`. Full intensity inside `inner_angle`,
+    /// fading out towards `outer_angle`. Both angles are measured from the cone axis.
+    Spot {
+        position: glam::Vec3,
+        direction: glam::Vec3,
+        color: glam::Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: Radians,
+        outer_angle: Radians,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+struct GpuLightHeader {
+    ambient: [f32; 4],
+    light_count: [u32; 4],
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+struct GpuLight {
+    position_type: [f32; 4],
+    direction_range: [f32; 4],
+    color_intensity: [f32; 4],
+    cone: [f32; 4],
+}
+
+impl From<&Light> for GpuLight {
+    fn from(light: &Light) -> Self {
+        match *light {
+            Light::Directional { direction, color, intensity } => GpuLight {
+                position_type: [0.0, 0.0, 0.0, 0.0],
+                direction_range: direction.normalize().extend(0.0).to_array(),
+                color_intensity: color.extend(intensity).to_array(),
+                cone: [0.0; 4],
+            },
+            Light::Point { position, color, intensity, range } => GpuLight {
+                position_type: position.extend(1.0).to_array(),
+                direction_range: [0.0, 0.0, 0.0, range],
+                color_intensity: color.extend(intensity).to_array(),
+                cone: [0.0; 4],
+            },
+            Light::Spot { position, direction, color, intensity, range, inner_angle, outer_angle } => GpuLight {
+                position_type: position.extend(2.0).to_array(),
+                direction_range: direction.normalize().extend(range).to_array(),
+                color_intensity: color.extend(intensity).to_array(),
+                cone: [inner_angle.0.cos(), outer_angle.0.cos(), 0.0, 0.0],
+            },
+        }
+    }
+}
+
+/// Owns the light shader storage buffer.
+pub struct Lights {
+    ambient: glam::Vec3,
+    lights: Vec<GpuLight>,
+    ssbo: gl::types::GLuint,
+    /// Size in bytes the buffer was last allocated with.
+    capacity: usize,
+    memory: Allocation,
+}
+
+impl Lights {
+    pub fn new(ambient: glam::Vec3) -> Self {
+        let mut ssbo: gl::types::GLuint = 0;
+        unsafe { gl::GenBuffers(1, &mut ssbo); }
+
+        Lights {
+            ambient,
+            lights: Vec::new(),
+            ssbo,
+            capacity: 0,
+            memory: Allocation::new(Category::Streaming, 0),
+        }
+    }
+
+    pub fn ambient(&self) -> glam::Vec3 {
+        self.ambient
+    }
+
+    pub fn set_ambient(&mut self, ambient: glam::Vec3) {
+        self.ambient = ambient;
+    }
+
+    /// Number of lights gathered by the last `collect()`.
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// Gather every `Light` component in `world` and upload them.
+    pub fn collect(&mut self, world: &World) {
+        self.lights.clear();
+
+        match world.query::<(&Light,)>() {
+            Ok(mut query) => {
+                for light in query.iter() {
+                    self.lights.push(light.into());
+                }
+            },
+            Err(e) => {
+                LOGGER().a.error(format!("failed to query lights: {:?}", e).as_str());
+            },
+        }
+
+        self.upload();
+    }
+
+    fn upload(&mut self) {
+        let header = GpuLightHeader {
+            ambient: self.ambient.extend(0.0).to_array(),
+            light_count: [self.lights.len() as u32, 0, 0, 0],
+        };
+
+        let header_size = std::mem::size_of::<GpuLightHeader>();
+        let lights_size = self.lights.len() * std::mem::size_of::<GpuLight>();
+
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.ssbo);
+
+            // Grow (and orphan) the buffer only when the lights no longer fit
+            if header_size + lights_size > self.capacity {
+                self.capacity = (header_size + lights_size).next_power_of_two();
+                gl::BufferData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    self.capacity as gl::types::GLsizeiptr,
+                    std::ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                self.memory.resize(self.capacity);
+            }
+
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                header_size as gl::types::GLsizeiptr,
+                &header as *const GpuLightHeader as *const gl::types::GLvoid,
+            );
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                header_size as gl::types::GLintptr,
+                lights_size as gl::types::GLsizeiptr,
+                self.lights.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+    }
+
+    /// Bind the light buffer to `LIGHTS_BINDING` for subsequent draws.
+    pub fn bind(&self) {
+        unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, LIGHTS_BINDING, self.ssbo); }
+    }
+}
+
+impl Drop for Lights {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &mut self.ssbo); }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::resource::Resource;
+
+use super::shader::{self, Program};
+use super::state::RenderState;
+
+/// How a material treats fragment alpha, for cutouts like foliage, fences and hair cards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored, or left to blending.
+    Opaque,
+    /// Fragments with alpha below `cutoff` are discarded, leaving hard, aliased edges.
+    Mask { cutoff: f32 },
+    /// Alpha-to-coverage: on multisampled targets, alpha picks how many samples a fragment covers, so cutout edges
+    /// get anti-aliased like geometry edges. Falls back to `Mask` on targets without multisampling. `sharpen`
+    /// rescales alpha around `cutoff` to about a pixel wide, which keeps edges crisp instead of dithered and stops
+    /// cutouts thinning out with distance.
+    Coverage { cutoff: f32, sharpen: bool },
+}
+
+impl AlphaMode {
+    /// Load the variant of the `<name>.vert`/`<name>.frag` program for this mode. The fragment shader picks it up
+    /// through `ALPHA_MASK` or `ALPHA_COVERAGE`, `ALPHA_SHARPEN`, and `ALPHA_CUTOFF`, see `test.frag`.
+    pub fn program(&self, res: &Resource, name: &str) -> Result<Program, shader::Error> {
+        let (mode, cutoff, sharpen) = match *self {
+            AlphaMode::Opaque => return Program::from_res(res, name),
+            AlphaMode::Mask { cutoff } => ("ALPHA_MASK", cutoff, false),
+            AlphaMode::Coverage { cutoff, sharpen } => ("ALPHA_COVERAGE", cutoff, sharpen),
+        };
+
+        // Always with a decimal point, GLSL won't compare an int literal against a float
+        let cutoff = format!("{:?}", cutoff.clamp(0.0, 1.0));
+        let mut defines = vec![(mode, "1"), ("ALPHA_CUTOFF", cutoff.as_str())];
+        if sharpen {
+            defines.push(("ALPHA_SHARPEN", "1"));
+        }
+
+        Program::from_res_with_defines(res, name, &defines)
+    }
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Opaque
+    }
+}
+
+/// How a mesh is drawn: the shader program it uses and the fixed-function state it needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Program deletion is done externally, many materials can share one.
+    pub program_id: gl::types::GLuint,
+    pub render_state: RenderState,
+    /// Has to match the variant `program_id` was loaded as, see `AlphaMode::program()`.
+    pub alpha_mode: AlphaMode,
+}
+
+impl Material {
+    pub fn new(program_id: gl::types::GLuint, render_state: RenderState) -> Self {
+        Material { program_id, render_state, alpha_mode: AlphaMode::Opaque }
+    }
+
+    /// A cutout material drawn with `program`, which has to be `alpha_mode`'s variant. Turns alpha-to-coverage on in
+    /// `render_state` for `AlphaMode::Coverage`.
+    pub fn cutout(program_id: gl::types::GLuint, render_state: RenderState, alpha_mode: AlphaMode) -> Self {
+        let render_state = RenderState {
+            alpha_to_coverage: matches!(alpha_mode, AlphaMode::Coverage { .. }),
+            ..render_state
+        };
+        Material { program_id, render_state, alpha_mode }
+    }
+}
@@ -0,0 +1,84 @@
+//! GPU memory accounting.
+//!
+//! OpenGL has no portable way to ask how much video memory is in use, so every owner of a texture, renderbuffer or
+//! buffer keeps an `Allocation` sized like what it asked the driver for. The totals are estimates, since drivers
+//! pad and align, but they track content closely enough to budget against.
+//! ## Example
+//! ```ignore
+//! let textures = gfx::memory::usage(gfx::memory::Category::Textures);
+//! LOGGER().a.info(format!("{} of {} bytes are textures", textures, gfx::memory::total()).as_str());
+//! ```
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a piece of video memory is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Sampled textures and render target attachments.
+    Textures,
+    /// Depth/stencil renderbuffers.
+    Renderbuffers,
+    /// Mesh vertices and indices, with their per-instance buffers.
+    Meshes,
+    /// Buffers re-uploaded every frame, like debug lines, text and lights.
+    Streaming,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [Category::Textures, Category::Renderbuffers, Category::Meshes, Category::Streaming];
+
+    fn counter(self) -> &'static AtomicUsize {
+        &USAGE[self as usize]
+    }
+}
+
+static USAGE: [AtomicUsize; 4] = [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+
+/// Bytes currently allocated for `category`.
+pub fn usage(category: Category) -> usize {
+    category.counter().load(Ordering::Relaxed)
+}
+
+/// Bytes currently allocated over all categories.
+pub fn total() -> usize {
+    Category::ALL.iter().map(|c| usage(*c)).sum()
+}
+
+/// Counts `bytes` towards a category for as long as it's alive.
+#[derive(Debug)]
+pub(crate) struct Allocation {
+    category: Category,
+    bytes: usize,
+}
+
+impl Allocation {
+    pub(crate) fn new(category: Category, bytes: usize) -> Self {
+        category.counter().fetch_add(bytes, Ordering::Relaxed);
+        Allocation { category, bytes }
+    }
+
+    /// Account for the buffer being reallocated with a different size.
+    pub(crate) fn resize(&mut self, bytes: usize) {
+        self.category.counter().fetch_sub(self.bytes, Ordering::Relaxed);
+        self.category.counter().fetch_add(bytes, Ordering::Relaxed);
+        self.bytes = bytes;
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.category.counter().fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// Bytes per texel of a sized internal format, assuming 4 for any this doesn't know.
+pub(crate) fn bytes_per_texel(internal_format: gl::types::GLenum) -> usize {
+    match internal_format {
+        gl::R8 => 1,
+        gl::RG8 | gl::R16F | gl::DEPTH_COMPONENT16 => 2,
+        gl::RGB8 | gl::SRGB8 | gl::DEPTH_COMPONENT24 => 3,
+        gl::RGBA16F | gl::RG32F | gl::DEPTH32F_STENCIL8 => 8,
+        gl::RGBA32F => 16,
+        _ => 4,
+    }
+}
@@ -0,0 +1,226 @@
+//! Top-down map of the surroundings in a corner of the screen, with icons for marked entities.
+//!
+//! `Minimap::render()` draws the world from an orthographic camera looking straight down into its own target, north
+//! (+Z) up, through a callback like `SecondaryView::render()`, so the caller decides what's worth drawing on the map.
+//! `draw()` then shows the target in a square anchored to a corner of the screen, and `draw_markers()` places the
+//! icon of every entity with a `MinimapMarker` and a `Transform3` on top of it.
+//! ## Example
+//! ```ignore
+//! let mut minimap = gfx::Minimap::new(&res, 256, 20.0, gfx::ColorSpace::Srgb)?;
+//! world.spawn((Transform3::identity(), MinimapMarker::new('!', glam::vec4(1.0, 0.8, 0.0, 1.0))));
+//!
+//! // Every frame, before the main pass
+//! minimap.render(player_position, |camera, viewport| {
+//!     // Set camera uniforms and draw the scene, as in the main pass
+//! });
+//!
+//! // After resolving the scene to the window
+//! minimap.draw(&viewport);
+//! minimap.draw_markers(&world, &mut text, &viewport);
+//! text.flush(&viewport);
+//! ```
+
+use crate::log::LOGGER;
+use crate::logic::{QueryIter, World};
+use crate::math::isometry::Transform3;
+use crate::resource::Resource;
+
+use super::camera::Camera;
+use super::color::ColorSpace;
+use super::post::{self, post_program};
+use super::shader::Program;
+use super::state::{self, RenderState};
+use super::target::RenderTarget;
+use super::text::TextRenderer;
+use super::viewport::Viewport;
+
+/// Component showing an entity on the minimap as `icon`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapMarker {
+    pub icon: char,
+    pub color: glam::Vec4,
+    /// Keep showing the icon at the edge of the map while the entity is outside it, e.g. for objectives.
+    pub pin_to_edge: bool,
+}
+
+impl MinimapMarker {
+    pub fn new(icon: char, color: glam::Vec4) -> Self {
+        MinimapMarker { icon, color, pin_to_edge: false }
+    }
+}
+
+/// Screen corner the minimap is anchored to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+pub struct Minimap {
+    target: RenderTarget,
+    camera: Camera,
+    /// Half the width of the area shown, in world units.
+    extent: f32,
+    pub corner: Corner,
+    /// Gap between the map and the screen edges, in pixels.
+    pub margin: f32,
+    blit_program: Program,
+    vao: gl::types::GLuint, // empty, the fullscreen triangle is generated from gl_VertexID
+}
+
+/// How far above the map center the camera is, and so the tallest thing it sees.
+const HEIGHT: f32 = 50.0;
+
+impl Minimap {
+    /// A `size` x `size` pixel map showing `extent` world units in every direction from its center. `color_space`
+    /// should match the framebuffer the map is drawn onto.
+    pub fn new(res: &Resource, size: i32, extent: f32, color_space: ColorSpace) -> Result<Self, post::Error> {
+        let mut vao: gl::types::GLuint = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao); }
+
+        let mut minimap = Minimap {
+            target: RenderTarget::new(size, size, color_space)?,
+            camera: Camera::from_transform3(glam::Mat4::IDENTITY, &Transform3::identity(), glam::Vec3::Y),
+            extent,
+            corner: Corner::TopRight,
+            margin: 8.0,
+            blit_program: post_program(res, "blit")?,
+            vao,
+        };
+        minimap.center_on(glam::Vec3::ZERO);
+
+        Ok(minimap)
+    }
+
+    pub fn size(&self) -> i32 {
+        self.target.width()
+    }
+
+    pub fn extent(&self) -> f32 {
+        self.extent
+    }
+
+    /// Zoom to show `extent` world units in every direction. Takes effect on the next `render()`.
+    pub fn set_extent(&mut self, extent: f32) {
+        self.extent = extent.max(f32::EPSILON);
+    }
+
+    /// The top-down camera of the last `render()`.
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    fn center_on(&mut self, center: glam::Vec3) {
+        let e = self.extent;
+        let projection = if state::is_reverse_z() {
+            glam::Mat4::orthographic_lh(-e, e, -e, e, HEIGHT * 2.0, 0.0)
+        } else {
+            glam::Mat4::orthographic_lh(-e, e, -e, e, 0.0, HEIGHT * 2.0)
+        };
+
+        // Looking down -Y with +Z at the top of the map
+        let transform = Transform3::new(
+            center + glam::Vec3::Y * HEIGHT,
+            glam::Quat::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            glam::Vec3::ONE,
+        );
+        self.camera = Camera::from_transform3(projection, &transform, glam::Vec3::Y);
+    }
+
+    /// Render the map centered on `center`. `draw` draws the world from the camera it's given into a cleared target
+    /// whose viewport is set to the one it's given. Leaves the map's target bound.
+    pub fn render<D>(&mut self, center: glam::Vec3, mut draw: D)
+        where D: FnMut(&Camera, &Viewport)
+    {
+        self.center_on(center);
+
+        let viewport = Viewport::make_viewport(self.size(), self.size());
+        self.target.bind();
+        viewport.use_viewport();
+        state::clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        draw(&self.camera, &viewport);
+    }
+
+    /// Where the map is on `screen`, as `(top left, size)` in pixels from the top left of `screen`.
+    pub fn rect(&self, screen: &Viewport) -> (glam::Vec2, f32) {
+        let size = (self.size() as f32).min(screen.width as f32 - self.margin * 2.0).max(0.0);
+        let far_x = screen.width as f32 - self.margin - size;
+        let far_y = screen.height as f32 - self.margin - size;
+
+        let top_left = match self.corner {
+            Corner::TopLeft => glam::vec2(self.margin, self.margin),
+            Corner::TopRight => glam::vec2(far_x, self.margin),
+            Corner::BottomLeft => glam::vec2(self.margin, far_y),
+            Corner::BottomRight => glam::vec2(far_x, far_y),
+        };
+
+        (top_left, size)
+    }
+
+    /// Draw the last rendered map into its corner of `screen`, over whatever is in the bound framebuffer.
+    pub fn draw(&self, screen: &Viewport) {
+        let (top_left, size) = self.rect(screen);
+        let size = size as i32;
+        // Window coordinates count up from the bottom
+        let map_viewport = Viewport {
+            x: screen.x + top_left.x as i32,
+            y: screen.y + screen.height - top_left.y as i32 - size,
+            width: size,
+            height: size,
+        };
+
+        map_viewport.use_viewport();
+        RenderState::fullscreen().apply();
+        self.blit_program.use_program();
+        self.target.color().bind(0);
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+
+        screen.use_viewport();
+    }
+
+    /// Where `point` is on the map, in pixels from the top left of `screen`. `None` if it's off the map, unless
+    /// `pin_to_edge`, which moves it onto the nearest edge instead.
+    pub fn project(&self, point: glam::Vec3, screen: &Viewport, pin_to_edge: bool) -> Option<glam::Vec2> {
+        let (top_left, size) = self.rect(screen);
+        let map = Viewport::make_viewport(self.size(), self.size());
+        let on_map = self.camera.project(point, &map)? / self.size() as f32;
+
+        let inside = (0.0..=1.0).contains(&on_map.x) && (0.0..=1.0).contains(&on_map.y);
+        if !inside && !pin_to_edge {
+            return None;
+        }
+
+        Some(top_left + on_map.clamp(glam::Vec2::ZERO, glam::Vec2::ONE) * size)
+    }
+
+    /// Queue the icon of every marked entity on `text`, centered on where it is on the map. `text` still has to be
+    /// flushed over `screen`.
+    pub fn draw_markers(&self, world: &World, text: &mut TextRenderer, screen: &Viewport) {
+        let half_glyph = text.glyph_size() * 0.5;
+
+        match world.query::<(&MinimapMarker, &Transform3)>() {
+            Ok(mut query) => {
+                for (marker, transform) in query.iter() {
+                    if let Some(position) = self.project(transform.position, screen, marker.pin_to_edge) {
+                        text.draw(&marker.icon.to_string(), position - half_glyph, 1.0, marker.color);
+                    }
+                }
+            },
+            Err(e) => {
+                LOGGER().a.error(format!("failed to query minimap markers: {:?}", e).as_str());
+            },
+        }
+    }
+}
+
+impl Drop for Minimap {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &mut self.vao); }
+    }
+}
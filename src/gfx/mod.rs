@@ -2,11 +2,118 @@ pub mod shader;
 pub mod viewport;
 pub mod batch;
 pub mod camera;
+pub mod color;
+pub mod texture;
+pub mod target;
+pub mod post;
+pub mod state;
+pub mod material;
+pub mod extract;
+pub mod outline;
+pub mod light;
+pub mod shadow;
+pub mod cull;
+pub mod depth;
+pub mod profiler;
+pub mod debug_draw;
+pub mod debug_group;
+pub mod text;
+pub mod select;
+pub mod snapshot;
+pub mod quality;
+pub mod pick;
+pub mod screenshot;
+pub mod headless;
+pub mod memory;
+pub mod plot;
+pub mod view;
+pub mod gl_error;
+pub mod caps;
+pub mod backend;
+pub mod time_of_day;
+pub mod queue;
+pub mod minimap;
+pub mod graph;
+pub mod label;
+pub mod particle;
+pub mod gpu_particle;
+pub mod validate;
+pub mod fog;
+pub mod frame;
+pub mod streaming;
+pub mod occlusion;
+pub mod layer;
+pub mod environment;
+pub mod capture;
 
 pub use shader::Program as Program;
 pub use shader::Shader as Shader;
 pub use viewport::Viewport as Viewport;
+pub use viewport::ScalePolicy as ScalePolicy;
 pub use batch::Batch as Batch;
 pub use batch::Vertex as Vertex;
 pub use batch::Mesh as Mesh;
-pub use camera::Camera as Camera;
\ No newline at end of file
+pub use batch::Indices as Indices;
+pub use batch::Lod as Lod;
+pub use batch::Submesh as Submesh;
+pub use batch::draw_sorted as draw_sorted;
+pub use camera::Camera as Camera;
+pub use camera::CameraMode as CameraMode;
+pub use camera::Smoothing as Smoothing;
+pub use layer::RenderLayers as RenderLayers;
+pub use color::ColorSpace as ColorSpace;
+pub use texture::Texture as Texture;
+pub use target::RenderTarget as RenderTarget;
+pub use target::DepthTarget as DepthTarget;
+pub use target::MultisampleTarget as MultisampleTarget;
+pub use post::PostProcess as PostProcess;
+pub use post::AntiAliasing as AntiAliasing;
+pub use state::RenderState as RenderState;
+pub use state::BlendMode as BlendMode;
+pub use material::Material as Material;
+pub use material::AlphaMode as AlphaMode;
+pub use extract::BatchExtractor as BatchExtractor;
+pub use extract::MeshHandle as MeshHandle;
+pub use extract::MaterialHandle as MaterialHandle;
+pub use extract::Mobility as Mobility;
+pub use outline::Outline as Outline;
+pub use outline::Outlined as Outlined;
+pub use light::Light as Light;
+pub use light::Lights as Lights;
+pub use shadow::ShadowMap as ShadowMap;
+pub use environment::EnvironmentMap as EnvironmentMap;
+pub use cull::GpuCulling as GpuCulling;
+pub use occlusion::OcclusionCulling as OcclusionCulling;
+pub use profiler::GpuProfiler as GpuProfiler;
+pub use debug_draw::DebugDraw as DebugDraw;
+pub use debug_group::debug_group as debug_group;
+pub use text::TextRenderer as TextRenderer;
+pub use snapshot::VertexSnapshot as VertexSnapshot;
+pub use quality::AutoQuality as AutoQuality;
+pub use screenshot::capture_screenshot as capture_screenshot;
+pub use screenshot::capture_target_screenshot as capture_target_screenshot;
+pub use capture::FrameCapture as FrameCapture;
+pub use capture::CaptureOutput as CaptureOutput;
+pub use headless::HeadlessContext as HeadlessContext;
+pub use view::SecondaryView as SecondaryView;
+pub use caps::Capabilities as Capabilities;
+pub use caps::capabilities as capabilities;
+pub use time_of_day::TimeOfDay as TimeOfDay;
+pub use time_of_day::advance_time_of_day as advance_time_of_day;
+pub use queue::RenderQueue as RenderQueue;
+pub use queue::SortKey as SortKey;
+pub use minimap::Minimap as Minimap;
+pub use minimap::MinimapMarker as MinimapMarker;
+pub use graph::RenderGraph as RenderGraph;
+pub use label::WorldLabel as WorldLabel;
+pub use label::WorldLabels as WorldLabels;
+pub use label::advance_world_labels as advance_world_labels;
+pub use particle::ParticleSystem as ParticleSystem;
+pub use particle::advance_particles as advance_particles;
+pub use particle::EmitterConfig as EmitterConfig;
+pub use gpu_particle::GpuParticles as GpuParticles;
+pub use fog::Fog as Fog;
+pub use fog::FogMode as FogMode;
+pub use frame::FrameUniforms as FrameUniforms;
+pub use streaming::TextureStreamer as TextureStreamer;
+pub use streaming::StreamedTexture as StreamedTexture;
@@ -0,0 +1,177 @@
+//! Occlusion culling against a hierarchical depth pyramid, for instances hidden behind large occluders.
+//!
+//! Before a camera's draw, `OcclusionCulling` renders the opaque scene depth-only into a small depth target, then
+//! reduces it into a mip pyramid where every texel holds the farthest depth of the area it covers. GPU culling then
+//! projects each instance's bounding sphere, picks the pyramid level where its screen bounds span 2x2 texels, and
+//! drops the instance if its nearest point is behind all four. Those four cover the whole sphere, so nothing visible
+//! is ever culled. An instance never hides itself, since its bounding sphere is nearer than any of its surfaces.
+//!
+//! Only batches culled on the GPU are tested, see `GpuCulling::applies_to()`. The depth pass is at the pyramid's
+//! resolution, so it's cheap next to the draws it saves, but it does cost a pass whenever something is culled
+//! this way.
+//! ## Example
+//! ```ignore
+//! extractor.set_gpu_culling(Some(gfx::GpuCulling::new(&res, 256)?));
+//! extractor.set_occlusion_culling(Some(gfx::OcclusionCulling::new(&res, 256, 128)?));
+//!
+//! // Every frame, occluders are drawn and tested inside
+//! extractor.draw(&camera);
+//! ```
+
+use crate::resource::Resource;
+
+use super::camera::Camera;
+use super::caps;
+use super::memory::{Allocation, Category};
+use super::shader::{self, Program};
+use super::state::{self, RenderState};
+use super::target::{self, DepthTarget};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load occlusion culling programs: {0}")]
+    Program(#[from] shader::Error),
+    #[error("failed to create occluder depth target: {0}")]
+    Target(#[from] target::Error),
+    #[error("occlusion culling needs compute shaders")]
+    Unsupported,
+}
+
+/// Texture unit the pyramid is bound to for culling. Units 0 to 2 are taken by materials, shadows and secondary views.
+pub const PYRAMID_UNIT: u32 = 3;
+/// Must match `local_size_x` and `local_size_y` in `hiz.comp`.
+const WORKGROUP_SIZE: i32 = 8;
+
+pub struct OcclusionCulling {
+    target: DepthTarget,
+    /// R32F with a full mip chain, level 0 being a copy of the target's depth.
+    pyramid: gl::types::GLuint,
+    levels: i32,
+    depth_program: Program,
+    pyramid_program: Program,
+    /// Of the camera the pyramid was last built from.
+    view_projection: glam::Mat4,
+    /// Framebuffer and viewport bound before `begin()`, restored by `end()`.
+    previous: (gl::types::GLint, [gl::types::GLint; 4]),
+    _memory: Allocation,
+}
+
+impl OcclusionCulling {
+    /// The pyramid's base level is `width` by `height`, whatever the camera's aspect ratio.
+    pub fn new(res: &Resource, width: i32, height: i32) -> Result<Self, Error> {
+        if !caps::capabilities().compute_shaders {
+            return Err(Error::Unsupported);
+        }
+
+        // Depth only through the shadow pass's program, with the camera in place of the light
+        let depth_program = Program::from_res(res, "shaders/shadow")?;
+        let pyramid_program = Program::from_res_compute(res, "shaders/hiz")?;
+        let target = DepthTarget::new(width, height)?;
+
+        let levels = 32 - (width.max(height).max(1) as u32).leading_zeros() as i32;
+        let mut pyramid: gl::types::GLuint = 0;
+        unsafe {
+            // Read with texelFetch, not compared like a shadow map
+            gl::BindTexture(gl::TEXTURE_2D, target.depth().id());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::NONE as gl::types::GLint);
+
+            gl::GenTextures(1, &mut pyramid);
+            gl::BindTexture(gl::TEXTURE_2D, pyramid);
+            gl::TexStorage2D(gl::TEXTURE_2D, levels, gl::R32F, width, height);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_NEAREST as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as gl::types::GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        // A full mip chain adds a third on top of the base level
+        let bytes = width as usize * height as usize * std::mem::size_of::<f32>() * 4 / 3;
+
+        Ok(OcclusionCulling {
+            target,
+            pyramid,
+            levels,
+            depth_program,
+            pyramid_program,
+            view_projection: glam::Mat4::IDENTITY,
+            previous: (0, [0; 4]),
+            _memory: Allocation::new(Category::Textures, bytes),
+        })
+    }
+
+    /// Start the occluder depth pass from `camera`. Occluders are drawn with `program_id()`.
+    pub fn begin(&mut self, camera: &Camera) {
+        self.view_projection = camera.projection * camera.view;
+
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut self.previous.0);
+            gl::GetIntegerv(gl::VIEWPORT, self.previous.1.as_mut_ptr());
+        }
+
+        self.target.bind();
+        unsafe { gl::Viewport(0, 0, self.target.width(), self.target.height()); }
+
+        RenderState::default().apply();
+        state::clear(gl::DEPTH_BUFFER_BIT);
+
+        self.depth_program.set_mat4fv("LightSpace", self.view_projection, 0);
+    }
+
+    pub fn program_id(&self) -> gl::types::GLuint {
+        self.depth_program.id()
+    }
+
+    /// Finish the depth pass and build the pyramid from it, going back to the framebuffer and viewport bound
+    /// before `begin()`.
+    pub fn end(&self) {
+        let [x, y, width, height] = self.previous.1;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.previous.0 as gl::types::GLuint);
+            gl::Viewport(x, y, width, height);
+        }
+
+        let program = &self.pyramid_program;
+        program.set_i32("Depth", 0);
+        program.set_i32("ReverseZ", state::is_reverse_z() as i32);
+
+        unsafe {
+            gl::UseProgram(program.id());
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.target.depth().id());
+
+            for level in 0..self.levels {
+                program.set_i32("Level", level);
+                gl::BindImageTexture(0, self.pyramid, level, gl::FALSE, 0, gl::WRITE_ONLY, gl::R32F);
+                gl::BindImageTexture(1, self.pyramid, (level - 1).max(0), gl::FALSE, 0, gl::READ_ONLY, gl::R32F);
+
+                let width = (self.target.width() >> level).max(1);
+                let height = (self.target.height() >> level).max(1);
+                gl::DispatchCompute(
+                    ((width + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as gl::types::GLuint,
+                    ((height + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as gl::types::GLuint,
+                    1,
+                );
+                // Each level reads the one before it, and culling reads them all
+                gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
+            }
+        }
+    }
+
+    /// Bind the pyramid to `PYRAMID_UNIT` and set the uniforms `cull.comp` tests it with on `program`.
+    pub(super) fn apply(&self, program: &Program) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + PYRAMID_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D, self.pyramid);
+        }
+
+        program.set_i32("OcclusionPyramid", PYRAMID_UNIT as i32);
+        program.set_mat4fv("OcclusionViewProjection", self.view_projection, 0);
+        program.set_i32("OcclusionLevels", self.levels);
+        program.set_i32("OcclusionReverseZ", state::is_reverse_z() as i32);
+    }
+}
+
+impl Drop for OcclusionCulling {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &mut self.pyramid); }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::resource::Resource;
+
+use super::batch::Batch;
+use super::camera::Camera;
+use super::shader::{self, Program};
+use super::state::{CompareFunc, RenderState, Stencil};
+
+/// Marker component for entities that should be drawn with an outline, e.g. because they're selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Outlined;
+
+/// Stencil-based outline pass.
+///
+/// The batch is drawn normally while writing `STENCIL_REF` into the stencil buffer, then drawn again slightly
+/// scaled up with a flat color, only where the stencil test fails. What's left is a silhouette around the mesh.
+/// Requires a framebuffer with a stencil attachment.
+pub struct Outline {
+    program: Program,
+    color: glam::Vec4,
+    scale: f32,
+}
+
+const STENCIL_REF: i32 = 1;
+
+impl Outline {
+    /// `scale` is how much bigger than the original mesh the outline is drawn, e.g. `1.05`.
+    pub fn new(res: &Resource, color: glam::Vec4, scale: f32) -> Result<Self, shader::Error> {
+        Ok(Outline {
+            program: Program::from_res(res, "shaders/outline")?,
+            color,
+            scale,
+        })
+    }
+
+    pub fn set_color(&mut self, color: glam::Vec4) {
+        self.color = color;
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Draw `batch` and an outline around each of its instances.
+    pub fn draw(&self, batch: &Batch, camera: &Camera) {
+        let mut mask_state = batch.render_state().copied().unwrap_or_default();
+        mask_state.stencil = Some(Stencil::write(STENCIL_REF));
+        mask_state.apply();
+        batch.draw_with_program(batch.program_id());
+
+        // Outline is drawn on top of everything so selections stay visible behind other geometry
+        let outline_state = RenderState {
+            depth_test: false,
+            depth_write: false,
+            stencil: Some(Stencil::test(CompareFunc::NotEqual, STENCIL_REF)),
+            ..RenderState::default()
+        };
+        outline_state.apply();
+
+        self.program.set_mat4fv("View", camera.view, 0);
+        self.program.set_mat4fv("Projection", camera.projection, 0);
+        self.program.set_vec4f("OutlineColor", self.color);
+        self.program.set_f32("OutlineScale", self.scale);
+        batch.draw_with_program(self.program.id());
+    }
+}
@@ -0,0 +1,329 @@
+//! CPU-simulated particles, drawn as camera-facing quads through the batch infrastructure.
+//!
+//! Every emitter owns a `Batch` of `EmitterConfig::max_particles` quad instances. Each frame, live particles are
+//! sorted back to front and written into its transforms as billboards, with the unused bottom row of every transform
+//! carrying the particle's color to `particle.vert`. Slots past the live particles get a zero transform, which
+//! collapses their quad to nothing. Emitters are drawn through a `RenderQueue`, so they blend back to front as well.
+//! ## Example
+//! ```ignore
+//! let mut particles = gfx::ParticleSystem::new(&res)?;
+//! let sparks = particles.add_emitter(EmitterConfig {
+//!     rate: 40.0,
+//!     color: Curve::new(vec![(0.0, glam::vec4(1.0, 0.8, 0.3, 1.0)), (1.0, glam::vec4(1.0, 0.2, 0.0, 0.0))]),
+//!     ..EmitterConfig::default()
+//! }, glam::vec3(0.0, 1.0, 0.0))?;
+//!
+//! world.insert_resource(particles);
+//! schedule.add_system(Stage::Update, "particles", advance_particles);
+//!
+//! // Every frame, after the opaque scene, with its depth still bound
+//! world.resource::<ParticleSystem>().unwrap().draw(&camera);
+//! ```
+
+use std::time::Duration;
+
+use crate::logic::system::FrameTime;
+use crate::logic::{Res, ResMut};
+use crate::math::curve::Curve;
+use crate::resource::Resource;
+
+use super::batch::{self, Batch, Mesh, Vertex};
+use super::camera::Camera;
+use super::queue::{RenderQueue, SortKey};
+use super::shader::{self, Program};
+use super::state::BlendMode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load particle program: {0}")]
+    Program(#[from] shader::Error),
+    #[error("failed to create particle batch: {0}")]
+    Batch(#[from] batch::Error),
+    #[error("emitter needs room for at least one particle")]
+    NoCapacity,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitterConfig {
+    /// Particles spawned per second.
+    pub rate: f32,
+    /// Seconds each particle lives, picked uniformly from `(min, max)`.
+    pub lifetime: (f32, f32),
+    /// Velocity particles start with, in m/s.
+    pub velocity: glam::Vec3,
+    /// Largest random change to `velocity` in any direction, in m/s.
+    pub velocity_spread: f32,
+    /// Applied to every particle, e.g. gravity.
+    pub acceleration: glam::Vec3,
+    /// Half the size of the box around the emitter particles spawn in.
+    pub spawn_extents: glam::Vec3,
+    /// Color over each particle's life, from 0 at birth to 1 at death.
+    pub color: Curve<glam::Vec4>,
+    /// Width and height in world units over each particle's life.
+    pub size: Curve<f32>,
+    /// Live particles past this aren't spawned. Also the size of the emitter's batch.
+    pub max_particles: usize,
+    pub blend: BlendMode,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        EmitterConfig {
+            rate: 10.0,
+            lifetime: (1.0, 2.0),
+            velocity: glam::vec3(0.0, 1.0, 0.0),
+            velocity_spread: 0.2,
+            acceleration: glam::Vec3::ZERO,
+            spawn_extents: glam::Vec3::ZERO,
+            color: Curve::new(vec![(0.0, glam::Vec4::ONE), (1.0, glam::vec4(1.0, 1.0, 1.0, 0.0))]),
+            size: Curve::constant(0.1),
+            max_particles: 256,
+            blend: BlendMode::Alpha,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Particle {
+    position: glam::Vec3,
+    velocity: glam::Vec3,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Handle to an emitter of a `ParticleSystem`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EmitterId(usize);
+
+pub struct Emitter {
+    pub config: EmitterConfig,
+    pub position: glam::Vec3,
+    /// Stops spawning while false, particles already alive live out their lifetime.
+    pub enabled: bool,
+    particles: Vec<Particle>,
+    /// Fraction of a particle owed from previous updates.
+    accumulator: f32,
+    batch: Batch,
+}
+
+impl Emitter {
+    /// Particles alive.
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Kill every live particle.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.accumulator = 0.0;
+    }
+}
+
+pub struct ParticleSystem {
+    program: Program,
+    emitters: Vec<Emitter>,
+    rng: u64,
+}
+
+impl ParticleSystem {
+    pub fn new(res: &Resource) -> Result<Self, Error> {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Ok(ParticleSystem {
+            program: Program::from_res(res, "shaders/particle")?,
+            emitters: Vec::new(),
+            rng: seed | 1,
+        })
+    }
+
+    pub fn add_emitter(&mut self, config: EmitterConfig, position: glam::Vec3) -> Result<EmitterId, Error> {
+        if config.max_particles == 0 {
+            return Err(Error::NoCapacity);
+        }
+
+        let mut batch = Batch::new(self.program.id(), quad(), &vec![glam::Mat4::ZERO; config.max_particles])?;
+        batch.set_blend_mode(config.blend);
+
+        self.emitters.push(Emitter {
+            config,
+            position,
+            enabled: true,
+            particles: Vec::new(),
+            accumulator: 0.0,
+            batch,
+        });
+
+        Ok(EmitterId(self.emitters.len() - 1))
+    }
+
+    pub fn emitter(&self, id: EmitterId) -> &Emitter {
+        &self.emitters[id.0]
+    }
+
+    pub fn emitter_mut(&mut self, id: EmitterId) -> &mut Emitter {
+        &mut self.emitters[id.0]
+    }
+
+    /// Particles alive across every emitter.
+    pub fn len(&self) -> usize {
+        self.emitters.iter().map(Emitter::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.emitters.iter().all(Emitter::is_empty)
+    }
+
+    /// Spawn `count` particles from `id` at once, as far as its `max_particles` allows, enabled or not.
+    pub fn burst(&mut self, id: EmitterId, count: usize) {
+        for _ in 0..count {
+            if !self.spawn(id.0) {
+                break;
+            }
+        }
+    }
+
+    /// Age, move and spawn particles by `dt`.
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        for i in 0..self.emitters.len() {
+            let emitter = &mut self.emitters[i];
+            let acceleration = emitter.config.acceleration;
+            emitter.particles.retain_mut(|particle| {
+                particle.age += dt;
+                particle.velocity += acceleration * dt;
+                particle.position += particle.velocity * dt;
+                particle.age < particle.lifetime
+            });
+
+            if !emitter.enabled {
+                emitter.accumulator = 0.0;
+                continue;
+            }
+
+            emitter.accumulator += emitter.config.rate.max(0.0) * dt;
+            let due = emitter.accumulator.floor();
+            emitter.accumulator -= due;
+            for _ in 0..due as usize {
+                if !self.spawn(i) {
+                    // Full, don't save up particles to spawn in a burst once there's room
+                    self.emitters[i].accumulator = 0.0;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Draw every emitter's particles facing `camera`, over the bound framebuffer. Depth is tested but not written,
+    /// so this belongs after opaque geometry.
+    pub fn draw(&mut self, camera: &Camera) {
+        let eye = camera.transform.position;
+        // The view matrix rows are the camera axes in world space
+        let right = camera.view.row(0).truncate().normalize();
+        let up = camera.view.row(1).truncate().normalize();
+        let forward = camera.view.row(2).truncate().normalize();
+
+        self.program.use_program();
+        self.program.set_mat4fv("View", camera.view, 0);
+        self.program.set_mat4fv("Projection", camera.projection, 0);
+
+        let mut transforms = Vec::new();
+        for emitter in self.emitters.iter_mut().filter(|e| !e.is_empty()) {
+            emitter.particles.sort_by(|a, b| {
+                b.position.distance_squared(eye).total_cmp(&a.position.distance_squared(eye))
+            });
+
+            transforms.clear();
+            transforms.extend(emitter.particles.iter().map(|particle| {
+                let life = particle.age / particle.lifetime;
+                let size = emitter.config.size.sample(life);
+                let color = emitter.config.color.sample(life);
+                glam::Mat4::from_cols(
+                    (right * size).extend(color.x),
+                    (up * size).extend(color.y),
+                    forward.extend(color.z),
+                    particle.position.extend(color.w),
+                )
+            }));
+            transforms.resize(emitter.config.max_particles, glam::Mat4::ZERO);
+            emitter.batch.set_all_transforms(&transforms);
+        }
+
+        let mut queue = RenderQueue::new();
+        for emitter in self.emitters.iter().filter(|e| !e.is_empty()) {
+            let batch = &emitter.batch;
+            let depth = emitter.position.distance(eye);
+            queue.push(SortKey::new(0, batch.blend_mode().is_transparent(), batch.program_id(), 0, depth), batch);
+        }
+        queue.execute();
+    }
+
+    /// Spawn one particle from emitter `index`. False if it's full.
+    fn spawn(&mut self, index: usize) -> bool {
+        let (lifetime, offset, spread) = (self.random(), self.random_in_cube(), self.random_in_sphere());
+
+        let emitter = &mut self.emitters[index];
+        if emitter.particles.len() >= emitter.config.max_particles {
+            return false;
+        }
+
+        let config = &emitter.config;
+        let (min, max) = config.lifetime;
+        emitter.particles.push(Particle {
+            position: emitter.position + offset * config.spawn_extents,
+            velocity: config.velocity + spread * config.velocity_spread,
+            age: 0.0,
+            lifetime: (min + (max - min) * lifetime).max(f32::EPSILON),
+        });
+
+        true
+    }
+
+    /// Uniform in [0, 1), from xorshift64*.
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        bits as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in [-1, 1) on every axis.
+    fn random_in_cube(&mut self) -> glam::Vec3 {
+        glam::vec3(self.random(), self.random(), self.random()) * 2.0 - glam::Vec3::ONE
+    }
+
+    /// Uniform within the unit sphere, by rejection.
+    fn random_in_sphere(&mut self) -> glam::Vec3 {
+        loop {
+            let point = self.random_in_cube();
+            if point.length_squared() <= 1.0 {
+                return point;
+            }
+        }
+    }
+}
+
+/// System aging, moving and spawning the particles of the `ParticleSystem` resource by the frame's length.
+pub fn advance_particles(frame: Res<FrameTime>, mut particles: ResMut<ParticleSystem>) {
+    particles.update(frame.0);
+}
+
+/// Unit quad in the XY plane, centered on the origin.
+fn quad() -> Mesh {
+    let corner = |x: f32, y: f32| Vertex {
+        pos: (x, y, 0.0).into(),
+        color: (1.0, 1.0, 1.0, 1.0).into(),
+        normal: (0.0, 0.0, -1.0).into(),
+    };
+
+    Mesh::new(
+        vec![corner(-0.5, -0.5), corner(0.5, -0.5), corner(0.5, 0.5), corner(-0.5, 0.5)],
+        vec![0, 1, 2, 2, 3, 0],
+    )
+}
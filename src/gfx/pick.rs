@@ -0,0 +1,119 @@
+//! Mouse picking on the CPU: which rendered entity is under a point on the screen.
+//!
+//! A ray is cast from the camera through the point, tested against every entity's bounding sphere first, then
+//! exactly against the triangles of the ones it passes through. No GPU readback, so the answer is available
+//! immediately, at the cost of walking the meshes near the ray.
+//! ## Example
+//! ```ignore
+//! if let Some(hit) = gfx::pick::pick(&mut world, &extractor, &camera, &viewport, mouse_position) {
+//!     world.add_component(hit.entity, gfx::select::Selected).unwrap();
+//! }
+//! ```
+
+use crate::logic::{Entity, World};
+use crate::math::isometry::Transform3;
+
+use super::camera::Camera;
+use super::extract::{BatchExtractor, MeshHandle};
+use super::snapshot::VertexSnapshot;
+use super::viewport::Viewport;
+
+/// Closest entity along a picking ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    pub entity: Entity,
+    /// World-space point where the ray hit the entity's surface.
+    pub point: glam::Vec3,
+    /// From the camera to `point`.
+    pub distance: f32,
+}
+
+/// Closest rendered entity drawn at `cursor`, in pixels from the top left corner of `viewport`.
+pub fn pick(
+    world: &mut World,
+    extractor: &BatchExtractor,
+    camera: &Camera,
+    viewport: &Viewport,
+    cursor: glam::Vec2,
+) -> Option<Pick> {
+    let (origin, direction) = camera.screen_ray(cursor, viewport);
+    pick_ray(world, extractor, origin, direction)
+}
+
+/// Closest rendered entity hit by the ray from `origin` along the normalized `direction`.
+pub fn pick_ray(world: &mut World, extractor: &BatchExtractor, origin: glam::Vec3, direction: glam::Vec3) -> Option<Pick> {
+    let entities: Vec<Entity> = world.iter_entities().collect();
+    let mut closest: Option<Pick> = None;
+
+    for entity in entities {
+        let (mesh, transform) = match (world.get_component::<MeshHandle>(entity).map(|m| *m),
+                                       world.get_component::<Transform3>(entity).map(|t| t.clone())) {
+            (Ok(mesh), Ok(transform)) => (mesh, transform),
+            _ => continue,
+        };
+
+        // Cheap rejection before touching any triangles
+        let (center, radius) = extractor.mesh(mesh).bounding_sphere();
+        let center = transform.matrix().transform_point3(center);
+        let radius = radius * transform.scale.abs().max_element();
+        match ray_sphere(origin, direction, center, radius) {
+            Some(distance) if closest.map_or(true, |c| distance < c.distance) => {},
+            _ => continue,
+        }
+
+        let snapshot = VertexSnapshot::new(extractor.mesh(mesh), &transform);
+        for i in 0..snapshot.triangle_count() {
+            if let Some(distance) = ray_triangle(origin, direction, snapshot.triangle(i)) {
+                if closest.map_or(true, |c| distance < c.distance) {
+                    closest = Some(Pick { entity, point: origin + direction * distance, distance });
+                }
+            }
+        }
+    }
+
+    closest
+}
+
+/// Distance along the ray to where it enters the sphere, or 0 if it starts inside.
+fn ray_sphere(origin: glam::Vec3, direction: glam::Vec3, center: glam::Vec3, radius: f32) -> Option<f32> {
+    let to_center = center - origin;
+    let along = to_center.dot(direction);
+    let distance_squared = to_center.length_squared() - along * along;
+    if distance_squared > radius * radius {
+        return None;
+    }
+
+    let half_chord = (radius * radius - distance_squared).sqrt();
+    if along + half_chord < 0.0 {
+        return None;
+    }
+
+    Some((along - half_chord).max(0.0))
+}
+
+/// Möller–Trumbore intersection, hitting either side of the triangle.
+fn ray_triangle(origin: glam::Vec3, direction: glam::Vec3, [a, b, c]: [glam::Vec3; 3]) -> Option<f32> {
+    let edge_ab = b - a;
+    let edge_ac = c - a;
+    let p = direction.cross(edge_ac);
+    let determinant = edge_ab.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(edge_ab);
+    let v = direction.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge_ac.dot(q) * inverse;
+    if distance >= 0.0 { Some(distance) } else { None }
+}
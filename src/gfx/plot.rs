@@ -0,0 +1,140 @@
+//! Immediate-mode plots of values over time, for tuning things like physics or camera smoothing.
+//!
+//! `debug_plot!` can be called from anywhere, once per frame per series, and keeps the last `HISTORY` samples of
+//! each series. `draw()` renders them as small line graphs in the diagnostics overlay, and `write_csv()` dumps
+//! everything for a closer look elsewhere.
+//! ## Example
+//! ```ignore
+//! debug_plot!("camera speed", velocity.length());
+//!
+//! // In the overlay
+//! gfx::plot::draw(&mut debug_draw, &mut text, glam::vec2(8.0, 64.0));
+//! debug_draw.flush_screen(&viewport);
+//! text.flush(&viewport);
+//! ```
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+
+use super::debug_draw::DebugDraw;
+use super::text::TextRenderer;
+
+/// Samples kept per series.
+pub const HISTORY: usize = 240;
+/// Size of each graph in pixels.
+const GRAPH_SIZE: glam::Vec2 = glam::const_vec2!([240.0, 48.0]);
+const GRAPH_SPACING: f32 = 8.0;
+
+/// Record `value` as the next sample of the series `name`, creating the series on first use.
+#[macro_export]
+macro_rules! debug_plot {
+    ($name:expr, $value:expr) => {
+        $crate::gfx::plot::record($name, $value as f32)
+    };
+}
+
+struct Series {
+    name: String,
+    samples: VecDeque<f32>,
+}
+
+static SERIES: Mutex<Vec<Series>> = Mutex::new(Vec::new());
+
+fn series() -> std::sync::MutexGuard<'static, Vec<Series>> {
+    // Plots are only diagnostics, so a panic while one was held shouldn't take them down too
+    SERIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Use `debug_plot!` instead, which converts the value.
+pub fn record(name: &str, value: f32) {
+    let mut series = series();
+
+    let index = match series.iter().position(|s| s.name == name) {
+        Some(index) => index,
+        None => {
+            series.push(Series { name: name.to_owned(), samples: VecDeque::with_capacity(HISTORY) });
+            series.len() - 1
+        },
+    };
+
+    let samples = &mut series[index].samples;
+    if samples.len() == HISTORY {
+        samples.pop_front();
+    }
+    samples.push_back(value);
+}
+
+/// Names of every series, in the order they were first recorded.
+pub fn names() -> Vec<String> {
+    series().iter().map(|s| s.name.clone()).collect()
+}
+
+/// Samples of the series `name`, oldest first.
+pub fn samples(name: &str) -> Option<Vec<f32>> {
+    series().iter().find(|s| s.name == name).map(|s| s.samples.iter().copied().collect())
+}
+
+/// Forget every series.
+pub fn clear() {
+    series().clear();
+}
+
+/// Write every series as a column of CSV, with the series names as the header. Rows are aligned on the newest
+/// sample, so series that started later have empty cells at the top.
+pub fn write_csv<W: Write>(mut writer: W) -> std::io::Result<()> {
+    let series = series();
+
+    let names: Vec<String> = series.iter().map(|s| format!("\"{}\"", s.name.replace('"', "\"\""))).collect();
+    writeln!(writer, "{}", names.join(","))?;
+
+    let rows = series.iter().map(|s| s.samples.len()).max().unwrap_or(0);
+    for row in 0..rows {
+        let cells: Vec<String> = series.iter().map(|s| {
+            match (row + s.samples.len()).checked_sub(rows) {
+                Some(i) => s.samples[i].to_string(),
+                None => String::new(),
+            }
+        }).collect();
+        writeln!(writer, "{}", cells.join(","))?;
+    }
+
+    writer.flush()
+}
+
+/// Queue a graph of every series, stacked down from `origin` in pixels from the top left of the viewport, each
+/// scaled to its own range and labelled with its latest value. The caller flushes `debug_draw` with
+/// `flush_screen()` and `text` with `flush()`.
+pub fn draw(debug_draw: &mut DebugDraw, text: &mut TextRenderer, origin: glam::Vec2) {
+    let series = series();
+    let frame_color = glam::vec4(1.0, 1.0, 1.0, 0.3);
+    let line_color = glam::vec4(0.3, 1.0, 0.4, 1.0);
+
+    for (i, s) in series.iter().enumerate() {
+        let top_left = origin + glam::vec2(0.0, i as f32 * (GRAPH_SIZE.y + GRAPH_SPACING));
+        let bottom_right = top_left + GRAPH_SIZE;
+
+        debug_draw.line_2d(top_left, glam::vec2(bottom_right.x, top_left.y), frame_color);
+        debug_draw.line_2d(glam::vec2(top_left.x, bottom_right.y), bottom_right, frame_color);
+
+        let (min, max) = s.samples.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        });
+        // Flat series sit in the middle instead of dividing by zero
+        let range = if max > min { max - min } else { 1.0 };
+        let offset = if max > min { 0.0 } else { 0.5 };
+
+        let point = |index: usize, value: f32| glam::vec2(
+            top_left.x + index as f32 / (HISTORY - 1) as f32 * GRAPH_SIZE.x,
+            bottom_right.y - ((value - min) / range + offset) * GRAPH_SIZE.y,
+        );
+        // Newest sample on the right edge
+        let start = HISTORY - s.samples.len();
+        for (j, (a, b)) in s.samples.iter().zip(s.samples.iter().skip(1)).enumerate() {
+            debug_draw.line_2d(point(start + j, *a), point(start + j + 1, *b), line_color);
+        }
+
+        let latest = s.samples.back().copied().unwrap_or(0.0);
+        text.draw(&format!("{} {:.3}", s.name, latest), top_left + glam::vec2(2.0, 2.0), 0.5, glam::Vec4::ONE);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::resource::Resource;
+
+use super::color::ColorSpace;
+use super::shader::{self, Program, Shader};
+use super::state::RenderState;
+use super::target::{self, MultisampleTarget, RenderTarget};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to build post-processing program")]
+    Program(#[from] shader::Error),
+    #[error("failed to build post-processing target")]
+    Target(#[from] target::Error),
+}
+
+/// Anti-aliasing applied when the scene is resolved to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AntiAliasing {
+    None,
+    /// Fast approximate anti-aliasing. A single fullscreen pass that blurs along detected luma edges,
+    /// much cheaper than multisampled targets at the cost of some texture sharpness.
+    Fxaa,
+    /// The scene is drawn into a target with this many samples per pixel, resolved before post-processing.
+    /// Smooths geometry edges, and alpha-to-coverage cutouts, at the cost of memory and fill rate.
+    Msaa(i32),
+}
+
+/// Renders the scene into an offscreen target, then resolves it onto the default framebuffer
+/// with a fullscreen pass.
+/// ## Example
+/// ```ignore
+/// let mut post = gfx::PostProcess::new(&res, 640, 480, gfx::AntiAliasing::Fxaa, gfx::ColorSpace::Srgb).unwrap();
+///
+/// post.begin();
+/// batch.draw();
+/// post.end();
+/// ```
+pub struct PostProcess {
+    target: RenderTarget,
+    /// Drawn into instead of `target` with `AntiAliasing::Msaa`.
+    multisample: Option<MultisampleTarget>,
+    anti_aliasing: AntiAliasing,
+    blit_program: Program,
+    fxaa_program: Program,
+    vao: gl::types::GLuint, // empty, the fullscreen triangle is generated from gl_VertexID
+}
+
+impl PostProcess {
+    /// `color_space` is the storage of the intermediate scene target and should match the default framebuffer,
+    /// so that enabling `GL_FRAMEBUFFER_SRGB` encodes the scene once when written and decodes it when resolved.
+    pub fn new(
+        res: &Resource,
+        width: i32,
+        height: i32,
+        anti_aliasing: AntiAliasing,
+        color_space: ColorSpace,
+    ) -> Result<Self, Error> {
+        let blit_program = post_program(res, "blit")?;
+        let fxaa_program = post_program(res, "fxaa")?;
+
+        let mut vao: gl::types::GLuint = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao); }
+
+        let mut post = PostProcess {
+            target: RenderTarget::new(width, height, color_space)?,
+            multisample: None,
+            anti_aliasing: AntiAliasing::None,
+            blit_program,
+            fxaa_program,
+            vao,
+        };
+        post.set_anti_aliasing(anti_aliasing)?;
+
+        Ok(post)
+    }
+
+    pub fn anti_aliasing(&self) -> AntiAliasing {
+        self.anti_aliasing
+    }
+
+    /// Switching to or between `AntiAliasing::Msaa` sample counts allocates a new multisampled target.
+    pub fn set_anti_aliasing(&mut self, anti_aliasing: AntiAliasing) -> Result<(), Error> {
+        self.multisample = match anti_aliasing {
+            AntiAliasing::Msaa(samples) => match self.multisample.take() {
+                Some(multisample) if self.anti_aliasing == anti_aliasing => Some(multisample),
+                _ => Some(MultisampleTarget::new(
+                    self.target.width(),
+                    self.target.height(),
+                    samples,
+                    self.target.color().color_space(),
+                )?),
+            },
+            _ => None,
+        };
+        self.anti_aliasing = anti_aliasing;
+
+        Ok(())
+    }
+
+    /// Samples per pixel of the target `begin()` binds, 1 unless multisampling.
+    pub fn samples(&self) -> i32 {
+        self.multisample.as_ref().map_or(1, MultisampleTarget::samples)
+    }
+
+    /// Should be called whenever the window is resized.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        if let Some(multisample) = &mut self.multisample {
+            multisample.resize(width, height)?;
+        }
+        Ok(self.target.resize(width, height)?)
+    }
+
+    /// Redirect all following draws into the offscreen scene target. While multisampling, it can't be read back
+    /// from, e.g. with `depth::read_depth()`, until `end()` resolves it.
+    pub fn begin(&self) {
+        match &self.multisample {
+            Some(multisample) => multisample.bind(),
+            None => self.target.bind(),
+        }
+    }
+
+    /// Resolve the scene target onto the default framebuffer using the selected anti-aliasing.
+    pub fn end(&self) {
+        if let Some(multisample) = &self.multisample {
+            multisample.resolve(&self.target);
+        }
+        RenderTarget::bind_default();
+
+        let program = match self.anti_aliasing {
+            AntiAliasing::None | AntiAliasing::Msaa(_) => &self.blit_program,
+            AntiAliasing::Fxaa => {
+                self.fxaa_program.set_vec2f("InverseScreenSize", glam::vec2(
+                    1.0 / self.target.width() as f32,
+                    1.0 / self.target.height() as f32,
+                ));
+                &self.fxaa_program
+            },
+        };
+
+        RenderState::fullscreen().apply();
+        program.use_program();
+        self.target.color().bind(0);
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+}
+
+/// Link the shared fullscreen triangle vertex shader with `shaders/post/<name>.frag`.
+pub(super) fn post_program(res: &Resource, name: &str) -> Result<Program, Error> {
+    let shaders = [
+        Shader::from_res(res, "shaders/post/fullscreen.vert")?,
+        Shader::from_res(res, &format!("shaders/post/{}.frag", name))?,
+    ];
+
+    Program::from_shaders(&shaders).map_err(|message| Error::Program(shader::Error::LinkError {
+        name: format!("shaders/post/{}", name),
+        message,
+    }))
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &mut self.vao); }
+    }
+}
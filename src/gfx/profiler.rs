@@ -0,0 +1,354 @@
+//! GPU frame profiling with timestamp queries.
+//!
+//! Scopes record a timestamp when they start and one when they end. Results are only read back
+//! `FRAMES_IN_FLIGHT` frames later, by which point the GPU has almost always finished with them, so profiling
+//! never waits on the GPU. Frames whose queries still aren't done are dropped instead.
+//!
+//! For deeper investigations, `capture()` records every scope of the next few frames, on both the CPU and the GPU,
+//! into a `Trace` that can be saved for chrome://tracing or Perfetto.
+//! ## Example
+//! ```ignore
+//! let mut profiler = gfx::GpuProfiler::new();
+//!
+//! // Every frame
+//! profiler.begin_frame();
+//! {
+//!     let _scope = profiler.scope("shadow pass");
+//!     // ...
+//! }
+//! for timing in profiler.results() {
+//!     println!("{}: {:?}", timing.name, timing.elapsed);
+//! }
+//!
+//! // Later, once the capture is done
+//! if let Some(trace) = profiler.take_trace() {
+//!     trace.write_chrome_json(std::fs::File::create("trace.json")?)?;
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// How many frames of queries are kept before reading them back.
+const FRAMES_IN_FLIGHT: usize = 4;
+
+/// GPU time spent in one scope of a finished frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeTiming {
+    pub name: String,
+    /// How many scopes this one is nested in.
+    pub depth: usize,
+    pub elapsed: Duration,
+}
+
+/// Which timeline a trace event happened on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    Cpu,
+    Gpu,
+}
+
+/// One scope instance in a captured trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    pub name: String,
+    pub track: Track,
+    /// Since the start of the capture.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Every scope recorded during a capture.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+    /// Name of the thread the CPU scopes ran on, the one that owns the profiler.
+    pub cpu_thread: String,
+}
+
+impl Trace {
+    /// Write the trace in the Chrome trace event JSON format, which chrome://tracing and Perfetto open directly.
+    /// CPU and GPU scopes show up as separate threads, the CPU one under its thread name.
+    pub fn write_chrome_json<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "{{\"traceEvents\":[")?;
+        writeln!(
+            writer,
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{{\"name\":\"CPU {}\"}}}},",
+            escape_json(&self.cpu_thread),
+        )?;
+        write!(writer, "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":2,\"args\":{{\"name\":\"GPU\"}}}}")?;
+
+        for event in self.events.iter() {
+            let tid = match event.track {
+                Track::Cpu => 1,
+                Track::Gpu => 2,
+            };
+            write!(
+                writer,
+                ",\n{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+                escape_json(&event.name),
+                tid,
+                event.start.as_secs_f64() * 1e6,
+                event.duration.as_secs_f64() * 1e6,
+            )?;
+        }
+
+        writeln!(writer, "\n],\"displayTimeUnit\":\"ms\"}}")?;
+        writer.flush()
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+struct PendingScope {
+    name: String,
+    depth: usize,
+    begin: gl::types::GLuint,
+    end: Option<gl::types::GLuint>,
+    cpu_begin: Instant,
+}
+
+#[derive(Default)]
+struct Frame {
+    scopes: Vec<PendingScope>,
+    /// Most recently issued timestamp.
+    last_query: Option<gl::types::GLuint>,
+    /// Whether this frame's scopes go into the capture in progress.
+    captured: bool,
+}
+
+struct Capture {
+    /// Frames still to be recorded, not counting ones already in flight.
+    frames_left: usize,
+    /// When the capture started on each clock. GPU timestamps are in nanoseconds.
+    cpu_epoch: Instant,
+    gpu_epoch: i64,
+    trace: Trace,
+}
+
+struct Inner {
+    frames: Vec<Frame>,
+    frame: usize,
+    depth: usize,
+    /// Query objects not used by any frame in flight.
+    free_queries: Vec<gl::types::GLuint>,
+    results: Vec<ScopeTiming>,
+    capture: Option<Capture>,
+    /// Finished capture waiting to be taken.
+    trace: Option<Trace>,
+}
+
+impl Inner {
+    fn query(&mut self) -> gl::types::GLuint {
+        match self.free_queries.pop() {
+            Some(query) => query,
+            None => {
+                let mut query: gl::types::GLuint = 0;
+                unsafe { gl::GenQueries(1, &mut query); }
+                query
+            },
+        }
+    }
+
+    fn timestamp(&mut self) -> gl::types::GLuint {
+        let query = self.query();
+        unsafe { gl::QueryCounter(query, gl::TIMESTAMP); }
+
+        let frame = self.frame;
+        self.frames[frame].last_query = Some(query);
+        query
+    }
+
+    /// Read back the frame in `slot` if all of its queries are done, and recycle them either way.
+    fn collect(&mut self, slot: usize) {
+        let Frame { scopes, last_query, captured } = std::mem::take(&mut self.frames[slot]);
+        let last = match last_query {
+            Some(query) => query,
+            None => return,
+        };
+
+        // Queries finish in order, so the last timestamp being available means every one is
+        let mut available: gl::types::GLint = 0;
+        unsafe { gl::GetQueryObjectiv(last, gl::QUERY_RESULT_AVAILABLE, &mut available); }
+
+        if available != 0 {
+            self.results.clear();
+            for scope in scopes.iter() {
+                if let Some(end) = scope.end {
+                    let (mut begin_ns, mut end_ns): (u64, u64) = (0, 0);
+                    unsafe {
+                        gl::GetQueryObjectui64v(scope.begin, gl::QUERY_RESULT, &mut begin_ns);
+                        gl::GetQueryObjectui64v(end, gl::QUERY_RESULT, &mut end_ns);
+                    }
+
+                    self.results.push(ScopeTiming {
+                        name: scope.name.clone(),
+                        depth: scope.depth,
+                        elapsed: Duration::from_nanos(end_ns.saturating_sub(begin_ns)),
+                    });
+
+                    if let Some(capture) = self.capture.as_mut().filter(|_| captured) {
+                        capture.trace.events.push(TraceEvent {
+                            name: scope.name.clone(),
+                            track: Track::Gpu,
+                            start: Duration::from_nanos((begin_ns as i64 - capture.gpu_epoch).max(0) as u64),
+                            duration: Duration::from_nanos(end_ns.saturating_sub(begin_ns)),
+                        });
+                    }
+                }
+            }
+        }
+
+        for scope in scopes {
+            self.free_queries.push(scope.begin);
+            self.free_queries.extend(scope.end);
+        }
+    }
+}
+
+pub struct GpuProfiler {
+    inner: RefCell<Inner>,
+}
+
+impl GpuProfiler {
+    pub fn new() -> Self {
+        GpuProfiler {
+            inner: RefCell::new(Inner {
+                frames: (0..FRAMES_IN_FLIGHT).map(|_| Frame::default()).collect(),
+                frame: 0,
+                depth: 0,
+                free_queries: Vec::new(),
+                results: Vec::new(),
+                capture: None,
+                trace: None,
+            }),
+        }
+    }
+
+    /// Start recording a new frame, picking up the results of the oldest frame in flight if they're ready.
+    pub fn begin_frame(&mut self) {
+        let inner = self.inner.get_mut();
+        inner.frame = (inner.frame + 1) % FRAMES_IN_FLIGHT;
+        inner.depth = 0;
+
+        let slot = inner.frame;
+        inner.collect(slot);
+
+        if let Some(capture) = inner.capture.as_mut() {
+            if capture.frames_left > 0 {
+                capture.frames_left -= 1;
+                inner.frames[slot].captured = true;
+            } else if !inner.frames.iter().any(|f| f.captured) {
+                // Every captured frame has been read back, or dropped
+                inner.trace = inner.capture.take().map(|c| c.trace);
+            }
+        }
+    }
+
+    /// Record every scope of the next `frames` frames into a trace, replacing any capture in progress.
+    /// It's ready from `take_trace()` once the last of those frames finished on the GPU.
+    pub fn capture(&mut self, frames: usize) {
+        let mut gpu_epoch: gl::types::GLint64 = 0;
+        unsafe { gl::GetInteger64v(gl::TIMESTAMP, &mut gpu_epoch); }
+
+        let inner = self.inner.get_mut();
+        for frame in inner.frames.iter_mut() {
+            frame.captured = false;
+        }
+        inner.capture = Some(Capture {
+            frames_left: frames,
+            cpu_epoch: Instant::now(),
+            gpu_epoch,
+            trace: Trace { events: Vec::new(), cpu_thread: crate::system::thread::current_name() },
+        });
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.inner.borrow().capture.is_some()
+    }
+
+    /// The finished capture, if there is one that hasn't been taken yet.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.inner.get_mut().trace.take()
+    }
+
+    /// Time GPU work issued until the returned guard is dropped, and the CPU time taken while capturing.
+    /// Scopes can be nested.
+    pub fn scope(&self, name: &str) -> GpuScope<'_> {
+        let mut inner = self.inner.borrow_mut();
+        let begin = inner.timestamp();
+        let depth = inner.depth;
+        let frame = inner.frame;
+
+        inner.frames[frame].scopes.push(PendingScope {
+            name: name.to_owned(),
+            depth,
+            begin,
+            end: None,
+            cpu_begin: Instant::now(),
+        });
+        inner.depth += 1;
+
+        GpuScope { profiler: self, index: inner.frames[frame].scopes.len() - 1 }
+    }
+
+    /// Scope timings of the most recent frame that finished on the GPU, in the order the scopes were opened.
+    pub fn results(&self) -> Vec<ScopeTiming> {
+        self.inner.borrow().results.clone()
+    }
+}
+
+impl Drop for GpuProfiler {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        let mut queries: Vec<gl::types::GLuint> = inner.free_queries.drain(..).collect();
+        for scope in inner.frames.iter().flat_map(|f| f.scopes.iter()) {
+            queries.push(scope.begin);
+            queries.extend(scope.end);
+        }
+
+        unsafe { gl::DeleteQueries(queries.len() as gl::types::GLsizei, queries.as_ptr()); }
+    }
+}
+
+/// Ends its profiler scope when dropped.
+pub struct GpuScope<'a> {
+    profiler: &'a GpuProfiler,
+    index: usize,
+}
+
+impl<'a> Drop for GpuScope<'a> {
+    fn drop(&mut self) {
+        let mut inner = self.profiler.inner.borrow_mut();
+        let end = inner.timestamp();
+        let frame = inner.frame;
+
+        inner.frames[frame].scopes[self.index].end = Some(end);
+        inner.depth -= 1;
+
+        if inner.frames[frame].captured {
+            let scope = &inner.frames[frame].scopes[self.index];
+            let event = inner.capture.as_ref().map(|capture| TraceEvent {
+                name: scope.name.clone(),
+                track: Track::Cpu,
+                start: scope.cpu_begin.saturating_duration_since(capture.cpu_epoch),
+                duration: scope.cpu_begin.elapsed(),
+            });
+            if let (Some(capture), Some(event)) = (inner.capture.as_mut(), event) {
+                capture.trace.events.push(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Automatic quality scaling to hold a GPU frame time budget.
+//!
+//! Each quality setting the renderer can trade for speed is registered as a knob with a number of levels,
+//! 0 being the cheapest, and the profiler scope its cost shows up in. `AutoQuality::update()` watches the
+//! profiler's timings: when frames stay over budget, the knob whose scope takes longest is stepped down, and when
+//! they stay comfortably under, the most recently lowered knob is stepped back up. Both directions have to hold
+//! for a number of frames, and a margin around the target keeps it from oscillating.
+//! ## Example
+//! ```ignore
+//! let mut quality = gfx::AutoQuality::new(std::time::Duration::from_secs_f32(1.0 / 60.0));
+//! let shadow_filtering = quality.add_knob("shadow filtering", "scene", 3, 2);
+//!
+//! // Every frame
+//! if let Some(change) = quality.update(&profiler.results()) {
+//!     if change.knob == shadow_filtering {
+//!         shadow.set_pcf_radius(change.to as i32);
+//!     }
+//! }
+//! ```
+
+use std::time::Duration;
+
+use crate::log::LOGGER;
+
+use super::profiler::ScopeTiming;
+
+struct Knob {
+    name: String,
+    /// Profiler scope the knob's cost is measured by.
+    scope: String,
+    levels: usize,
+    level: usize,
+}
+
+/// A knob stepped to a new level by `AutoQuality::update()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QualityChange {
+    /// Index returned by `add_knob()`.
+    pub knob: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+pub struct AutoQuality {
+    target: Duration,
+    enabled: bool,
+    /// Fraction of the target frame time has to be over or under it by to count.
+    margin: f32,
+    /// Consecutive frames over budget before stepping down.
+    down_frames: usize,
+    /// Consecutive frames under budget before stepping up, longer so quality isn't raised back too eagerly.
+    up_frames: usize,
+    over: usize,
+    under: usize,
+    knobs: Vec<Knob>,
+    /// Knobs stepped down, most recent last, so quality comes back in reverse order.
+    lowered: Vec<usize>,
+}
+
+impl AutoQuality {
+    /// Hold GPU frames to `target`, with a 10% margin, stepping down after 30 frames over it and up after 240 under.
+    pub fn new(target: Duration) -> Self {
+        AutoQuality {
+            target,
+            enabled: true,
+            margin: 0.1,
+            down_frames: 30,
+            up_frames: 240,
+            over: 0,
+            under: 0,
+            knobs: Vec::new(),
+            lowered: Vec::new(),
+        }
+    }
+
+    /// Register a setting with `levels` levels, currently at `level`. Its cost is judged by the profiler scope
+    /// named `scope`. Returns the index `QualityChange`s refer to it by.
+    pub fn add_knob(&mut self, name: &str, scope: &str, levels: usize, level: usize) -> usize {
+        assert!(level < levels, "knob level out of range");
+
+        self.knobs.push(Knob { name: name.to_owned(), scope: scope.to_owned(), levels, level });
+        self.knobs.len() - 1
+    }
+
+    pub fn level(&self, knob: usize) -> usize {
+        self.knobs[knob].level
+    }
+
+    pub fn target(&self) -> Duration {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Duration) {
+        self.target = target;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// While disabled, `update()` never changes anything.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.over = 0;
+        self.under = 0;
+    }
+
+    /// See `new()` for what these mean.
+    pub fn set_hysteresis(&mut self, margin: f32, down_frames: usize, up_frames: usize) {
+        self.margin = margin;
+        self.down_frames = down_frames;
+        self.up_frames = up_frames;
+    }
+
+    /// Feed the latest profiler timings, returning the knob that changed level, if any. At most one knob
+    /// changes per call, and the counters start over after each change to let it take effect.
+    pub fn update(&mut self, timings: &[ScopeTiming]) -> Option<QualityChange> {
+        if !self.enabled || timings.is_empty() {
+            return None;
+        }
+
+        let frame_time: Duration = timings.iter().filter(|t| t.depth == 0).map(|t| t.elapsed).sum();
+        let target = self.target.as_secs_f32();
+
+        if frame_time.as_secs_f32() > target * (1.0 + self.margin) {
+            self.over += 1;
+            self.under = 0;
+        } else if frame_time.as_secs_f32() < target * (1.0 - self.margin) {
+            self.under += 1;
+            self.over = 0;
+        } else {
+            self.over = 0;
+            self.under = 0;
+        }
+
+        let change = if self.over >= self.down_frames {
+            self.step_down(timings)
+        } else if self.under >= self.up_frames {
+            self.step_up()
+        } else {
+            None
+        };
+
+        if let Some(change) = change {
+            self.over = 0;
+            self.under = 0;
+
+            LOGGER().a.info(format!(
+                "auto quality: {} {} -> {} (GPU frame {:.2} ms, target {:.2} ms)",
+                self.knobs[change.knob].name,
+                change.from,
+                change.to,
+                frame_time.as_secs_f32() * 1000.0,
+                target * 1000.0,
+            ).as_str());
+        }
+
+        change
+    }
+
+    /// Lower the knob whose scope is the most expensive, out of the ones that can still go lower.
+    fn step_down(&mut self, timings: &[ScopeTiming]) -> Option<QualityChange> {
+        let cost = |knob: &Knob| -> Duration {
+            timings.iter().filter(|t| t.name == knob.scope).map(|t| t.elapsed).sum()
+        };
+
+        let knob = self
+            .knobs
+            .iter()
+            .enumerate()
+            .filter(|(_, knob)| knob.level > 0)
+            .max_by_key(|(_, knob)| cost(knob))
+            .map(|(i, _)| i)?;
+
+        let from = self.knobs[knob].level;
+        self.knobs[knob].level -= 1;
+        self.lowered.push(knob);
+
+        Some(QualityChange { knob, from, to: from - 1 })
+    }
+
+    fn step_up(&mut self) -> Option<QualityChange> {
+        let knob = self.lowered.pop()?;
+        if self.knobs[knob].level + 1 >= self.knobs[knob].levels {
+            return None;
+        }
+
+        let from = self.knobs[knob].level;
+        self.knobs[knob].level += 1;
+
+        Some(QualityChange { knob, from, to: from + 1 })
+    }
+}
@@ -0,0 +1,130 @@
+//! Sorted draw submission.
+//!
+//! Instead of drawing batches as they're visited, renderers push them into a `RenderQueue` with a `SortKey`, and
+//! `execute()` sorts everything once and draws it in key order. The key packs, from most to least significant:
+//! ```text
+//! 63..56  layer                  e.g. world before overlays
+//! 55      transparent            opaque first
+//! opaque:      program (16), material (16), depth (23)   fewest program and state changes, then front-to-back
+//! transparent: inverted depth (23), program (16), material (16)   back-to-front
+//! ```
+//! Render state is only reapplied between draws that need a different one.
+//! ## Example
+//! ```ignore
+//! let mut queue = gfx::RenderQueue::new();
+//! for (batch, material) in visible {
+//!     let depth = batch.center().distance(eye);
+//!     queue.push(SortKey::new(0, batch.blend_mode().is_transparent(), batch.program_id(), material, depth), batch);
+//! }
+//! queue.execute();
+//! ```
+
+use super::batch::Batch;
+use super::state::RenderState;
+
+const DEPTH_BITS: u32 = 23;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SortKey(pub u64);
+
+impl SortKey {
+    /// `program` and `material` only group draws, so anything wider than 16 bits is folded in. `depth` is the
+    /// distance from the camera, negative depths counting as 0.
+    pub fn new(layer: u8, transparent: bool, program: u32, material: u32, depth: f32) -> Self {
+        let program = fold16(program);
+        let material = fold16(material);
+        let depth = quantize_depth(depth);
+
+        let sorted = if transparent {
+            let back_to_front = !depth & ((1 << DEPTH_BITS) - 1);
+            (back_to_front << 32) | (program << 16) | material
+        } else {
+            (program << (16 + DEPTH_BITS)) | (material << DEPTH_BITS) | depth
+        };
+
+        SortKey(((layer as u64) << 56) | ((transparent as u64) << 55) | sorted)
+    }
+
+    pub fn layer(self) -> u8 {
+        (self.0 >> 56) as u8
+    }
+
+    pub fn is_transparent(self) -> bool {
+        self.0 & (1 << 55) != 0
+    }
+}
+
+fn fold16(value: u32) -> u64 {
+    ((value ^ (value >> 16)) & 0xFFFF) as u64
+}
+
+/// Top `DEPTH_BITS` bits of the float. Non-negative floats order the same as their bit patterns.
+fn quantize_depth(depth: f32) -> u64 {
+    let depth = if depth > 0.0 { depth } else { 0.0 };
+    (depth.to_bits() >> (32 - DEPTH_BITS)) as u64
+}
+
+/// One queued draw of a whole batch.
+#[derive(Clone, Copy)]
+struct Draw<'a> {
+    key: SortKey,
+    batch: &'a Batch,
+    program: gl::types::GLuint,
+    render_state: Option<RenderState>,
+}
+
+/// Draws collected over a frame, run in `SortKey` order. Borrows the batches until executed or dropped.
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    draws: Vec<Draw<'a>>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        RenderQueue { draws: Vec::new() }
+    }
+
+    /// Queue `batch` to be drawn with its own program and render state.
+    pub fn push(&mut self, key: SortKey, batch: &'a Batch) {
+        self.draws.push(Draw { key, batch, program: batch.program_id(), render_state: batch.render_state().copied() });
+    }
+
+    /// Queue `batch` to be drawn with `program` and `render_state` instead of its own, e.g. for depth-only
+    /// passes. `None` keeps whatever state the previous draw left.
+    pub fn push_with(
+        &mut self,
+        key: SortKey,
+        batch: &'a Batch,
+        program: gl::types::GLuint,
+        render_state: Option<RenderState>,
+    ) {
+        self.draws.push(Draw { key, batch, program, render_state });
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Sort and draw everything queued, leaving the queue empty. Instances within a transparent batch are drawn
+    /// in the batch's own order, see `Batch::sort_back_to_front`.
+    pub fn execute(&mut self) {
+        // Stable, so equal keys keep submission order
+        self.draws.sort_by_key(|draw| draw.key);
+
+        let mut current_state: Option<RenderState> = None;
+        for draw in self.draws.drain(..) {
+            if let Some(render_state) = draw.render_state {
+                if current_state != Some(render_state) {
+                    render_state.apply();
+                    current_state = Some(render_state);
+                }
+            }
+
+            draw.batch.draw_with_program(draw.program);
+        }
+    }
+}
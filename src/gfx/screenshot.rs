@@ -0,0 +1,258 @@
+//! Screenshots.
+//!
+//! The pixels are read back on the calling thread, since that needs the GL context, but flipping and PNG encoding
+//! happen on a separate thread so saving a screenshot doesn't hitch the frame.
+//! ## Example
+//! ```ignore
+//! // After everything is drawn to the window, before swapping
+//! gfx::capture_screenshot("screenshot.png");
+//! window.gl_swap_window();
+//! ```
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+use crate::log::LOGGER;
+use crate::system::thread;
+
+use super::target::{self, RenderTarget};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to write screenshot")]
+    Io(#[from] std::io::Error),
+
+    #[error("nothing to capture, the viewport is {}x{}", width, height)]
+    EmptyImage {
+        width: i32,
+        height: i32,
+    },
+}
+
+/// Save the current viewport of the window's default framebuffer as a PNG at `path`.
+pub fn capture_screenshot<P: AsRef<Path>>(path: P) -> Result<JoinHandle<Result<(), Error>>, Error> {
+    let mut viewport = [0; 4];
+    unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()); }
+
+    let pixels = read_pixels(0, viewport[0], viewport[1], viewport[2], viewport[3])?;
+    encode_async(path.as_ref().to_owned(), viewport[2], viewport[3], pixels)
+}
+
+/// Save the color attachment of `target` as a PNG at `path`.
+pub fn capture_target_screenshot<P: AsRef<Path>>(
+    path: P,
+    target: &RenderTarget,
+) -> Result<JoinHandle<Result<(), Error>>, Error> {
+    let pixels = read_pixels(target.fbo(), 0, 0, target.width(), target.height())?;
+    encode_async(path.as_ref().to_owned(), target.width(), target.height(), pixels)
+}
+
+/// The rows stay bottom-up, flipping them is left to the encoder thread.
+fn read_pixels(fbo: gl::types::GLuint, x: i32, y: i32, width: i32, height: i32) -> Result<Vec<u8>, Error> {
+    if width <= 0 || height <= 0 {
+        return Err(Error::EmptyImage { width, height });
+    }
+
+    Ok(target::read_framebuffer(fbo, x, y, width, height))
+}
+
+fn encode_async(path: PathBuf, width: i32, height: i32, pixels: Vec<u8>) -> Result<JoinHandle<Result<(), Error>>, Error> {
+    let handle = thread::spawn_named("screenshot encoder", move || {
+        let result = write_png(&path, width as u32, height as u32, &pixels);
+        match &result {
+            Ok(()) => LOGGER().a.info(format!("saved screenshot {}", path.display()).as_str()),
+            Err(e) => LOGGER().a.error(format!("failed to save screenshot {}: {}", path.display(), e).as_str()),
+        }
+        result
+    })?;
+
+    Ok(handle)
+}
+
+/// Write RGBA8 `pixels`, bottom row first, as a PNG at `path`.
+pub(super) fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+    let stride = width as usize * 4;
+
+    // Each scanline gets a filter byte, and OpenGL's rows are bottom-up while PNG's are top-down
+    let mut scanlines = Vec::with_capacity((stride + 1) * height as usize);
+    for row in pixels.chunks_exact(stride).rev() {
+        // Sub filter, storing each byte as the difference to the same channel of the pixel to its left
+        scanlines.push(1);
+        scanlines.extend_from_slice(&row[..4]);
+        scanlines.extend(row[4..].iter().zip(row).map(|(byte, left)| byte.wrapping_sub(*left)));
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, adaptive filtering, not interlaced
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(&mut file, b"IHDR", &header)?;
+    write_chunk(&mut file, b"IDAT", &zlib_compress(&scanlines))?;
+    write_chunk(&mut file, b"IEND", &[])?;
+    file.flush()?;
+
+    Ok(())
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+
+    let mut crc = 0xffffffffu32;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        crc = table[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xffffffff
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be summed before b can overflow
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// Deflate packs bits starting from the least significant one.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are the one thing stored most significant bit first.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Write a literal/length symbol with the fixed Huffman code from the deflate spec.
+fn write_literal(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASES.iter().rposition(|base| *base as usize <= length).unwrap();
+    write_literal(writer, 257 + code as u32);
+    writer.write((length - LENGTH_BASES[code] as usize) as u32, LENGTH_EXTRA_BITS[code] as u32);
+
+    let code = DISTANCE_BASES.iter().rposition(|base| *base as usize <= distance).unwrap();
+    writer.write_code(code as u32, 5);
+    writer.write((distance - DISTANCE_BASES[code] as usize) as u32, DISTANCE_EXTRA_BITS[code] as u32);
+}
+
+/// Compress `data` into a zlib stream of a single fixed Huffman block, finding matches through a hash of the last
+/// position each 3 bytes were seen at. Nowhere near zlib's ratio, but fast, and it catches the long runs of flat
+/// color most frames have.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: vec![0x78, 0x01], buffer: 0, count: 0 };
+    // Final block, fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let hash = |i: usize| {
+        let key = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (key.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+    };
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            let candidate = last_seen[h];
+            last_seen[h] = i;
+
+            if candidate != usize::MAX && i - candidate <= WINDOW_SIZE {
+                let max = MAX_MATCH.min(data.len() - i);
+                while length < max && data[candidate + length] == data[i + length] {
+                    length += 1;
+                }
+                distance = i - candidate;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            write_match(&mut writer, length, distance);
+            for j in i + 1..(i + length).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                last_seen[hash(j)] = j;
+            }
+            i += length;
+        } else {
+            write_literal(&mut writer, data[i] as u32);
+            i += 1;
+        }
+    }
+    write_literal(&mut writer, 256);
+
+    let mut bytes = writer.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
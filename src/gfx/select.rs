@@ -0,0 +1,158 @@
+//! Editor-style entity selection.
+//!
+//! Selected entities carry the `Selected` marker. `marquee_select()` updates it from a rectangle dragged across
+//! the screen, testing the screen-space bounds of every rendered entity, and reports what changed as a
+//! `SelectionChanged` for whatever mirrors the selection, like an outliner.
+//! ## Example
+//! ```ignore
+//! use gfx::select::{Marquee, SelectMode};
+//!
+//! let marquee = Marquee::new(drag_start, mouse_position);
+//! let changed = gfx::select::marquee_select(&mut world, &extractor, &camera, &viewport, &marquee, SelectMode::Replace);
+//! for entity in changed.added.iter() {
+//!     world.add_component(*entity, gfx::Outlined).unwrap();
+//! }
+//! ```
+
+use crate::logic::{Entity, World};
+use crate::math::isometry::Transform3;
+
+use super::camera::Camera;
+use super::extract::{BatchExtractor, MeshHandle};
+use super::viewport::Viewport;
+
+/// Marker component for entities selected in the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Selected;
+
+/// How a new selection combines with the current one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectMode {
+    /// Select exactly what was hit.
+    Replace,
+    /// Select what was hit on top of the current selection, e.g. while holding shift.
+    Add,
+    /// Deselect what was hit, e.g. while holding ctrl.
+    Remove,
+}
+
+/// Screen rectangle dragged from `start` to `end`, in pixels from the top left corner of the viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marquee {
+    pub start: glam::Vec2,
+    pub end: glam::Vec2,
+}
+
+impl Marquee {
+    pub fn new(start: glam::Vec2, end: glam::Vec2) -> Self {
+        Marquee { start, end }
+    }
+
+    pub fn min(&self) -> glam::Vec2 {
+        self.start.min(self.end)
+    }
+
+    pub fn max(&self) -> glam::Vec2 {
+        self.start.max(self.end)
+    }
+
+    /// Whether the marquee overlaps the rectangle from `min` to `max`.
+    pub fn overlaps(&self, min: glam::Vec2, max: glam::Vec2) -> bool {
+        let (own_min, own_max) = (self.min(), self.max());
+        own_min.x <= max.x && min.x <= own_max.x && own_min.y <= max.y && min.y <= own_max.y
+    }
+}
+
+/// Outcome of a selection change.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelectionChanged {
+    /// Every selected entity after the change.
+    pub selected: Vec<Entity>,
+    pub added: Vec<Entity>,
+    pub removed: Vec<Entity>,
+}
+
+impl SelectionChanged {
+    /// Whether anything was selected or deselected.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Screen rectangle covered by the bounding sphere of `mesh` placed at `transform`, as `(min, max)`.
+/// `None` if it's entirely behind the camera.
+pub fn screen_bounds(
+    extractor: &BatchExtractor,
+    mesh: MeshHandle,
+    transform: &Transform3,
+    camera: &Camera,
+    viewport: &Viewport,
+) -> Option<(glam::Vec2, glam::Vec2)> {
+    let (center, radius) = extractor.mesh(mesh).bounding_sphere();
+    let center = transform.matrix().transform_point3(center);
+    let radius = radius * transform.scale.abs().max_element();
+
+    // Corners of the box around the sphere, the ones behind the camera can't be projected and are left out
+    let corners = (0..8).filter_map(|i| {
+        let offset = glam::vec3(
+            if i & 1 == 0 { -radius } else { radius },
+            if i & 2 == 0 { -radius } else { radius },
+            if i & 4 == 0 { -radius } else { radius },
+        );
+        camera.project(center + offset, viewport)
+    });
+
+    corners.fold(None, |bounds, corner| match bounds {
+        Some((min, max)) => Some((corner.min(min), corner.max(max))),
+        None => Some((corner, corner)),
+    })
+}
+
+/// Update `Selected` on every rendered entity from the ones whose screen bounds overlap `marquee`.
+pub fn marquee_select(
+    world: &mut World,
+    extractor: &BatchExtractor,
+    camera: &Camera,
+    viewport: &Viewport,
+    marquee: &Marquee,
+    mode: SelectMode,
+) -> SelectionChanged {
+    let mut changed = SelectionChanged::default();
+    let entities: Vec<Entity> = world.iter_entities().collect();
+
+    for entity in entities {
+        let was_selected = world.has_component::<Selected>(entity);
+        let hit = match (world.get_component::<MeshHandle>(entity).map(|m| *m),
+                         world.get_component::<Transform3>(entity).map(|t| t.clone())) {
+            (Ok(mesh), Ok(transform)) => screen_bounds(extractor, mesh, &transform, camera, viewport)
+                .map_or(false, |(min, max)| marquee.overlaps(min, max)),
+            // Only rendered entities have bounds, anything else keeps its selection
+            _ => {
+                if was_selected {
+                    changed.selected.push(entity);
+                }
+                continue;
+            },
+        };
+
+        let selected = match mode {
+            SelectMode::Replace => hit,
+            SelectMode::Add => was_selected || hit,
+            SelectMode::Remove => was_selected && !hit,
+        };
+
+        if selected && !was_selected {
+            world.add_component(entity, Selected).unwrap();
+            changed.added.push(entity);
+        } else if !selected && was_selected {
+            world.remove_component::<Selected>(entity).unwrap();
+            changed.removed.push(entity);
+        }
+
+        if selected {
+            changed.selected.push(entity);
+        }
+    }
+
+    changed
+}
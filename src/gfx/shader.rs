@@ -1,6 +1,9 @@
 use crate::resource::Resource;
 use crate::log::LOGGER;
 
+use super::caps;
+use super::debug_group;
+
 use std::collections::HashMap;
 
 #[derive(thiserror::Error, Debug)]
@@ -24,8 +27,16 @@ pub enum Error {
         name: String,
         message: String
     },
+    #[error("bad #include in shader '{}': {}", name, message)]
+    IncludeError {
+        name: String,
+        message: String
+    },
 }
 
+/// How deep `#include`s may nest before assuming a file includes itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
 pub struct Program {
     id: gl::types::GLuint,
     uniforms: HashMap<String, UniformInfo>,
@@ -42,6 +53,12 @@ struct UniformInfo {
 
 impl Program {
     pub fn from_res(res: &Resource, name: &str) -> Result<Self, Error> {
+        Program::from_res_with_defines(res, name, &[])
+    }
+
+    /// Load a variant of `<name>.vert` and `<name>.frag`, with `#define <name> <value>` for every define added to
+    /// both right after their `#version`.
+    pub fn from_res_with_defines(res: &Resource, name: &str, defines: &[(&str, &str)]) -> Result<Self, Error> {
         const POSSIBLE_EXTENSIONS: [&str; 2] = [".vert", ".frag"];
 
         let resource_names = POSSIBLE_EXTENSIONS
@@ -51,13 +68,29 @@ impl Program {
         
         let shaders = resource_names
             .iter()
-            .map(|resource_name| Shader::from_res(res, resource_name))
+            .map(|resource_name| Shader::from_res_with_defines(res, resource_name, defines))
             .collect::<Result<Vec<Shader>, Error>>()?;
         
-        Program::from_shaders(&shaders[..]).map_err(|message| Error::LinkError {
+        let program = Program::from_shaders(&shaders[..]).map_err(|message| Error::LinkError {
             name: name.into(),
             message,
-        })
+        })?;
+        debug_group::label(gl::PROGRAM, program.id, name);
+
+        Ok(program)
+    }
+
+    /// Load a compute program from `<name>.comp`.
+    pub fn from_res_compute(res: &Resource, name: &str) -> Result<Self, Error> {
+        let shader = Shader::from_res(res, &format!("{}.comp", name))?;
+
+        let program = Program::from_shaders(&[shader]).map_err(|message| Error::LinkError {
+            name: name.into(),
+            message,
+        })?;
+        debug_group::label(gl::PROGRAM, program.id, name);
+
+        Ok(program)
     }
 
     pub fn from_shaders(shaders: &[Shader]) -> Result<Self, String> {
@@ -170,11 +203,21 @@ impl Program {
         unsafe { gl::UseProgram(self.id); }
     }
 
+    /// Whether the program has an active uniform `uniform_name`. The setters panic on uniforms it doesn't have.
+    pub fn has_uniform(&self, uniform_name: &str) -> bool {
+        self.uniforms.contains_key(uniform_name)
+    }
+
     #[inline(always)]
     pub fn set_i32(&self, uniform_name: &str, value: i32) {
         unsafe { gl::ProgramUniform1i(self.id, self.uniforms.get(uniform_name).unwrap().location, value); }
     }
 
+    #[inline(always)]
+    pub fn set_u32(&self, uniform_name: &str, value: u32) {
+        unsafe { gl::ProgramUniform1ui(self.id, self.uniforms.get(uniform_name).unwrap().location, value); }
+    }
+
     #[inline(always)]
     pub fn set_f32(&self, uniform_name: &str, value: f32) {
         unsafe { gl::ProgramUniform1f(self.id, self.uniforms.get(uniform_name).unwrap().location, value); }
@@ -198,6 +241,24 @@ impl Program {
             value.x, value.y, value.z, value.w); }
     }
 
+    /// Set a `vec4` array uniform starting from its first element.
+    pub fn set_vec4f_array(&self, uniform_name: &str, values: &[glam::Vec4]) {
+        let uniform = self.uniforms.get(uniform_name)
+            .or_else(|| self.uniforms.get(&format!("{}[0]", uniform_name)));
+
+        match uniform {
+            Some(p) => {
+                let flat: Vec<f32> = values.iter().flat_map(|v| v.to_array()).collect();
+                unsafe { gl::ProgramUniform4fv(self.id, p.location, values.len() as gl::types::GLsizei, flat.as_ptr()); }
+            },
+            _ => {
+                LOGGER().a.error(format!(
+                    "attempted to set uniform '{}' but it doesn't exist in the uniform map!", uniform_name
+                ).as_str());
+            }
+        }
+    }
+
     #[inline(always)]
     pub fn set_mat4fv(&self, uniform_name: &str, value: glam::Mat4, transpose: gl::types::GLboolean) {
         unsafe {
@@ -225,8 +286,13 @@ impl Drop for Program {
 
 impl Shader {
     pub fn from_res(res: &Resource, name: &str) -> Result<Self, Error> {
-        const POSSIBLE_EXTENSIONS: [(&str, gl::types::GLenum); 2] = 
-            [(".vert", gl::VERTEX_SHADER), (".frag", gl::FRAGMENT_SHADER)];
+        Shader::from_res_with_defines(res, name, &[])
+    }
+
+    /// Load `name` with `#define <name> <value>` for every define added right after its `#version`.
+    pub fn from_res_with_defines(res: &Resource, name: &str, defines: &[(&str, &str)]) -> Result<Self, Error> {
+        const POSSIBLE_EXTENSIONS: [(&str, gl::types::GLenum); 3] = 
+            [(".vert", gl::VERTEX_SHADER), (".frag", gl::FRAGMENT_SHADER), (".comp", gl::COMPUTE_SHADER)];
 
         let shader_kind = POSSIBLE_EXTENSIONS
             .iter()
@@ -234,15 +300,17 @@ impl Shader {
             .map(|&(_, kind)| kind)
             .ok_or_else(|| Error::UnknownShaderTypeForResource { name: name.into() })?;
         
-        let source = res.load_cstring(name).map_err(|e| Error::ResourceLoadError {
-            name: name.into(),
-            inner: e,
-        })?;
+        let source = add_defines(&caps::rewrite_glsl_version(&load_source(res, name, 0)?), defines);
+        // Sources were checked for nil bytes when loaded
+        let source = std::ffi::CString::new(source).unwrap();
 
-        Shader::from_source(&source, shader_kind).map_err(|message| Error::CompileError {
+        let shader = Shader::from_source(&source, shader_kind).map_err(|message| Error::CompileError {
             name: name.into(),
             message,
-        })
+        })?;
+        debug_group::label(gl::SHADER, shader.id, name);
+
+        Ok(shader)
     }
 
     pub fn from_source(source: &std::ffi::CStr, kind: gl::types::GLenum) -> Result<Shader, String> {
@@ -262,6 +330,60 @@ impl Drop for Shader {
     }
 }
 
+/// Load a shader source, replacing every `#include "path"` line with the contents of `path`,
+/// resolved relative to the including file.
+fn load_source(res: &Resource, name: &str, depth: usize) -> Result<String, Error> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(Error::IncludeError {
+            name: name.into(),
+            message: format!("includes nested deeper than {} levels", MAX_INCLUDE_DEPTH),
+        });
+    }
+
+    let source = res.load_cstring(name).map_err(|e| Error::ResourceLoadError {
+        name: name.into(),
+        inner: e,
+    })?;
+    let source = source.to_string_lossy();
+
+    let directory = match name.rfind('/') {
+        Some(i) => &name[..=i],
+        None => "",
+    };
+
+    let mut expanded = String::with_capacity(source.len());
+    for line in source.lines() {
+        match line.trim_start().strip_prefix("#include") {
+            Some(rest) => {
+                let path = rest.trim().strip_prefix('"').and_then(|p| p.strip_suffix('"')).ok_or_else(|| {
+                    Error::IncludeError { name: name.into(), message: format!("expected a quoted path: {}", line) }
+                })?;
+
+                expanded.push_str(&load_source(res, &format!("{}{}", directory, path), depth + 1)?);
+            },
+            None => expanded.push_str(line),
+        }
+        expanded.push('\n');
+    }
+
+    Ok(expanded)
+}
+
+/// Insert a `#define` line per define after the `#version` line, which has to stay first.
+fn add_defines(source: &str, defines: &[(&str, &str)]) -> String {
+    if defines.is_empty() {
+        return source.to_owned();
+    }
+
+    let mut lines: Vec<String> = source.lines().map(str::to_owned).collect();
+    let after_version = lines.iter().position(|line| line.trim_start().starts_with("#version")).map_or(0, |i| i + 1);
+    for (i, (name, value)) in defines.iter().enumerate() {
+        lines.insert(after_version + i, format!("#define {} {}", name, value));
+    }
+
+    lines.join("\n")
+}
+
 fn shader_from_source(source: &std::ffi::CStr, kind: gl::types::GLuint) -> Result<gl::types::GLuint, String> {
     let id = unsafe { gl::CreateShader(kind) };
     unsafe {
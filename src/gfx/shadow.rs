@@ -0,0 +1,140 @@
+//! Shadow mapping for directional lights.
+//!
+//! The scene is drawn depth-only from the light into a `DepthTarget`, then lit shaders compare each fragment's
+//! light-space depth against it. Shaders receiving shadows need these uniforms, set by `ShadowMap::apply`:
+//! ```text
+//! uniform mat4 LightSpace;         // world space -> light clip space
+//! uniform sampler2DShadow ShadowMap;
+//! uniform float ShadowBias;        // depth bias against shadow acne, scaled up on surfaces facing away from the light
+//! uniform int ShadowPcfRadius;     // PCF kernel is (2 * radius + 1)^2 taps, 0 for a single hardware-filtered tap
+//! uniform int ShadowReverseZ;      // 1 if the map was rendered with reverse-Z, see `state::set_reverse_z`
+//! ```
+//! ## Example
+//! ```ignore
+//! shadow.begin(sun_direction, scene_center, scene_radius);
+//! extractor.draw_with_program(shadow.program_id(), &shadow.frustum());
+//! shadow.end(&viewport);
+//! shadow.apply(&program, 1);
+//! ```
+
+use crate::math::frustum::Frustum;
+use crate::resource::Resource;
+
+use super::shader::{self, Program};
+use super::state::{self, RenderState};
+use super::target::{self, DepthTarget, RenderTarget};
+use super::viewport::Viewport;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load shadow program: {0}")]
+    Program(#[from] shader::Error),
+    #[error("failed to create shadow map: {0}")]
+    Target(#[from] target::Error),
+}
+
+pub struct ShadowMap {
+    target: DepthTarget,
+    program: Program,
+    light_space: glam::Mat4,
+    bias: f32,
+    pcf_radius: i32,
+}
+
+impl ShadowMap {
+    /// `size` is the width and height of the depth texture in texels.
+    pub fn new(res: &Resource, size: i32) -> Result<Self, Error> {
+        Ok(ShadowMap {
+            target: DepthTarget::new(size, size)?,
+            program: Program::from_res(res, "shaders/shadow")?,
+            light_space: glam::Mat4::IDENTITY,
+            bias: 0.005,
+            pcf_radius: 1,
+        })
+    }
+
+    pub fn size(&self) -> i32 {
+        self.target.width()
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    pub fn pcf_radius(&self) -> i32 {
+        self.pcf_radius
+    }
+
+    pub fn set_pcf_radius(&mut self, radius: i32) {
+        self.pcf_radius = radius.max(0);
+    }
+
+    /// World space to light clip space matrix used for the last `begin()`.
+    pub fn light_space(&self) -> glam::Mat4 {
+        self.light_space
+    }
+
+    /// Volume covered by the shadow map, for culling shadow casters.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.light_space)
+    }
+
+    /// Depth-only program batches should be drawn with between `begin()` and `end()`.
+    pub fn program_id(&self) -> gl::types::GLuint {
+        self.program.id()
+    }
+
+    /// Start the depth pass for a directional light shining along `direction`, covering the sphere at `center`
+    /// with `radius`. Everything outside of it is treated as lit.
+    pub fn begin(&mut self, direction: glam::Vec3, center: glam::Vec3, radius: f32) {
+        self.light_space = light_space_matrix(direction, center, radius);
+
+        self.target.bind();
+        unsafe { gl::Viewport(0, 0, self.target.width(), self.target.height()); }
+
+        RenderState::default().apply();
+        state::clear(gl::DEPTH_BUFFER_BIT);
+
+        self.program.set_mat4fv("LightSpace", self.light_space, 0);
+    }
+
+    /// Finish the depth pass, going back to the default framebuffer and `viewport`.
+    pub fn end(&self, viewport: &Viewport) {
+        RenderTarget::bind_default();
+        viewport.use_viewport();
+    }
+
+    /// Bind the shadow map to texture `unit` and set the shadow uniforms on `program`.
+    pub fn apply(&self, program: &Program, unit: u32) {
+        self.target.depth().bind(unit);
+
+        program.set_mat4fv("LightSpace", self.light_space, 0);
+        program.set_i32("ShadowMap", unit as i32);
+        program.set_f32("ShadowBias", self.bias);
+        program.set_i32("ShadowPcfRadius", self.pcf_radius);
+        program.set_i32("ShadowReverseZ", state::is_reverse_z() as i32);
+    }
+}
+
+/// Orthographic view-projection looking along `direction` that encloses the sphere at `center` with `radius`.
+/// Depth is reversed while `state::is_reverse_z()`, to match the flipped depth test.
+pub fn light_space_matrix(direction: glam::Vec3, center: glam::Vec3, radius: f32) -> glam::Mat4 {
+    let direction = direction.normalize();
+
+    // Any up vector works as long as it isn't parallel to the light
+    let up = if direction.dot(glam::Vec3::Y).abs() > 0.99 { glam::Vec3::Z } else { glam::Vec3::Y };
+
+    let eye = center - direction * radius;
+    let view = glam::Mat4::look_at_lh(eye, center, up);
+    let projection = if state::is_reverse_z() {
+        glam::Mat4::orthographic_lh(-radius, radius, -radius, radius, radius * 2.0, 0.0)
+    } else {
+        glam::Mat4::orthographic_lh(-radius, radius, -radius, radius, 0.0, radius * 2.0)
+    };
+
+    projection * view
+}
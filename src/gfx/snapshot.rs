@@ -0,0 +1,110 @@
+//! CPU copies of posed mesh geometry, for effects that need to know where a mesh's surface is right now,
+//! e.g. spawning particles on it or placing decals.
+//!
+//! Meshes are only ever posed by their entity's `Transform3`, so the snapshot applies the same transform the
+//! vertex shader does instead of reading anything back from the GPU.
+//! ## Example
+//! ```ignore
+//! if let Some(snapshot) = gfx::VertexSnapshot::of_entity(&mut world, &extractor, entity) {
+//!     let (position, normal) = snapshot.sample_surface(rand(), rand(), rand());
+//!     // Spawn a particle at `position` moving along `normal`
+//! }
+//! ```
+
+use crate::logic::{Entity, World};
+use crate::math::isometry::Transform3;
+
+use super::batch::Mesh;
+use super::extract::{BatchExtractor, MeshHandle};
+
+/// World-space positions and normals of a mesh placed by a transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexSnapshot {
+    pub positions: Vec<glam::Vec3>,
+    pub normals: Vec<glam::Vec3>,
+    pub indices: Vec<u32>,
+    /// Running total of triangle areas, for picking triangles proportionally to their size.
+    cumulative_area: Vec<f32>,
+}
+
+impl VertexSnapshot {
+    /// Pose `mesh` with `transform`.
+    pub fn new(mesh: &Mesh, transform: &Transform3) -> Self {
+        let matrix = transform.matrix();
+        // Normals need the inverse transpose so non-uniform scale doesn't skew them
+        let normal_matrix = glam::Mat3::from_mat4(matrix).inverse().transpose();
+
+        let positions: Vec<glam::Vec3> = mesh
+            .vertices()
+            .iter()
+            .map(|v| matrix.transform_point3(glam::vec3(v.pos.d0, v.pos.d1, v.pos.d2)))
+            .collect();
+        let normals = mesh
+            .vertices()
+            .iter()
+            .map(|v| (normal_matrix * glam::vec3(v.normal.d0, v.normal.d1, v.normal.d2)).normalize_or_zero())
+            .collect();
+        let indices: Vec<u32> = mesh.indices().iter().collect();
+
+        let mut total = 0.0;
+        let cumulative_area = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let (a, b, c) = (positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]);
+                total += (b - a).cross(c - a).length() * 0.5;
+                total
+            })
+            .collect();
+
+        VertexSnapshot { positions, normals, indices, cumulative_area }
+    }
+
+    /// Snapshot of the mesh `entity` is rendered with, posed by its `Transform3`.
+    /// `None` if it doesn't have both.
+    pub fn of_entity(world: &mut World, extractor: &BatchExtractor, entity: Entity) -> Option<Self> {
+        let mesh = *world.get_component::<MeshHandle>(entity).ok()?;
+        let transform = world.get_component::<Transform3>(entity).ok()?.clone();
+
+        Some(VertexSnapshot::new(extractor.mesh(mesh), &transform))
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Total surface area.
+    pub fn area(&self) -> f32 {
+        self.cumulative_area.last().copied().unwrap_or(0.0)
+    }
+
+    /// Corner positions of triangle `index`.
+    pub fn triangle(&self, index: usize) -> [glam::Vec3; 3] {
+        let t = &self.indices[index * 3..index * 3 + 3];
+        [self.positions[t[0] as usize], self.positions[t[1] as usize], self.positions[t[2] as usize]]
+    }
+
+    /// Point uniformly distributed over the surface, with its interpolated normal, from three random numbers
+    /// in `[0, 1)`. The first picks the triangle, weighted by area, the other two the point on it.
+    pub fn sample_surface(&self, r0: f32, r1: f32, r2: f32) -> (glam::Vec3, glam::Vec3) {
+        if self.triangle_count() == 0 {
+            return (glam::Vec3::ZERO, glam::Vec3::ZERO);
+        }
+
+        let target = r0 * self.area();
+        let index = self
+            .cumulative_area
+            .partition_point(|&area| area <= target)
+            .min(self.triangle_count() - 1);
+
+        // Folding the unit square onto the triangle keeps the distribution uniform
+        let (u, v) = if r1 + r2 > 1.0 { (1.0 - r1, 1.0 - r2) } else { (r1, r2) };
+        let w = 1.0 - u - v;
+
+        let t = &self.indices[index * 3..index * 3 + 3];
+        let position = self.positions[t[0] as usize] * w + self.positions[t[1] as usize] * u + self.positions[t[2] as usize] * v;
+        let normal = (self.normals[t[0] as usize] * w + self.normals[t[1] as usize] * u + self.normals[t[2] as usize] * v)
+            .normalize_or_zero();
+
+        (position, normal)
+    }
+}
@@ -0,0 +1,315 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::caps;
+
+/// Comparison used by the depth and stencil tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    pub fn gl_enum(&self) -> gl::types::GLenum {
+        match self {
+            CompareFunc::Never =>        gl::NEVER,
+            CompareFunc::Less =>         gl::LESS,
+            CompareFunc::Equal =>        gl::EQUAL,
+            CompareFunc::LessEqual =>    gl::LEQUAL,
+            CompareFunc::Greater =>      gl::GREATER,
+            CompareFunc::NotEqual =>     gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always =>       gl::ALWAYS,
+        }
+    }
+
+    /// The same comparison with its operands swapped, e.g. `Less` becomes `Greater`.
+    pub fn reversed(&self) -> Self {
+        match self {
+            CompareFunc::Less =>         CompareFunc::Greater,
+            CompareFunc::LessEqual =>    CompareFunc::GreaterEqual,
+            CompareFunc::Greater =>      CompareFunc::Less,
+            CompareFunc::GreaterEqual => CompareFunc::LessEqual,
+            other => *other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullFace {
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// No blending, the fragment replaces what's in the framebuffer.
+    Opaque,
+    /// Classic `src * a + dst * (1 - a)` transparency.
+    Alpha,
+    /// `src * a + dst`, for glows, fire, and other light-emitting effects.
+    Additive,
+    /// `src + dst * (1 - a)`, for colors that were already multiplied by their alpha.
+    Premultiplied,
+}
+
+impl BlendMode {
+    /// Whether draws using this mode read from the framebuffer, and so depend on draw order.
+    pub fn is_transparent(&self) -> bool {
+        *self != BlendMode::Opaque
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    Decrement,
+    Invert,
+}
+
+impl StencilOp {
+    pub fn gl_enum(&self) -> gl::types::GLenum {
+        match self {
+            StencilOp::Keep =>      gl::KEEP,
+            StencilOp::Zero =>      gl::ZERO,
+            StencilOp::Replace =>   gl::REPLACE,
+            StencilOp::Increment => gl::INCR,
+            StencilOp::Decrement => gl::DECR,
+            StencilOp::Invert =>    gl::INVERT,
+        }
+    }
+}
+
+/// Stencil test and write configuration. A fragment passes if `func(reference & read_mask, stored & read_mask)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stencil {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    /// Applied when the stencil test fails.
+    pub fail: StencilOp,
+    /// Applied when the stencil test passes but the depth test fails.
+    pub depth_fail: StencilOp,
+    /// Applied when both tests pass.
+    pub pass: StencilOp,
+}
+
+impl Stencil {
+    /// Always passes and writes `reference` wherever the fragment is drawn.
+    pub fn write(reference: i32) -> Self {
+        Stencil {
+            func: CompareFunc::Always,
+            reference,
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Replace,
+        }
+    }
+
+    /// Only passes where `func(reference, stored)` holds, leaving the stencil buffer untouched.
+    pub fn test(func: CompareFunc, reference: i32) -> Self {
+        Stencil {
+            func,
+            reference,
+            read_mask: 0xFF,
+            write_mask: 0x00,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// Scissor rectangle in window coordinates, origin at the bottom left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scissor {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The fixed-function OpenGL state a draw depends on.
+///
+/// `apply()` sets every piece of state it covers, rather than only the differences, so whatever the
+/// previous draw left behind can't leak into this one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_func: CompareFunc,
+    pub cull_face: CullFace,
+    pub blend: BlendMode,
+    pub stencil: Option<Stencil>,
+    pub scissor: Option<Scissor>,
+    /// Turn fragment alpha into the fraction of samples covered, for cutouts that stay smooth under MSAA.
+    /// No effect on targets without multisampling.
+    pub alpha_to_coverage: bool,
+}
+
+impl Default for RenderState {
+    /// Depth tested and written opaque geometry. Culling is off since nothing guarantees mesh winding order.
+    fn default() -> Self {
+        RenderState {
+            depth_test: true,
+            depth_write: true,
+            depth_func: CompareFunc::Less,
+            cull_face: CullFace::None,
+            blend: BlendMode::Opaque,
+            stencil: None,
+            scissor: None,
+            alpha_to_coverage: false,
+        }
+    }
+}
+
+impl RenderState {
+    /// State for fullscreen passes, which should never be depth tested, culled, or blended.
+    pub fn fullscreen() -> Self {
+        RenderState {
+            depth_test: false,
+            depth_write: false,
+            ..RenderState::default()
+        }
+    }
+
+    pub fn apply(&self) {
+        unsafe {
+            if self.depth_test {
+                let depth_func = if is_reverse_z() { self.depth_func.reversed() } else { self.depth_func };
+                gl::Enable(gl::DEPTH_TEST);
+                gl::DepthFunc(depth_func.gl_enum());
+            } else {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+            gl::DepthMask(if self.depth_write { gl::TRUE } else { gl::FALSE });
+
+            match self.cull_face {
+                CullFace::None => gl::Disable(gl::CULL_FACE),
+                CullFace::Back => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::BACK);
+                },
+                CullFace::Front => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::FRONT);
+                },
+            }
+
+            match self.blend {
+                BlendMode::Opaque => gl::Disable(gl::BLEND),
+                BlendMode::Alpha => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                },
+                BlendMode::Additive => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE);
+                },
+                BlendMode::Premultiplied => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                },
+            }
+
+            match self.stencil {
+                Some(s) => {
+                    gl::Enable(gl::STENCIL_TEST);
+                    gl::StencilFunc(s.func.gl_enum(), s.reference, s.read_mask);
+                    gl::StencilMask(s.write_mask);
+                    gl::StencilOp(s.fail.gl_enum(), s.depth_fail.gl_enum(), s.pass.gl_enum());
+                },
+                None => gl::Disable(gl::STENCIL_TEST),
+            }
+
+            match self.scissor {
+                Some(s) => {
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(s.x, s.y, s.width, s.height);
+                },
+                None => gl::Disable(gl::SCISSOR_TEST),
+            }
+
+            if self.alpha_to_coverage {
+                gl::Enable(gl::SAMPLE_ALPHA_TO_COVERAGE);
+            } else {
+                gl::Disable(gl::SAMPLE_ALPHA_TO_COVERAGE);
+            }
+        }
+    }
+}
+
+/// Clear buffers of the bound framebuffer, given a mask like `gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT`.
+///
+/// `glClear` respects the depth and stencil write masks and the scissor test, so they're all reset first in case
+/// the last applied `RenderState` disabled writes or set a scissor rectangle.
+pub fn clear(mask: gl::types::GLbitfield) {
+    unsafe {
+        gl::DepthMask(gl::TRUE);
+        gl::StencilMask(0xFF);
+        gl::Disable(gl::SCISSOR_TEST);
+        gl::Clear(mask);
+    }
+}
+
+/// Like `clear()`, but only within `scissor`, e.g. one split-screen region. Leaves the scissor test enabled.
+pub fn clear_scissored(mask: gl::types::GLbitfield, scissor: Scissor) {
+    unsafe {
+        gl::DepthMask(gl::TRUE);
+        gl::StencilMask(0xFF);
+        gl::Enable(gl::SCISSOR_TEST);
+        gl::Scissor(scissor.x, scissor.y, scissor.width, scissor.height);
+        gl::Clear(mask);
+    }
+}
+
+static REVERSE_Z: AtomicBool = AtomicBool::new(false);
+
+/// Switch depth between the standard mapping (`[-1, 1]` clip depth, cleared to 1) and reverse-Z (`[0, 1]` clip
+/// depth, near plane at 1, cleared to 0). Reverse-Z spreads float depth precision evenly over the whole view
+/// distance, so it should be paired with `Camera::perspective_infinite_reverse` and float depth buffers.
+///
+/// `RenderState` depth functions are written for standard depth and flipped automatically while reverse-Z is on.
+/// Call this before creating any render targets so they pick the matching depth format.
+/// Returns whether reverse-Z is on afterwards, which it can't be without `glClipControl` (OpenGL 4.5 or
+/// `ARB_clip_control`).
+pub fn set_reverse_z(enabled: bool) -> bool {
+    if !caps::capabilities().clip_control {
+        return false;
+    }
+
+    REVERSE_Z.store(enabled, Ordering::Relaxed);
+    unsafe {
+        gl::ClipControl(gl::LOWER_LEFT, if enabled { gl::ZERO_TO_ONE } else { gl::NEGATIVE_ONE_TO_ONE });
+        gl::ClearDepth(if enabled { 0.0 } else { 1.0 });
+    }
+
+    enabled
+}
+
+pub fn is_reverse_z() -> bool {
+    REVERSE_Z.load(Ordering::Relaxed)
+}
+
+/// Internal format for depth-only attachments under the current depth mapping.
+pub fn depth_format() -> gl::types::GLenum {
+    if is_reverse_z() { gl::DEPTH_COMPONENT32F } else { gl::DEPTH_COMPONENT24 }
+}
+
+/// Internal format for combined depth/stencil attachments under the current depth mapping.
+pub fn depth_stencil_format() -> gl::types::GLenum {
+    if is_reverse_z() { gl::DEPTH32F_STENCIL8 } else { gl::DEPTH24_STENCIL8 }
+}
@@ -0,0 +1,333 @@
+//! Texture streaming: uploading large texture sets a little every frame, and keeping only what's wanted resident.
+//!
+//! Textures are added to a `TextureStreamer` with their pixels, which it keeps on the CPU, and start out evicted.
+//! Every frame, whatever is about to be drawn asks for its textures with `request()` and a priority, like how close
+//! it is, and `update()` then:
+//! - marks textures whose uploads the GPU has finished with as resident, so `texture()` hands them out,
+//! - evicts textures nobody asked for lately, least recently requested first, while the budget is exceeded, or to
+//!   make room for requested ones,
+//! - copies up to `upload_bytes` of the most wanted textures into a pixel buffer object and starts the transfer
+//!   from it, a band of rows at a time, so large textures spread over several frames instead of stalling one.
+//!
+//! The copy into a PBO is a plain memcpy, and the transfer out of it into the texture runs asynchronously on the
+//! driver's side. A fence after a texture's last band says when it's safe to sample. Until then, draws fall back to
+//! whatever they use for missing textures.
+//! ## Example
+//! ```ignore
+//! let mut streamer = gfx::TextureStreamer::new(256 << 20, 4 << 20);
+//! let rock = streamer.add(2048, 2048, rock_pixels, gfx::ColorSpace::Srgb);
+//!
+//! // Every frame
+//! streamer.request(rock, 1.0 / rock_distance);
+//! streamer.update();
+//! streamer.texture(rock).unwrap_or(&placeholder).bind(0);
+//! ```
+
+use super::color::ColorSpace;
+use super::memory::{Allocation, Category};
+use super::texture::Texture;
+
+/// Pixel buffers used in turns, so a band is never written while the last one out of it may still be in flight.
+const PBO_COUNT: usize = 3;
+/// Frames a texture stays requested after the last `request()`, so ones flickering in and out of view don't thrash.
+const KEEP_FRAMES: u64 = 60;
+
+/// Handle to a texture added to a `TextureStreamer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StreamedTexture(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Residency {
+    /// Only on the CPU.
+    Evicted,
+    /// Allocated on the GPU and partly uploaded.
+    Uploading { rows: i32 },
+    /// Fully uploaded, waiting for the GPU to finish the transfer.
+    Transferring,
+    /// Ready to sample.
+    Resident,
+}
+
+struct Entry {
+    width: i32,
+    height: i32,
+    color_space: ColorSpace,
+    /// Tightly packed RGBA8, the first row being the top of the image.
+    pixels: Vec<u8>,
+    texture: Option<Texture>,
+    residency: Residency,
+    /// Signaled once the GPU is done with the last band, while `Transferring`.
+    fence: Option<gl::types::GLsync>,
+    last_requested: Option<u64>,
+    priority: f32,
+}
+
+impl Entry {
+    fn bytes(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn requested(&self, frame: u64) -> bool {
+        self.last_requested.map_or(false, |last| frame - last <= KEEP_FRAMES)
+    }
+
+    fn evict(&mut self) {
+        self.delete_fence();
+        self.texture = None;
+        self.residency = Residency::Evicted;
+    }
+
+    fn delete_fence(&mut self) {
+        if let Some(fence) = self.fence.take() {
+            unsafe { gl::DeleteSync(fence); }
+        }
+    }
+}
+
+pub struct TextureStreamer {
+    entries: Vec<Entry>,
+    /// Bytes textures may take on the GPU.
+    budget: usize,
+    /// Bytes copied into a pixel buffer per `update()`.
+    upload_bytes: usize,
+    frame: u64,
+    pbos: [gl::types::GLuint; PBO_COUNT],
+    next_pbo: usize,
+    _memory: Allocation,
+}
+
+impl TextureStreamer {
+    /// Keep at most `budget` bytes of textures resident, uploading at most `upload_bytes` a frame. A texture row
+    /// is uploaded whole, so rows wider than `upload_bytes` take a frame each.
+    pub fn new(budget: usize, upload_bytes: usize) -> Self {
+        let mut pbos: [gl::types::GLuint; PBO_COUNT] = [0; PBO_COUNT];
+        unsafe { gl::GenBuffers(PBO_COUNT as gl::types::GLsizei, pbos.as_mut_ptr()); }
+
+        TextureStreamer {
+            entries: Vec::new(),
+            budget,
+            upload_bytes: upload_bytes.max(1),
+            frame: 0,
+            pbos,
+            next_pbo: 0,
+            _memory: Allocation::new(Category::Streaming, upload_bytes * PBO_COUNT),
+        }
+    }
+
+    /// Add an RGBA8 texture from tightly packed `pixels`, the first row being the top of the image. Nothing is
+    /// uploaded until it's requested.
+    pub fn add(&mut self, width: i32, height: i32, pixels: Vec<u8>, color_space: ColorSpace) -> StreamedTexture {
+        assert_eq!(pixels.len(), (width * height * 4) as usize, "pixel data doesn't match texture size");
+
+        self.entries.push(Entry {
+            width,
+            height,
+            color_space,
+            pixels,
+            texture: None,
+            residency: Residency::Evicted,
+            fence: None,
+            last_requested: None,
+            priority: 0.0,
+        });
+
+        StreamedTexture(self.entries.len() - 1)
+    }
+
+    /// Ask for `id` to be made resident, or kept so. Higher `priority` textures are uploaded first and evicted last.
+    pub fn request(&mut self, id: StreamedTexture, priority: f32) {
+        let entry = &mut self.entries[id.0];
+        // Several requests in a frame keep the most urgent one
+        if entry.last_requested == Some(self.frame) {
+            entry.priority = entry.priority.max(priority);
+        } else {
+            entry.priority = priority;
+        }
+        entry.last_requested = Some(self.frame);
+    }
+
+    /// `id`'s texture, once it's resident.
+    pub fn texture(&self, id: StreamedTexture) -> Option<&Texture> {
+        let entry = &self.entries[id.0];
+        match entry.residency {
+            Residency::Resident => entry.texture.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn residency(&self, id: StreamedTexture) -> Residency {
+        self.entries[id.0].residency
+    }
+
+    /// Bytes taken on the GPU by textures resident or on their way.
+    pub fn resident_bytes(&self) -> usize {
+        self.entries.iter().filter(|e| e.texture.is_some()).map(Entry::bytes).sum()
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Takes effect on the next `update()`, which evicts textures until they fit.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+    }
+
+    /// Finish, evict and upload textures, see the module documentation. Call once a frame, after this frame's
+    /// `request()`s.
+    pub fn update(&mut self) {
+        self.finish_transfers();
+
+        // Over budget, e.g. after it was lowered: drop what nobody wants first
+        let mut resident = self.resident_bytes();
+        while resident > self.budget {
+            match self.eviction_candidate(f32::INFINITY, true) {
+                Some(index) => {
+                    resident -= self.entries[index].bytes();
+                    self.entries[index].evict();
+                },
+                None => break,
+            }
+        }
+
+        self.upload();
+        self.frame += 1;
+    }
+
+    fn finish_transfers(&mut self) {
+        for entry in self.entries.iter_mut().filter(|e| e.residency == Residency::Transferring) {
+            let status = entry.fence.map_or(gl::ALREADY_SIGNALED, |fence| unsafe { gl::ClientWaitSync(fence, 0, 0) });
+            if status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED {
+                entry.delete_fence();
+                entry.residency = Residency::Resident;
+            }
+        }
+    }
+
+    /// The texture to evict to make room for one with `priority`, if any: least recently requested first, then
+    /// least urgent. Textures still requested only go to make room for more urgent ones, unless `over_budget`.
+    fn eviction_candidate(&self, priority: f32, over_budget: bool) -> Option<usize> {
+        self.entries.iter().enumerate()
+            .filter(|(_, e)| e.texture.is_some())
+            .filter(|(_, e)| !e.requested(self.frame) || e.priority < priority || over_budget)
+            .min_by(|(_, a), (_, b)| {
+                let (a_requested, b_requested) = (a.requested(self.frame), b.requested(self.frame));
+                a_requested.cmp(&b_requested)
+                    .then(a.last_requested.cmp(&b.last_requested))
+                    .then(a.priority.total_cmp(&b.priority))
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// Copy the next band of the most urgent requested texture into a pixel buffer and start its transfer.
+    fn upload(&mut self) {
+        // Finish what's started before starting anything else, so a partial texture isn't evicted for another
+        let uploading = self.entries.iter().position(|e| matches!(e.residency, Residency::Uploading { .. }));
+        let index = match uploading {
+            Some(index) => index,
+            None => {
+                let wanted = self.entries.iter().enumerate()
+                    .filter(|(_, e)| e.residency == Residency::Evicted && e.requested(self.frame))
+                    .max_by(|(_, a), (_, b)| {
+                        a.last_requested.cmp(&b.last_requested).then(a.priority.total_cmp(&b.priority))
+                    })
+                    .map(|(i, _)| i);
+                match wanted {
+                    Some(index) if self.make_room(index) => index,
+                    _ => return,
+                }
+            },
+        };
+
+        let pbo = self.pbos[self.next_pbo];
+        self.next_pbo = (self.next_pbo + 1) % PBO_COUNT;
+
+        let entry = &mut self.entries[index];
+        let (width, height, color_space) = (entry.width, entry.height, entry.color_space);
+        let texture = entry.texture.get_or_insert_with(|| Texture::new_color(width, height, color_space));
+        let first_row = match entry.residency {
+            Residency::Uploading { rows } => rows,
+            _ => 0,
+        };
+        let row_bytes = entry.width as usize * 4;
+        let rows = ((self.upload_bytes / row_bytes).max(1) as i32).min(entry.height - first_row);
+        let band = &entry.pixels[first_row as usize * row_bytes..(first_row + rows) as usize * row_bytes];
+
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, pbo);
+            // Orphaned every time, so the driver never waits for the transfer out of the previous contents
+            gl::BufferData(
+                gl::PIXEL_UNPACK_BUFFER,
+                band.len() as gl::types::GLsizeiptr,
+                std::ptr::null(),
+                gl::STREAM_DRAW,
+            );
+            let mapped = gl::MapBufferRange(
+                gl::PIXEL_UNPACK_BUFFER,
+                0,
+                band.len() as gl::types::GLsizeiptr,
+                gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_BUFFER_BIT,
+            ) as *mut u8;
+            if mapped.is_null() {
+                gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+                return;
+            }
+            std::ptr::copy_nonoverlapping(band.as_ptr(), mapped, band.len());
+            gl::UnmapBuffer(gl::PIXEL_UNPACK_BUFFER);
+
+            // Offset 0 into the bound pixel buffer, not a client pointer
+            gl::BindTexture(gl::TEXTURE_2D, texture.id());
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                first_row,
+                entry.width,
+                rows,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        }
+
+        if first_row + rows < entry.height {
+            entry.residency = Residency::Uploading { rows: first_row + rows };
+        } else {
+            entry.fence = Some(unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) });
+            entry.residency = Residency::Transferring;
+        }
+    }
+
+    /// Evict textures until entry `index` fits in the budget. False if it can't without evicting more urgent ones.
+    fn make_room(&mut self, index: usize) -> bool {
+        let needed = self.entries[index].bytes();
+        let priority = self.entries[index].priority;
+        if needed > self.budget {
+            return false;
+        }
+
+        let mut resident = self.resident_bytes();
+        while resident + needed > self.budget {
+            match self.eviction_candidate(priority, false) {
+                Some(victim) => {
+                    resident -= self.entries[victim].bytes();
+                    self.entries[victim].evict();
+                },
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl Drop for TextureStreamer {
+    fn drop(&mut self) {
+        for entry in &mut self.entries {
+            entry.delete_fence();
+        }
+        unsafe { gl::DeleteBuffers(PBO_COUNT as gl::types::GLsizei, self.pbos.as_ptr()); }
+    }
+}
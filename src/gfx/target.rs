@@ -0,0 +1,297 @@
+use super::color::ColorSpace;
+use super::memory::{self, Allocation, Category};
+use super::state;
+use super::texture::Texture;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("framebuffer is incomplete, status: {}", status)]
+    IncompleteFramebuffer {
+        status: u32
+    },
+}
+
+/// An offscreen framebuffer with a color texture attachment that can be sampled once rendered to,
+/// and a combined depth/stencil renderbuffer in the format `state::depth_stencil_format()` picks.
+pub struct RenderTarget {
+    fbo: gl::types::GLuint,
+    color: Texture,
+    depth_stencil_rbo: gl::types::GLuint,
+    width: i32,
+    height: i32,
+    _memory: Allocation,
+}
+
+impl RenderTarget {
+    pub fn new(width: i32, height: i32, color_space: ColorSpace) -> Result<Self, Error> {
+        let mut fbo: gl::types::GLuint = 0;
+        let mut depth_stencil_rbo: gl::types::GLuint = 0;
+        let color = Texture::new_color(width, height, color_space);
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, color.id(), 0);
+
+            gl::GenRenderbuffers(1, &mut depth_stencil_rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_stencil_rbo);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, state::depth_stencil_format(), width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, depth_stencil_rbo);
+        }
+
+        let bytes = width as usize * height as usize * memory::bytes_per_texel(state::depth_stencil_format());
+        let _memory = Allocation::new(Category::Renderbuffers, bytes);
+        let target = RenderTarget { fbo, color, depth_stencil_rbo, width, height, _memory };
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0); }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(Error::IncompleteFramebuffer { status });
+        }
+
+        Ok(target)
+    }
+
+    /// Reallocate all attachments with a new size. Previous contents are lost.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        *self = RenderTarget::new(width, height, self.color.color_space())?;
+
+        Ok(())
+    }
+
+    /// Redirect subsequent draw calls into this target.
+    pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo); }
+    }
+
+    /// Restore the window's default framebuffer.
+    pub fn bind_default() {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0); }
+    }
+
+    pub fn color(&self) -> &Texture {
+        &self.color
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Read back the color attachment as RGBA8, top row first. Stalls until rendering to it is done.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let stride = self.width as usize * 4;
+        let pixels = read_framebuffer(self.fbo, 0, 0, self.width, self.height);
+
+        pixels.chunks_exact(stride).rev().flatten().copied().collect()
+    }
+
+    pub(crate) fn fbo(&self) -> gl::types::GLuint {
+        self.fbo
+    }
+}
+
+/// Read a rectangle of `fbo`'s color as RGBA8, bottom row first like OpenGL stores it.
+pub(crate) fn read_framebuffer(fbo: gl::types::GLuint, x: i32, y: i32, width: i32, height: i32) -> Vec<u8> {
+    let mut pixels = vec![0u8; width.max(0) as usize * height.max(0) as usize * 4];
+    unsafe {
+        let mut previous: gl::types::GLint = 0;
+        gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(x, y, width, height, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut gl::types::GLvoid);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as gl::types::GLuint);
+    }
+
+    pixels
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &mut self.fbo);
+            gl::DeleteRenderbuffers(1, &mut self.depth_stencil_rbo);
+        }
+    }
+}
+
+/// An offscreen framebuffer with multisampled color and depth/stencil renderbuffers. It can't be sampled or read
+/// back directly, `resolve()` averages its samples into a `RenderTarget` first.
+pub struct MultisampleTarget {
+    fbo: gl::types::GLuint,
+    color_rbo: gl::types::GLuint,
+    depth_stencil_rbo: gl::types::GLuint,
+    width: i32,
+    height: i32,
+    samples: i32,
+    color_space: ColorSpace,
+    _memory: Allocation,
+}
+
+impl MultisampleTarget {
+    /// `samples` is clamped to what the driver supports.
+    pub fn new(width: i32, height: i32, samples: i32, color_space: ColorSpace) -> Result<Self, Error> {
+        let mut fbo: gl::types::GLuint = 0;
+        let mut color_rbo: gl::types::GLuint = 0;
+        let mut depth_stencil_rbo: gl::types::GLuint = 0;
+        let mut max_samples: gl::types::GLint = 1;
+        unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples); }
+        let samples = samples.clamp(1, max_samples.max(1));
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+
+            gl::GenRenderbuffers(1, &mut color_rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, color_rbo);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, color_space.rgba8_format(), width, height);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, color_rbo);
+
+            gl::GenRenderbuffers(1, &mut depth_stencil_rbo);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_stencil_rbo);
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, state::depth_stencil_format(), width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, depth_stencil_rbo);
+        }
+
+        let texel = memory::bytes_per_texel(color_space.rgba8_format())
+            + memory::bytes_per_texel(state::depth_stencil_format());
+        let bytes = width as usize * height as usize * samples as usize * texel;
+        let _memory = Allocation::new(Category::Renderbuffers, bytes);
+        let target = MultisampleTarget {
+            fbo,
+            color_rbo,
+            depth_stencil_rbo,
+            width,
+            height,
+            samples,
+            color_space,
+            _memory,
+        };
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0); }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(Error::IncompleteFramebuffer { status });
+        }
+
+        Ok(target)
+    }
+
+    /// Reallocate all attachments with a new size. Previous contents are lost.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        *self = MultisampleTarget::new(width, height, self.samples, self.color_space)?;
+
+        Ok(())
+    }
+
+    /// Redirect subsequent draw calls into this target.
+    pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo); }
+    }
+
+    /// Average the color samples into `target`, which has to be the same size. Leaves `target` bound.
+    pub fn resolve(&self, target: &RenderTarget) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.fbo());
+            gl::BlitFramebuffer(
+                0, 0, self.width, self.height,
+                0, 0, target.width(), target.height(),
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo());
+        }
+    }
+
+    /// Samples per pixel, after clamping to what the driver supports.
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+}
+
+impl Drop for MultisampleTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &mut self.fbo);
+            gl::DeleteRenderbuffers(1, &mut self.color_rbo);
+            gl::DeleteRenderbuffers(1, &mut self.depth_stencil_rbo);
+        }
+    }
+}
+
+/// An offscreen framebuffer with only a depth texture attachment, e.g. for shadow maps.
+pub struct DepthTarget {
+    fbo: gl::types::GLuint,
+    depth: Texture,
+}
+
+impl DepthTarget {
+    pub fn new(width: i32, height: i32) -> Result<Self, Error> {
+        let mut fbo: gl::types::GLuint = 0;
+        let depth = Texture::new_depth(width, height);
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, depth.id(), 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+        }
+
+        let target = DepthTarget { fbo, depth };
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0); }
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(Error::IncompleteFramebuffer { status });
+        }
+
+        Ok(target)
+    }
+
+    /// Redirect subsequent draw calls into this target.
+    pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo); }
+    }
+
+    pub fn depth(&self) -> &Texture {
+        &self.depth
+    }
+
+    pub fn width(&self) -> i32 {
+        self.depth.width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.depth.height()
+    }
+}
+
+impl Drop for DepthTarget {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &mut self.fbo); }
+    }
+}
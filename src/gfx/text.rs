@@ -0,0 +1,347 @@
+//! Text drawn from a font atlas, on screen for FPS counters and debug overlays, or in the world for nameplates.
+//!
+//! The atlas is a BMP holding a 16x16 grid of equally sized cells, one per character code 0-255 in row-major
+//! order. Only fixed-width fonts are supported, every glyph advances by one cell. Atlases come in two kinds:
+//! * Bitmap atlases have white glyphs on black, and look best drawn at their native size.
+//! * Signed distance field atlases store the distance to the glyph edge instead, 0.5 being the edge and larger
+//!   values inside, so glyphs stay crisp at any scale and angle. `fonts/mono_sdf.bmp` is one.
+//!
+//! Like `DebugDraw`, strings are accumulated over the frame as quads and drawn in one call by `flush()`
+//! or `flush_world()`.
+//! ## Example
+//! ```ignore
+//! let mut text = gfx::TextRenderer::new_sdf(&res, "fonts/mono_sdf.bmp").unwrap();
+//!
+//! // With the scene still bound, so nameplates are hidden behind geometry
+//! text.draw_world("Matsumoto", head_position, 0.2, glam::Vec4::ONE, &camera);
+//! text.flush_world(&camera);
+//!
+//! // After post-processing, straight onto the window
+//! text.draw("Hello", glam::vec2(8.0, 8.0), 1.0, glam::Vec4::ONE);
+//! text.flush(&viewport);
+//! ```
+
+use crate::resource::{self, Resource};
+
+use super::camera::Camera;
+use super::color::ColorSpace;
+use super::memory::{Allocation, Category};
+use super::shader::{self, Program, Shader};
+use super::state::{BlendMode, RenderState};
+use super::texture::Texture;
+use super::viewport::Viewport;
+
+/// Cells per row and column of the atlas.
+const ATLAS_GRID: i32 = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load font atlas: {0}")]
+    Load(#[from] resource::Error),
+    #[error("failed to decode font atlas: {0}")]
+    Image(String),
+    #[error("failed to load text program: {0}")]
+    Program(#[from] shader::Error),
+}
+
+/// What a font atlas stores, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontKind {
+    Bitmap,
+    Sdf,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct TextVertex {
+    pos: [f32; 3],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+pub struct TextRenderer {
+    program: Program,
+    atlas: Texture,
+    kind: FontKind,
+    /// Size of one glyph cell in atlas pixels.
+    glyph_size: glam::Vec2,
+    /// Screen-space quads, in pixels from the top left corner.
+    vertices: Vec<TextVertex>,
+    world_vertices: Vec<TextVertex>,
+
+    vao: gl::types::GLuint,
+    vbo: gl::types::GLuint,
+    /// Size in vertices the vertex buffer was last allocated with.
+    capacity: usize,
+    memory: Allocation,
+}
+
+impl TextRenderer {
+    /// Load the bitmap font atlas `name`, see the module documentation for its layout.
+    pub fn new(res: &Resource, name: &str) -> Result<Self, Error> {
+        TextRenderer::with_kind(res, name, FontKind::Bitmap)
+    }
+
+    /// Load the signed distance field font atlas `name`.
+    pub fn new_sdf(res: &Resource, name: &str) -> Result<Self, Error> {
+        TextRenderer::with_kind(res, name, FontKind::Sdf)
+    }
+
+    fn with_kind(res: &Resource, name: &str, kind: FontKind) -> Result<Self, Error> {
+        let program = text_program(res, kind)?;
+        let atlas = load_atlas(res, name)?;
+        let glyph_size = glam::vec2(
+            (atlas.width() / ATLAS_GRID) as f32,
+            (atlas.height() / ATLAS_GRID) as f32,
+        );
+
+        let mut vao: gl::types::GLuint = 0;
+        let mut vbo: gl::types::GLuint = 0;
+        let stride = std::mem::size_of::<TextVertex>() as gl::types::GLsizei;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+            );
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribPointer(
+                2,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (5 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
+            );
+
+            gl::BindVertexArray(0);
+        }
+
+        Ok(TextRenderer {
+            program,
+            atlas,
+            kind,
+            glyph_size,
+            vertices: Vec::new(),
+            world_vertices: Vec::new(),
+            vao,
+            vbo,
+            capacity: 0,
+            memory: Allocation::new(Category::Streaming, 0),
+        })
+    }
+
+    pub fn kind(&self) -> FontKind {
+        self.kind
+    }
+
+    /// Size in pixels of one glyph at scale 1.
+    pub fn glyph_size(&self) -> glam::Vec2 {
+        self.glyph_size
+    }
+
+    /// Size in pixels `text` would take up at `scale`.
+    pub fn measure(&self, text: &str, scale: f32) -> glam::Vec2 {
+        let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        let rows = text.lines().count();
+        glam::vec2(columns as f32, rows as f32) * self.glyph_size * scale
+    }
+
+    /// Queue `text` with its top left corner at `position`, in pixels from the top left of the screen.
+    /// `\n` starts a new line, and characters missing from the atlas are drawn as `?`.
+    pub fn draw(&mut self, text: &str, position: glam::Vec2, scale: f32, color: glam::Vec4) {
+        let size = self.glyph_size * scale;
+        // Bitmap glyphs line up with the pixel grid only from whole pixel positions
+        let position = if self.kind == FontKind::Bitmap { position.round() } else { position };
+
+        push_glyphs(&mut self.vertices, text, position.extend(0.0), glam::Vec3::X * size.x, glam::Vec3::Y * size.y, color);
+    }
+
+    /// Queue `text` in world space facing `camera`, horizontally centered on `anchor` with its last line
+    /// resting on it. `height` is the height of one line in world units.
+    pub fn draw_world(&mut self, text: &str, anchor: glam::Vec3, height: f32, color: glam::Vec4, camera: &Camera) {
+        // The view matrix rows are the camera axes in world space
+        let right = camera.view.row(0).truncate().normalize() * height * self.glyph_size.x / self.glyph_size.y;
+        let down = -camera.view.row(1).truncate().normalize() * height;
+
+        let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        let rows = text.lines().count();
+        let origin = anchor - right * columns as f32 * 0.5 - down * rows as f32;
+
+        push_glyphs(&mut self.world_vertices, text, origin, right, down, color);
+    }
+
+    /// Draw the screen-space text queued since the last flush over the currently bound framebuffer, then forget it.
+    pub fn flush(&mut self, viewport: &Viewport) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let transform = glam::Mat4::orthographic_rh_gl(0.0, viewport.width as f32, viewport.height as f32, 0.0, -1.0, 1.0);
+        let state = RenderState {
+            blend: BlendMode::Alpha,
+            ..RenderState::fullscreen()
+        };
+
+        // Taken out only for the draw, the allocation is reused next frame
+        let mut vertices = std::mem::take(&mut self.vertices);
+        self.draw_vertices(&vertices, transform, state);
+        vertices.clear();
+        self.vertices = vertices;
+    }
+
+    /// Draw the world-space text queued since the last flush as seen from `camera`, depth tested against the scene,
+    /// then forget it.
+    pub fn flush_world(&mut self, camera: &Camera) {
+        if self.world_vertices.is_empty() {
+            return;
+        }
+
+        let state = RenderState {
+            depth_write: false,
+            blend: BlendMode::Alpha,
+            ..RenderState::default()
+        };
+
+        // Taken out only for the draw, the allocation is reused next frame
+        let mut vertices = std::mem::take(&mut self.world_vertices);
+        self.draw_vertices(&vertices, camera.projection * camera.view, state);
+        vertices.clear();
+        self.world_vertices = vertices;
+    }
+
+    fn draw_vertices(&mut self, vertices: &[TextVertex], transform: glam::Mat4, state: RenderState) {
+        let size = vertices.len() * std::mem::size_of::<TextVertex>();
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+            // Grow (and orphan) the buffer only when the vertices no longer fit
+            if vertices.len() > self.capacity {
+                self.capacity = vertices.len().next_power_of_two();
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (self.capacity * std::mem::size_of::<TextVertex>()) as gl::types::GLsizeiptr,
+                    std::ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+                self.memory.resize(self.capacity * std::mem::size_of::<TextVertex>());
+            }
+
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                size as gl::types::GLsizeiptr,
+                vertices.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+
+        state.apply();
+
+        self.program.set_mat4fv("Transform", transform, 0);
+        self.program.set_i32("Atlas", 0);
+        self.atlas.bind(0);
+
+        unsafe {
+            gl::UseProgram(self.program.id());
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, vertices.len() as gl::types::GLsizei);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &mut self.vbo);
+            gl::DeleteVertexArrays(1, &mut self.vao);
+        }
+    }
+}
+
+/// Append a quad per glyph of `text`, starting at the top left corner `origin` and stepping
+/// one `right` per glyph and one `down` per line.
+fn push_glyphs(
+    vertices: &mut Vec<TextVertex>,
+    text: &str,
+    origin: glam::Vec3,
+    right: glam::Vec3,
+    down: glam::Vec3,
+    color: glam::Vec4,
+) {
+    let color = color.to_array();
+    let mut line_start = origin;
+    let mut pen = origin;
+
+    for c in text.chars() {
+        if c == '\n' {
+            line_start += down;
+            pen = line_start;
+            continue;
+        }
+
+        let code = if (c as u32) < (ATLAS_GRID * ATLAS_GRID) as u32 { c as u32 as i32 } else { '?' as i32 };
+        let uv_min = glam::vec2((code % ATLAS_GRID) as f32, (code / ATLAS_GRID) as f32) / ATLAS_GRID as f32;
+        let uv_max = uv_min + glam::Vec2::splat(1.0 / ATLAS_GRID as f32);
+
+        let corner = |x: bool, y: bool| TextVertex {
+            pos: (pen + if x { right } else { glam::Vec3::ZERO } + if y { down } else { glam::Vec3::ZERO }).to_array(),
+            uv: [if x { uv_max.x } else { uv_min.x }, if y { uv_max.y } else { uv_min.y }],
+            color,
+        };
+        vertices.extend_from_slice(&[
+            corner(false, false), corner(false, true), corner(true, true),
+            corner(false, false), corner(true, true), corner(true, false),
+        ]);
+
+        pen += right;
+    }
+}
+
+/// Link the shared text vertex shader with the fragment shader decoding atlases of `kind`.
+fn text_program(res: &Resource, kind: FontKind) -> Result<Program, Error> {
+    let name = match kind {
+        FontKind::Bitmap => "shaders/text/bitmap",
+        FontKind::Sdf => "shaders/text/sdf",
+    };
+    let shaders = [
+        Shader::from_res(res, "shaders/text/text.vert")?,
+        Shader::from_res(res, &format!("{}.frag", name))?,
+    ];
+
+    Program::from_shaders(&shaders).map_err(|message| Error::Program(shader::Error::LinkError {
+        name: name.into(),
+        message,
+    }))
+}
+
+/// Decode the BMP resource `name` into a texture. Coverage isn't a color, so it's kept linear.
+fn load_atlas(res: &Resource, name: &str) -> Result<Texture, Error> {
+    let bytes = res.load_bytes(name)?;
+    let mut rwops = sdl2::rwops::RWops::from_bytes(&bytes).map_err(Error::Image)?;
+    let surface = sdl2::surface::Surface::load_bmp_rw(&mut rwops)
+        .and_then(|s| s.convert_format(sdl2::pixels::PixelFormatEnum::RGBA32))
+        .map_err(Error::Image)?;
+
+    let width = surface.width() as usize;
+    let pitch = surface.pitch() as usize;
+    let pixels: Vec<u8> = surface.with_lock(|data| {
+        data.chunks(pitch).flat_map(|row| &row[..width * 4]).copied().collect()
+    });
+
+    Ok(Texture::from_rgba8(surface.width() as i32, surface.height() as i32, &pixels, ColorSpace::Linear))
+}
@@ -0,0 +1,125 @@
+use super::color::ColorSpace;
+use super::memory::{self, Allocation, Category};
+use super::state::{self, CompareFunc};
+
+/// Owned handle to an immutable-storage OpenGL 2D texture.
+pub struct Texture {
+    id: gl::types::GLuint,
+    width: i32,
+    height: i32,
+    color_space: ColorSpace,
+    _memory: Allocation,
+}
+
+impl Texture {
+    /// Allocate storage for a texture without uploading any data, e.g. for use as a framebuffer attachment.
+    /// Sampling is linear and clamped to edge.
+    pub fn new_empty(width: i32, height: i32, internal_format: gl::types::GLenum) -> Self {
+        let mut id: gl::types::GLuint = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexStorage2D(gl::TEXTURE_2D, 1, internal_format, width, height);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as gl::types::GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        let bytes = width as usize * height as usize * memory::bytes_per_texel(internal_format);
+        Texture {
+            id,
+            width,
+            height,
+            color_space: ColorSpace::of_format(internal_format),
+            _memory: Allocation::new(Category::Textures, bytes),
+        }
+    }
+
+    /// Allocate an empty depth texture set up for shadow lookups with a `sampler2DShadow`, in the format
+    /// `state::depth_format()` picks. Linear filtering makes each lookup a hardware 2x2 comparison, and anything
+    /// outside the texture reads as lit. The comparison follows the depth mapping active when it's created.
+    pub fn new_depth(width: i32, height: i32) -> Self {
+        let texture = Texture::new_empty(width, height, state::depth_format());
+
+        // Border is the far plane, which never occludes anything
+        let far = if state::is_reverse_z() { 0.0 } else { 1.0 };
+        let border: [f32; 4] = [far, far, far, far];
+        let compare = CompareFunc::LessEqual;
+        let compare = if state::is_reverse_z() { compare.reversed() } else { compare };
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, compare.gl_enum() as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as gl::types::GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as gl::types::GLint);
+            gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        texture
+    }
+
+    /// Allocate an empty 8-bit RGBA texture stored in `color_space`.
+    pub fn new_color(width: i32, height: i32, color_space: ColorSpace) -> Self {
+        Texture::new_empty(width, height, color_space.rgba8_format())
+    }
+
+    /// Create an 8-bit RGBA texture from tightly packed `pixels`, the first row being the top of the image.
+    pub fn from_rgba8(width: i32, height: i32, pixels: &[u8], color_space: ColorSpace) -> Self {
+        assert_eq!(pixels.len(), (width * height * 4) as usize, "pixel data doesn't match texture size");
+
+        let texture = Texture::new_color(width, height, color_space);
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        texture
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Whether the texture holds sRGB encoded data, which is decoded to linear when sampled.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &mut self.id); }
+    }
+}
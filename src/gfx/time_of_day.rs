@@ -0,0 +1,166 @@
+//! Day/night cycle driving the sun, ambient light and fog.
+//!
+//! `TimeOfDay` keeps the in-game hour and moves it along by `day_length` of real time per 24 hours. The sun
+//! rises in the east (+X) at 6:00, passes `tilt` away from the zenith at noon and sets in the west at 18:00, its
+//! color, intensity, the ambient term and the fog follow curves over the hour, which can all be replaced.
+//! As a world resource, the `advance_time_of_day` system moves it along and writes the sun into every directional
+//! `Light` in the world. `sample()` returns the rest, for the ambient of `Lights`, the shadow pass to follow the sun
+//! and `FrameUniforms` the fog through `Sky::fog()`.
+//! ## Example
+//! ```ignore
+//! let mut time_of_day = TimeOfDay::new(9.0, Duration::from_secs(600));
+//! time_of_day.set_hours(18.5); // Skip to dusk
+//! world.insert_resource(time_of_day);
+//! schedule.add_system(Stage::Update, "time of day", advance_time_of_day);
+//!
+//! // Every frame, after the schedule
+//! let sky = world.resource::<TimeOfDay>().unwrap().sample();
+//! lights.set_ambient(sky.ambient);
+//! shadow.begin(sky.sun_direction, glam::Vec3::ZERO, 10.0);
+//! ```
+
+use std::time::Duration;
+
+use glam::{vec3, Vec3};
+
+use crate::logic::system::FrameTime;
+use crate::logic::{Query, QueryIter, Res, ResMut};
+use crate::math::curve::Curve;
+use crate::math::units::{Degrees, Radians};
+
+use super::fog::Fog;
+use super::light::Light;
+
+pub const HOURS_PER_DAY: f32 = 24.0;
+
+/// Everything the time of day controls, sampled at one hour.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sky {
+    /// Direction sunlight travels in, pointing up while the sun is below the horizon.
+    pub sun_direction: Vec3,
+    /// Linear RGB.
+    pub sun_color: Vec3,
+    pub sun_intensity: f32,
+    pub ambient: Vec3,
+    pub fog_color: Vec3,
+    pub fog_density: f32,
+}
+
+impl Sky {
+    /// The sky's fog, thickening with the square of distance.
+    pub fn fog(&self) -> Fog {
+        Fog::exponential_squared(self.fog_color, self.fog_density)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeOfDay {
+    hours: f32,
+    /// Real time one in-game day takes.
+    pub day_length: Duration,
+    /// Multiplier on how fast time passes, e.g. to fast-forward through the night.
+    pub speed: f32,
+    pub paused: bool,
+    /// How far from the zenith towards the south (-Z) the sun passes at noon.
+    pub tilt: Radians,
+    /// Curves over the hour, looping every 24 hours.
+    pub sun_color: Curve<Vec3>,
+    pub sun_intensity: Curve<f32>,
+    pub ambient: Curve<Vec3>,
+    pub fog_color: Curve<Vec3>,
+    pub fog_density: Curve<f32>,
+}
+
+impl TimeOfDay {
+    /// Start at `hours` with the default curves, a clear day with orange sunrise and sunset and a dark blue night.
+    pub fn new(hours: f32, day_length: Duration) -> Self {
+        let day = |keys| Curve::looping(HOURS_PER_DAY, keys);
+        let night = vec3(0.02, 0.03, 0.06);
+
+        TimeOfDay {
+            hours: hours.rem_euclid(HOURS_PER_DAY),
+            day_length,
+            speed: 1.0,
+            paused: false,
+            tilt: Degrees(30.0).into(),
+            sun_color: day(vec![
+                (6.0, vec3(1.0, 0.45, 0.25)),
+                (8.0, vec3(1.0, 0.9, 0.8)),
+                (12.0, vec3(1.0, 1.0, 1.0)),
+                (16.0, vec3(1.0, 0.9, 0.8)),
+                (18.0, vec3(1.0, 0.45, 0.25)),
+            ]),
+            sun_intensity: day(vec![(5.5, 0.0), (7.0, 0.6), (12.0, 1.0), (17.0, 0.6), (18.5, 0.0)]),
+            ambient: day(vec![
+                (0.0, night),
+                (6.0, vec3(0.1, 0.08, 0.08)),
+                (12.0, vec3(0.15, 0.15, 0.15)),
+                (18.0, vec3(0.1, 0.08, 0.08)),
+            ]),
+            fog_color: day(vec![
+                (0.0, night),
+                (6.0, vec3(0.8, 0.6, 0.5)),
+                (12.0, vec3(0.6, 0.7, 0.8)),
+                (18.0, vec3(0.8, 0.5, 0.4)),
+            ]),
+            fog_density: day(vec![(0.0, 0.02), (6.0, 0.04), (9.0, 0.01), (18.0, 0.01)]),
+        }
+    }
+
+    /// In-game hour, in [0, 24).
+    pub fn hours(&self) -> f32 {
+        self.hours
+    }
+
+    /// Jump to `hours`, wrapped into [0, 24).
+    pub fn set_hours(&mut self, hours: f32) {
+        self.hours = hours.rem_euclid(HOURS_PER_DAY);
+    }
+
+    /// Move time along by `dt` of real time, unless paused.
+    pub fn advance(&mut self, dt: Duration) {
+        if self.paused || self.day_length.is_zero() {
+            return;
+        }
+
+        let days = dt.as_secs_f32() / self.day_length.as_secs_f32() * self.speed;
+        self.set_hours(self.hours + days * HOURS_PER_DAY);
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        // Angle above the eastern horizon, a full turn every day
+        let angle = (self.hours - 6.0) / HOURS_PER_DAY * std::f32::consts::TAU;
+        let to_sun = vec3(angle.cos(), angle.sin() * self.tilt.0.cos(), -angle.sin() * self.tilt.0.sin());
+        -to_sun
+    }
+
+    pub fn sample(&self) -> Sky {
+        Sky {
+            sun_direction: self.sun_direction(),
+            sun_color: self.sun_color.sample(self.hours),
+            sun_intensity: self.sun_intensity.sample(self.hours).max(0.0),
+            ambient: self.ambient.sample(self.hours),
+            fog_color: self.fog_color.sample(self.hours),
+            fog_density: self.fog_density.sample(self.hours).max(0.0),
+        }
+    }
+}
+
+/// System advancing the `TimeOfDay` resource by the frame's length, then pointing every directional light along the
+/// sun. Run before `Lights::collect()`.
+pub fn advance_time_of_day(
+    frame: Res<FrameTime>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut lights: Query<(&mut Light,)>,
+) {
+    time_of_day.advance(frame.0);
+    let sky = time_of_day.sample();
+
+    for mut light in lights.iter() {
+        if let Light::Directional { direction, color, intensity } = &mut *light {
+            *direction = sky.sun_direction;
+            *color = sky.sun_color;
+            *intensity = sky.sun_intensity;
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Debug-build checks that render passes leave OpenGL the way they declared they would.
+//!
+//! State leaking out of a pass, like a blend mode left enabled or another framebuffer left bound, otherwise only
+//! shows up as glitches in whatever happens to draw next. `RenderGraph` snapshots the state it sets up right before
+//! each pass, and compares it against what's bound once the pass returns: the framebuffer and viewport it was given,
+//! and the depth, blend and cull state of the `RenderState` the pass declared, if it declared one. Any difference
+//! panics, naming the pass and every mismatch.
+//!
+//! Checks are on by default in debug builds and compiled out of release builds.
+//! ## Example
+//! ```ignore
+//! graph.add_pass_with_state("hud", &[], &[backbuffer], RenderState::fullscreen(), |frame, _| {
+//!     // Panics after the pass if this leaves blending on or binds another framebuffer
+//!     frame.hud.draw();
+//! });
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::state::{self, BlendMode, CullFace, RenderState};
+use super::viewport::Viewport;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turn pass validation on or off. Has no effect in release builds, where it's always off.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    cfg!(debug_assertions) && ENABLED.load(Ordering::Relaxed)
+}
+
+/// The critical parts of the current OpenGL state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    pub framebuffer: gl::types::GLuint,
+    pub viewport: Viewport,
+    /// `None` while the depth test is disabled.
+    pub depth_func: Option<gl::types::GLenum>,
+    pub depth_write: bool,
+    /// `[src rgb, dst rgb, src alpha, dst alpha]`, `None` while blending is disabled.
+    pub blend: Option<[gl::types::GLenum; 4]>,
+    /// `None` while face culling is disabled.
+    pub cull_face: Option<gl::types::GLenum>,
+}
+
+impl Snapshot {
+    /// Read back the current state. Queries stall the pipeline, so this is for debugging only.
+    pub fn capture() -> Self {
+        let integer = |name: gl::types::GLenum| {
+            let mut value: gl::types::GLint = 0;
+            unsafe { gl::GetIntegerv(name, &mut value); }
+            value as gl::types::GLenum
+        };
+        let enabled = |capability: gl::types::GLenum| unsafe { gl::IsEnabled(capability) == gl::TRUE };
+
+        let mut viewport: [gl::types::GLint; 4] = [0; 4];
+        let mut depth_write: gl::types::GLboolean = gl::FALSE;
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut depth_write);
+        }
+
+        Snapshot {
+            framebuffer: integer(gl::DRAW_FRAMEBUFFER_BINDING),
+            viewport: Viewport { x: viewport[0], y: viewport[1], width: viewport[2], height: viewport[3] },
+            depth_func: enabled(gl::DEPTH_TEST).then(|| integer(gl::DEPTH_FUNC)),
+            depth_write: depth_write == gl::TRUE,
+            blend: enabled(gl::BLEND).then(|| [
+                integer(gl::BLEND_SRC_RGB),
+                integer(gl::BLEND_DST_RGB),
+                integer(gl::BLEND_SRC_ALPHA),
+                integer(gl::BLEND_DST_ALPHA),
+            ]),
+            cull_face: enabled(gl::CULL_FACE).then(|| integer(gl::CULL_FACE_MODE)),
+        }
+    }
+}
+
+/// What a pass is expected to leave bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Expected {
+    /// `None` for passes writing imported resources, which bind their own targets.
+    framebuffer_and_viewport: Option<(gl::types::GLuint, Viewport)>,
+    render_state: Option<RenderState>,
+}
+
+impl Expected {
+    /// Expect the framebuffer and viewport bound right now, unless `binds_own_target`, and `render_state`.
+    pub(super) fn current(binds_own_target: bool, render_state: Option<RenderState>) -> Self {
+        let snapshot = Snapshot::capture();
+        Expected {
+            framebuffer_and_viewport: (!binds_own_target).then(|| (snapshot.framebuffer, snapshot.viewport)),
+            render_state,
+        }
+    }
+
+    /// Panic if the current state differs from what's expected, listing every difference.
+    pub(super) fn check(&self, pass: &str) {
+        let actual = Snapshot::capture();
+        let mut mismatches = Vec::new();
+
+        if let Some((framebuffer, viewport)) = self.framebuffer_and_viewport {
+            if actual.framebuffer != framebuffer {
+                mismatches.push(format!("framebuffer {} bound instead of {}", actual.framebuffer, framebuffer));
+            }
+            if actual.viewport != viewport {
+                mismatches.push(format!("viewport {:?} instead of {:?}", actual.viewport, viewport));
+            }
+        }
+
+        if let Some(render_state) = &self.render_state {
+            let depth_func = render_state.depth_test.then(|| {
+                let func = render_state.depth_func;
+                if state::is_reverse_z() { func.reversed().gl_enum() } else { func.gl_enum() }
+            });
+            if actual.depth_func != depth_func {
+                mismatches.push(format!("depth test {:?} instead of {:?}", actual.depth_func, depth_func));
+            }
+            if actual.depth_write != render_state.depth_write {
+                mismatches.push(format!("depth writes {} instead of {}", actual.depth_write, render_state.depth_write));
+            }
+            let blend = blend_funcs(render_state.blend);
+            if actual.blend != blend {
+                let expected = format!("{:?} ({:?})", blend, render_state.blend);
+                mismatches.push(format!("blending {:?} instead of {}", actual.blend, expected));
+            }
+            let cull_face = match render_state.cull_face {
+                CullFace::None => None,
+                CullFace::Back => Some(gl::BACK),
+                CullFace::Front => Some(gl::FRONT),
+            };
+            if actual.cull_face != cull_face {
+                mismatches.push(format!("face culling {:?} instead of {:?}", actual.cull_face, cull_face));
+            }
+        }
+
+        if !mismatches.is_empty() {
+            panic!("render pass `{}` leaked OpenGL state: {}", pass, mismatches.join(", "));
+        }
+    }
+}
+
+/// Blend functions `RenderState::apply()` sets for `blend`.
+fn blend_funcs(blend: BlendMode) -> Option<[gl::types::GLenum; 4]> {
+    match blend {
+        BlendMode::Opaque => None,
+        BlendMode::Alpha => Some([gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA]),
+        BlendMode::Additive => Some([gl::SRC_ALPHA, gl::ONE, gl::SRC_ALPHA, gl::ONE]),
+        BlendMode::Premultiplied => Some([gl::ONE, gl::ONE_MINUS_SRC_ALPHA, gl::ONE, gl::ONE_MINUS_SRC_ALPHA]),
+    }
+}
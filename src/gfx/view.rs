@@ -0,0 +1,125 @@
+//! Rendering the scene from a secondary camera into a texture, for mirrors, portals or security cameras.
+//!
+//! Materials sample the result through the texture bound to `SECONDARY_VIEW_UNIT`, e.g. with
+//! `layout (binding = 2) uniform sampler2D SecondaryView;` in a fragment shader like `shaders/mirror.frag`.
+//!
+//! A view can see itself, like two mirrors facing each other, so each recursion level gets its own target. Levels
+//! are drawn deepest first, each one sampling the level below it, and the deepest one sampling a plain fallback
+//! color, so no pass ever reads the texture it's drawing to. `max_depth` bounds how many times the scene is drawn.
+//! ## Example
+//! ```ignore
+//! let mut mirror = gfx::SecondaryView::new(width, height, gfx::ColorSpace::Linear, 2)?;
+//!
+//! // Before the main pass
+//! mirror.render(&camera, |camera| Some(camera.reflected(mirror_point, mirror_normal)), |camera, viewport| {
+//!     // Set camera uniforms and draw the scene, as in the main pass
+//! });
+//! // The main pass then samples the first level
+//! ```
+
+use super::camera::Camera;
+use super::color::ColorSpace;
+use super::state;
+use super::target::{self, RenderTarget};
+use super::texture::Texture;
+use super::viewport::Viewport;
+
+/// Texture unit the view's texture is bound to while drawing. Units 0 and 1 are taken by materials and shadows.
+pub const SECONDARY_VIEW_UNIT: u32 = 2;
+
+pub struct SecondaryView {
+    /// One target per recursion level, level 0 being what the main pass sees.
+    levels: Vec<RenderTarget>,
+    /// Sampled by the deepest level, where recursion stops.
+    fallback: Texture,
+    color_space: ColorSpace,
+}
+
+impl SecondaryView {
+    /// `max_depth` is at least 1, the view itself.
+    pub fn new(width: i32, height: i32, color_space: ColorSpace, max_depth: usize) -> Result<Self, target::Error> {
+        let levels = (0..max_depth.max(1))
+            .map(|_| RenderTarget::new(width, height, color_space))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fallback = Texture::from_rgba8(1, 1, &[0, 0, 0, 255], color_space);
+
+        Ok(SecondaryView { levels, fallback, color_space })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.levels[0].width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.levels[0].height()
+    }
+
+    /// Reallocate every level with a new size, e.g. to keep matching the main viewport.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), target::Error> {
+        for level in &mut self.levels {
+            level.resize(width, height)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) -> Result<(), target::Error> {
+        let (width, height) = (self.width(), self.height());
+        self.levels.truncate(max_depth.max(1));
+        while self.levels.len() < max_depth {
+            self.levels.push(RenderTarget::new(width, height, self.color_space)?);
+        }
+
+        Ok(())
+    }
+
+    /// What the main pass sees.
+    pub fn texture(&self) -> &Texture {
+        self.levels[0].color()
+    }
+
+    /// Render the view as seen from `camera`.
+    ///
+    /// `next` maps a camera to the one looking through the view, e.g. `Camera::reflected()` for a mirror, or `None`
+    /// when the view isn't visible from it, which stops the recursion early. `draw` then draws the scene for each
+    /// level with that level's camera, into a cleared target whose viewport is set to the one it's given.
+    ///
+    /// Afterwards the first level is bound to `SECONDARY_VIEW_UNIT`, and the last level's target and viewport are
+    /// still bound, so the caller has to bind its own again.
+    pub fn render<N, D>(&self, camera: &Camera, mut next: N, mut draw: D)
+        where N: FnMut(&Camera) -> Option<Camera>,
+              D: FnMut(&Camera, &Viewport)
+    {
+        let mut cameras: Vec<Camera> = Vec::with_capacity(self.levels.len());
+        while cameras.len() < self.levels.len() {
+            match next(cameras.last().unwrap_or(camera)) {
+                Some(camera) => cameras.push(camera),
+                None => break,
+            }
+        }
+
+        let viewport = Viewport::make_viewport(self.width(), self.height());
+        for (level, camera) in cameras.iter().enumerate().rev() {
+            match self.levels.get(level + 1).filter(|_| level + 1 < cameras.len()) {
+                Some(deeper) => deeper.color().bind(SECONDARY_VIEW_UNIT),
+                None => self.fallback.bind(SECONDARY_VIEW_UNIT),
+            }
+
+            self.levels[level].bind();
+            viewport.use_viewport();
+            state::clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            draw(camera, &viewport);
+        }
+
+        // Not visible at all, so nothing better to show than the fallback
+        if cameras.is_empty() {
+            self.fallback.bind(SECONDARY_VIEW_UNIT);
+        } else {
+            self.texture().bind(SECONDARY_VIEW_UNIT);
+        }
+    }
+}
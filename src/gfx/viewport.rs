@@ -1,4 +1,28 @@
+use super::camera::Camera;
+use super::state::{self, Scissor};
 
+/// How the viewport follows the window size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalePolicy {
+    /// Cover the whole window, the aspect ratio changing along with it.
+    Stretch,
+    /// The largest rectangle of `aspect_ratio` that fits, centered, with bars on the remaining sides.
+    Letterbox {
+        aspect_ratio: f32,
+    },
+    /// The largest whole multiple of `width` x `height` that fits, centered, so pixel art stays sharp.
+    /// Never scaled below 1x, so windows smaller than that crop the edges instead.
+    IntegerScale {
+        width: i32,
+        height: i32,
+    },
+}
+
+/// A rectangle of the framebuffer in window coordinates, origin at the bottom left.
+///
+/// Several viewports can share a frame for split-screen, see `split()`. Each region then gets its own camera,
+/// with a projection made for the region's `aspect_ratio()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Viewport {
     pub x: i32,
     pub y: i32,
@@ -11,12 +35,96 @@ impl Viewport {
         Viewport { x: 0, y: 0, width, height }
     }
 
+    /// Viewport for a `window_width` x `window_height` window under `policy`.
+    pub fn fit(window_width: i32, window_height: i32, policy: ScalePolicy) -> Self {
+        let (width, height) = match policy {
+            ScalePolicy::Stretch => (window_width, window_height),
+            ScalePolicy::Letterbox { aspect_ratio } => {
+                if (window_width as f32) < window_height as f32 * aspect_ratio {
+                    (window_width, (window_width as f32 / aspect_ratio).round() as i32)
+                } else {
+                    ((window_height as f32 * aspect_ratio).round() as i32, window_height)
+                }
+            },
+            ScalePolicy::IntegerScale { width, height } => {
+                let scale = (window_width / width.max(1)).min(window_height / height.max(1)).max(1);
+                (width * scale, height * scale)
+            },
+        };
+
+        Viewport { x: (window_width - width) / 2, y: (window_height - height) / 2, width, height }
+    }
+
     pub fn update_size(&mut self, width: i32, height: i32) {
         self.width = width;
         self.height = height;
     }
+
+    /// Refit to a resized window under `policy`, make it the current viewport, and match `camera`'s projection
+    /// to the new aspect ratio. Everything outside the viewport should be cleared to the bar color every frame.
+    pub fn resize(&mut self, window_width: i32, window_height: i32, policy: ScalePolicy, camera: &mut Camera) {
+        *self = Viewport::fit(window_width, window_height, policy);
+        self.use_viewport();
+        camera.set_aspect_ratio(self.aspect_ratio());
+    }
     
     pub fn use_viewport(&self) {
         unsafe { gl::Viewport(self.x, self.y, self.width, self.height); }
     }
-}
\ No newline at end of file
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    /// Scissor rectangle covering exactly this viewport.
+    pub fn scissor(&self) -> Scissor {
+        Scissor { x: self.x, y: self.y, width: self.width, height: self.height }
+    }
+
+    /// Make this the viewport for the following draws and clear `mask` within it only, leaving the rest of the
+    /// framebuffer to the other regions.
+    pub fn begin_region(&self, mask: gl::types::GLbitfield) {
+        self.use_viewport();
+        state::clear_scissored(mask, self.scissor());
+    }
+
+    /// Divide this viewport into `count` regions for split-screen, ordered left to right then top to bottom:
+    /// two players side by side, three or four in a 2x2 grid with the last cell left empty for three.
+    /// Sizes are rounded so the regions always tile this viewport without gaps.
+    pub fn split(&self, count: usize) -> Vec<Viewport> {
+        let (columns, rows) = match count {
+            0 => return Vec::new(),
+            1 => (1, 1),
+            2 => (2, 1),
+            3 | 4 => (2, 2),
+            _ => {
+                let columns = (count as f32).sqrt().ceil() as i32;
+                (columns, (count as i32 + columns - 1) / columns)
+            },
+        };
+
+        (0..count as i32).map(|i| {
+            let (column, row) = (i % columns, i / columns);
+            let x0 = self.x + self.width * column / columns;
+            let x1 = self.x + self.width * (column + 1) / columns;
+            // Rows count down from the top, window coordinates count up from the bottom
+            let y1 = self.y + self.height - self.height * row / rows;
+            let y0 = self.y + self.height - self.height * (row + 1) / rows;
+
+            Viewport { x: x0, y: y0, width: x1 - x0, height: y1 - y0 }
+        }).collect()
+    }
+
+    /// Whether `point`, in pixels from the top left of the window of height `window_height`, is inside this viewport.
+    pub fn contains(&self, point: glam::Vec2, window_height: i32) -> bool {
+        let y = window_height as f32 - point.y;
+        point.x >= self.x as f32 && point.x < (self.x + self.width) as f32
+            && y >= self.y as f32 && y < (self.y + self.height) as f32
+    }
+
+    /// Convert `point` from pixels from the top left of the window to pixels from the top left of this viewport,
+    /// the form `Camera::project()` and `Camera::screen_ray()` work in.
+    pub fn to_local(&self, point: glam::Vec2, window_height: i32) -> glam::Vec2 {
+        glam::vec2(point.x - self.x as f32, point.y - (window_height - self.y - self.height) as f32)
+    }
+}
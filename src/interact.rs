@@ -0,0 +1,140 @@
+//! Looking at things and using them.
+//!
+//! Entities that can be used carry an `Interactable`. Every frame `Interaction::update()` casts a ray from the
+//! center of the camera's view, and focuses the closest rendered entity it hits if that entity is interactable,
+//! enabled and in range. Something in front of an interactable hides it, since only the closest hit counts. The
+//! focused entity is outlined, its prompt is drawn under the crosshair by `draw_prompt()`, and using it queues an
+//! `Interacted` event for gameplay code to drain, e.g. into an `InteractionEvents` resource for systems to read.
+//! ## Example
+//! ```ignore
+//! world.spawn((mesh, material, gfx::Mobility::Static, transform, Interactable::new("Open door", 2.0)));
+//!
+//! // Every frame
+//! interaction.update(&mut world, &extractor, &camera, &viewport, input.is_key_pressed(&Keycode::F));
+//! interaction.draw_prompt(&mut world, &mut text, &viewport);
+//! world.insert_resource(InteractionEvents(interaction.drain_events().collect()));
+//!
+//! fn open_doors(events: Res<InteractionEvents>, mut doors: Query<(Entity, &mut Door)>) {
+//!     // Open them
+//! }
+//! ```
+
+use crate::gfx::camera::Camera;
+use crate::gfx::extract::BatchExtractor;
+use crate::gfx::outline::Outlined;
+use crate::gfx::pick;
+use crate::gfx::text::TextRenderer;
+use crate::gfx::viewport::Viewport;
+use crate::logic::{Entity, World};
+
+/// Component for entities the player can use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interactable {
+    /// Shown while the entity is focused, e.g. "Open door".
+    pub prompt: String,
+    /// Farthest distance from the camera it can be used from.
+    pub range: f32,
+    /// Disabled interactables can't be focused, e.g. a door that's already open.
+    pub enabled: bool,
+}
+
+impl Interactable {
+    pub fn new(prompt: &str, range: f32) -> Self {
+        Interactable { prompt: prompt.to_owned(), range, enabled: true }
+    }
+}
+
+/// The focused entity was used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interacted(pub Entity);
+
+/// Resource holding the frame's `Interacted` events, for systems to react to them.
+#[derive(Debug, Clone, Default)]
+pub struct InteractionEvents(pub Vec<Interacted>);
+
+#[derive(Debug, Default)]
+pub struct Interaction {
+    focused: Option<Entity>,
+    /// Whether `focused` was given its `Outlined` by us, and so should lose it again on losing focus.
+    outlined: bool,
+    events: Vec<Interacted>,
+}
+
+impl Interaction {
+    pub fn new() -> Self {
+        Interaction::default()
+    }
+
+    /// Refocus on whatever is in the center of `camera`'s view, and queue an `Interacted` if `use_pressed` and
+    /// something is focused.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        extractor: &BatchExtractor,
+        camera: &Camera,
+        viewport: &Viewport,
+        use_pressed: bool,
+    ) {
+        let center = glam::vec2(viewport.width as f32, viewport.height as f32) * 0.5;
+        let focused = pick::pick(world, extractor, camera, viewport, center).filter(|hit| {
+            match world.get_component::<Interactable>(hit.entity) {
+                Ok(interactable) => interactable.enabled && hit.distance <= interactable.range,
+                Err(_) => false,
+            }
+        });
+
+        self.set_focus(world, focused.map(|hit| hit.entity));
+
+        if let (true, Some(entity)) = (use_pressed, self.focused) {
+            self.events.push(Interacted(entity));
+        }
+    }
+
+    pub fn focused(&self) -> Option<Entity> {
+        self.focused
+    }
+
+    /// Draw the focused entity's prompt centered a little below the middle of `viewport`. Queued on `text`, which
+    /// still has to be flushed.
+    pub fn draw_prompt(&self, world: &mut World, text: &mut TextRenderer, viewport: &Viewport) {
+        let entity = match self.focused {
+            Some(entity) => entity,
+            None => return,
+        };
+        let prompt = match world.get_component::<Interactable>(entity) {
+            Ok(interactable) => interactable.prompt.clone(),
+            Err(_) => return,
+        };
+
+        let size = text.measure(&prompt, 1.0);
+        let position = glam::vec2(
+            (viewport.width as f32 - size.x) * 0.5,
+            viewport.height as f32 * 0.5 + size.y * 2.0,
+        );
+        text.draw(&prompt, position, 1.0, glam::Vec4::ONE);
+    }
+
+    /// Take every `Interacted` queued since the last drain, oldest first.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, Interacted> {
+        self.events.drain(..)
+    }
+
+    fn set_focus(&mut self, world: &mut World, entity: Option<Entity>) {
+        if entity == self.focused {
+            return;
+        }
+
+        // Despawned entities have nothing left to remove
+        if let (Some(previous), true) = (self.focused, self.outlined) {
+            let _ = world.remove_component::<Outlined>(previous);
+        }
+
+        self.focused = entity;
+        self.outlined = match entity {
+            Some(entity) if !world.has_component::<Outlined>(entity) => {
+                world.add_component(entity, Outlined).is_ok()
+            },
+            _ => false,
+        };
+    }
+}
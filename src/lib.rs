@@ -0,0 +1,28 @@
+//! The engine, usable without its demo: `main.rs` is one binary built on it, and `benches/` another that only
+//! needs a `World`.
+
+extern crate gl;
+extern crate sdl2;
+extern crate thiserror;
+extern crate winapi;
+extern crate glam;
+extern crate rusttest_derive;
+extern crate serde;
+extern crate ron;
+
+// Lets derived code name `::rusttest` from inside the crate as well
+extern crate self as rusttest;
+
+pub mod anim;
+pub mod audio;
+pub mod budget;
+pub mod gfx;
+pub mod interact;
+pub mod math;
+pub mod system;
+pub mod resource;
+pub mod log;
+pub mod logic;
+pub mod selfcheck;
+pub mod surface;
+pub mod weather;
@@ -65,9 +65,10 @@ impl Logger {
         }
     }
 
-    /// Log to both stdout and file.
+    /// Log to both stdout and file, tagged with the name of the calling thread.
     fn log_message(&self, severity: Severity, message: &str) {
-        let mut msg = LogMessage::new(&("").to_string(), message, severity);
+        let prefix = format!("[{}]", crate::system::thread::current_name());
+        let mut msg = LogMessage::new(&prefix, message, severity);
         print!("{}", msg.formatted(true));
         self.log_message_to_file(&mut msg);
     }
@@ -0,0 +1,118 @@
+//! Entities put together at runtime, one component at a time, for when the set of components isn't known until then
+//! (spawning from scene files or scripts) or is longer than the 12 a tuple `ComponentBundle` can have.
+//! ## Example
+//! ```ignore
+//! let mut builder = EntityBuilder::new();
+//! builder.add(Position(0.0, 0.0)).add(Sprite("goblin"));
+//! if hostile {
+//!     builder.add(Hostile);
+//! }
+//! let entity = builder.spawn(&mut world);
+//! ```
+
+use std::any::{Any, TypeId};
+
+use super::name::Name;
+use super::world::*;
+
+struct BuilderComponent {
+    type_id: TypeId,
+    value: Box<dyn Any + Send + Sync>,
+    /// An empty column of the component's type, for when the entity's archetype doesn't exist yet.
+    new_store: fn() -> ComponentStore,
+    /// Unboxes the value into its column.
+    push: fn(&mut Archetype, usize, Box<dyn Any + Send + Sync>),
+}
+
+fn push_boxed<T: 'static>(archetype: &mut Archetype, component_index: usize, value: Box<dyn Any + Send + Sync>) {
+    archetype.push(component_index, *value.downcast::<T>().unwrap());
+}
+
+/// Accumulates components of any type to spawn as one entity.
+#[derive(Default)]
+pub struct EntityBuilder {
+    components: Vec<BuilderComponent>,
+}
+
+impl EntityBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a component, replacing any already added of the same type.
+    pub fn add<T: 'static + Send + Sync>(&mut self, t: T) -> &mut Self {
+        let type_id = TypeId::of::<T>();
+        let component = BuilderComponent {
+            type_id,
+            value: Box::new(t),
+            new_store: ComponentStore::new::<T>,
+            push: push_boxed::<T>,
+        };
+
+        match self.components.iter_mut().find(|c| c.type_id == type_id) {
+            Some(existing) => *existing = component,
+            None => self.components.push(component),
+        }
+        self
+    }
+
+    pub fn has<T: 'static>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.components.iter().any(|c| c.type_id == type_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Spawn an entity with everything added so far, leaving the builder empty to be reused.
+    pub fn spawn(&mut self, world: &mut World) -> Entity {
+        let (index, generation) = world.allocate_entity();
+        let location = self.spawn_at(world, index);
+
+        world.entities[index as usize] = EntityInfo { generation, location };
+
+        let entity = Entity { index, generation };
+        if world.archetypes[location.archetype_index as usize].component_index::<Name>().is_some() {
+            world.index_name(entity);
+        }
+        entity
+    }
+
+    /// Move the components into their archetype as entity `index`, leaving its `EntityInfo` to the caller.
+    pub(super) fn spawn_at(&mut self, world: &mut World, index: EntityId) -> EntityLocation {
+        let mut components = std::mem::take(&mut self.components);
+
+        let mut types: Vec<(usize, TypeId)> = components.iter().map(|c| c.type_id).enumerate().collect();
+        let mut order = vec![0; components.len()];
+        let archetype_index = bundle_archetype(world, &mut types, &mut order, || {
+            let mut stores: Vec<ComponentStore> = components.iter().map(|c| (c.new_store)()).collect();
+            stores.sort_unstable_by(|a, b| a.type_id.cmp(&b.type_id));
+
+            let mut archetype = Archetype::new();
+            archetype.components = stores;
+            archetype
+        });
+
+        let tick = world.change_tick();
+        let archetype = &mut world.archetypes[archetype_index];
+        let location = EntityLocation::new(archetype_index as EntityId, archetype.len() as EntityId);
+
+        archetype.entities.push(index);
+        for (component, column) in components.drain(..).zip(order) {
+            (component.push)(archetype, column, component.value);
+        }
+        for c in archetype.components.iter_mut() {
+            c.mark_added(tick);
+        }
+
+        // Hand the allocation back for the next entity
+        self.components = components;
+
+        location
+    }
+}
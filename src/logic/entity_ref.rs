@@ -0,0 +1,124 @@
+//! Accessors for one entity, returned by `World::entity()` and `World::entity_mut()`.
+//!
+//! The entity's generation is checked and its location looked up once, when the accessor is made, rather than for
+//! every component. `EntityMut` keeps that location up to date as components are added and removed.
+//! ## Example
+//! ```ignore
+//! let mut goblin = world.entity_mut(goblin).unwrap();
+//! goblin.insert(Burning(3.0));
+//! if goblin.has::<Frozen>() {
+//!     goblin.remove::<Frozen>();
+//! }
+//! ```
+
+use super::world::*;
+
+/// Read access to one live entity.
+pub struct EntityRef<'world_borrow> {
+    world: &'world_borrow World,
+    entity: Entity,
+    location: EntityLocation,
+}
+
+impl<'world_borrow> EntityRef<'world_borrow> {
+    pub(super) fn new(world: &'world_borrow World, entity: Entity, location: EntityLocation) -> Self {
+        Self { world, entity, location }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// The archetype the entity is in, which is every entity with exactly the same component types.
+    pub fn archetype(&self) -> &'world_borrow Archetype {
+        &self.world.archetypes[self.location.archetype_index as usize]
+    }
+
+    pub fn has<T: 'static>(&self) -> bool {
+        self.archetype().component_index::<T>().is_some() || self.world.has_component::<T>(self.entity)
+    }
+
+    /// `None` if the entity has no `T`, or its column is borrowed mutably at the time.
+    pub fn get<T: 'static>(&self) -> Option<ComponentRef<'world_borrow, T>> {
+        let archetype = self.archetype();
+        let component_index = match archetype.component_index::<T>() {
+            Some(component_index) => component_index,
+            // Could be sparse
+            None => return self.world.get_component::<T>(self.entity).ok(),
+        };
+
+        let borrow = archetype.get::<T>(component_index).try_read().ok()?;
+        Some(ComponentRef::new(borrow, self.location.index_in_archetype as usize))
+    }
+}
+
+/// Read and write access to one live entity, which can also add and remove its components or despawn it.
+pub struct EntityMut<'world_borrow> {
+    world: &'world_borrow mut World,
+    entity: Entity,
+    /// Moved along with the entity by `insert()` and `remove()`; nothing else can move it while the world is borrowed.
+    location: EntityLocation,
+}
+
+impl<'world_borrow> EntityMut<'world_borrow> {
+    pub(super) fn new(world: &'world_borrow mut World, entity: Entity, location: EntityLocation) -> Self {
+        Self { world, entity, location }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn archetype(&self) -> &Archetype {
+        &self.world.archetypes[self.location.archetype_index as usize]
+    }
+
+    pub fn has<T: 'static>(&self) -> bool {
+        self.archetype().component_index::<T>().is_some()
+            || self.world.sparse_set::<T>().map_or(false, |set| set.read().unwrap().contains(self.entity.index))
+    }
+
+    /// `None` if the entity has no `T`. Holding `&mut World` means nothing else can have the column borrowed.
+    pub fn get<T: 'static>(&self) -> Option<ComponentRef<T>> {
+        let archetype = self.archetype();
+        let component_index = match archetype.component_index::<T>() {
+            Some(component_index) => component_index,
+            // Could be sparse
+            None => {
+                let set = self.world.sparse_set::<T>()?.read().unwrap();
+                let index = set.index_of(self.entity.index)?;
+                return Some(ComponentRef::sparse(set, index));
+            },
+        };
+
+        let borrow = archetype.get::<T>(component_index).try_read().ok()?;
+        Some(ComponentRef::new(borrow, self.location.index_in_archetype as usize))
+    }
+
+    /// Marks the component's column as changed, like `World::get_component_mut()`.
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        let tick = self.world.change_tick();
+        let archetype_index = self.location.archetype_index as usize;
+        if self.world.archetypes[archetype_index].component_index::<T>().is_some() {
+            self.world.archetypes[archetype_index].get_component_mut(self.location.index_in_archetype, tick).ok()
+        } else {
+            // Could be sparse
+            self.world.sparse_component_mut::<T>(self.entity.index)
+        }
+    }
+
+    /// Add a component, replacing any `T` the entity already has.
+    pub fn insert<T: 'static + Send + Sync>(&mut self, t: T) -> &mut Self {
+        self.world.add_component_at(self.entity, &mut self.location, t);
+        self
+    }
+
+    /// Remove a component, returning it if the entity had one.
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.world.remove_component_at::<T>(self.entity, &mut self.location).ok()
+    }
+
+    pub fn despawn(self) {
+        self.world.despawn_at(self.entity, self.location);
+    }
+}
@@ -2,8 +2,25 @@
 pub enum FetchError {
     ComponentAlreadyBorrowed(ComponentAlreadyBorrowed),
     ComponentDoesNotExist(ComponentDoesNotExist),
+    NotSingle(NotSingle),
+    ResourceDoesNotExist(ResourceDoesNotExist),
+    ResourceAlreadyBorrowed(ResourceAlreadyBorrowed),
 }
 
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::ComponentAlreadyBorrowed(e) => e.fmt(f),
+            FetchError::ComponentDoesNotExist(e) => e.fmt(f),
+            FetchError::NotSingle(e) => e.fmt(f),
+            FetchError::ResourceDoesNotExist(e) => e.fmt(f),
+            FetchError::ResourceAlreadyBorrowed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
 #[derive(Debug)]
 pub struct ComponentAlreadyBorrowed(&'static str);
 
@@ -37,3 +54,66 @@ impl std::fmt::Display for ComponentDoesNotExist {
 }
 
 impl std::error::Error for ComponentDoesNotExist {}
+
+/// Asked for the one entity with a component, but there were none or several.
+#[derive(Debug)]
+pub struct NotSingle {
+    type_name: &'static str,
+    count: usize,
+}
+
+impl NotSingle {
+    pub fn new<T>(count: usize) -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            count,
+        }
+    }
+
+    /// How many entities had the component.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl std::fmt::Display for NotSingle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected exactly one [{}], found {}", self.type_name, self.count)
+    }
+}
+
+impl std::error::Error for NotSingle {}
+
+#[derive(Debug)]
+pub struct ResourceDoesNotExist(&'static str);
+
+impl ResourceDoesNotExist {
+    pub fn new<T>() -> Self {
+        Self(std::any::type_name::<T>())
+    }
+}
+
+impl std::fmt::Display for ResourceDoesNotExist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "resource [{}] does not exist", self.0)
+    }
+}
+
+impl std::error::Error for ResourceDoesNotExist {}
+
+#[derive(Debug)]
+pub struct ResourceAlreadyBorrowed(&'static str);
+
+impl ResourceAlreadyBorrowed {
+    pub fn new<T>() -> Self {
+        Self(std::any::type_name::<T>())
+    }
+}
+
+impl std::fmt::Display for ResourceAlreadyBorrowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "resource [{}] is already borrowed", self.0)
+    }
+}
+
+impl std::error::Error for ResourceAlreadyBorrowed {}
@@ -0,0 +1,187 @@
+//! Parent/child links between entities, and the world matrices they result in.
+//!
+//! A child's `Transform3` is relative to its parent. `propagate_transforms()` walks down from every entity without a
+//! `Parent` and gives each entity in the hierarchy a `GlobalTransform`, its transform composed with all of its
+//! ancestors'. Links are only kept consistent through `set_parent()`, `remove_parent()` and `despawn_recursive()`;
+//! a plain `despawn()` of an entity in a hierarchy leaves its relatives pointing at a dead handle, which lookups
+//! treat as gone.
+//! ## Example
+//! ```ignore
+//! let body = world.spawn((mesh, material, gfx::Mobility::Dynamic, Transform3::identity()));
+//! let arm = world.spawn((mesh, material, gfx::Mobility::Dynamic, Transform3::identity()));
+//! world.set_parent(arm, body).unwrap();
+//!
+//! // After anything moves
+//! schedule.add_exclusive_system(Stage::PostUpdate, "propagate transforms", hierarchy::propagate_transforms);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::isometry::Transform3;
+
+use super::query::{QueryIter, Without};
+use super::save::MapEntities;
+use super::world::*;
+
+/// The entity this one's transform is relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parent(Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) {
+        self.0 = map(self.0);
+    }
+}
+
+/// Entities with this one as their `Parent`, in the order they were parented.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Children(Vec<Entity>);
+
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) {
+        for child in self.0.iter_mut() {
+            *child = map(*child);
+        }
+    }
+}
+
+/// An entity's transform in world space, written by `propagate_transforms()`.
+#[derive(Debug, Clone, Copy)]
+pub struct GlobalTransform(pub glam::Mat4);
+
+#[derive(Debug)]
+pub enum HierarchyError {
+    NoSuchEntity(NoSuchEntity),
+    /// The parent is the child, or one of its descendants.
+    Cycle,
+}
+
+impl std::fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HierarchyError::NoSuchEntity(e) => e.fmt(f),
+            HierarchyError::Cycle => write!(f, "an entity cannot be parented to itself or one of its descendants"),
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+impl World {
+    /// Make `child`'s transform relative to `parent`'s, taking it away from any previous parent.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), HierarchyError> {
+        if !self.is_alive(child) || !self.is_alive(parent) {
+            return Err(HierarchyError::NoSuchEntity(NoSuchEntity));
+        }
+
+        // Walk up from the new parent, which must not lead back to the child
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            if entity == child {
+                return Err(HierarchyError::Cycle);
+            }
+            ancestor = self.get_component::<Parent>(entity).ok().map(|p| p.get());
+        }
+
+        self.remove_parent(child);
+
+        match self.get_component_mut::<Children>(parent) {
+            Ok(children) => children.0.push(child),
+            Err(_) => {
+                self.add_component(parent, Children(vec![child])).unwrap();
+            },
+        }
+        self.add_component(child, Parent(parent)).unwrap();
+
+        Ok(())
+    }
+
+    /// Make `child` a root again, returning its previous parent if it had one.
+    pub fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        let parent = self.remove_component::<Parent>(child).ok()?.get();
+
+        if let Ok(children) = self.get_component_mut::<Children>(parent) {
+            children.0.retain(|&c| c != child);
+            if children.0.is_empty() {
+                let _ = self.remove_component::<Children>(parent);
+            }
+        }
+
+        Some(parent)
+    }
+
+    /// Despawn an entity along with all of its descendants, detaching it from its parent.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        if !self.is_alive(entity) {
+            return Err(NoSuchEntity);
+        }
+        self.remove_parent(entity);
+
+        let mut stack = vec![entity];
+        while let Some(entity) = stack.pop() {
+            if let Ok(children) = self.get_component::<Children>(entity) {
+                stack.extend(children.iter());
+            }
+            // Descendants despawned on their own already are skipped
+            let _ = self.despawn(entity);
+        }
+
+        Ok(())
+    }
+
+    fn is_alive(&self, entity: Entity) -> bool {
+        self.entity(entity).is_ok()
+    }
+}
+
+/// Write every hierarchy's `GlobalTransform`s from its `Transform3`s, parents before children. Entities without a
+/// `Transform3` end the branch they're in. Only `GlobalTransform`s that come out different are written, so
+/// `Changed<GlobalTransform>` still picks out what moved. Needs `&mut World` to add `GlobalTransform` to entities that
+/// don't have one yet, so it's scheduled as an exclusive system.
+pub fn propagate_transforms(world: &mut World) {
+    let mut stack: Vec<(Entity, glam::Mat4)> = match world.query::<(Entity, &Transform3, Without<Parent>)>() {
+        Ok(mut roots) => roots.iter().map(|(entity, transform, _)| (entity, transform.matrix())).collect(),
+        Err(_) => return,
+    };
+
+    while let Some((entity, matrix)) = stack.pop() {
+        let current = world.get_component::<GlobalTransform>(entity).ok().map(|global| global.0);
+        match current {
+            Some(current) if current == matrix => {},
+            Some(_) => world.get_component_mut::<GlobalTransform>(entity).unwrap().0 = matrix,
+            None => {
+                world.add_component(entity, GlobalTransform(matrix)).unwrap();
+            },
+        }
+
+        let children: Vec<Entity> = match world.get_component::<Children>(entity) {
+            Ok(children) => children.iter().copied().collect(),
+            Err(_) => continue,
+        };
+        for child in children {
+            if let Ok(transform) = world.get_component::<Transform3>(child) {
+                stack.push((child, matrix * transform.matrix()));
+            }
+        }
+    }
+}
@@ -1,18 +1,54 @@
-use std::iter::Zip;
+use std::iter::{Repeat, Take};
 
-/// This first iterator wraps the standard library `Zip` iterator and flattens nested tuples of values returned to a 
-/// flat list.
+use crate::system::thread;
+
+/// Query iterators over rows of an archetype that can be cut in two at a row without walking up to it, so `ParIter`
+/// can hand out ranges of rows.
+pub trait SplitAt: Iterator + Sized {
+    /// The first `row` items, and the rest.
+    fn split_at(self, row: usize) -> (Self, Self);
+
+    /// How many rows are left, whether or not they'll all be yielded.
+    fn rows(&self) -> usize {
+        self.size_hint().0
+    }
+}
+
+impl<'a, T> SplitAt for std::slice::Iter<'a, T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let slice = self.as_slice();
+        let (first, rest) = slice.split_at(row.min(slice.len()));
+        (first.iter(), rest.iter())
+    }
+}
+
+impl<T: Clone> SplitAt for Repeat<T> {
+    fn split_at(self, _row: usize) -> (Self, Self) {
+        (self.clone(), self)
+    }
+}
+
+impl SplitAt for Take<Repeat<()>> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let len = self.size_hint().0;
+        let row = row.min(len);
+        (std::iter::repeat(()).take(row), std::iter::repeat(()).take(len - row))
+    }
+}
+
+/// Zips iterators into a flat tuple of their items, ending with the shortest one. The iterators are kept as they are
+/// rather than nested in the standard library's `Zip`, so they can still be split.
 macro_rules! impl_zip {
-    ($name: ident, $zip_type: ty, $m_stuff: expr, $($T: ident),*) => {
+    ($name: ident, $($T: ident),*) => {
         pub struct $name<A: Iterator, $($T: Iterator,)*> {
-            inner: $zip_type,
+            inner: (A, $($T,)*),
         }
 
         impl<A: Iterator, $($T: Iterator,)*> $name<A, $($T,)*> {
             #[allow(non_snake_case)]
             pub fn new (A: A, $($T: $T,)*) -> Self {
                 Self {
-                    inner: A$(.zip($T))*
+                    inner: (A, $($T,)*)
                 }
             }
         }
@@ -21,26 +57,108 @@ macro_rules! impl_zip {
             type Item = (A::Item, $($T::Item,)*);
 
             #[inline(always)]
+            #[allow(non_snake_case)]
             fn next(&mut self) -> Option<Self::Item> {
-                self.inner.next().map($m_stuff)
+                let (A, $($T,)*) = &mut self.inner;
+                Some((A.next()?, $($T.next()?,)*))
             }
             #[inline]
+            #[allow(non_snake_case)]
             fn size_hint(&self) -> (usize, Option<usize>) {
-                self.inner.size_hint()
+                let (A, $($T,)*) = &self.inner;
+                let (mut min, mut max) = A.size_hint();
+                $(
+                    let (t_min, t_max) = $T.size_hint();
+                    min = min.min(t_min);
+                    max = match (max, t_max) {
+                        (Some(max), Some(t_max)) => Some(max.min(t_max)),
+                        (max, t_max) => max.or(t_max),
+                    };
+                )*
+                (min, max)
+            }
+        }
+
+        impl<A: SplitAt, $($T: SplitAt,)*> SplitAt for $name<A, $($T,)*> {
+            #[allow(non_snake_case)]
+            fn split_at(self, row: usize) -> (Self, Self) {
+                let (A, $($T,)*) = self.inner;
+                let A = A.split_at(row);
+                $(let $T = $T.split_at(row);)*
+                (Self { inner: (A.0, $($T.0,)*) }, Self { inner: (A.1, $($T.1,)*) })
             }
         }
     };
 }
 
-// TODO
-// HOW DO YOU WRITE RECURSIVE MACROS
-// FUCK
-impl_zip! {Zip3, Zip<Zip<A, B>, C>, |((a, b), c)| {(a, b, c)}, B, C}
-impl_zip! {Zip4, Zip<Zip<Zip<A, B>, C>, D>, |(((a, b), c), d)| {(a, b, c, d)}, B, C, D}
-impl_zip! {Zip5, Zip<Zip<Zip<Zip<A, B>, C>, D>, E>, |((((a, b), c), d), e)| {(a, b, c, d, e)}, B, C, D, E}
-impl_zip! {Zip6, Zip<Zip<Zip<Zip<Zip<A, B>, C>, D>, E>, F>, |(((((a, b), c), d), e), f)| {(a, b, c, d, e, f)}, B, C, D, E, F}
-impl_zip! {Zip7, Zip<Zip<Zip<Zip<Zip<Zip<A, B>, C>, D>, E>, F>, G>, |((((((a, b), c), d), e), f), g)| {(a, b, c, d, e, f, g)}, B, C, D, E, F, G}
-impl_zip! {Zip8, Zip<Zip<Zip<Zip<Zip<Zip<Zip<A, B>, C>, D>, E>, F>, G>, H>, |(((((((a, b), c), d), e), f), g), h)| {(a, b, c, d, e, f, g, h)}, B, C, D, E, F, G, H}
+impl_zip! {Zip2, B}
+impl_zip! {Zip3, B, C}
+impl_zip! {Zip4, B, C, D}
+impl_zip! {Zip5, B, C, D, E}
+impl_zip! {Zip6, B, C, D, E, F}
+impl_zip! {Zip7, B, C, D, E, F, G}
+impl_zip! {Zip8, B, C, D, E, F, G, H}
+
+/// Skips the rows of an archetype a query's filters cleared, for filters on components kept outside archetypes, like
+/// sparse sets. Yields every row if there's no mask.
+pub struct Masked<'a, I> {
+    inner: I,
+    rows: Option<&'a [bool]>,
+}
+
+impl<'a, I> Masked<'a, I> {
+    pub fn new(inner: I, rows: Option<&'a [bool]>) -> Self {
+        Self { inner, rows }
+    }
+}
+
+impl<I: Iterator> Iterator for Masked<'_, I> {
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let item = self.inner.next()?;
+            match self.rows {
+                Some(rows) => {
+                    let (&keep, rest) = rows.split_first()?;
+                    self.rows = Some(rest);
+                    if keep {
+                        return Some(item);
+                    }
+                },
+                None => return Some(item),
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.rows {
+            Some(_) => (0, self.inner.size_hint().1),
+            None => self.inner.size_hint(),
+        }
+    }
+}
+
+impl<I: SplitAt> SplitAt for Masked<'_, I> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let (inner, rest) = self.inner.split_at(row);
+        let (rows, rest_rows) = match self.rows {
+            Some(rows) => {
+                let (first, rest) = rows.split_at(row.min(rows.len()));
+                (Some(first), Some(rest))
+            },
+            None => (None, None),
+        };
+
+        (Masked { inner, rows }, Masked { inner: rest, rows: rest_rows })
+    }
+
+    fn rows(&self) -> usize {
+        self.inner.rows()
+    }
+}
 
 /// A series of iterators of the same type that are traversed in a row.
 pub struct ChainedIterator<I: Iterator> {
@@ -56,6 +174,13 @@ impl<I: Iterator> ChainedIterator<I> {
             iterators,
         }
     }
+
+    /// The iterators not yet finished, in no particular order.
+    pub fn into_iterators(self) -> Vec<I> {
+        let mut iterators = self.iterators;
+        iterators.extend(self.current_iter);
+        iterators
+    }
 }
 
 impl<I: Iterator> Iterator for ChainedIterator<I> {
@@ -64,21 +189,15 @@ impl<I: Iterator> Iterator for ChainedIterator<I> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         // Chain the iterators together.
-        // If the end of one iterator is reached go to the next.
-        match self.current_iter {
-            Some(ref mut iter) => match iter.next() {
-                None => {
-                    self.current_iter = self.iterators.pop();
-                    if let Some(ref mut iter) = self.current_iter {
-                        iter.next()
-                    } else {
-                        None
-                    }
-                }
-                item => item,
-            },
-            None => None,
+        // If the end of one iterator is reached go to the next, skipping any that are empty
+        // (archetypes everything was despawned from, or ones a change filter left out).
+        while let Some(ref mut iter) = self.current_iter {
+            match iter.next() {
+                None => self.current_iter = self.iterators.pop(),
+                item => return item,
+            }
         }
+        None
     }
 
     #[inline]
@@ -102,3 +221,48 @@ impl<I: Iterator> Iterator for ChainedIterator<I> {
         (min, Some(max))
     }
 }
+
+/// A query's rows split into ranges that are handed out to the worker threads of `system::thread::workers()`. See
+/// `Query::par_iter()`.
+pub struct ParIter<I: SplitAt> {
+    archetypes: Vec<I>,
+    chunk_size: usize,
+}
+
+impl<I: SplitAt> ParIter<I> {
+    pub fn new(archetypes: Vec<I>, chunk_size: usize) -> Self {
+        Self {
+            archetypes,
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Call `f` on every item, `chunk_size` rows of an archetype at a time on each worker, returning once all are
+    /// done. Chunks are handed out as workers become free, so a slow chunk doesn't hold up the others.
+    pub fn for_each<F>(self, f: F)
+    where
+        F: Fn(I::Item) + Sync,
+        I: Send,
+    {
+        let rows: usize = self.archetypes.iter().map(SplitAt::rows).sum();
+        let workers = thread::workers();
+        if workers.is_empty() || rows <= self.chunk_size {
+            self.archetypes.into_iter().flatten().for_each(f);
+            return;
+        }
+
+        let (f, chunk_size) = (&f, self.chunk_size);
+        workers.scope(|scope| {
+            for mut rows in self.archetypes {
+                while rows.rows() > chunk_size {
+                    let (chunk, rest) = rows.split_at(chunk_size);
+                    scope.execute(move || chunk.for_each(f));
+                    rows = rest;
+                }
+                if rows.rows() > 0 {
+                    scope.execute(move || rows.for_each(f));
+                }
+            }
+        });
+    }
+}
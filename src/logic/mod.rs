@@ -1,8 +1,22 @@
 pub mod world;
 pub mod system;
 pub mod query;
+pub mod builder;
+pub mod entity_ref;
+pub mod hierarchy;
+pub mod name;
+pub mod save;
+pub mod snapshot;
+pub mod sparse;
 mod iterator;
 mod error;
 
 pub use world::*;
-pub use query::QueryIter;
+pub use query::{Mut, Query, QueryIter, Res, ResMut};
+pub use builder::EntityBuilder;
+pub use entity_ref::{EntityMut, EntityRef};
+pub use hierarchy::{Children, GlobalTransform, Parent};
+pub use name::Name;
+pub use save::{ComponentRegistry, MapEntities};
+pub use snapshot::Snapshot;
+pub use rusttest_derive::Bundle;
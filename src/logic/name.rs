@@ -0,0 +1,80 @@
+//! Names for entities, to find them by in debugging, scripts and scene files.
+//!
+//! The world keeps an index from each name to the entities with it, updated whenever a `Name` is spawned, added,
+//! removed or despawned. Renaming an entity in place through `&mut Name` isn't seen by the index; add a new `Name`
+//! with `add_component()` instead. Names don't have to be unique.
+//! ## Example
+//! ```ignore
+//! let player = world.spawn((Name::new("player"), Health(100)));
+//!
+//! assert_eq!(world.find_by_name("player"), Some(player));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use super::world::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Name(String);
+
+impl Name {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl World {
+    /// The first entity spawned or named `name` that still has it.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.find_all_by_name(name).first().copied()
+    }
+
+    /// Every entity named `name`, in the order they got the name.
+    pub fn find_all_by_name(&self, name: &str) -> &[Entity] {
+        self.names.get(name).map_or(&[], |entities| entities.as_slice())
+    }
+
+    /// Add the entity to the index under its `Name`, if it has one.
+    pub(super) fn index_name(&mut self, entity: Entity) {
+        let name = match self.get_component::<Name>(entity) {
+            Ok(name) => name.0.clone(),
+            Err(_) => return,
+        };
+        self.names.entry(name).or_insert_with(Vec::new).push(entity);
+    }
+
+    /// Take the entity out of the index, while it still has the `Name` it was indexed under.
+    pub(super) fn unindex_name(&mut self, entity: Entity) {
+        let name = match self.get_component::<Name>(entity) {
+            Ok(name) => name.0.clone(),
+            Err(_) => return,
+        };
+        if let Some(entities) = self.names.get_mut(&name) {
+            entities.retain(|&e| e != entity);
+            if entities.is_empty() {
+                self.names.remove(&name);
+            }
+        }
+    }
+
+    /// Index every named entity from scratch, after the world's entities were replaced wholesale.
+    pub(super) fn rebuild_name_index(&mut self) {
+        let named: Vec<Entity> = self.iter_entities().filter(|&entity| self.has_component::<Name>(entity)).collect();
+
+        self.names.clear();
+        for entity in named {
+            self.index_name(entity);
+        }
+    }
+}
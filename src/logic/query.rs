@@ -9,30 +9,121 @@
 //!
 //! `FetchItem` exists so that RwLocks can be held in the scope that calls the user system.
 //! but the user system receives a simple &T or &mut T.
+//!
+//! Filters like `With<T>` and `Without<T>` only take part in matching archetypes and never borrow a column.
+//! `Option<&T>` and `Option<&mut T>` match any archetype, borrowing the column only where there is one.
+//! `Entity` reads the archetype's list of entities rather than a column.
+//! `Added<T>` and `Changed<T>` compare a column's ticks to the world's, and fetch nothing for archetypes they skip.
+//! `&mut T` yields a `Mut<T>`, which stamps the column as changed once it's written through, not when it's borrowed,
+//! so a query that only sometimes writes (or is filtered by `Changed<T>` itself) doesn't mark everything changed.
+//!
+//! Which archetypes a set of `QueryParameters` matches is cached in the world by the parameters' type, and only
+//! archetypes created since the last query of the same type are checked.
 
 use super::world::*;
 use super::iterator::*;
 use super::error::*;
+use super::sparse::SparseSet;
 
-use std::iter::Zip;
-use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::{any::TypeId, usize};
 
+/// The component and resource types something borrows from the world, so the scheduler can tell which systems may
+/// run at once.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    resource_reads: Vec<TypeId>,
+    resource_writes: Vec<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: 'static>(&mut self) {
+        self.reads.push(TypeId::of::<T>());
+    }
+
+    pub fn write<T: 'static>(&mut self) {
+        self.writes.push(TypeId::of::<T>());
+    }
+
+    pub fn read_resource<T: 'static>(&mut self) {
+        self.resource_reads.push(TypeId::of::<T>());
+    }
+
+    pub fn write_resource<T: 'static>(&mut self) {
+        self.resource_writes.push(TypeId::of::<T>());
+    }
+
+    /// Whether the two can't hold their borrows at the same time: one writes something the other reads or writes.
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        let conflicts = |writes: &[TypeId], reads: &[TypeId], other_writes: &[TypeId], other_reads: &[TypeId]| {
+            writes.iter().any(|t| other_reads.contains(t) || other_writes.contains(t))
+                || other_writes.iter().any(|t| reads.contains(t))
+        };
+
+        conflicts(&self.writes, &self.reads, &other.writes, &other.reads)
+            || conflicts(&self.resource_writes, &self.resource_reads, &other.resource_writes, &other.resource_reads)
+    }
+
+    /// Add everything `other` borrows.
+    pub fn extend(&mut self, other: &Access) {
+        self.reads.extend_from_slice(&other.reads);
+        self.writes.extend_from_slice(&other.writes);
+        self.resource_reads.extend_from_slice(&other.resource_reads);
+        self.resource_writes.extend_from_slice(&other.resource_writes);
+    }
+}
+
 pub trait SystemParameter {
     /// Specify how and what to request from the World.
     type Fetch: for<'a> Fetch<'a>;
+    /// Record what the parameter borrows, without borrowing it.
+    fn access(access: &mut Access);
 }
 
 impl<'a, T: QueryParameters> SystemParameter for Query<'a, T> {
     type Fetch = QueryFetch<T>;
+
+    fn access(access: &mut Access) {
+        T::access(access);
+    }
 }
 
 impl<T: 'static> SystemParameter for &T {
     type Fetch = Self;
+
+    fn access(access: &mut Access) {
+        access.read::<T>();
+    }
 }
 
 impl<T: 'static> SystemParameter for &mut T {
     type Fetch = Self;
+
+    fn access(access: &mut Access) {
+        access.write::<T>();
+    }
+}
+
+impl<'a, T: 'static + Send + Sync> SystemParameter for Res<'a, T> {
+    type Fetch = ResFetch<T>;
+
+    fn access(access: &mut Access) {
+        access.read_resource::<T>();
+    }
+}
+
+impl<'a, T: 'static + Send + Sync> SystemParameter for ResMut<'a, T> {
+    type Fetch = ResMutFetch<T>;
+
+    fn access(access: &mut Access) {
+        access.write_resource::<T>();
+    }
 }
 
 pub struct QueryFetch<T> {
@@ -64,6 +155,25 @@ pub struct Query<'world_borrow, T: QueryParameters> {
     _world: &'world_borrow World,
 }
 
+impl<'world_borrow, T: QueryParameters> Query<'world_borrow, T> {
+    /// Iterate on worker threads, `chunk_size` entities of an archetype to a thread at a time. Worth it for queries
+    /// over many entities that do a fair bit of work for each, like particles or boids; small ones are quicker with
+    /// `iter()`.
+    /// ## Example
+    /// ```ignore
+    /// let mut query = world.query::<(&mut Position, &Velocity)>().unwrap();
+    /// query.par_iter(256).for_each(|(mut position, velocity)| {
+    ///     position.0 += velocity.0 * dt;
+    /// });
+    /// ```
+    pub fn par_iter<'a, I: SplitAt>(&'a mut self, chunk_size: usize) -> ParIter<I>
+    where
+        Self: QueryIter<'a, Iter = ChainedIterator<I>>,
+    {
+        ParIter::new(self.iter().into_iterators(), chunk_size)
+    }
+}
+
 impl<'a, 'world_borrow, T: QueryParameters> FetchItem<'a> for Option<Query<'world_borrow, T>> {
     type InnerItem = Query<'world_borrow, T>;
     fn inner(&'a mut self) -> Self::InnerItem {
@@ -85,65 +195,247 @@ impl<'a, 'world_borrow, T: 'a> FetchItem<'a> for RwLockWriteGuard<'world_borrow,
     }
 }
 
+/// The only `T`, first in either its archetype's column or the sparse set of `T`s.
 pub struct Single<'world_borrow, T> {
-    borrow: RwLockReadGuard<'world_borrow, Vec<T>>,
+    borrow: SingleBorrow<'world_borrow, T>,
+}
+
+enum SingleBorrow<'world_borrow, T> {
+    Column(RwLockReadGuard<'world_borrow, Vec<T>>),
+    Sparse(RwLockReadGuard<'world_borrow, SparseSet<T>>),
 }
 
 impl<'a, 'world_borrow, T: 'a> FetchItem<'a> for Single<'world_borrow, T> {
     type InnerItem = &'a T;
     fn inner(&'a mut self) -> Self::InnerItem {
-        &self.borrow[0]
+        match &self.borrow {
+            SingleBorrow::Column(column) => &column[0],
+            SingleBorrow::Sparse(set) => set.get_dense(0),
+        }
     }
 }
 
+/// Stamps the column or sparse set as changed when written to through `DerefMut`. Systems taking `&mut T` are handed
+/// the component through `inner()` with no way of telling whether they write, so that counts as a write.
 pub struct SingleMut<'world_borrow, T> {
-    borrow: RwLockWriteGuard<'world_borrow, Vec<T>>,
+    borrow: SingleBorrowMut<'world_borrow, T>,
+    tick: u64,
+}
+
+enum SingleBorrowMut<'world_borrow, T> {
+    Column(RwLockWriteGuard<'world_borrow, Vec<T>>, &'world_borrow ComponentStore),
+    Sparse(RwLockWriteGuard<'world_borrow, SparseSet<T>>),
+}
+
+impl<T> SingleMut<'_, T> {
+    fn get_mut(&mut self) -> &mut T {
+        match &mut self.borrow {
+            SingleBorrowMut::Column(column, store) => {
+                store.mark_changed(self.tick);
+                &mut column[0]
+            },
+            SingleBorrowMut::Sparse(set) => {
+                set.mark_changed(self.tick);
+                set.get_dense_mut(0)
+            },
+        }
+    }
 }
 
 impl<'a, 'world_borrow, T: 'a> FetchItem<'a> for SingleMut<'world_borrow, T> {
     type InnerItem = &'a mut T;
     fn inner(&'a mut self) -> Self::InnerItem {
-        &mut self.borrow[0]
+        self.get_mut()
     }
 }
 
-impl<'world_borrow, T: 'static> Fetch<'world_borrow> for &T {
-    type Item = Single<'world_borrow, T>;
+impl<T> std::ops::Deref for SingleMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.borrow {
+            SingleBorrowMut::Column(column, _) => &column[0],
+            SingleBorrowMut::Sparse(set) => set.get_dense(0),
+        }
+    }
+}
+
+impl<T> std::ops::DerefMut for SingleMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+/// A resource borrowed from the world, by a system taking `Res<T>` or through `World::resource()`. Stays read-locked
+/// until dropped.
+pub struct Res<'world_borrow, T> {
+    borrow: RwLockReadGuard<'world_borrow, T>,
+}
+
+impl<T> std::ops::Deref for Res<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.borrow
+    }
+}
+
+/// A resource borrowed for writing, by a system taking `ResMut<T>` or through `World::resource_mut()`.
+pub struct ResMut<'world_borrow, T> {
+    borrow: RwLockWriteGuard<'world_borrow, T>,
+}
+
+impl<T> std::ops::Deref for ResMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.borrow
+    }
+}
+
+impl<T> std::ops::DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.borrow
+    }
+}
+
+pub struct ResFetch<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+pub struct ResMutFetch<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+impl<'world_borrow, T: 'static + Send + Sync> Fetch<'world_borrow> for ResFetch<T> {
+    type Item = Option<Res<'world_borrow, T>>;
     fn fetch(world: &'world_borrow World) -> Result<Self::Item, FetchError> {
-        // The archetypes must be found here.
-        let type_id = TypeId::of::<T>();
-        for archetype in world.archetypes.iter() {
-            for (i, c) in archetype.components.iter().enumerate() {
-                if c.type_id == type_id {
-                    let borrow = archetype.get(i).try_read().unwrap();
-                    return Ok(Single { borrow });
-                }
-            }
+        let lock = world.resource_lock::<T>()
+                        .ok_or_else(|| FetchError::ResourceDoesNotExist(ResourceDoesNotExist::new::<T>()))?;
+        match lock.try_read() {
+            Ok(borrow) => Ok(Some(Res { borrow })),
+            Err(_) => Err(FetchError::ResourceAlreadyBorrowed(ResourceAlreadyBorrowed::new::<T>())),
+        }
+    }
+}
+
+impl<'world_borrow, T: 'static + Send + Sync> Fetch<'world_borrow> for ResMutFetch<T> {
+    type Item = Option<ResMut<'world_borrow, T>>;
+    fn fetch(world: &'world_borrow World) -> Result<Self::Item, FetchError> {
+        let lock = world.resource_lock::<T>()
+                        .ok_or_else(|| FetchError::ResourceDoesNotExist(ResourceDoesNotExist::new::<T>()))?;
+        match lock.try_write() {
+            Ok(borrow) => Ok(Some(ResMut { borrow })),
+            Err(_) => Err(FetchError::ResourceAlreadyBorrowed(ResourceAlreadyBorrowed::new::<T>())),
         }
+    }
+}
+
+impl<'a, 'world_borrow, T> FetchItem<'a> for Option<Res<'world_borrow, T>> {
+    type InnerItem = Res<'world_borrow, T>;
+    fn inner(&'a mut self) -> Self::InnerItem {
+        self.take().unwrap()
+    }
+}
+
+impl<'a, 'world_borrow, T> FetchItem<'a> for Option<ResMut<'world_borrow, T>> {
+    type InnerItem = ResMut<'world_borrow, T>;
+    fn inner(&'a mut self) -> Self::InnerItem {
+        self.take().unwrap()
+    }
+}
+
+/// A component yielded by a query for `&mut T`. Reading it changes nothing; the first write through it stamps its
+/// column as changed for `Changed<T>`.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    store: &'a ComponentStore,
+    tick: u64,
+}
+
+impl<T> std::ops::Deref for Mut<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> std::ops::DerefMut for Mut<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.store.mark_changed(self.tick);
+        self.value
+    }
+}
+
+/// Where the only entity with a `T` keeps it.
+enum SingleLocation<'world_borrow, T> {
+    /// An archetype, and the column of `T`s in it.
+    Column(&'world_borrow Archetype, usize),
+    Sparse(&'world_borrow RwLock<SparseSet<T>>),
+}
+
+/// Find the only `T`, in the archetypes and the sparse set of `T`s. Errors if there isn't exactly one.
+fn single_location<T: 'static>(world: &World) -> Result<SingleLocation<T>, FetchError> {
+    let mut found = None;
+    let mut count = 0;
+    for archetype in world.archetypes.iter() {
+        // Archetypes everything was despawned from still have their columns
+        if let Some(i) = archetype.component_index::<T>().filter(|_| !archetype.entities.is_empty()) {
+            count += archetype.entities.len();
+            found = Some(SingleLocation::Column(archetype, i));
+        }
+    }
+
+    if let Some(set) = world.sparse_set::<T>() {
+        let len = set.try_read().map_err(|_| already_borrowed::<T>())?.len();
+        if len > 0 {
+            count += len;
+            found = Some(SingleLocation::Sparse(set));
+        }
+    }
+
+    match found {
+        Some(found) if count == 1 => Ok(found),
+        _ => Err(FetchError::NotSingle(NotSingle::new::<T>(count))),
+    }
+}
 
-        Err(FetchError::ComponentDoesNotExist(
-            ComponentDoesNotExist::new::<T>(),
-        ))
+fn already_borrowed<T: 'static>() -> FetchError {
+    FetchError::ComponentAlreadyBorrowed(ComponentAlreadyBorrowed::new::<T>())
+}
+
+impl<'world_borrow, T: 'static> Fetch<'world_borrow> for &T {
+    type Item = Single<'world_borrow, T>;
+    fn fetch(world: &'world_borrow World) -> Result<Self::Item, FetchError> {
+        let borrow = match single_location::<T>(world)? {
+            SingleLocation::Column(archetype, i) => {
+                SingleBorrow::Column(archetype.get(i).try_read().map_err(|_| already_borrowed::<T>())?)
+            },
+            SingleLocation::Sparse(set) => SingleBorrow::Sparse(set.try_read().map_err(|_| already_borrowed::<T>())?),
+        };
+        Ok(Single { borrow })
     }
 }
 
 impl<'world_borrow, T: 'static> Fetch<'world_borrow> for &mut T {
     type Item = SingleMut<'world_borrow, T>;
     fn fetch(world: &'world_borrow World) -> Result<Self::Item, FetchError> {
-        // The archetypes must be found here.
-        let type_id = TypeId::of::<T>();
-        for archetype in world.archetypes.iter() {
-            for (i, c) in archetype.components.iter().enumerate() {
-                if c.type_id == type_id {
-                    let borrow = archetype.get(i).try_write().unwrap();
-                    return Ok(SingleMut { borrow });
-                }
-            }
-        }
-
-        Err(FetchError::ComponentDoesNotExist(
-            ComponentDoesNotExist::new::<T>(),
-        ))
+        let borrow = match single_location::<T>(world)? {
+            SingleLocation::Column(archetype, i) => SingleBorrowMut::Column(
+                archetype.get(i).try_write().map_err(|_| already_borrowed::<T>())?,
+                &archetype.components[i],
+            ),
+            SingleLocation::Sparse(set) => {
+                SingleBorrowMut::Sparse(set.try_write().map_err(|_| already_borrowed::<T>())?)
+            },
+        };
+        Ok(SingleMut {
+            borrow,
+            tick: world.change_tick(),
+        })
     }
 }
 
@@ -184,25 +476,44 @@ impl<'a, T: 'static> QueryParameterFetch<'a> for ReadQueryParameterFetch<T> {
 /// In the future this can (hopefully) be made better with Generic Associated Types.
 pub trait QueryParameter {
     type QueryParameterFetch: for<'a> QueryParameterFetch<'a>;
-    fn matches_archetype(archetype: &Archetype) -> bool;
+    /// A `'static` stand-in for the parameter, so queries can be told apart by `TypeId` whatever they borrow for.
+    type Signature: 'static;
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool;
+    /// Record the columns the parameter borrows. Filters borrow none.
+    fn access(_access: &mut Access) {}
+    /// Clear the `rows` of a matched archetype the parameter leaves out, for filters on components kept outside of
+    /// archetypes. `None` is every row.
+    fn filter_rows(_world: &World, _archetype: usize, _rows: &mut Option<Vec<bool>>) -> Result<(), FetchError> {
+        Ok(())
+    }
 }
 
 impl<T: 'static> QueryParameter for &T {
     type QueryParameterFetch = ReadQueryParameterFetch<T>;
+    type Signature = &'static T;
 
-    fn matches_archetype(archetype: &Archetype) -> bool {
+    fn matches_archetype(_world: &World, archetype: &Archetype) -> bool {
         let type_id = TypeId::of::<T>();
         archetype.components.iter().any(|c| c.type_id == type_id)
     }
+
+    fn access(access: &mut Access) {
+        access.read::<T>();
+    }
 }
 
 impl<T: 'static> QueryParameter for &mut T {
     type QueryParameterFetch = WriteQueryParameterFetch<T>;
+    type Signature = &'static mut T;
 
-    fn matches_archetype(archetype: &Archetype) -> bool {
+    fn matches_archetype(_world: &World, archetype: &Archetype) -> bool {
         let type_id = TypeId::of::<T>();
         archetype.components.iter().any(|c| c.type_id == type_id)
     }
+
+    fn access(access: &mut Access) {
+        access.write::<T>();
+    }
 }
 
 /// This is used to test if an entity has a component, without actually
@@ -233,18 +544,414 @@ impl<'a, 'world_borrow> QueryIter<'a> for bool {
 
 impl<T: 'static> QueryParameter for Has<T> {
     type QueryParameterFetch = Self;
+    type Signature = Self;
+
+    fn matches_archetype(_world: &World, _archetype: &Archetype) -> bool {
+        true
+    }
+}
+
+/// Filters a query down to entities that have a `T`, without borrowing any `T`s.
+/// Yields `()` for every entity, so it can be ignored with `_` when iterating.
+/// ## Example
+/// ```ignore
+/// let mut query = world.query::<(&mut Velocity, With<Player>)>().unwrap();
+/// for (velocity, _) in query.iter() {
+///     // only players
+/// }
+/// ```
+pub struct With<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+/// Filters a query down to entities that don't have a `T`.
+/// Yields `()` for every entity, like `With`.
+/// ## Example
+/// ```ignore
+/// let mut query = world.query::<(&mut Position, &Velocity, Without<Frozen>)>().unwrap();
+/// for (position, velocity, _) in query.iter() {
+///     // nothing frozen
+/// }
+/// ```
+pub struct Without<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+/// What filters fetch for an archetype: only how many entities it has, so queries of nothing but filters still end.
+pub struct FilterFetch {
+    len: usize,
+}
+
+impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for With<T> {
+    type FetchItem = FilterFetch;
+    fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
+        Ok(FilterFetch { len: world.archetypes[archetype].entities.len() })
+    }
+}
+
+impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for Without<T> {
+    type FetchItem = FilterFetch;
+    fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
+        Ok(FilterFetch { len: world.archetypes[archetype].entities.len() })
+    }
+}
+
+impl<'a> QueryIter<'a> for FilterFetch {
+    type Iter = std::iter::Take<std::iter::Repeat<()>>;
+    fn iter(&'a mut self) -> Self::Iter {
+        std::iter::repeat(()).take(self.len)
+    }
+}
+
+/// Clear the rows of the archetype's entities `keep` says no to.
+fn retain_rows(archetype: &Archetype, rows: &mut Option<Vec<bool>>, keep: impl Fn(EntityId) -> bool) {
+    let rows = rows.get_or_insert_with(|| vec![true; archetype.entities.len()]);
+    for (row, &entity) in rows.iter_mut().zip(archetype.entities.iter()) {
+        *row = *row && keep(entity);
+    }
+}
+
+/// The sparse set of `T`s, if `T` is sparse.
+fn read_sparse<T: 'static>(world: &World) -> Result<Option<RwLockReadGuard<SparseSet<T>>>, FetchError> {
+    world.sparse_set::<T>()
+         .map(|set| set.try_read().map_err(|_| already_borrowed::<T>()))
+         .transpose()
+}
+
+impl<T: 'static> QueryParameter for With<T> {
+    type QueryParameterFetch = Self;
+    type Signature = Self;
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        archetype.component_index::<T>().is_some() || world.is_sparse::<T>()
+    }
+
+    fn filter_rows(world: &World, archetype: usize, rows: &mut Option<Vec<bool>>) -> Result<(), FetchError> {
+        let archetype = &world.archetypes[archetype];
+        if archetype.component_index::<T>().is_none() {
+            if let Some(set) = read_sparse::<T>(world)? {
+                retain_rows(archetype, rows, |entity| set.contains(entity));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: 'static> QueryParameter for Without<T> {
+    type QueryParameterFetch = Self;
+    type Signature = Self;
+
+    fn matches_archetype(_world: &World, archetype: &Archetype) -> bool {
+        archetype.component_index::<T>().is_none()
+    }
+
+    fn filter_rows(world: &World, archetype: usize, rows: &mut Option<Vec<bool>>) -> Result<(), FetchError> {
+        if let Some(set) = read_sparse::<T>(world)? {
+            retain_rows(&world.archetypes[archetype], rows, |entity| !set.contains(entity));
+        }
+        Ok(())
+    }
+}
+
+/// Filters a query down to archetypes whose `T`s were added to since the world's last change tick.
+/// Like `With`, it yields `()` for every entity.
+/// ## Example
+/// ```ignore
+/// let mut query = world.query::<(&mut Collider, &Mesh, Added<Mesh>)>().unwrap();
+/// for (collider, mesh, _) in query.iter() {
+///     // only meshes spawned or added since last frame
+/// }
+/// ```
+pub struct Added<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+/// Filters a query down to archetypes whose `T`s were added to or written to since the world's last change tick.
+/// Like `With`, it yields `()` for every entity.
+/// ## Example
+/// ```ignore
+/// let mut query = world.query::<(&Transform3, &MeshHandle, Changed<Transform3>)>().unwrap();
+/// for (transform, mesh, _) in query.iter() {
+///     // rebuild only what moved
+/// }
+/// ```
+pub struct Changed<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+/// An empty `FilterFetch` for archetypes whose column of `T` is older than the world's last change tick, which ends
+/// the zipped iterators of the whole archetype straight away. Archetypes without a column are matched for a sparse `T`,
+/// and left to `tick_filter_rows()`.
+fn tick_filter_fetch<T: 'static>(world: &World, archetype: usize, tick: fn(&ComponentStore) -> u64) -> FilterFetch {
+    let archetype = &world.archetypes[archetype];
+    let type_id = TypeId::of::<T>();
 
-    fn matches_archetype(_archetype: &Archetype) -> bool {
+    let touched = archetype.components
+                           .iter()
+                           .find(|c| c.type_id == type_id)
+                           .map_or(true, |c| tick(c) >= world.last_change_tick());
+
+    FilterFetch { len: if touched { archetype.entities.len() } else { 0 } }
+}
+
+impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for Added<T> {
+    type FetchItem = FilterFetch;
+    fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
+        Ok(tick_filter_fetch::<T>(world, archetype, ComponentStore::added_tick))
+    }
+}
+
+impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for Changed<T> {
+    type FetchItem = FilterFetch;
+    fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
+        Ok(tick_filter_fetch::<T>(world, archetype, ComponentStore::changed_tick))
+    }
+}
+
+/// For an archetype without a column of `T`, keep the rows of entities with a sparse `T`, or none if the sparse set
+/// is older than the world's last change tick.
+fn tick_filter_rows<T: 'static>(
+    world: &World,
+    archetype: usize,
+    rows: &mut Option<Vec<bool>>,
+    tick: fn(&SparseSet<T>) -> u64,
+) -> Result<(), FetchError> {
+    let archetype = &world.archetypes[archetype];
+    if archetype.component_index::<T>().is_none() {
+        if let Some(set) = read_sparse::<T>(world)? {
+            let touched = tick(&set) >= world.last_change_tick();
+            retain_rows(archetype, rows, |entity| touched && set.contains(entity));
+        }
+    }
+    Ok(())
+}
+
+impl<T: 'static> QueryParameter for Added<T> {
+    type QueryParameterFetch = Self;
+    type Signature = Self;
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        archetype.component_index::<T>().is_some() || world.is_sparse::<T>()
+    }
+
+    fn filter_rows(world: &World, archetype: usize, rows: &mut Option<Vec<bool>>) -> Result<(), FetchError> {
+        tick_filter_rows(world, archetype, rows, SparseSet::<T>::added_tick)
+    }
+}
+
+impl<T: 'static> QueryParameter for Changed<T> {
+    type QueryParameterFetch = Self;
+    type Signature = Self;
+
+    fn matches_archetype(world: &World, archetype: &Archetype) -> bool {
+        archetype.component_index::<T>().is_some() || world.is_sparse::<T>()
+    }
+
+    fn filter_rows(world: &World, archetype: usize, rows: &mut Option<Vec<bool>>) -> Result<(), FetchError> {
+        tick_filter_rows(world, archetype, rows, SparseSet::<T>::changed_tick)
+    }
+}
+
+/// `Option<&T>` and `Option<&mut T>` match every archetype, yielding `None` for entities without a `T`.
+/// ## Example
+/// ```ignore
+/// let mut query = world.query::<(&Name, Option<&Health>)>().unwrap();
+/// for (name, health) in query.iter() {
+///     if let Some(health) = health {
+///         // ...
+///     }
+/// }
+/// ```
+impl<Q: QueryParameter> QueryParameter for Option<Q> {
+    type QueryParameterFetch = OptionalQueryParameterFetch<Q>;
+    type Signature = Option<Q::Signature>;
+
+    fn matches_archetype(_world: &World, _archetype: &Archetype) -> bool {
         true
     }
+
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+}
+
+pub struct OptionalQueryParameterFetch<Q> {
+    phantom: std::marker::PhantomData<Q>,
+}
+
+/// The column of an optional parameter if the archetype has it, and how many entities the archetype has either way.
+pub struct OptionalFetch<F> {
+    column: Option<F>,
+    len: usize,
+}
+
+impl<'world_borrow, Q: QueryParameter> QueryParameterFetch<'world_borrow> for OptionalQueryParameterFetch<Q> {
+    type FetchItem = OptionalFetch<QueryParameterItem<'world_borrow, Q>>;
+    fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
+        let column = if Q::matches_archetype(world, &world.archetypes[archetype]) {
+            Some(<Q::QueryParameterFetch as QueryParameterFetch<'world_borrow>>::fetch(world, archetype)?)
+        } else {
+            None
+        };
+
+        Ok(OptionalFetch {
+            column,
+            len: world.archetypes[archetype].entities.len(),
+        })
+    }
+}
+
+impl<'a, F: QueryIter<'a>> QueryIter<'a> for OptionalFetch<F> {
+    type Iter = OptionalIter<F::Iter>;
+    fn iter(&'a mut self) -> Self::Iter {
+        OptionalIter {
+            inner: self.column.as_mut().map(|column| column.iter()),
+            remaining: self.len,
+        }
+    }
+}
+
+/// Wraps every item of the column's iterator in `Some`, or yields `None`s for a missing column.
+pub struct OptionalIter<I> {
+    inner: Option<I>,
+    remaining: usize,
+}
+
+impl<I: Iterator> Iterator for OptionalIter<I> {
+    type Item = Option<I::Item>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            Some(inner) => inner.next().map(Some),
+            None if self.remaining > 0 => {
+                self.remaining -= 1;
+                Some(None)
+            },
+            None => None,
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            Some(inner) => inner.size_hint(),
+            None => (self.remaining, Some(self.remaining)),
+        }
+    }
+}
+
+impl<I: SplitAt> SplitAt for OptionalIter<I> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let first = row.min(self.remaining);
+        let (inner, rest) = match self.inner {
+            Some(inner) => {
+                let (inner, rest) = inner.split_at(row);
+                (Some(inner), Some(rest))
+            },
+            None => (None, None),
+        };
+
+        (
+            OptionalIter { inner, remaining: first },
+            OptionalIter { inner: rest, remaining: self.remaining - first },
+        )
+    }
+}
+
+/// `Entity` yields the handle of every entity alongside its components, to despawn it or add to it after iterating.
+/// ## Example
+/// ```ignore
+/// let mut query = world.query::<(Entity, &Health)>().unwrap();
+/// let dead: Vec<Entity> = query.iter().filter(|(_, health)| health.0 == 0).map(|(entity, _)| entity).collect();
+/// drop(query);
+///
+/// for entity in dead {
+///     world.despawn(entity).unwrap();
+/// }
+/// ```
+impl QueryParameter for Entity {
+    type QueryParameterFetch = EntityQueryParameterFetch;
+    type Signature = Self;
+
+    fn matches_archetype(_world: &World, _archetype: &Archetype) -> bool {
+        true
+    }
+}
+
+pub struct EntityQueryParameterFetch;
+
+/// The entities of an archetype, with the world's entity info to look their generations up in.
+pub struct EntityFetch<'world_borrow> {
+    ids: &'world_borrow [EntityId],
+    entities: &'world_borrow [EntityInfo],
+}
+
+impl<'world_borrow> QueryParameterFetch<'world_borrow> for EntityQueryParameterFetch {
+    type FetchItem = EntityFetch<'world_borrow>;
+    fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
+        Ok(EntityFetch {
+            ids: &world.archetypes[archetype].entities,
+            entities: &world.entities,
+        })
+    }
+}
+
+impl<'a, 'world_borrow> QueryIter<'a> for EntityFetch<'world_borrow> {
+    type Iter = EntityIter<'world_borrow>;
+    fn iter(&'a mut self) -> Self::Iter {
+        EntityIter {
+            ids: self.ids.iter(),
+            entities: self.entities,
+        }
+    }
+}
+
+pub struct EntityIter<'world_borrow> {
+    ids: std::slice::Iter<'world_borrow, EntityId>,
+    entities: &'world_borrow [EntityInfo],
+}
+
+impl Iterator for EntityIter<'_> {
+    type Item = Entity;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ids.next().map(|&index| Entity {
+            index,
+            generation: self.entities[index as usize].generation,
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+}
+
+impl SplitAt for EntityIter<'_> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let (ids, rest) = self.ids.split_at(row);
+        (
+            EntityIter { ids, entities: self.entities },
+            EntityIter { ids: rest, entities: self.entities },
+        )
+    }
 }
 
 pub struct WriteQueryParameterFetch<T> {
     phantom: std::marker::PhantomData<T>,
 }
 
+/// A column borrowed for writing, along with what's needed to stamp it once it's written to.
+pub struct WriteFetch<'world_borrow, T> {
+    borrow: RwLockWriteGuard<'world_borrow, Vec<T>>,
+    store: &'world_borrow ComponentStore,
+    tick: u64,
+}
+
 impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for WriteQueryParameterFetch<T> {
-    type FetchItem = RwLockWriteGuard<'world_borrow, Vec<T>>;
+    type FetchItem = WriteFetch<'world_borrow, T>;
     fn fetch(world: &'world_borrow World, archetype: usize) -> Result<Self::FetchItem, FetchError> {
         let archetype = &world.archetypes[archetype];
         let type_id = TypeId::of::<T>();
@@ -253,8 +960,12 @@ impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for WriteQuer
                              .iter()
                              .position(|c| c.type_id == type_id)
                              .unwrap();
-        if let Ok(write_guard) = archetype.get(index).try_write() {
-            Ok(write_guard)
+        if let Ok(borrow) = archetype.get(index).try_write() {
+            Ok(WriteFetch {
+                borrow,
+                store: &archetype.components[index],
+                tick: world.change_tick(),
+            })
         } else {
             Err(FetchError::ComponentAlreadyBorrowed(
                 ComponentAlreadyBorrowed::new::<T>(),
@@ -263,30 +974,73 @@ impl<'world_borrow, T: 'static> QueryParameterFetch<'world_borrow> for WriteQuer
     }
 }
 
-pub trait QueryParameters: for<'a> QueryParameterFetch<'a> {}
+pub trait QueryParameters: for<'a> QueryParameterFetch<'a> {
+    fn access(access: &mut Access);
+}
+
+/// Archetypes a query signature matched, out of the first `archetype_count` of the world's archetypes.
+#[derive(Default)]
+pub(super) struct CachedQuery {
+    archetype_count: usize,
+    archetype_indices: Vec<usize>,
+}
+
+/// Indices of the archetypes `matches`, remembered under `signature` so only archetypes created since the last call
+/// have to be checked. Anything that removes or reorders archetypes, or registers a sparse type, must clear
+/// `World::query_cache`.
+fn matching_archetypes(world: &World, signature: TypeId, matches: fn(&World, &Archetype) -> bool) -> Vec<usize> {
+    if let Some(cached) = world.query_cache.read().unwrap().get(&signature) {
+        if cached.archetype_count == world.archetypes.len() {
+            return cached.archetype_indices.clone();
+        }
+    }
+
+    let mut cache = world.query_cache.write().unwrap();
+    let cached = cache.entry(signature).or_default();
+    for (i, archetype) in world.archetypes.iter().enumerate().skip(cached.archetype_count) {
+        if matches(world, archetype) {
+            cached.archetype_indices.push(i);
+        }
+    }
+    cached.archetype_count = world.archetypes.len();
+
+    cached.archetype_indices.clone()
+}
 
 macro_rules! query_parameters_impl {
     ($($name: ident),*) => {
         impl<'world_borrow, $($name: QueryParameter,)*> QueryParameters
             for ($($name,)*)
-        {}
+        {
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+        }
 
         impl<'world_borrow, $($name: QueryParameter,)*> QueryParameterFetch<'world_borrow> for ($($name,)*) {
+            /// For every matched archetype, the rows the filters kept and what each parameter fetched.
             #[allow(unused_parens)]
-            type FetchItem = Vec<($(<$name::QueryParameterFetch as QueryParameterFetch<'world_borrow>>::FetchItem),*)>;
+            type FetchItem = Vec<(
+                Option<Vec<bool>>,
+                ($(<$name::QueryParameterFetch as QueryParameterFetch<'world_borrow>>::FetchItem),*),
+            )>;
 
             fn fetch(world: &'world_borrow World, _archetype: usize) -> Result<Self::FetchItem, FetchError> {
-                let mut archetype_indices = Vec::new();
-                for (i, archetype) in world.archetypes.iter().enumerate() {
-                    let matches = $($name::matches_archetype(&archetype))&&*;
-                    if matches {
-                        archetype_indices.push(i);
-                    }
-                }
+                // Signatures leave lifetimes out, so every borrow of the same parameters shares one entry
+                let archetype_indices = matching_archetypes(
+                    world,
+                    TypeId::of::<($($name::Signature,)*)>(),
+                    |world, archetype| $($name::matches_archetype(world, archetype))&&*,
+                );
 
                 let mut result = Vec::with_capacity(archetype_indices.len());
                 for index in archetype_indices {
-                    result.push(($(<$name::QueryParameterFetch as QueryParameterFetch<'world_borrow>>::fetch(world, index)?),*));
+                    let mut rows = None;
+                    $($name::filter_rows(world, index, &mut rows)?;)*
+                    result.push((
+                        rows,
+                        ($(<$name::QueryParameterFetch as QueryParameterFetch<'world_borrow>>::fetch(world, index)?),*),
+                    ));
                 }
 
                 Ok(result)
@@ -322,39 +1076,67 @@ impl<'a, 'world_borrow, T: 'static> QueryIter<'a> for RwLockReadGuard<'world_bor
     }
 }
 
-impl<'a, 'world_borrow, T: 'static> QueryIter<'a> for RwLockWriteGuard<'world_borrow, Vec<T>> {
-    type Iter = std::slice::IterMut<'a, T>;
+impl<'a, 'world_borrow, T: 'static> QueryIter<'a> for WriteFetch<'world_borrow, T> {
+    type Iter = MutIter<'a, T>;
     fn iter(&'a mut self) -> Self::Iter {
-        <[T]>::iter_mut(self)
+        MutIter {
+            inner: self.borrow.iter_mut(),
+            store: self.store,
+            tick: self.tick,
+        }
     }
 }
 
-impl<'a, 'world_borrow, A: QueryParameter> QueryIter<'a> for Query<'world_borrow, (A,)>
-where
-    QueryParameterItem<'world_borrow, A>: QueryIter<'a>,
-{
-    type Iter = ChainedIterator<QueryParameterIter<'a, 'world_borrow, A>>;
-    fn iter(&'a mut self) -> Self::Iter {
-        ChainedIterator::new(self.data.iter_mut().map(|v| v.iter()).collect())
+pub struct MutIter<'a, T> {
+    inner: std::slice::IterMut<'a, T>,
+    store: &'a ComponentStore,
+    tick: u64,
+}
+
+impl<'a, T> Iterator for MutIter<'a, T> {
+    type Item = Mut<'a, T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (store, tick) = (self.store, self.tick);
+        self.inner.next().map(|value| Mut { value, store, tick })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
 }
 
-type QueryParameterIter<'a, 'world_borrow, A> = <QueryParameterItem<'world_borrow, A> as QueryIter<'a>>::Iter;
-impl<'a, 'world_borrow, A: QueryParameter, B: QueryParameter> QueryIter<'a> for Query<'world_borrow, (A, B)>
+impl<T> SplitAt for MutIter<'_, T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let slice = self.inner.into_slice();
+        let row = row.min(slice.len());
+        let (first, rest) = slice.split_at_mut(row);
+        (
+            MutIter { inner: first.iter_mut(), store: self.store, tick: self.tick },
+            MutIter { inner: rest.iter_mut(), store: self.store, tick: self.tick },
+        )
+    }
+}
+
+impl<'a, 'world_borrow, A: QueryParameter> QueryIter<'a> for Query<'world_borrow, (A,)>
 where
     QueryParameterItem<'world_borrow, A>: QueryIter<'a>,
-    QueryParameterItem<'world_borrow, B>: QueryIter<'a>,
 {
-    type Iter = ChainedIterator<Zip<QueryParameterIter<'a, 'world_borrow, A>, QueryParameterIter<'a, 'world_borrow, B>>>;
+    type Iter = ChainedIterator<Masked<'a, QueryParameterIter<'a, 'world_borrow, A>>>;
     fn iter(&'a mut self) -> Self::Iter {
         ChainedIterator::new(
-            self.data.iter_mut()
-                     .map(|(a, b)| a.iter().zip(b.iter()))
-                     .collect(),
+            self.data
+                .iter_mut()
+                .map(|&mut (ref rows, ref mut v)| Masked::new(v.iter(), rows.as_deref()))
+                .collect(),
         )
     }
 }
 
+type QueryParameterIter<'a, 'world_borrow, A> = <QueryParameterItem<'world_borrow, A> as QueryIter<'a>>::Iter;
+
 macro_rules! query_iter {
     ($zip_type: ident, $($name: ident),*) => {
         #[allow(non_snake_case)]
@@ -362,12 +1144,14 @@ macro_rules! query_iter {
         where
             $(QueryParameterItem<'world_borrow, $name>: QueryIter<'a>),*
              {
-            type Iter = ChainedIterator<$zip_type<$(QueryParameterIter<'a, 'world_borrow, $name>,)*>>;
+            type Iter = ChainedIterator<Masked<'a, $zip_type<$(QueryParameterIter<'a, 'world_borrow, $name>,)*>>>;
             fn iter(&'a mut self) -> Self::Iter {
                 ChainedIterator::new(
                     self.data
                     .iter_mut()
-                    .map(|($(ref mut $name,)*)| $zip_type::new($($name.iter(),)*))
+                    .map(|&mut (ref rows, ($(ref mut $name,)*))| {
+                        Masked::new($zip_type::new($($name.iter(),)*), rows.as_deref())
+                    })
                     .collect()
                 )
             }
@@ -375,6 +1159,7 @@ macro_rules! query_iter {
     }
 }
 
+query_iter! {Zip2, A, B}
 query_iter! {Zip3, A, B, C}
 query_iter! {Zip4, A, B, C, D}
 query_iter! {Zip5, A, B, C, D, E}
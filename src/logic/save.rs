@@ -0,0 +1,296 @@
+//! Saving the world to RON and loading it back.
+//!
+//! Only components whose types are registered in the world's `ComponentRegistry` resource are saved, each under the
+//! name it was registered with, so renaming or moving a type doesn't break old saves. Components that aren't
+//! registered are left out, and entities with none that are aren't saved at all.
+//!
+//! Every saved entity gets an id local to the save, numbered in the order the entities were spawned in, and the save
+//! lists them in that order. Entities are spawned anew on load and get new handles, so components holding an `Entity`
+//! (like `Parent` and `Children`) implement `MapEntities` and are registered with `register_mapped()`: their handles
+//! are written as ids and pointed at the loaded entities again. Handles of entities that weren't saved load as ones
+//! that are never alive.
+//! ## Example
+//! ```ignore
+//! let mut registry = ComponentRegistry::new();
+//! registry.register::<Position>("position");
+//! registry.register::<Health>("health");
+//! registry.register_mapped::<Parent>("parent");
+//! registry.register_mapped::<Children>("children");
+//! world.insert_resource(registry);
+//!
+//! world.save(std::fs::File::create("save.ron")?)?;
+//!
+//! let mut loaded = World::new();
+//! loaded.insert_resource(registry_again);
+//! loaded.load(std::fs::File::open("save.ron")?)?;
+//! ```
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::builder::EntityBuilder;
+use super::world::*;
+
+/// Bumped whenever the layout of `SaveFile` changes.
+pub const SAVE_VERSION: u32 = 1;
+
+/// What handles of entities that weren't saved are written and loaded as. Out of range of any world's entities, so
+/// it's never alive.
+const UNSAVED: Entity = Entity { index: EntityId::MAX, generation: 0 };
+
+#[derive(thiserror::Error, Debug)]
+pub enum SaveError {
+    #[error("the world has no `ComponentRegistry` resource")]
+    NoRegistry,
+    #[error("[{0}] is borrowed mutably, so it can't be saved")]
+    AlreadyBorrowed(&'static str),
+    #[error("save is version {0}, only version {} can be loaded", SAVE_VERSION)]
+    Version(u32),
+    #[error("no component is registered as `{0}`")]
+    UnknownComponent(String),
+    #[error("`{name}`: {message}")]
+    Component {
+        name: String,
+        message: String,
+    },
+    #[error("{0}")]
+    Format(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    /// In order of their ids.
+    entities: Vec<SavedEntity>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEntity {
+    /// What saved `Entity` handles refer to the entity by, as `Entity { index: id, generation: 0 }`.
+    id: EntityId,
+    /// Every registered component written out as RON, by registered name.
+    components: BTreeMap<String, String>,
+}
+
+/// Components holding `Entity` handles, which have to be swapped for saved ids when saved and for the loaded
+/// entities' handles when loaded. See `ComponentRegistry::register_mapped()`.
+pub trait MapEntities {
+    /// Replace every handle held with `map(handle)`.
+    fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity);
+}
+
+struct Registration {
+    name: &'static str,
+    /// Every component in one of an archetype's columns as RON, in entity order, with any handles passed through the
+    /// map first.
+    save_column: fn(&Archetype, usize, &mut dyn FnMut(Entity) -> Entity) -> Result<Vec<String>, SaveError>,
+    load: fn(&str, &mut EntityBuilder) -> Result<(), SaveError>,
+    /// Point the handles of a loaded entity's component, still saved ids, at the loaded entities.
+    map_loaded: Option<fn(&mut World, Entity, &mut dyn FnMut(Entity) -> Entity)>,
+}
+
+/// Component types that can be saved, and the names they're saved under.
+#[derive(Default)]
+pub struct ComponentRegistry {
+    registrations: HashMap<TypeId, Registration>,
+    by_name: HashMap<&'static str, TypeId>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save `T`s under `name`, replacing whatever was registered under that name and for that type before.
+    pub fn register<T: Serialize + DeserializeOwned + Send + Sync + 'static>(&mut self, name: &'static str) {
+        self.insert::<T>(Registration {
+            name,
+            save_column: save_column::<T>,
+            load: load_component::<T>,
+            map_loaded: None,
+        });
+    }
+
+    /// `register()` for components holding `Entity` handles, which are kept pointing at the same entities through a
+    /// save and load.
+    pub fn register_mapped<T>(&mut self, name: &'static str)
+    where
+        T: MapEntities + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        self.insert::<T>(Registration {
+            name,
+            save_column: save_mapped_column::<T>,
+            load: load_component::<T>,
+            map_loaded: Some(map_loaded::<T>),
+        });
+    }
+
+    /// Registering a type under a name can collide with two earlier registrations, one of the type and one of the
+    /// name, and both go.
+    fn insert<T: 'static>(&mut self, registration: Registration) {
+        let type_id = TypeId::of::<T>();
+        if let Some(previous) = self.registrations.remove(&type_id) {
+            self.by_name.remove(previous.name);
+        }
+        if let Some(previous) = self.by_name.remove(registration.name) {
+            self.registrations.remove(&previous);
+        }
+
+        self.by_name.insert(registration.name, type_id);
+        self.registrations.insert(type_id, registration);
+    }
+
+    pub fn is_registered<T: 'static>(&self) -> bool {
+        self.registrations.contains_key(&TypeId::of::<T>())
+    }
+}
+
+fn to_ron<T: Serialize>(t: &T) -> Result<String, SaveError> {
+    ron::to_string(t).map_err(|e| SaveError::Format(e.to_string()))
+}
+
+fn save_column<T: Serialize + 'static>(
+    archetype: &Archetype,
+    column: usize,
+    _map: &mut dyn FnMut(Entity) -> Entity,
+) -> Result<Vec<String>, SaveError> {
+    let column = archetype.get::<T>(column)
+                          .try_read()
+                          .map_err(|_| SaveError::AlreadyBorrowed(std::any::type_name::<T>()))?;
+    column.iter().map(to_ron).collect()
+}
+
+fn save_mapped_column<T: MapEntities + Clone + Serialize + 'static>(
+    archetype: &Archetype,
+    column: usize,
+    map: &mut dyn FnMut(Entity) -> Entity,
+) -> Result<Vec<String>, SaveError> {
+    let column = archetype.get::<T>(column)
+                          .try_read()
+                          .map_err(|_| SaveError::AlreadyBorrowed(std::any::type_name::<T>()))?;
+    column.iter()
+          .map(|t| {
+              let mut t = t.clone();
+              t.map_entities(map);
+              to_ron(&t)
+          })
+          .collect()
+}
+
+fn load_component<T: DeserializeOwned + Send + Sync + 'static>(
+    text: &str,
+    builder: &mut EntityBuilder,
+) -> Result<(), SaveError> {
+    let t = ron::from_str::<T>(text).map_err(|e| SaveError::Format(e.to_string()))?;
+    builder.add(t);
+    Ok(())
+}
+
+fn map_loaded<T: MapEntities + 'static>(world: &mut World, entity: Entity, map: &mut dyn FnMut(Entity) -> Entity) {
+    if let Ok(t) = world.get_component_mut::<T>(entity) {
+        t.map_entities(map);
+    }
+}
+
+impl World {
+    /// Write every entity's registered components as RON.
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), SaveError> {
+        let registry = self.resource::<ComponentRegistry>().map_err(|_| SaveError::NoRegistry)?;
+
+        // Ids go by entity index, so they come out the same however entities are spread over archetypes
+        let saved_archetype = |archetype: &&Archetype| {
+            archetype.components.iter().any(|c| registry.registrations.contains_key(&c.type_id))
+        };
+        let mut saved: Vec<EntityId> = self.archetypes
+                                           .iter()
+                                           .filter(saved_archetype)
+                                           .flat_map(|archetype| archetype.entities.iter().copied())
+                                           .collect();
+        saved.sort_unstable();
+        let ids: HashMap<EntityId, EntityId> = saved.iter()
+                                                    .enumerate()
+                                                    .map(|(id, &index)| (index, id as EntityId))
+                                                    .collect();
+
+        let mut map = |entity: Entity| match ids.get(&entity.index) {
+            Some(&id) if self.entity(entity).is_ok() => Entity { index: id, generation: 0 },
+            _ => UNSAVED,
+        };
+
+        let mut entities: Vec<SavedEntity> = (0..saved.len())
+            .map(|id| SavedEntity { id: id as EntityId, components: BTreeMap::new() })
+            .collect();
+        for archetype in self.archetypes.iter() {
+            for (column, c) in archetype.components.iter().enumerate() {
+                let registration = match registry.registrations.get(&c.type_id) {
+                    Some(registration) => registration,
+                    None => continue,
+                };
+
+                let components = (registration.save_column)(archetype, column, &mut map)?;
+                for (index, component) in archetype.entities.iter().zip(components) {
+                    entities[ids[index] as usize].components.insert(registration.name.to_owned(), component);
+                }
+            }
+        }
+
+        let file = SaveFile {
+            version: SAVE_VERSION,
+            entities,
+        };
+        ron::ser::to_writer_pretty(writer, &file, ron::ser::PrettyConfig::default())
+            .map_err(|e| SaveError::Format(e.to_string()))
+    }
+
+    /// Spawn every entity of a save written by `save()`, returning their new handles in the order they were saved.
+    /// Nothing is spawned if any of it fails to load.
+    pub fn load<R: Read>(&mut self, reader: R) -> Result<Vec<Entity>, SaveError> {
+        let file: SaveFile = ron::de::from_reader(reader).map_err(|e| SaveError::Format(e.to_string()))?;
+        if file.version != SAVE_VERSION {
+            return Err(SaveError::Version(file.version));
+        }
+
+        let registry = self.resource::<ComponentRegistry>().map_err(|_| SaveError::NoRegistry)?;
+        let mut builders = Vec::with_capacity(file.entities.len());
+        let mut ids = Vec::with_capacity(file.entities.len());
+        // What has to be mapped once every entity has its new handle, which needs the registry to be let go of
+        let mut mapped = Vec::new();
+
+        for entity in file.entities {
+            let mut builder = EntityBuilder::new();
+            for (name, text) in entity.components {
+                let registration = match registry.by_name.get(name.as_str()) {
+                    Some(type_id) => &registry.registrations[type_id],
+                    None => return Err(SaveError::UnknownComponent(name)),
+                };
+
+                (registration.load)(&text, &mut builder).map_err(|e| SaveError::Component {
+                    name,
+                    message: e.to_string(),
+                })?;
+                if let Some(map) = registration.map_loaded {
+                    mapped.push((builders.len(), map));
+                }
+            }
+            builders.push(builder);
+            ids.push(entity.id);
+        }
+        drop(registry);
+
+        let spawned: Vec<Entity> = builders.iter_mut().map(|builder| builder.spawn(self)).collect();
+        let by_id: HashMap<EntityId, Entity> = ids.into_iter().zip(spawned.iter().copied()).collect();
+
+        let mut map = |saved: Entity| by_id.get(&saved.index).copied().unwrap_or(UNSAVED);
+        for (i, map_loaded) in mapped {
+            map_loaded(self, spawned[i], &mut map);
+        }
+
+        Ok(spawned)
+    }
+}
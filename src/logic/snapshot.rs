@@ -0,0 +1,131 @@
+//! Copies of the world's entities and components that it can be rolled back to, for rollback netcode and undo.
+//!
+//! Every component type in the world has to be registered with `register_snapshot()` first, which needs it to be
+//! `Clone`. That includes sparse components, though a sparse set that's empty when the snapshot is taken doesn't need
+//! to be, and is emptied again on restoring. Resources and change ticks aren't part of a snapshot: restoring one
+//! counts as changing every column.
+//! ## Example
+//! ```ignore
+//! world.register_snapshot::<Position>();
+//! world.register_snapshot::<Velocity>();
+//!
+//! let confirmed = world.snapshot().unwrap();
+//! // ...predict a few frames ahead, then the server disagrees
+//! world.restore(&confirmed).unwrap();
+//! ```
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use super::sparse::{SparseSet, SparseStorage};
+use super::world::*;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SnapshotError {
+    #[error("[{0}] is not registered with `World::register_snapshot()`")]
+    NotRegistered(&'static str),
+}
+
+/// The entities of a `World` and all of their components at one point in time.
+pub struct Snapshot {
+    archetypes: Vec<Archetype>,
+    bundle_id_to_archetype: HashMap<u64, usize>,
+    entities: Vec<EntityInfo>,
+    free_entities: Vec<EntityId>,
+    sparse: HashMap<TypeId, Box<dyn SparseStorage>>,
+}
+
+fn clone_store<T: Clone + Send + Sync + 'static>(store: &ComponentStore) -> ComponentStore {
+    store.clone_as::<T>()
+}
+
+fn clone_sparse<T: Clone + Send + Sync + 'static>(set: &dyn SparseStorage) -> Box<dyn SparseStorage> {
+    let set = set.as_any().downcast_ref::<RwLock<SparseSet<T>>>().unwrap();
+    Box::new(RwLock::new(set.read().unwrap().clone()))
+}
+
+/// Copies of the non-empty sparse sets, which all have to be registered.
+fn clone_sparse_sets(
+    sets: &HashMap<TypeId, Box<dyn SparseStorage>>,
+    cloners: &HashMap<TypeId, fn(&dyn SparseStorage) -> Box<dyn SparseStorage>>,
+) -> Result<HashMap<TypeId, Box<dyn SparseStorage>>, SnapshotError> {
+    let mut cloned = HashMap::new();
+    for (&type_id, set) in sets.iter().filter(|(_, set)| !set.is_empty()) {
+        let clone = cloners.get(&type_id).ok_or(SnapshotError::NotRegistered(set.type_name()))?;
+        cloned.insert(type_id, clone(set.as_ref()));
+    }
+    Ok(cloned)
+}
+
+fn clone_archetypes(
+    archetypes: &[Archetype],
+    cloners: &HashMap<TypeId, fn(&ComponentStore) -> ComponentStore>,
+) -> Result<Vec<Archetype>, SnapshotError> {
+    let mut cloned = Vec::with_capacity(archetypes.len());
+    for archetype in archetypes {
+        let mut copy = Archetype::new();
+        copy.entities = archetype.entities.clone();
+
+        for c in archetype.components.iter() {
+            let clone = cloners.get(&c.type_id).ok_or(SnapshotError::NotRegistered(c.type_name))?;
+            copy.components.push(clone(c));
+        }
+        cloned.push(copy);
+    }
+    Ok(cloned)
+}
+
+impl World {
+    /// Let `T`s be copied into snapshots.
+    pub fn register_snapshot<T: Clone + Send + Sync + 'static>(&mut self) {
+        self.cloners.insert(TypeId::of::<T>(), clone_store::<T>);
+        self.sparse_cloners.insert(TypeId::of::<T>(), clone_sparse::<T>);
+    }
+
+    /// Copy every entity and component. Takes `&mut self` to flush reserved entities first, and so that no column is
+    /// borrowed while it's copied.
+    pub fn snapshot(&mut self) -> Result<Snapshot, SnapshotError> {
+        self.flush();
+
+        Ok(Snapshot {
+            archetypes: clone_archetypes(&self.archetypes, &self.cloners)?,
+            bundle_id_to_archetype: self.bundle_id_to_archetype.clone(),
+            entities: self.entities.clone(),
+            free_entities: self.free_entities.clone(),
+            sparse: clone_sparse_sets(&self.sparse, &self.sparse_cloners)?,
+        })
+    }
+
+    /// Put every entity and component back the way they were when `snapshot` was taken. The snapshot is left as it
+    /// is, so it can be restored again.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        self.flush();
+
+        let mut archetypes = clone_archetypes(&snapshot.archetypes, &self.cloners)?;
+        let mut sparse = clone_sparse_sets(&snapshot.sparse, &self.sparse_cloners)?;
+        let tick = self.change_tick();
+        for archetype in archetypes.iter_mut() {
+            for c in archetype.components.iter() {
+                c.mark_changed(tick);
+            }
+        }
+
+        self.archetypes = archetypes;
+        self.query_cache.get_mut().unwrap().clear();
+        self.bundle_id_to_archetype = snapshot.bundle_id_to_archetype.clone();
+        self.entities = snapshot.entities.clone();
+        self.free_entities = snapshot.free_entities.clone();
+        for (type_id, set) in self.sparse.iter_mut() {
+            match sparse.remove(type_id) {
+                Some(restored) => *set = restored,
+                None => set.clear(),
+            }
+        }
+        self.sparse.extend(sparse);
+        self.reset_free_cursor();
+        self.rebuild_name_index();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+//! Sparse-set storage for component types that come and go often, like status effects or "selected" tags.
+//!
+//! A component of a type registered with `World::register_sparse()` that's added with `add_component()` doesn't
+//! move its entity to another archetype; it goes into a set of its own, indexed by entity. Adding and removing is
+//! then cheap, at the cost of being slower to query. Filters check the set for every entity of an archetype without a
+//! `T` column: `With<T>`, `Without<T>`, `Added<T>` and `Changed<T>`, along with `get_single()`, see sparse `T`s.
+//! Queries don't borrow them as `&T` or `&mut T`, nor do they count for `Has<T>`; they're read through
+//! `get_component()`, `get_component_mut()` or `World::iter_sparse()`. Change ticks are kept for the whole set.
+//! Snapshots cover sparse sets as well, saves only cover archetypes.
+//! ## Example
+//! ```ignore
+//! world.register_sparse::<Stunned>();
+//! world.add_component(goblin, Stunned(2.0)).unwrap();
+//!
+//! world.iter_sparse::<Stunned, _>(|entity, stunned| {
+//!     // ...
+//! });
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use super::world::*;
+
+/// Components packed together, with a map from entity to where in the pack each is.
+/// Change ticks cover the whole set, as they do a whole column of an archetype.
+#[derive(Clone)]
+pub struct SparseSet<T> {
+    dense: Vec<T>,
+    entities: Vec<EntityId>,
+    indices: HashMap<EntityId, usize>,
+    added_tick: u64,
+    changed_tick: u64,
+}
+
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        Self {
+            dense: Vec::new(),
+            entities: Vec::new(),
+            indices: HashMap::new(),
+            added_tick: 0,
+            changed_tick: 0,
+        }
+    }
+
+    pub fn added_tick(&self) -> u64 {
+        self.added_tick
+    }
+
+    pub fn changed_tick(&self) -> u64 {
+        self.changed_tick
+    }
+
+    pub(super) fn mark_added(&mut self, tick: u64) {
+        self.added_tick = tick;
+        self.changed_tick = tick;
+    }
+
+    pub(super) fn mark_changed(&mut self, tick: u64) {
+        self.changed_tick = tick;
+    }
+
+    /// Returns the component the entity had before, if any.
+    pub fn insert(&mut self, entity: EntityId, t: T) -> Option<T> {
+        match self.indices.get(&entity) {
+            Some(&index) => Some(std::mem::replace(&mut self.dense[index], t)),
+            None => {
+                self.indices.insert(entity, self.dense.len());
+                self.dense.push(t);
+                self.entities.push(entity);
+                None
+            },
+        }
+    }
+
+    pub fn remove(&mut self, entity: EntityId) -> Option<T> {
+        let index = self.indices.remove(&entity)?;
+
+        // The last component takes the removed one's place
+        self.entities.swap_remove(index);
+        if let Some(&moved) = self.entities.get(index) {
+            self.indices.insert(moved, index);
+        }
+        Some(self.dense.swap_remove(index))
+    }
+
+    pub fn contains(&self, entity: EntityId) -> bool {
+        self.indices.contains_key(&entity)
+    }
+
+    pub(super) fn index_of(&self, entity: EntityId) -> Option<usize> {
+        self.indices.get(&entity).copied()
+    }
+
+    pub(super) fn get_dense(&self, index: usize) -> &T {
+        &self.dense[index]
+    }
+
+    pub(super) fn get_dense_mut(&mut self, index: usize) -> &mut T {
+        &mut self.dense[index]
+    }
+
+    pub fn get_mut(&mut self, entity: EntityId) -> Option<&mut T> {
+        let index = *self.indices.get(&entity)?;
+        Some(&mut self.dense[index])
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.dense.shrink_to_fit();
+        self.entities.shrink_to_fit();
+        self.indices.shrink_to_fit();
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    /// Every entity with a component and its component, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.entities.iter().copied().zip(self.dense.iter())
+    }
+}
+
+/// A `SparseSet` of any type.
+pub(super) trait SparseStorage: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn type_name(&self) -> &'static str;
+    fn is_empty(&self) -> bool;
+    fn clear(&mut self);
+    /// Returns whether the entity had a component.
+    fn remove_entity(&mut self, entity: EntityId) -> bool;
+    fn shrink_to_fit(&mut self);
+}
+
+impl<T: Send + Sync + 'static> SparseStorage for RwLock<SparseSet<T>> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.read().unwrap().is_empty()
+    }
+
+    fn clear(&mut self) {
+        *self.get_mut().unwrap() = SparseSet::new();
+    }
+
+    fn remove_entity(&mut self, entity: EntityId) -> bool {
+        self.get_mut().unwrap().remove(entity).is_some()
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.get_mut().unwrap().shrink_to_fit();
+    }
+}
+
+impl World {
+    /// Store `T`s added with `add_component()` in a sparse set instead of the entity's archetype. Any `T`s already in
+    /// archetypes stay there.
+    pub fn register_sparse<T: Send + Sync + 'static>(&mut self) {
+        self.sparse
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RwLock::new(SparseSet::<T>::new())));
+        // `With<T>` and the change filters match archetypes without a `T` column once `T` is sparse
+        self.query_cache.get_mut().unwrap().clear();
+    }
+
+    pub fn is_sparse<T: 'static>(&self) -> bool {
+        self.sparse.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Call `f` with every entity that has a sparse `T`, and its `T`.
+    pub fn iter_sparse<T: 'static, F: FnMut(Entity, &T)>(&self, mut f: F) {
+        if let Some(set) = self.sparse_set::<T>() {
+            for (index, t) in set.read().unwrap().iter() {
+                let generation = self.entities[index as usize].generation;
+                f(Entity { index, generation }, t);
+            }
+        }
+    }
+
+    pub(super) fn sparse_set<T: 'static>(&self) -> Option<&RwLock<SparseSet<T>>> {
+        self.sparse
+            .get(&TypeId::of::<T>())
+            .map(|set| set.as_any().downcast_ref::<RwLock<SparseSet<T>>>().unwrap())
+    }
+
+    pub(super) fn sparse_set_mut<T: 'static>(&mut self) -> Option<&mut SparseSet<T>> {
+        self.sparse
+            .get_mut(&TypeId::of::<T>())
+            .map(|set| set.as_any_mut().downcast_mut::<RwLock<SparseSet<T>>>().unwrap().get_mut().unwrap())
+    }
+
+    /// Take the entity out of every sparse set, returning the types it had.
+    pub(super) fn remove_sparse_entity(&mut self, entity: EntityId) -> Vec<TypeId> {
+        self.sparse
+            .iter_mut()
+            .filter_map(|(&type_id, set)| if set.remove_entity(entity) { Some(type_id) } else { None })
+            .collect()
+    }
+}
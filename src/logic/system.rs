@@ -1,10 +1,35 @@
+//! Systems are plain functions over queries, and a `Schedule` runs them in stages every frame.
+//!
+//! Stages run in the order `PreUpdate`, `Update`, `PostUpdate`, `Render`. Within a stage, a system runs after every
+//! system added before it that borrows the same components, and alongside the ones that don't. Systems get the world
+//! immutably, so anything they write goes through `&mut T` queries and `ResMut<T>` resources. Exclusive systems get it
+//! mutably instead and run on their own, for changes like adding components. Entities reserved by systems are added
+//! before each exclusive system and at the end of each stage.
+//! ## Example
+//! ```ignore
+//! let mut schedule = Schedule::new();
+//! schedule.add_system(Stage::Update, "movement", movement);
+//! schedule.add_system(Stage::PostUpdate, "despawn dead", despawn_dead);
+//! schedule.add_exclusive_system(Stage::PostUpdate, "propagate transforms", hierarchy::propagate_transforms);
+//!
+//! // Every frame
+//! world.insert_resource(FrameTime(last_frame.elapsed()));
+//! if let Err(e) = schedule.run(&mut world) {
+//!     LOGGER().a.error(format!("{}", e).as_str());
+//! }
+//! ```
+
 use super::world::*;
 use super::query::*;
 use super::error::*;
+use crate::system::thread;
+
+use std::ops::Range;
+use std::time::Duration;
 
 /// A function that can be run as a system by pulling in queries from the world.
 /// ## Example
-/// ```
+/// ```ignore
 /// struct A {}
 /// struct B {}
 /// struct C {}
@@ -25,10 +50,13 @@ use super::error::*;
 /// ```
 pub trait System<P> {
     fn run(self, world: &World) -> Result<(), FetchError>;
+    /// Everything the system's parameters borrow.
+    fn access() -> Access;
 }
 
 pub trait IntoSystem<P> {
     fn system(self) -> Box<dyn FnMut(&World) -> Result<(), FetchError> + Send + Sync>;
+    fn access(&self) -> Access;
 }
 
 pub trait OuterSystem {
@@ -47,6 +75,10 @@ impl<P, S: System<P> + Sync + Send + 'static + Copy> IntoSystem<P> for S {
     fn system(self) -> Box<dyn FnMut(&World) -> Result<(), FetchError> + Send + Sync> {
         Box::new(move |world| self.run(world))
     }
+
+    fn access(&self) -> Access {
+        <S as System<P>>::access()
+    }
 }
 
 macro_rules! system_impl {
@@ -62,6 +94,13 @@ macro_rules! system_impl {
                 self($($name.inner(),)*);
                 Ok(())
             }
+
+            #[allow(unused_mut)]
+            fn access() -> Access {
+                let mut access = Access::new();
+                $($name::access(&mut access);)*
+                access
+            }
         }
     };
 }
@@ -77,4 +116,228 @@ system_impl! {A, B, C, D, E, F, G}
 system_impl! {A, B, C, D, E, F, G, H}
 system_impl! {A, B, C, D, E, F, G, H, I}
 system_impl! {A, B, C, D, E, F, G, H, I, J, K}
-system_impl! {A, B, C, D, E, F, G, H, I, J, K, L}
\ No newline at end of file
+system_impl! {A, B, C, D, E, F, G, H, I, J, K, L}
+type BoxedSystem = Box<dyn FnMut(&World) -> Result<(), FetchError> + Send + Sync>;
+type BoxedExclusiveSystem = Box<dyn FnMut(&mut World) + Send + Sync>;
+
+/// How long the last frame took, for systems that advance things over time. Inserted as a resource every frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTime(pub Duration);
+
+/// When in a frame a system runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// Input and anything else the rest of the frame reads.
+    PreUpdate,
+    /// Game logic.
+    Update,
+    /// Reacting to what `Update` did, e.g. cleaning up despawned entities.
+    PostUpdate,
+    /// Extracting the world for drawing.
+    Render,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::PreUpdate, Stage::Update, Stage::PostUpdate, Stage::Render];
+}
+
+enum SystemKind {
+    Parallel(BoxedSystem),
+    Exclusive(BoxedExclusiveSystem),
+}
+
+struct ScheduledSystem {
+    label: &'static str,
+    access: Access,
+    system: SystemKind,
+}
+
+/// Run systems one after another, carrying on past failures and returning the first one.
+fn run_in_order(systems: &mut [ScheduledSystem], world: &World) -> Result<(), SystemError> {
+    let mut result = Ok(());
+    for s in systems.iter_mut() {
+        let SystemKind::Parallel(system) = &mut s.system else {
+            unreachable!("exclusive systems are batched on their own");
+        };
+        if let Err(error) = system(world) {
+            if result.is_ok() {
+                result = Err(SystemError { label: s.label, error });
+            }
+        }
+    }
+    result
+}
+
+/// A stage's systems, and the runs of consecutive systems that don't conflict with each other.
+#[derive(Default)]
+struct StageSystems {
+    systems: Vec<ScheduledSystem>,
+    batches: Vec<Range<usize>>,
+}
+
+impl StageSystems {
+    /// Batches only ever hold consecutive systems, so a system still runs after every system added before it that
+    /// it conflicts with. Exclusive systems are always a batch of their own.
+    fn rebuild_batches(&mut self) {
+        self.batches.clear();
+
+        let mut start = 0;
+        let mut access = Access::new();
+        let mut previous_exclusive = false;
+        for (i, s) in self.systems.iter().enumerate() {
+            let exclusive = matches!(s.system, SystemKind::Exclusive(_));
+            if i > start && (exclusive || previous_exclusive || s.access.conflicts_with(&access)) {
+                self.batches.push(start..i);
+                start = i;
+                access = Access::new();
+            }
+            access.extend(&s.access);
+            previous_exclusive = exclusive;
+        }
+        if start < self.systems.len() {
+            self.batches.push(start..self.systems.len());
+        }
+    }
+}
+
+/// A system failed to fetch its parameters, so it didn't run.
+#[derive(Debug)]
+pub struct SystemError {
+    pub label: &'static str,
+    pub error: FetchError,
+}
+
+impl std::fmt::Display for SystemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "system \"{}\" could not run: {}", self.label, self.error)
+    }
+}
+
+impl std::error::Error for SystemError {}
+
+/// Labeled systems grouped into `Stage`s.
+///
+/// What each system borrows is known from its parameter types, so consecutive systems that don't write to anything
+/// the others read or write run at the same time, spread across worker threads. Conflicting systems are also kept
+/// from fetching at the same time by the columns' and resources' `RwLock`s, so this only decides what can run at
+/// once, not what's safe.
+pub struct Schedule {
+    stages: [StageSystems; 4],
+    threads: usize,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self {
+            stages: Default::default(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// At most how many systems run at once, on the threads of `system::thread::workers()` and the calling one. 1 runs
+    /// everything on the calling thread.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Add a system to the end of a stage. Labels are only used to name systems in errors and to remove them, so they
+    /// don't have to be unique, but `remove_system` removes every system with the label.
+    pub fn add_system<P>(&mut self, stage: Stage, label: &'static str, system: impl IntoSystem<P>) -> &mut Self {
+        let stage = &mut self.stages[stage as usize];
+        stage.systems.push(ScheduledSystem {
+            label,
+            access: system.access(),
+            system: SystemKind::Parallel(system.system()),
+        });
+        stage.rebuild_batches();
+        self
+    }
+
+    /// Add a system that gets the world mutably to the end of a stage, for what queries can't do, like adding
+    /// components or despawning. It runs on its own, after every system added before it.
+    pub fn add_exclusive_system(
+        &mut self,
+        stage: Stage,
+        label: &'static str,
+        system: impl FnMut(&mut World) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let stage = &mut self.stages[stage as usize];
+        stage.systems.push(ScheduledSystem {
+            label,
+            access: Access::new(),
+            system: SystemKind::Exclusive(Box::new(system)),
+        });
+        stage.rebuild_batches();
+        self
+    }
+
+    /// Returns whether any system had the label.
+    pub fn remove_system(&mut self, label: &'static str) -> bool {
+        let mut removed = false;
+        for stage in self.stages.iter_mut() {
+            let len = stage.systems.len();
+            stage.systems.retain(|s| s.label != label);
+            if stage.systems.len() != len {
+                stage.rebuild_batches();
+                removed = true;
+            }
+        }
+        removed
+    }
+
+    /// Labels of a stage's systems in the order they're added.
+    pub fn labels(&self, stage: Stage) -> impl Iterator<Item = &'static str> + '_ {
+        self.stages[stage as usize].systems.iter().map(|s| s.label)
+    }
+
+    /// Run every system of one stage, a batch of non-conflicting systems at a time. A system that fails to fetch
+    /// doesn't stop the others, only the first error is returned.
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World) -> Result<(), SystemError> {
+        let stage = &mut self.stages[stage as usize];
+        let mut result = Ok(());
+
+        for batch in stage.batches.iter() {
+            let systems = &mut stage.systems[batch.clone()];
+
+            let batch_result = if let SystemKind::Exclusive(system) = &mut systems[0].system {
+                // So it sees the entities the systems before it reserved
+                world.flush();
+                system(world);
+                Ok(())
+            } else if systems.len() == 1 || self.threads == 1 {
+                run_in_order(systems, world)
+            } else {
+                let world = &*world;
+                let per_thread = (systems.len() + self.threads - 1) / self.threads;
+                let mut results: Vec<Result<(), SystemError>> = Vec::new();
+                results.resize_with((systems.len() + per_thread - 1) / per_thread, || Ok(()));
+
+                thread::workers().scope(|scope| {
+                    for (chunk, result) in systems.chunks_mut(per_thread).zip(results.iter_mut()) {
+                        scope.execute(move || *result = run_in_order(chunk, world));
+                    }
+                });
+                results.into_iter().find(Result::is_err).unwrap_or(Ok(()))
+            };
+
+            if result.is_ok() {
+                result = batch_result;
+            }
+        }
+        world.flush();
+
+        result
+    }
+
+    /// Run every stage in order.
+    pub fn run(&mut self, world: &mut World) -> Result<(), SystemError> {
+        let mut result = Ok(());
+        for stage in Stage::ALL {
+            let stage_result = self.run_stage(stage, world);
+            if result.is_ok() {
+                result = stage_result;
+            }
+        }
+        result
+    }
+}
@@ -1,8 +1,9 @@
 //! The ECS hierarchy used can be described by
 //! 
-//! ```
+//! ```text
 //! World
 //! ├ // * Various entity metadata ...
+//! ├ Resources, one of each type and belonging to no entity
 //! └ Vec<Archetype>
 //!       ├ components: Vec<ComponentStore>
 //!       │                 ├ TypeId
@@ -15,6 +16,10 @@
 //! `ComponentColumn` is just a `Vec` of component traits. The diagram below should make it easier to understand
 //! why I choose to call it a column.
 //! 
+//! Each column also remembers the world tick its components were last added to and last written at, which is what
+//! the `Added<T>` and `Changed<T>` query filters compare against. Ticks are per column, not per entity, so a write
+//! to one entity marks its whole archetype's column as changed.
+//! 
 //! This is largely a traditional data-oriented designed ECS, but with archetypes.
 //! Entities sharing identical components belong to the same `Archetype`.
 //! 
@@ -23,7 +28,7 @@
 //! for **n** components. This is perfect for my use case.
 //! 
 //! ## Non-archetypal ECS
-//! ```
+//! ```text
 //!         world.create_entity()
 //!             .with(Position)─┐
 //!             .with(Sprite);──│─────┐
@@ -43,7 +48,7 @@
 //!         └──────────┴──────────┴────────┘
 //! ```
 //! ## Archetypal ECS
-//! ```
+//! ```text
 //!         world.insert_entity(
 //!             (Position, Sprite)
 //!         );    │          │
@@ -65,8 +70,15 @@
 use std::any::{Any, TypeId};
 use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::hash::{Hash, Hasher};
-use std::sync::RwLock;
+use std::sync::{RwLock, RwLockReadGuard};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
 
+use super::builder::EntityBuilder;
+use super::entity_ref::{EntityMut, EntityRef};
+use super::name::Name;
+use super::sparse::{SparseSet, SparseStorage};
 use super::query::*;
 use super::error::*;
 
@@ -80,6 +92,7 @@ trait ComponentColumn: Sync + Send {
     fn swap_remove(&mut self, index: EntityId);
     fn migrate(&mut self, entity_index: EntityId, other_archetype: &mut dyn ComponentColumn);
     fn new_empty_column(&self) -> Box<dyn ComponentColumn + Send + Sync>;
+    fn shrink_to_fit(&mut self);
 }
 
 impl<T: Sync + Send + 'static> ComponentColumn for RwLock<Vec<T>> {
@@ -108,6 +121,22 @@ impl<T: Sync + Send + 'static> ComponentColumn for RwLock<Vec<T>> {
     fn new_empty_column(&self) -> Box<dyn ComponentColumn + Send + Sync> {
         Box::new(RwLock::new(Vec::<T>::new()))
     }
+
+    fn shrink_to_fit(&mut self) {
+        self.get_mut().unwrap().shrink_to_fit();
+    }
+}
+
+/// Columns of components that can be copied, for snapshots. Not part of `ComponentColumn` since most components
+/// aren't `Clone`, so it's only reachable for types registered with `World::register_snapshot()`.
+trait CloneColumn {
+    fn clone_column(&self) -> Box<dyn ComponentColumn + Send + Sync>;
+}
+
+impl<T: Clone + Sync + Send + 'static> CloneColumn for RwLock<Vec<T>> {
+    fn clone_column(&self) -> Box<dyn ComponentColumn + Send + Sync> {
+        Box::new(RwLock::new(self.read().unwrap().clone()))
+    }
 }
 
 /// TODO: This can be made unchecked in the future iif there's confidence in everything else.
@@ -121,14 +150,22 @@ fn component_column_to_mut<T: 'static>(c: &mut dyn ComponentColumn) -> &mut Vec<
 
 pub struct ComponentStore {
     pub type_id: TypeId,
+    pub type_name: &'static str,
     data: Box<dyn ComponentColumn + Send + Sync>,
+    /// World tick at which an entity last gained this component.
+    added_tick: AtomicU64,
+    /// World tick at which the column was last added to or borrowed mutably.
+    changed_tick: AtomicU64,
 }
 
 impl ComponentStore {
     pub fn new<T: 'static + Send + Sync>() -> Self {
         Self {
             type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
             data: Box::new(RwLock::new(Vec::<T>::new())),
+            added_tick: AtomicU64::new(0),
+            changed_tick: AtomicU64::new(0),
         }
     }
 
@@ -136,9 +173,43 @@ impl ComponentStore {
     pub fn new_same_type(&self) -> Self {
         Self {
             type_id: self.type_id,
+            type_name: self.type_name,
             data: self.data.new_empty_column(),
+            added_tick: AtomicU64::new(0),
+            changed_tick: AtomicU64::new(0),
+        }
+    }
+
+    /// A copy of the store and everything in it. `T` must be the store's type.
+    pub(super) fn clone_as<T: Clone + Send + Sync + 'static>(&self) -> Self {
+        let column = self.data.as_any().downcast_ref::<RwLock<Vec<T>>>().unwrap();
+        Self {
+            type_id: self.type_id,
+            type_name: self.type_name,
+            data: column.clone_column(),
+            added_tick: AtomicU64::new(self.added_tick()),
+            changed_tick: AtomicU64::new(self.changed_tick()),
         }
     }
+
+    pub fn added_tick(&self) -> u64 {
+        self.added_tick.load(Ordering::Relaxed)
+    }
+
+    pub fn changed_tick(&self) -> u64 {
+        self.changed_tick.load(Ordering::Relaxed)
+    }
+
+    /// Takes `&self` so that queries borrowing the world immutably can still mark what they write to.
+    pub fn mark_changed(&self, tick: u64) {
+        self.changed_tick.store(tick, Ordering::Relaxed);
+    }
+
+    /// An added component also counts as a changed one.
+    pub fn mark_added(&mut self, tick: u64) {
+        *self.added_tick.get_mut() = tick;
+        *self.changed_tick.get_mut() = tick;
+    }
 }
 
 pub struct Archetype {
@@ -147,6 +218,10 @@ pub struct Archetype {
     /// A collection of `ComponentStore`, which is an abstracted away `Box<dyn ComponentColumn>` 
     /// with thread boundary transfer/sharing and an associated `TypeId`.
     pub components: Vec<ComponentStore>,
+    /// Archetype an entity moves to when a component of the type is added, and where that component's column is in it.
+    add_edges: HashMap<TypeId, (usize, usize)>,
+    /// Archetype an entity moves to when a component of the type is removed, and where the column being removed is.
+    remove_edges: HashMap<TypeId, (usize, usize)>,
 }
 
 impl Archetype {
@@ -154,6 +229,8 @@ impl Archetype {
         Self {
             entities: Vec::new(),
             components: Vec::new(),
+            add_edges: HashMap::new(),
+            remove_edges: HashMap::new(),
         }
     }
 
@@ -165,6 +242,20 @@ impl Archetype {
             .unwrap()
     }
 
+    /// Which of the archetype's columns holds `T`s, if any.
+    pub fn component_index<T: 'static>(&self) -> Option<usize> {
+        let type_id = TypeId::of::<T>();
+        self.components.iter().position(|c| c.type_id == type_id)
+    }
+
+    /// Give back memory the archetype's columns grew into but no longer use.
+    pub fn shrink_to_fit(&mut self) {
+        self.entities.shrink_to_fit();
+        for c in self.components.iter_mut() {
+            c.data.shrink_to_fit();
+        }
+    }
+
     pub fn remove_entity(&mut self, index: EntityId) -> EntityId {
         for c in self.components.iter_mut() {
             c.data.swap_remove(index)
@@ -180,15 +271,21 @@ impl Archetype {
         component_column_to_mut(&mut *self.components[component_index].data)
     }
 
-    pub fn replace_component<T: 'static>(&mut self, component_index: usize, index: EntityId, t: T) {
+    pub fn replace_component<T: 'static>(&mut self, component_index: usize, index: EntityId, t: T, tick: u64) {
         self.mutable_component_store(component_index)[index as usize] = t;
+        self.components[component_index].mark_changed(tick);
     }
 
     pub fn push<T: 'static>(&mut self, component_index: usize, t: T) {
         self.mutable_component_store(component_index).push(t)
     }
 
-    pub fn get_component_mut<T: 'static>(&mut self, index: EntityId) -> Result<&mut T, EntityMissingComponent> {
+    /// Marks the component's column as changed at `tick`, since there's no telling whether the caller writes to it.
+    pub fn get_component_mut<T: 'static>(
+        &mut self,
+        index: EntityId,
+        tick: u64,
+    ) -> Result<&mut T, EntityMissingComponent> {
         let type_id = TypeId::of::<T>();
         let mut component_index = None;
 
@@ -200,6 +297,7 @@ impl Archetype {
         }
 
         if let Some(component_index) = component_index {
+            self.components[component_index].mark_changed(tick);
             Ok(&mut self.mutable_component_store(component_index)[index as usize])
         } else {
             Err(EntityMissingComponent::new::<T>(index))
@@ -223,8 +321,14 @@ impl Archetype {
 /// Entity location in `World`.
 #[derive(Debug, Clone, Copy)]
 pub struct EntityLocation {
-    archetype_index: EntityId,
-    index_in_archetype: EntityId,
+    pub(super) archetype_index: EntityId,
+    pub(super) index_in_archetype: EntityId,
+}
+
+impl EntityLocation {
+    pub(super) fn new(archetype_index: EntityId, index_in_archetype: EntityId) -> Self {
+        Self { archetype_index, index_in_archetype }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -234,7 +338,7 @@ pub struct EntityInfo {
 }
 
 /// Handle to an `Entity` in `World`.
-#[derive(Debug, Clone, Copy, Hash, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Hash, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Entity {
     pub index: EntityId,
     pub generation: EntityId,
@@ -243,9 +347,31 @@ pub struct Entity {
 /// Holds all components and associates entities.
 pub struct World {
     pub archetypes: Vec<Archetype>,
-    bundle_id_to_archetype: HashMap<u64, usize>,
+    pub(super) bundle_id_to_archetype: HashMap<u64, usize>,
     pub entities: Vec<EntityInfo>,
-    free_entities: Vec<EntityId>,
+    pub(super) free_entities: Vec<EntityId>,
+    /// How many of `free_entities` haven't been handed out by `reserve_entity()` yet. Goes negative once they're all
+    /// gone, counting reservations past the end of `entities`.
+    free_cursor: AtomicI64,
+    /// Stamped on columns as they're written to.
+    change_tick: u64,
+    /// `Added<T>` and `Changed<T>` only match columns stamped at or after this tick.
+    last_change_tick: u64,
+    /// Entities that lost a component of each type this tick, either by `remove_component` or `despawn`.
+    removed: HashMap<TypeId, Vec<Entity>>,
+    /// One value of each type that isn't tied to any entity, each in a `RwLock` of its own so systems can borrow them
+    /// from `&World`.
+    resources: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    /// How to copy the columns of each type registered with `register_snapshot()`.
+    pub(super) cloners: HashMap<TypeId, fn(&ComponentStore) -> ComponentStore>,
+    /// How to copy the sparse sets of each type registered with `register_snapshot()`.
+    pub(super) sparse_cloners: HashMap<TypeId, fn(&dyn SparseStorage) -> Box<dyn SparseStorage>>,
+    /// Component types registered with `register_sparse()`, stored outside of archetypes.
+    pub(super) sparse: HashMap<TypeId, Box<dyn SparseStorage>>,
+    /// Archetypes each query signature matched, behind a lock since queries are made from `&World`.
+    pub(super) query_cache: RwLock<HashMap<TypeId, CachedQuery>>,
+    /// Entities with each `Name`, for `find_by_name()`.
+    pub(super) names: HashMap<String, Vec<Entity>>,
 }
 
 impl World {
@@ -255,18 +381,164 @@ impl World {
             bundle_id_to_archetype: HashMap::new(),
             entities: Vec::new(),
             free_entities: Vec::new(),
+            free_cursor: AtomicI64::new(0),
+            change_tick: 0,
+            last_change_tick: 0,
+            removed: HashMap::new(),
+            resources: HashMap::new(),
+            cloners: HashMap::new(),
+            sparse_cloners: HashMap::new(),
+            sparse: HashMap::new(),
+            query_cache: RwLock::new(HashMap::new()),
+            names: HashMap::new(),
+        }
+    }
+
+    /// The tick that writes are currently stamped with.
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick
+    }
+
+    /// The oldest tick `Added<T>` and `Changed<T>` still match.
+    pub fn last_change_tick(&self) -> u64 {
+        self.last_change_tick
+    }
+
+    /// Lets something that remembers when it last looked (like a system run every few frames) see every change
+    /// since then instead of only the ones made around the latest tick.
+    pub fn set_last_change_tick(&mut self, tick: u64) {
+        self.last_change_tick = tick;
+    }
+
+    /// Move on to the next tick. Call once per frame.
+    ///
+    /// Change filters match writes made during the current and the previous tick, so a system sees what was written
+    /// after it ran last frame. The flip side is that a write made before a system ran can be seen by it twice.
+    /// Removals are only kept for the tick they happened in, so nothing is cleaned up twice.
+    pub fn advance_tick(&mut self) -> u64 {
+        for entities in self.removed.values_mut() {
+            entities.clear();
         }
+        self.last_change_tick = self.change_tick;
+        self.change_tick += 1;
+        self.change_tick
     }
 
     /// Spawn an entity with components passed as tuple.
     /// ## Example
-    /// ```
+    /// ```ignore
     /// let mut world = World::new();
-    /// let entity = world.spawn((Name("Matsumoto"), Health(100)));
+    /// let entity = world.spawn((Name::new("Matsumoto"), Health(100)));
     /// ```
     pub fn spawn(&mut self, b: impl ComponentBundle) -> Entity {
-        let (index, generation) = if let Some(index) = self.free_entities.pop() {
+        let (index, generation) = self.allocate_entity();
+        let location = b.spawn_in_world(self, index);
+
+        self.entities[index as usize] = EntityInfo {
+            generation: generation,
+            location: location,
+        };
+
+        let entity = Entity {
+            index: index,
+            generation: generation,
+        };
+        // Most entities have no `Name`, and checking the archetype saves looking for one
+        if self.archetypes[location.archetype_index as usize].component_index::<Name>().is_some() {
+            self.index_name(entity);
+        }
+        entity
+    }
+
+    /// Spawn many entities of the same bundle type at once. The archetype is looked up once and its columns grown
+    /// once for the whole batch, instead of for every entity.
+    /// ## Example
+    /// ```ignore
+    /// let particles = world.spawn_batch((0..10_000).map(|i| (Position(i as f32, 0.0), Velocity(0.0, 1.0))));
+    /// ```
+    pub fn spawn_batch<B: ComponentBundle, I: IntoIterator<Item = B>>(&mut self, bundles: I) -> Vec<Entity> {
+        B::spawn_batch_in_world(bundles.into_iter(), self)
+    }
+
+    /// Hand out an entity from `&World`, so systems running in parallel can create entities and refer to them straight
+    /// away. It only becomes part of the world, with no components, at the next `flush()`; until then lookups act as if
+    /// it doesn't exist. Anything that changes the world through `&mut World` flushes first.
+    /// ## Example
+    /// ```ignore
+    /// // In a system
+    /// let projectile = world.reserve_entity();
+    ///
+    /// // Once systems are done
+    /// world.flush();
+    /// world.add_component(projectile, Velocity(0.0, 10.0)).unwrap();
+    /// ```
+    pub fn reserve_entity(&self) -> Entity {
+        let cursor = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
+        if cursor > 0 {
+            let index = self.free_entities[cursor as usize - 1];
+            let (generation, _) = self.entities[index as usize].generation.overflowing_add(1);
+
+            Entity { index, generation }
+        } else {
+            Entity {
+                index: (self.entities.len() as i64 - cursor) as EntityId,
+                generation: 0,
+            }
+        }
+    }
+
+    /// Add every reserved entity to the world, in the archetype of entities without components.
+    pub fn flush(&mut self) {
+        let cursor = *self.free_cursor.get_mut();
+        if cursor == self.free_entities.len() as i64 {
+            return;
+        }
+
+        let mut types: [(usize, TypeId); 0] = [];
+        let mut order: [usize; 0] = [];
+        let archetype_index = bundle_archetype(self, &mut types, &mut order, Archetype::new);
+
+        let reused: Vec<EntityId> = self.free_entities.drain(cursor.max(0) as usize..).collect();
+        let new_count = (-cursor).max(0) as usize;
+
+        for index in reused {
+            let (generation, _) = self.entities[index as usize].generation.overflowing_add(1);
+            let archetype = &mut self.archetypes[archetype_index];
+
+            self.entities[index as usize] = EntityInfo {
+                generation,
+                location: EntityLocation::new(archetype_index as EntityId, archetype.len() as EntityId),
+            };
+            archetype.entities.push(index);
+        }
+
+        for _ in 0..new_count {
+            let index = self.entities.len() as EntityId;
+            let archetype = &mut self.archetypes[archetype_index];
+
+            self.entities.push(EntityInfo {
+                generation: 0,
+                location: EntityLocation::new(archetype_index as EntityId, archetype.len() as EntityId),
+            });
+            archetype.entities.push(index);
+        }
+
+        self.reset_free_cursor();
+    }
+
+    /// Call after replacing `free_entities`, with nothing reserved.
+    pub(super) fn reset_free_cursor(&mut self) {
+        *self.free_cursor.get_mut() = self.free_entities.len() as i64;
+    }
+
+    /// Index and generation for a new entity, reusing a despawned one's slot if there is one. Its location is left for
+    /// the caller to fill in.
+    pub(super) fn allocate_entity(&mut self) -> (EntityId, EntityId) {
+        self.flush();
+
+        if let Some(index) = self.free_entities.pop() {
             let (generation, _) = self.entities[index as usize].generation.overflowing_add(1);
+            self.reset_free_cursor();
 
             (index, generation)
         } else {
@@ -283,18 +555,90 @@ impl World {
             debug_assert!(self.entities.len() <= EntityId::MAX as usize);
             
             ((self.entities.len() - 1) as EntityId, 0)
-        };
+        }
+    }
 
-        let location = b.spawn_in_world(self, index);
+    /// Entities that had a `T` removed or were despawned with one since the last `advance_tick`, in the order it
+    /// happened. Their handles are stale by the time they're read, so this is for cleaning up whatever lives outside
+    /// the world (GL buffers, physics bodies) keyed by them.
+    /// ## Example
+    /// ```ignore
+    /// for entity in world.removed::<RigidBody>() {
+    ///     physics.remove_body(*entity);
+    /// }
+    /// ```
+    pub fn removed<T: 'static>(&self) -> &[Entity] {
+        self.removed.get(&TypeId::of::<T>()).map_or(&[], |entities| entities.as_slice())
+    }
 
-        self.entities[index as usize] = EntityInfo {
-            generation: generation,
-            location: location,
-        };
+    fn record_removal(&mut self, type_id: TypeId, entity: Entity) {
+        self.removed.entry(type_id).or_insert_with(Vec::new).push(entity);
+    }
 
-        Entity {
-            index: index,
-            generation: generation,
+    /// Every live entity, in no particular order.
+    pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.archetypes
+            .iter()
+            .flat_map(|archetype| archetype.entities.iter())
+            .map(move |&index| Entity {
+                index: index,
+                generation: self.entities[index as usize].generation,
+            })
+    }
+
+    /// Number of live entities.
+    pub fn entity_count(&self) -> usize {
+        self.archetypes.iter().map(|archetype| archetype.entities.len()).sum()
+    }
+
+    /// Drop every archetype without entities and shrink what's left to fit, so that long sessions going through many
+    /// short-lived component combinations don't keep their memory around. Archetype indices change, so anything
+    /// holding on to one (like an `EntityLocation`) is only valid until the next call.
+    pub fn compact(&mut self) {
+        self.flush();
+
+        let mut kept = 0;
+        let remap: Vec<Option<usize>> = self.archetypes
+            .iter()
+            .map(|archetype| {
+                if archetype.entities.is_empty() {
+                    None
+                } else {
+                    kept += 1;
+                    Some(kept - 1)
+                }
+            })
+            .collect();
+
+        if kept < self.archetypes.len() {
+            // `retain` keeps order, so kept archetypes end up at the index they were remapped to
+            self.archetypes.retain(|archetype| !archetype.entities.is_empty());
+            self.query_cache.get_mut().unwrap().clear();
+
+            let remap_index = |index: &mut usize| match remap[*index] {
+                Some(new_index) => {
+                    *index = new_index;
+                    true
+                },
+                None => false,
+            };
+
+            self.bundle_id_to_archetype.retain(|_, index| remap_index(index));
+            for (archetype_index, archetype) in self.archetypes.iter_mut().enumerate() {
+                archetype.add_edges.retain(|_, (target, _)| remap_index(target));
+                archetype.remove_edges.retain(|_, (target, _)| remap_index(target));
+
+                for &entity in archetype.entities.iter() {
+                    self.entities[entity as usize].location.archetype_index = archetype_index as EntityId;
+                }
+            }
+        }
+
+        for archetype in self.archetypes.iter_mut() {
+            archetype.shrink_to_fit();
+        }
+        for set in self.sparse.values_mut() {
+            set.shrink_to_fit();
         }
     }
 
@@ -305,18 +649,135 @@ impl World {
 
     /// Remove an entity and all of its components from the world. Error if entity does not exist.
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
-        // Remove an entity, update swapped entity position if an entity was moved
+        self.flush();
         let entity_info = self.entities[entity.index as usize];
         if entity_info.generation == entity.generation {
-            self.entities[entity.index as usize].generation += 1;
-            let moved_entity = self.archetypes[entity_info.location.archetype_index as usize]
-                               .remove_entity(entity_info.location.index_in_archetype);
-            self.free_entities.push(entity.index);
+            self.despawn_at(entity, entity_info.location);
+            Ok(())
+        } else {
+            Err(NoSuchEntity)
+        }
+    }
 
-            // Update position of an entity that was moved
-            self.entities[moved_entity as usize].location = entity_info.location;
+    /// `despawn()` for an entity already known to be alive at `location`.
+    pub(super) fn despawn_at(&mut self, entity: Entity, location: EntityLocation) {
+        // Remove an entity, update swapped entity position if an entity was moved
+        self.unindex_name(entity);
+        let type_ids: Vec<TypeId> = self.archetypes[location.archetype_index as usize]
+                                        .components
+                                        .iter()
+                                        .map(|c| c.type_id)
+                                        .collect();
+        let sparse_type_ids = self.remove_sparse_entity(entity.index);
+        for type_id in type_ids.into_iter().chain(sparse_type_ids) {
+            self.record_removal(type_id, entity);
+        }
 
-            Ok(())
+        self.entities[entity.index as usize].generation += 1;
+        let moved_entity = self.archetypes[location.archetype_index as usize]
+                           .remove_entity(location.index_in_archetype);
+        self.free_entities.push(entity.index);
+        self.reset_free_cursor();
+
+        // Update position of an entity that was moved
+        self.entities[moved_entity as usize].location = location;
+    }
+
+    /// Get read access to a single component on an `Entity`. Only needs `&World`, so it works from systems too, but
+    /// it fails if the component's column is borrowed mutably at the time (by a query or another system).
+    /// ## Example
+    /// ```ignore
+    /// let health = world.get_component::<Health>(entity).unwrap();
+    /// println!("{}", health.0);
+    /// ```
+    pub fn get_component<T: 'static>(&self, entity: Entity) -> Result<ComponentRef<T>, ComponentError> {
+        let entity_info = match self.entities.get(entity.index as usize) {
+            Some(entity_info) => *entity_info,
+            // Reserved, but not flushed yet
+            None => return Err(ComponentError::NoSuchEntity(NoSuchEntity)),
+        };
+        if entity_info.generation != entity.generation {
+            return Err(ComponentError::NoSuchEntity(NoSuchEntity));
+        }
+
+        if let Some(set) = self.sparse_set::<T>() {
+            let set = set.try_read()
+                         .map_err(|_| ComponentError::AlreadyBorrowed(ComponentAlreadyBorrowed::new::<T>()))?;
+            if let Some(index) = set.index_of(entity.index) {
+                return Ok(ComponentRef::sparse(set, index));
+            }
+        }
+
+        let archetype = &self.archetypes[entity_info.location.archetype_index as usize];
+        let type_id = TypeId::of::<T>();
+        let component_index = archetype.components
+                                       .iter()
+                                       .position(|c| c.type_id == type_id)
+                                       .ok_or_else(|| {
+                                           ComponentError::EntityMissingComponent(
+                                               EntityMissingComponent::new::<T>(entity.index),
+                                           )
+                                       })?;
+
+        match archetype.get::<T>(component_index).try_read() {
+            Ok(borrow) => Ok(ComponentRef::new(borrow, entity_info.location.index_in_archetype as usize)),
+            Err(_) => Err(ComponentError::AlreadyBorrowed(ComponentAlreadyBorrowed::new::<T>())),
+        }
+    }
+
+    /// Whether a live `entity` has a `T`, without borrowing it. A sparse `T` can't be checked for while its set is
+    /// borrowed mutably, and counts as missing.
+    pub fn has_component<T: 'static>(&self, entity: Entity) -> bool {
+        let entity_info = match self.entities.get(entity.index as usize) {
+            Some(entity_info) => *entity_info,
+            // Reserved, but not flushed yet
+            None => return false,
+        };
+        if entity_info.generation != entity.generation {
+            return false;
+        }
+
+        let in_sparse_set = self.sparse_set::<T>()
+                                .and_then(|set| set.try_read().ok())
+                                .map_or(false, |set| set.contains(entity.index));
+        in_sparse_set || self.archetypes[entity_info.location.archetype_index as usize].component_index::<T>().is_some()
+    }
+
+    /// Look an entity up once to read several of its components.
+    /// ## Example
+    /// ```ignore
+    /// let player = world.entity(player).unwrap();
+    /// if let (Some(name), Some(health)) = (player.get::<Name>(), player.get::<Health>()) {
+    ///     println!("{} has {} health", name.0, health.0);
+    /// }
+    /// ```
+    pub fn entity(&self, entity: Entity) -> Result<EntityRef, NoSuchEntity> {
+        let entity_info = match self.entities.get(entity.index as usize) {
+            Some(entity_info) => *entity_info,
+            // Reserved, but not flushed yet
+            None => return Err(NoSuchEntity),
+        };
+        if entity_info.generation == entity.generation {
+            Ok(EntityRef::new(self, entity, entity_info.location))
+        } else {
+            Err(NoSuchEntity)
+        }
+    }
+
+    /// Look an entity up once to read, write, add and remove several of its components, or despawn it.
+    /// ## Example
+    /// ```ignore
+    /// let mut player = world.entity_mut(player).unwrap();
+    /// player.get_mut::<Health>().unwrap().0 -= 10;
+    /// if player.get::<Health>().unwrap().0 <= 0 {
+    ///     player.insert(Dead);
+    /// }
+    /// ```
+    pub fn entity_mut(&mut self, entity: Entity) -> Result<EntityMut, NoSuchEntity> {
+        self.flush();
+        let entity_info = self.entities[entity.index as usize];
+        if entity_info.generation == entity.generation {
+            Ok(EntityMut::new(self, entity, entity_info.location))
         } else {
             Err(NoSuchEntity)
         }
@@ -324,29 +785,90 @@ impl World {
 
     /// Get mutable access to a single component on an `Entity`.
     pub fn get_component_mut<T: 'static>(&mut self, entity: Entity) -> Result<&mut T, ComponentError> {
+        self.flush();
         let entity_info = self.entities[entity.index as usize];
         if entity_info.generation == entity.generation {
+            if self.sparse_set_mut::<T>().map_or(false, |set| set.contains(entity.index)) {
+                return Ok(self.sparse_component_mut(entity.index).unwrap());
+            }
+
             let archetype = &mut self.archetypes[entity_info.location.archetype_index as usize];
 
-            archetype.get_component_mut(entity_info.location.index_in_archetype)
+            archetype.get_component_mut(entity_info.location.index_in_archetype, self.change_tick)
                      .map_err(|e| ComponentError::EntityMissingComponent(e))
         } else {
             Err(ComponentError::NoSuchEntity(NoSuchEntity))
         }
     }
 
+    /// A sparse component, stamping its set as changed like `get_component_mut()` does a column.
+    pub(super) fn sparse_component_mut<T: 'static>(&mut self, entity: EntityId) -> Option<&mut T> {
+        let change_tick = self.change_tick;
+        let set = self.sparse_set_mut::<T>().filter(|set| set.contains(entity))?;
+        set.mark_changed(change_tick);
+        set.get_mut(entity)
+    }
+
     /// Add a component to an entity. If the component already exists, its data will be replaced. Expensive.
-    pub fn add_component<T: 'static + Send + Sync>(&mut self, entity: Entity,  t: T) -> Result<(), NoSuchEntity> {
+    pub fn add_component<T: 'static + Send + Sync>(&mut self, entity: Entity, t: T) -> Result<(), NoSuchEntity> {
+        self.flush();
+        let entity_info = self.entities[entity.index as usize];
+        if entity_info.generation != entity.generation {
+            return Err(NoSuchEntity);
+        }
+
+        let mut location = entity_info.location;
+        self.add_component_at(entity, &mut location, t);
+        Ok(())
+    }
+
+    /// `add_component()` for an entity already known to be alive at `location`, which is updated if it moves.
+    pub(super) fn add_component_at<T: 'static + Send + Sync>(
+        &mut self,
+        entity: Entity,
+        location: &mut EntityLocation,
+        t: T,
+    ) {
+        // The index is keyed by name, so a replaced `Name` has to be taken out while it can still be read
+        let named = TypeId::of::<T>() == TypeId::of::<Name>();
+        if named {
+            self.unindex_name(entity);
+        }
+
+        self.insert_component(entity, location, t);
+
+        if named {
+            self.index_name(entity);
+        }
+    }
+
+    fn insert_component<T: 'static + Send + Sync>(&mut self, entity: Entity, location: &mut EntityLocation, t: T) {
         // When a component is added the entity can be either migrated to 
         // - a brand new archetype, or
         // - an existing archetype.
-        // So, first, find if the entity exists
-        let entity_info = self.entities[entity.index as usize];
-        if entity_info.generation == entity.generation {
-            let type_id = TypeId::of::<T>();
+        let old_location = *location;
+        let type_id = TypeId::of::<T>();
+
+        let archetype_index = old_location.archetype_index as usize;
+
+        // Sparse components stay out of archetypes, unless the entity was spawned with one
+        if self.is_sparse::<T>() && self.archetypes[archetype_index].component_index::<T>().is_none() {
+            let change_tick = self.change_tick;
+            let set = self.sparse_set_mut::<T>().unwrap();
+            match set.insert(entity.index, t) {
+                Some(_) => set.mark_changed(change_tick),
+                None => set.mark_added(change_tick),
+            }
+            return;
+        }
 
+        // An edge is only cached once the archetype is known not to have a `T`
+        let cached_edge = self.archetypes[archetype_index].add_edges.get(&type_id).copied();
+        let (new_archetype_index, insert_index) = if let Some(edge) = cached_edge {
+            edge
+        } else {
             // First, check if the component already exists for this entity
-            let current_archetype = &self.archetypes[entity_info.location.archetype_index as usize];
+            let current_archetype = &self.archetypes[archetype_index];
 
             let mut type_ids: Vec<TypeId> = current_archetype.components
                                                              .iter()
@@ -356,198 +878,314 @@ impl World {
 
             if let Ok(insert_index) = binary_search_index {
                 // Component already exists, replace it
-                let current_archetype = &mut self.archetypes[entity_info.location.archetype_index as usize];
-                current_archetype.replace_component(insert_index, entity_info.location.index_in_archetype, t);
-            } else {
-                // The component does not already exist in the current archetype.
-                // Find an existing archetype to migrate to or create a new archetype
+                let current_archetype = &mut self.archetypes[archetype_index];
+                current_archetype.replace_component(
+                    insert_index,
+                    old_location.index_in_archetype,
+                    t,
+                    self.change_tick,
+                );
+                return;
+            }
 
-                let insert_index = binary_search_index.unwrap_or_else(|i| i);
+            // The component does not already exist in the current archetype.
+            // Find an existing archetype to migrate to or create a new archetype
 
-                type_ids.insert(insert_index, type_id);
-                let bundle_id = calculate_bundle_id(&type_ids);
+            let insert_index = binary_search_index.unwrap_or_else(|i| i);
 
-                let new_archetype_index = if let Some(new_archetype_index) = self.bundle_id_to_archetype.get(&bundle_id) {
-                    // Found an existing archetype to migrate data to
-                    *new_archetype_index
-                } else {
-                    // Create a new archetype with the structure of the current archetype and one additional component
-                    let mut archetype = Archetype::new();
-                    for c in current_archetype.components.iter() {
-                        archetype.components.push(c.new_same_type());
-                    }
+            type_ids.insert(insert_index, type_id);
+            let bundle_id = calculate_bundle_id(&type_ids);
 
-                    let new_archetype_index = self.archetypes.len();
-                    archetype.components.insert(insert_index, ComponentStore::new::<T>());
-                    self.bundle_id_to_archetype.insert(bundle_id, new_archetype_index);
+            let existing = self.bundle_id_to_archetype.get(&bundle_id);
+            let new_archetype_index = if let Some(new_archetype_index) = existing {
+                // Found an existing archetype to migrate data to
+                *new_archetype_index
+            } else {
+                // Create a new archetype with the structure of the current archetype and one additional component
+                let mut archetype = Archetype::new();
+                for c in current_archetype.components.iter() {
+                    archetype.components.push(c.new_same_type());
+                }
 
-                    self.archetypes.push(archetype);
+                let new_archetype_index = self.archetypes.len();
+                archetype.components.insert(insert_index, ComponentStore::new::<T>());
+                self.bundle_id_to_archetype.insert(bundle_id, new_archetype_index);
 
-                    new_archetype_index
-                };
+                self.archetypes.push(archetype);
 
-                // `index_twice` lets us mutably borrow from the world twice
-                let (old_archetype, new_archetype) = index_twice(
-                    &mut self.archetypes,
-                    entity_info.location.archetype_index as usize,
-                    new_archetype_index,
-                );
+                new_archetype_index
+            };
 
-                // If an entity is being moved, update its location
-                if let Some(last) = old_archetype.entities.last() {
-                    self.entities[*last as usize].location = entity_info.location;
-                }
+            // Removing `T` again leads straight back
+            self.archetypes[archetype_index].add_edges.insert(type_id, (new_archetype_index, insert_index));
+            self.archetypes[new_archetype_index].remove_edges.insert(type_id, (archetype_index, insert_index));
 
-                // First, update the entity's location to reflect the changes about to be made...
-                self.entities[entity.index as usize].location = EntityLocation {
-                    archetype_index: new_archetype_index as EntityId,
-                    index_in_archetype: (new_archetype.len()) as EntityId,
-                };
+            (new_archetype_index, insert_index)
+        };
 
-                // ...the new archetype is the same as the old one but with one additional component...
-                for i in 0..insert_index {
-                    old_archetype.migrate_component(
-                        i,
-                        entity_info.location.index_in_archetype,
-                        new_archetype,
-                        i,
-                    );
-                }
+        // `index_twice` lets us mutably borrow from the world twice
+        let (old_archetype, new_archetype) = index_twice(
+            &mut self.archetypes,
+            old_location.archetype_index as usize,
+            new_archetype_index,
+        );
 
-                // ...push the new component to the new archetype!
-                new_archetype.push(insert_index, t);
+        // If an entity is being moved, update its location
+        if let Some(last) = old_archetype.entities.last() {
+            self.entities[*last as usize].location = old_location;
+        }
 
-                let components_in_archetype = old_archetype.components.len();
+        // First, update the entity's location to reflect the changes about to be made...
+        *location = EntityLocation {
+            archetype_index: new_archetype_index as EntityId,
+            index_in_archetype: (new_archetype.len()) as EntityId,
+        };
+        self.entities[entity.index as usize].location = *location;
+
+        // ...the new archetype is the same as the old one but with one additional component...
+        for i in 0..insert_index {
+            old_archetype.migrate_component(
+                i,
+                old_location.index_in_archetype,
+                new_archetype,
+                i,
+            );
+        }
 
-                for i in insert_index..components_in_archetype {
-                    old_archetype.migrate_component(
-                        i,
-                        entity_info.location.index_in_archetype,
-                        new_archetype,
-                        i + 1,
-                    );
-                }
+        // ...push the new component to the new archetype!
+        new_archetype.push(insert_index, t);
+        new_archetype.components[insert_index].mark_added(self.change_tick);
 
-                old_archetype.entities.swap_remove(entity_info.location.index_in_archetype as usize);
-                new_archetype.entities.push(entity.index);
-            }
+        let components_in_archetype = old_archetype.components.len();
 
-            Ok(())
-        } else {
-            Err(NoSuchEntity)
+        for i in insert_index..components_in_archetype {
+            old_archetype.migrate_component(
+                i,
+                old_location.index_in_archetype,
+                new_archetype,
+                i + 1,
+            );
         }
+
+        old_archetype.entities.swap_remove(old_location.index_in_archetype as usize);
+        new_archetype.entities.push(entity.index);
     }
 
     /// Remove a single component from an entity. If successful, removed component is returned.
     /// ## Example
-    /// ```
-    /// let entity = world.spawn((Name("Matsumoto"), Health(100)));
+    /// ```ignore
+    /// let entity = world.spawn((Name::new("Matsumoto"), Health(100)));
     /// let b = world.remove_component::<Health>(entity).unwrap();
     /// ```
     pub fn remove_component<T: 'static>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        self.flush();
         let entity_info = self.entities[entity.index as usize];
+        if entity_info.generation != entity.generation {
+            // Entity is not in world
+            return Err(ComponentError::NoSuchEntity(NoSuchEntity));
+        }
 
-        if entity_info.generation == entity.generation {
-            let current_archetype = &self.archetypes[entity_info.location.archetype_index as usize];
+        let mut location = entity_info.location;
+        self.remove_component_at(entity, &mut location)
+    }
+
+    /// `remove_component()` for an entity already known to be alive at `location`, which is updated if it moves.
+    pub(super) fn remove_component_at<T: 'static>(
+        &mut self,
+        entity: Entity,
+        location: &mut EntityLocation,
+    ) -> Result<T, ComponentError> {
+        if TypeId::of::<T>() == TypeId::of::<Name>() {
+            self.unindex_name(entity);
+        }
+        let old_location = *location;
+        let type_id = TypeId::of::<T>();
+        let archetype_index = old_location.archetype_index as usize;
+
+        if let Some(removed) = self.sparse_set_mut::<T>().and_then(|set| set.remove(entity.index)) {
+            self.record_removal(type_id, entity);
+            return Ok(removed);
+        }
+
+        // An edge is only cached once the archetype is known to have a `T`
+        let cached_edge = self.archetypes[archetype_index].remove_edges.get(&type_id).copied();
+        let (new_archetype_index, remove_index) = if let Some(edge) = cached_edge {
+            edge
+        } else {
+            let current_archetype = &self.archetypes[archetype_index];
 
-            let type_id = TypeId::of::<T>();
             let mut type_ids: Vec<TypeId> = current_archetype.components
                                                              .iter()
                                                              .map(|c| c.type_id)
                                                              .collect();
-            let binary_search_index = type_ids.binary_search(&type_id);
-
-            if let Ok(remove_index) = binary_search_index {
-                type_ids.remove(remove_index);
-                let bundle_id = calculate_bundle_id(&type_ids);
-                let new_archetype_index = if let Some(new_archetype_index) = self.bundle_id_to_archetype.get(&bundle_id) {
-                    *new_archetype_index
-                } else {
-                    // Create a new archetype
-                    let mut archetype = Archetype::new();
-                    for c in current_archetype.components.iter() {
-                        if c.type_id != type_id {
-                            archetype.components.push(c.new_same_type());
-                        }
+            let remove_index = match type_ids.binary_search(&type_id) {
+                Ok(remove_index) => remove_index,
+                // Component is not in entity
+                Err(_) => {
+                    return Err(ComponentError::EntityMissingComponent(
+                        EntityMissingComponent::new::<T>(entity.index),
+                    ));
+                },
+            };
+
+            type_ids.remove(remove_index);
+            let bundle_id = calculate_bundle_id(&type_ids);
+            let existing = self.bundle_id_to_archetype.get(&bundle_id);
+            let new_archetype_index = if let Some(new_archetype_index) = existing {
+                *new_archetype_index
+            } else {
+                // Create a new archetype
+                let mut archetype = Archetype::new();
+                for c in current_archetype.components.iter() {
+                    if c.type_id != type_id {
+                        archetype.components.push(c.new_same_type());
                     }
+                }
 
-                    let new_archetype_index = self.archetypes.len();
-
-                    self.bundle_id_to_archetype.insert(bundle_id, new_archetype_index);
-                    self.archetypes.push(archetype);
-                    new_archetype_index
-                };
+                let new_archetype_index = self.archetypes.len();
 
-                // `index_twice` lets us mutably borrow from the world twice
-                let (old_archetype, new_archetype) = index_twice(
-                    &mut self.archetypes,
-                    entity_info.location.archetype_index as usize,
-                    new_archetype_index,
-                );
+                self.bundle_id_to_archetype.insert(bundle_id, new_archetype_index);
+                self.archetypes.push(archetype);
+                new_archetype_index
+            };
 
-                // If an entity is being moved, update its location
-                if let Some(last) = old_archetype.entities.last() {
-                    self.entities[*last as usize].location = entity_info.location;
-                }
+            // Adding `T` again leads straight back
+            self.archetypes[archetype_index].remove_edges.insert(type_id, (new_archetype_index, remove_index));
+            self.archetypes[new_archetype_index].add_edges.insert(type_id, (archetype_index, remove_index));
 
-                // First, update the entity's location to reflect the changes about to be made...
-                self.entities[entity.index as usize].location = EntityLocation {
-                    archetype_index: new_archetype_index as EntityId,
-                    index_in_archetype: (new_archetype.len()) as EntityId,
-                };
+            (new_archetype_index, remove_index)
+        };
 
-                // ...the new archetype is the same as the old one but with one fewer components!
-                for i in 0..remove_index {
-                    old_archetype.migrate_component(
-                        i,
-                        entity_info.location.index_in_archetype,
-                        new_archetype,
-                        i,
-                    );
-                }
+        // `index_twice` lets us mutably borrow from the world twice
+        let (old_archetype, new_archetype) = index_twice(
+            &mut self.archetypes,
+            archetype_index,
+            new_archetype_index,
+        );
 
-                let components_in_archetype = old_archetype.components.len();
+        // If an entity is being moved, update its location
+        if let Some(last) = old_archetype.entities.last() {
+            self.entities[*last as usize].location = old_location;
+        }
 
-                for i in (remove_index + 1)..components_in_archetype {
-                    old_archetype.migrate_component(
-                        i,
-                        entity_info.location.index_in_archetype,
-                        new_archetype,
-                        i - 1,
-                    );
-                }
+        // First, update the entity's location to reflect the changes about to be made...
+        *location = EntityLocation {
+            archetype_index: new_archetype_index as EntityId,
+            index_in_archetype: (new_archetype.len()) as EntityId,
+        };
+        self.entities[entity.index as usize].location = *location;
+
+        // ...the new archetype is the same as the old one but with one fewer components!
+        for i in 0..remove_index {
+            old_archetype.migrate_component(
+                i,
+                old_location.index_in_archetype,
+                new_archetype,
+                i,
+            );
+        }
 
-                old_archetype.entities.swap_remove(entity_info.location.index_in_archetype as usize);
-                new_archetype.entities.push(entity.index);
+        let components_in_archetype = old_archetype.components.len();
 
-                Ok(
-                    component_column_to_mut::<T>(&mut *old_archetype.components[remove_index].data)
-                        .swap_remove(entity_info.location.index_in_archetype as usize),
-                )
-            } else {
-                // Component is not in entity
-                Err(ComponentError::EntityMissingComponent(
-                    EntityMissingComponent::new::<T>(entity.index),
-                ))
-            }
-        } else {
-            // Entity is not in world
-            Err(ComponentError::NoSuchEntity(NoSuchEntity))
+        for i in (remove_index + 1)..components_in_archetype {
+            old_archetype.migrate_component(
+                i,
+                old_location.index_in_archetype,
+                new_archetype,
+                i - 1,
+            );
         }
+
+        old_archetype.entities.swap_remove(old_location.index_in_archetype as usize);
+        new_archetype.entities.push(entity.index);
+
+        let removed = component_column_to_mut::<T>(&mut *old_archetype.components[remove_index].data)
+            .swap_remove(old_location.index_in_archetype as usize);
+        self.record_removal(type_id, entity);
+
+        Ok(removed)
+    }
+
+    /// Store a value that the world holds only one of, like delta time or an input snapshot, replacing and returning
+    /// any previous one. Systems borrow resources with `Res<T>` and `ResMut<T>` parameters.
+    /// ## Example
+    /// ```ignore
+    /// world.insert_resource(Time { delta: 0.0 });
+    /// world.resource_mut::<Time>().unwrap().delta = dt;
+    /// let dt = world.resource::<Time>().unwrap().delta;
+    /// ```
+    pub fn insert_resource<T: 'static + Send + Sync>(&mut self, t: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(RwLock::new(t)))
+            .map(|previous| previous.downcast::<RwLock<T>>().unwrap().into_inner().unwrap())
+    }
+
+    pub fn remove_resource<T: 'static + Send + Sync>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .map(|resource| resource.downcast::<RwLock<T>>().unwrap().into_inner().unwrap())
+    }
+
+    /// Borrow a resource from `&World`, like a system's `Res<T>`. Fails if there's none, or it's borrowed mutably at
+    /// the time.
+    pub fn resource<T: 'static + Send + Sync>(&self) -> Result<Res<T>, FetchError> {
+        Ok(ResFetch::<T>::fetch(self)?.take().unwrap())
+    }
+
+    /// Borrow a resource mutably from `&World`, like a system's `ResMut<T>`, so it can be written to alongside reads
+    /// of the world. Fails if there's none, or it's borrowed at the time.
+    pub fn resource_mut<T: 'static + Send + Sync>(&self) -> Result<ResMut<T>, FetchError> {
+        Ok(ResMutFetch::<T>::fetch(self)?.take().unwrap())
+    }
+
+    pub(super) fn resource_lock<T: 'static + Send + Sync>(&self) -> Option<&RwLock<T>> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .map(|resource| resource.downcast_ref::<RwLock<T>>().unwrap())
     }
 
-     /// Query for an *immutable* reference to the first instance of a component found.
-     pub fn get_single<T: 'static>(&self) -> Result<Single<T>, FetchError> {
+    pub fn has_resource<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Query for an *immutable* reference to the only instance of a component, for things there's meant to be one of
+    /// (the player, the camera). `FetchError::NotSingle` if there are none or several.
+    /// ## Example
+    /// ```ignore
+    /// let mut player = world.get_single::<Player>().unwrap();
+    /// let player: &Player = player.inner();
+    /// ```
+    pub fn get_single<T: 'static>(&self) -> Result<Single<T>, FetchError> {
         <&T>::fetch(self)
     }
 
-    /// Query for a *mutable* reference to the first instance of a component found.
+    /// Query for a *mutable* reference to the only instance of a component. `FetchError::NotSingle` if there are
+    /// none or several. Its column is only marked changed once it's written through.
     pub fn get_single_mut<T: 'static>(&self) -> Result<SingleMut<T>, FetchError> {
         <&mut T>::fetch(self)
     }
 
+    /// `get_single()` for something there's at most one of, like a boss that may not have spawned yet. `None` if
+    /// there isn't one, still an error if there are several.
+    pub fn try_single<T: 'static>(&self) -> Result<Option<Single<T>>, FetchError> {
+        match self.get_single::<T>() {
+            Ok(single) => Ok(Some(single)),
+            Err(FetchError::NotSingle(e)) if e.count() == 0 => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `get_single_mut()` for something there's at most one of. `None` if there isn't one.
+    pub fn try_single_mut<T: 'static>(&self) -> Result<Option<SingleMut<T>>, FetchError> {
+        match self.get_single_mut::<T>() {
+            Ok(single) => Ok(Some(single)),
+            Err(FetchError::NotSingle(e)) if e.count() == 0 => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// ## Example
-    /// ```
+    /// ```ignore
     /// let query = world.query::<(&bool, &String)>();
     /// ```
     pub fn query<'world_borrow, T: QueryParameters>(&'world_borrow self) -> Result<Query<T>, FetchError> {
@@ -556,9 +1194,95 @@ impl World {
 }
 
 /// A bundle of components. Used to genericize tupled components argument in `World.spawn()`.
+/// Tuples implement it directly; structs can `#[derive(Bundle)]`, which only provides `component_stores` and
+/// `add_to_builder` and spawns through an `EntityBuilder`.
+/// ## Example
+/// ```ignore
+/// #[derive(Bundle)]
+/// struct PlayerBundle {
+///     name: Name,
+///     health: Health,
+///     #[bundle]
+///     body: BodyBundle,
+/// }
+///
+/// world.spawn(PlayerBundle { name: Name::new("Matsumoto"), health: Health(100), body: BodyBundle::default() });
+/// ```
 pub trait ComponentBundle: 'static + Send + Sync {
-    fn new_archetype(&self) -> Archetype;
-    fn spawn_in_world(self, world: &mut World, entity_index: EntityId) -> EntityLocation;
+    /// Push an empty column for each of the bundle's components.
+    fn component_stores(stores: &mut Vec<ComponentStore>)
+    where
+        Self: Sized;
+
+    /// Add each of the bundle's components to `builder`.
+    fn add_to_builder(self, builder: &mut EntityBuilder)
+    where
+        Self: Sized;
+
+    fn new_archetype(&self) -> Archetype
+    where
+        Self: Sized,
+    {
+        let mut components = Vec::new();
+        Self::component_stores(&mut components);
+        components.sort_unstable_by(|a, b| a.type_id.cmp(&b.type_id));
+
+        let mut archetype = Archetype::new();
+        archetype.components = components;
+        archetype
+    }
+
+    fn spawn_in_world(self, world: &mut World, entity_index: EntityId) -> EntityLocation
+    where
+        Self: Sized,
+    {
+        let mut builder = EntityBuilder::new();
+        self.add_to_builder(&mut builder);
+        builder.spawn_at(world, entity_index)
+    }
+
+    /// Spawn every bundle, leaving the world's `Name` index up to date like `World::spawn()` does.
+    fn spawn_batch_in_world<I: Iterator<Item = Self>>(bundles: I, world: &mut World) -> Vec<Entity>
+    where
+        Self: Sized,
+    {
+        bundles.map(|bundle| world.spawn(bundle)).collect()
+    }
+}
+
+/// Find the archetype for a bundle's types, creating it if it doesn't exist yet. `types` pairs each type with its
+/// position in the bundle, and is sorted here; `order` is filled with each bundle position's column in the archetype.
+pub(super) fn bundle_archetype(
+    world: &mut World,
+    types: &mut [(usize, TypeId)],
+    order: &mut [usize],
+    new_archetype: impl FnOnce() -> Archetype,
+) -> usize {
+    types.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+    debug_assert!(
+        types.windows(2).all(|x| x[0].1 != x[1].1),
+        "`ComponentBundle`s cannot have duplicate types"
+    );
+
+    // Is there a better way to map the original ordering to the sorted ordering?
+    for i in 0..order.len() {
+        order[types[i].0] = i;
+    }
+    let sorted: Vec<TypeId> = types.iter().map(|t| t.1).collect();
+
+    let bundle_id = calculate_bundle_id(&sorted);
+
+    // Find the appropriate archetype
+    // If it doesn't exist create a new archetype.
+    if let Some(archetype) = world.bundle_id_to_archetype.get(&bundle_id) {
+        *archetype
+    } else {
+        let index = world.archetypes.len();
+
+        world.bundle_id_to_archetype.insert(bundle_id, index);
+        world.archetypes.push(new_archetype());
+        index
+    }
 }
 
 /// Used in `World.add_component()` and `World.remove_component()`.
@@ -572,49 +1296,85 @@ fn calculate_bundle_id(types: &[TypeId]) -> u64 {
 macro_rules! component_bundle_impl {
     ($count: expr, $(($name: ident, $index: tt)),*) => {
         impl< $($name: 'static + Send + Sync),*> ComponentBundle for ($($name,)*) {
+            fn component_stores(stores: &mut Vec<ComponentStore>) {
+                $(stores.push(ComponentStore::new::<$name>());)*
+            }
+
+            fn add_to_builder(self, builder: &mut EntityBuilder) {
+                $(builder.add(self.$index);)*
+            }
+
             fn new_archetype(&self) -> Archetype {
                 let mut components = vec![$(ComponentStore::new::<$name>()), *];
                 components.sort_unstable_by(|a, b| a.type_id.cmp(&b.type_id));
-                Archetype { components, entities: Vec::new() }
+
+                let mut archetype = Archetype::new();
+                archetype.components = components;
+                archetype
             }
 
             fn spawn_in_world(self, world: &mut World, entity_index: EntityId) -> EntityLocation {
                 let mut types = [$(($index, TypeId::of::<$name>())), *];
-                types.sort_unstable_by(|a, b| a.1.cmp(&b.1));
-                debug_assert!(
-                    types.windows(2).all(|x| x[0].1 != x[1].1),
-                    "`ComponentBundle`s cannot have duplicate types"
-                );
-
-                // Is there a better way to map the original ordering to the sorted ordering?
                 let mut order = [0; $count];
-                for i in 0..order.len() {
-                    order[types[i].0] = i;
-                }
-                let types = [$(types[$index].1), *];
-
-                let bundle_id = calculate_bundle_id(&types);
-
-                // Find the appropriate archetype
-                // If it doesn't exist create a new archetype.
-                let archetype_index = if let Some(archetype) = world.bundle_id_to_archetype.get(&bundle_id) {
-                    *archetype
-                } else {
-                    let archetype = self.new_archetype();
-                    let index = world.archetypes.len();
-
-                    world.bundle_id_to_archetype.insert(bundle_id, index);
-                    world.archetypes.push(archetype);
-                    index
-                };
+                let archetype_index = bundle_archetype(world, &mut types, &mut order, || self.new_archetype());
 
                 world.archetypes[archetype_index].entities.push(entity_index);
                 $(world.archetypes[archetype_index].push(order[$index], self.$index);)*
+                for c in world.archetypes[archetype_index].components.iter_mut() {
+                    c.mark_added(world.change_tick);
+                }
                 EntityLocation {
                     archetype_index: archetype_index as EntityId,
                     index_in_archetype: (world.archetypes[archetype_index].len() - 1) as EntityId
                 }
             }
+
+            fn spawn_batch_in_world<Iter: Iterator<Item = Self>>(bundles: Iter, world: &mut World) -> Vec<Entity> {
+                let mut bundles = bundles.peekable();
+                let first = match bundles.peek() {
+                    Some(first) => first,
+                    None => return Vec::new(),
+                };
+
+                let mut types = [$(($index, TypeId::of::<$name>())), *];
+                let mut order = [0; $count];
+                let archetype_index = bundle_archetype(world, &mut types, &mut order, || first.new_archetype());
+
+                let (additional, _) = bundles.size_hint();
+                let archetype = &mut world.archetypes[archetype_index];
+                archetype.entities.reserve(additional);
+                $(archetype.mutable_component_store::<$name>(order[$index]).reserve(additional);)*
+
+                let mut spawned = Vec::with_capacity(additional);
+                for bundle in bundles {
+                    let (index, generation) = world.allocate_entity();
+                    let archetype = &mut world.archetypes[archetype_index];
+
+                    world.entities[index as usize] = EntityInfo {
+                        generation,
+                        location: EntityLocation {
+                            archetype_index: archetype_index as EntityId,
+                            index_in_archetype: archetype.len() as EntityId,
+                        },
+                    };
+
+                    archetype.entities.push(index);
+                    $(archetype.push(order[$index], bundle.$index);)*
+                    spawned.push(Entity { index, generation });
+                }
+
+                for c in world.archetypes[archetype_index].components.iter_mut() {
+                    c.mark_added(world.change_tick);
+                }
+
+                // Entities spawned one at a time are indexed by `spawn()`, these only ever are here
+                if world.archetypes[archetype_index].component_index::<Name>().is_some() {
+                    for &entity in spawned.iter() {
+                        world.index_name(entity);
+                    }
+                }
+                spawned
+            }
         }
     }
 }
@@ -673,4 +1433,37 @@ impl EntityMissingComponent {
 pub enum ComponentError {
     EntityMissingComponent(EntityMissingComponent),
     NoSuchEntity(NoSuchEntity),
+    AlreadyBorrowed(ComponentAlreadyBorrowed),
+}
+
+/// A component borrowed from its column by `World::get_component()`, which stays read-locked until this is dropped.
+pub struct ComponentRef<'world_borrow, T> {
+    borrow: ComponentBorrow<'world_borrow, T>,
+    index: usize,
+}
+
+enum ComponentBorrow<'world_borrow, T> {
+    Column(RwLockReadGuard<'world_borrow, Vec<T>>),
+    Sparse(RwLockReadGuard<'world_borrow, SparseSet<T>>),
+}
+
+impl<'world_borrow, T> ComponentRef<'world_borrow, T> {
+    pub(super) fn new(borrow: RwLockReadGuard<'world_borrow, Vec<T>>, index: usize) -> Self {
+        Self { borrow: ComponentBorrow::Column(borrow), index }
+    }
+
+    pub(super) fn sparse(borrow: RwLockReadGuard<'world_borrow, SparseSet<T>>, index: usize) -> Self {
+        Self { borrow: ComponentBorrow::Sparse(borrow), index }
+    }
+}
+
+impl<T> std::ops::Deref for ComponentRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.borrow {
+            ComponentBorrow::Column(column) => &column[self.index],
+            ComponentBorrow::Sparse(set) => set.get_dense(self.index),
+        }
+    }
 }
@@ -1,68 +1,40 @@
-extern crate gl;
-extern crate sdl2;
-extern crate thiserror;
-extern crate winapi;
-extern crate glam;
-
-pub mod gfx;
-pub mod math;
-pub mod system;
-pub mod resource;
-pub mod log;
-pub mod logic;
-
-use logic::*;
-use log::LOGGER;
-
-use crate::math::isometry::TransformEuler;
-
-extern "system" fn gl_debug_message_callback(
-    source: u32, ty: u32, id: u32, severity: u32, length: i32,
-    message: *const std::os::raw::c_char, user_param: *mut std::os::raw::c_void)
-{
-    let _ = (source, ty, id, severity, user_param);
-    match severity {
-        gl::DEBUG_SEVERITY_HIGH | gl::DEBUG_SEVERITY_MEDIUM | gl::DEBUG_SEVERITY_LOW => {
-            unsafe {
-                let message = std::slice::from_raw_parts(message as *const u8, length as usize);
-                let message = std::str::from_utf8(message);
-                match message {
-                    Ok(m) => {
-                        LOGGER().a.warn(
-                            format!("OpenGL callback: {}", m).as_str()
-                        );
-                    }
-                    Err(e) => {
-                        LOGGER().a.error(
-                            format!("received invalid OpenGL callback message: {}", e.to_string()).as_str()
-                        );
-                    }
-                }
-                
-            }
-        }
-        gl::DEBUG_SEVERITY_NOTIFICATION | _ => {}
-    }
-}
+use rusttest::{audio, budget, debug_plot, gfx, interact, resource, selfcheck, surface, system, weather};
+use rusttest::logic::*;
+use rusttest::logic::system::{FrameTime, Schedule, Stage};
+use rusttest::log::LOGGER;
 
-fn run() {
-    match LOGGER().a.set_log_path("debug.log") {
-        Err(e) => LOGGER().a.error(&e),
-        _ => {}
-    }
+use rusttest::math::curve::Curve;
+use rusttest::math::isometry::{Transform3, TransformEuler};
+use rusttest::math::units::{Degrees, Radians};
 
-    let res = resource::Resource::from_relative_exe_path(std::path::Path::new("assets")).unwrap();
+/// Run the engine with `assets` mounted as the asset root, until it quits or asks to be restarted with another root.
+/// Every GPU resource, mesh and entity is dropped on the way out, so a restart loads everything anew.
+fn run(assets: &std::path::Path) -> Option<std::path::PathBuf> {
+    let mut res = resource::Resource::from_path(assets);
+    LOGGER().a.info(format!("mounted asset root {}", res.root().display()).as_str());
+
+    // Mods sit next to the asset root, so remounting another root brings its own mods along
+    if let Some(mods) = assets.parent().map(|dir| dir.join("mods")).filter(|dir| dir.is_dir()) {
+        if let Err(e) = res.mount_mods(&mods) {
+            LOGGER().a.error(format!("failed to mount mods from {}: {}", mods.display(), e).as_str());
+        }
+    }
 
     let sdl = sdl2::init().expect("could not initialize SDL");
     let video_subsys = sdl.video().expect("could not initialize SDL video subsystem");
     
     let mut input = system::InputDevice::new(&sdl);
     
+    // Treat shader output as linear and let the driver gamma encode it when writing to the window
+    let srgb = true;
+
+    // The context version is probed once the window exists, see gfx::caps::create_context
     let gl_attr = video_subsys.gl_attr();
-    gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
-    gl_attr.set_context_version(4, 3);
     gl_attr.set_accelerated_visual(true);
     gl_attr.set_double_buffer(true);
+    gl_attr.set_depth_size(24);
+    gl_attr.set_stencil_size(8);
+    gl_attr.set_framebuffer_srgb_compatible(srgb);
     
     sdl.mouse().show_cursor(false);
     sdl.mouse().set_relative_mouse_mode(true);
@@ -75,8 +47,9 @@ fn run() {
         .build()
         .expect("could not build SDL window");
     
-    let _gl_context = window.gl_create_context().expect("could not create OpenGL context for SDL window");
-    let _gl = gl::load_with(|s| video_subsys.gl_get_proc_address(s) as *const _);
+    let gl_context = gfx::caps::create_context(&video_subsys, &window)
+        .expect("could not create OpenGL context for SDL window");
+    let capabilities = gfx::capabilities();
 
     let vsync = false;
     match video_subsys.gl_set_swap_interval(if vsync { 1 } else { 0 }) {
@@ -99,64 +72,255 @@ fn run() {
         unsafe { std::ffi::CStr::from_ptr(gl::GetString(gl::VERSION) as *const i8).to_str().unwrap().to_string() };
     LOGGER().a.info(format!("using OpenGL version {}", &gl_version_info).as_str());
     LOGGER().a.info(format!("using SDL2 version {}", sdl2::version::version().to_string()).as_str());
+    capabilities.log();
 
-    unsafe {
-        gl::Enable(gl::DEBUG_OUTPUT);
-        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
-        gl::DebugMessageCallback(Some(gl_debug_message_callback), std::ptr::null());
-        gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, std::ptr::null(), gl::TRUE);
-    }
+    gfx::gl_error::install();
     
-    let mut viewport = gfx::Viewport::make_viewport(640, 480);
+    // Keep the initial window's shape, with bars on whichever sides a resize leaves extra room
+    let scale_policy = gfx::ScalePolicy::Letterbox { aspect_ratio: 4.0 / 3.0 };
+    let mut viewport = gfx::Viewport::fit(640, 480, scale_policy);
     
+    // Reverse-Z needs glClipControl, standard depth is kept if it's missing
+    let reverse_z = gfx::state::set_reverse_z(true);
+    LOGGER().a.info(format!("reverse-Z depth: {}", reverse_z).as_str());
+
+    let color_space = if srgb { gfx::ColorSpace::Srgb } else { gfx::ColorSpace::Linear };
+
+    // Colors are authored in sRGB, decode them if the framebuffer is going to re-encode them
+    let mut clear_color = glam::vec4(0.3, 0.3, 0.5, 1.0);
+    if srgb {
+        clear_color = gfx::color::srgb_to_linear_rgba(clear_color);
+    }
+
     unsafe {
-        gl::ClearColor(0.3, 0.3, 0.5, 1.0);
+        if srgb {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+        }
+        gl::ClearColor(clear_color.x, clear_color.y, clear_color.z, clear_color.w);
     }
 
+    let anti_aliasing = gfx::AntiAliasing::Fxaa;
+    let mut post = gfx::PostProcess::new(&res, viewport.width, viewport.height, anti_aliasing, color_space).unwrap();
+
     let program = gfx::Program::from_res(&res, "shaders/test").unwrap();
 
     let vertices: Vec<gfx::Vertex> = vec![
         gfx::Vertex {
             pos: (0.5, -0.5, 0.0).into(),
-            color: (1.0, 0.0, 1.0).into()
+            color: (1.0, 0.0, 1.0).into(),
+            normal: (0.0, 0.0, -1.0).into()
         },
         gfx::Vertex {
             pos: (-0.5, -0.5, 0.0).into(),
-            color: (0.0, 1.0, 1.0).into()
+            color: (0.0, 1.0, 1.0).into(),
+            normal: (0.0, 0.0, -1.0).into()
         },
         gfx::Vertex {
             pos: (0.0, 0.5, 0.0).into(),
-            color: (1.0, 1.0, 0.0).into()
+            color: (1.0, 1.0, 0.0).into(),
+            normal: (0.0, 0.0, -1.0).into()
         },
     ];
     let indices: Vec<u32> = vec![
         0, 1, 2
     ];
     let mesh = gfx::Mesh::new(vertices, indices);
-    let mut transforms: Vec<glam::Mat4> = vec![
-        glam::Mat4::IDENTITY,
-    ];
 
-    let mut batch = gfx::Batch::new(program.id(), mesh, &transforms).unwrap();
+    let mut extractor = gfx::BatchExtractor::new();
+    let triangle_mesh = extractor.add_mesh(mesh);
+    let triangle_material = extractor.add_material(gfx::Material::new(program.id(), gfx::RenderState::default()));
+    // Mirror behind the triangle, facing the camera. Its reflection is seen from behind and with flipped winding,
+    // so back-face culling keeps it from blocking its own view.
+    let mirror_program = gfx::Program::from_res(&res, "shaders/mirror").unwrap();
+    let mirror_normal = glam::vec3(0.0, 0.0, -1.0);
+    let mirror_vertices: Vec<gfx::Vertex> = [(-1.0, -0.6), (1.0, -0.6), (1.0, 0.6), (-1.0, 0.6)]
+        .iter()
+        .map(|&(x, y)| gfx::Vertex {
+            pos: (x, y, 0.0).into(),
+            color: (0.85, 0.9, 1.0).into(),
+            normal: (0.0, 0.0, -1.0).into(),
+        })
+        .collect();
+    let mirror_mesh = extractor.add_mesh(gfx::Mesh::new(mirror_vertices, vec![0, 1, 2, 0, 2, 3]));
+    let mirror_material = extractor.add_material(gfx::Material::new(mirror_program.id(), gfx::RenderState {
+        cull_face: gfx::state::CullFace::Back,
+        ..gfx::RenderState::default()
+    }));
+    let mirror_point = glam::vec3(0.0, 0.0, 1.5);
+    let mut mirror = gfx::SecondaryView::new(viewport.width, viewport.height, color_space, 2).unwrap();
+    let mut minimap = gfx::Minimap::new(&res, 192, 5.0, color_space).unwrap();
+
+    // Three crossed cards, opaque in the middle and fading out at the corners, cut out into leafy diamonds
+    let foliage_mode = gfx::AlphaMode::Coverage { cutoff: 0.5, sharpen: true };
+    let foliage_program = foliage_mode.program(&res, "shaders/test").unwrap();
+    let card = gfx::Mesh::new(
+        [(-0.3, 0.0, 0.0), (0.3, 0.0, 0.0), (0.3, 0.6, 0.0), (-0.3, 0.6, 0.0), (0.0, 0.3, 1.0)].iter()
+            .map(|&(x, y, alpha)| gfx::Vertex {
+                pos: (x, y, 0.0).into(),
+                color: (0.2, 0.6, 0.15, alpha).into(),
+                normal: (0.0, 0.0, 1.0).into(),
+            })
+            .collect(),
+        vec![0, 1, 4, 1, 2, 4, 2, 3, 4, 3, 0, 4],
+    );
+    let cards: Vec<_> = (0..3)
+        .map(|i| (card.clone(), glam::Mat4::from_rotation_y(-(i as f32) * std::f32::consts::PI / 3.0)))
+        .collect();
+    let foliage_mesh = extractor.add_mesh(gfx::Mesh::merge(&cards));
+    // Every variant of the lit scene program, which all need the camera and shadows set
+    let lit_programs = [&program, &foliage_program];
+    let foliage_material = extractor.add_material(
+        gfx::Material::cutout(foliage_program.id(), gfx::RenderState::default(), foliage_mode),
+    );
+
+    // Matte surfaces, lit by the sky panorama too where it can be prefiltered. Its maps stay bound for good.
+    let environment = match gfx::EnvironmentMap::from_res(&res, "environments/sky.hdr", 128) {
+        Ok(environment) => Some(environment),
+        Err(e) => {
+            LOGGER().a.error(format!("failed to load environment map: {}", e).as_str());
+            None
+        },
+    };
+    for program in lit_programs {
+        program.set_f32("Roughness", 0.7);
+        program.set_f32("Metallic", 0.0);
+        if let Some(environment) = &environment {
+            environment.apply(program);
+        }
+    }
+
+    extractor.set_outline(Some(gfx::Outline::new(&res, glam::vec4(1.0, 0.6, 0.0, 1.0), 1.05).unwrap()));
+    if capabilities.compute_shaders {
+        extractor.set_gpu_culling(Some(gfx::GpuCulling::new(&res, 1024).unwrap()));
+        extractor.set_occlusion_culling(Some(gfx::OcclusionCulling::new(&res, 256, 128).unwrap()));
+    }
     
     let mut view: glam::Mat4 = glam::Mat4::IDENTITY;
-    let mut projection: glam::Mat4 = glam::Mat4::perspective_lh(
-        90.0,
-        viewport.width as f32 / viewport.height as f32,
-        0.01,
-        100.0
-    );
+    let fov: Radians = Degrees(90.0).into();
+    let perspective = |aspect_ratio: f32| {
+        if reverse_z {
+            gfx::Camera::perspective_infinite_reverse(fov, aspect_ratio, 0.01)
+        } else {
+            gfx::Camera::perspective(fov, aspect_ratio, 0.01, 100.0)
+        }
+    };
+    let mut projection: glam::Mat4 = perspective(viewport.width as f32 / viewport.height as f32);
     let mut camera_transform = TransformEuler::new(
         glam::vec3(0.0, 0.0, -1.0),
         glam::vec3(0.0, std::f32::consts::PI / 2.0, 0.0),
     );
     let mut camera = gfx::Camera::new(view, projection, camera_transform, glam::vec3(0.0, 1.0, 0.0));
+
+    // Second split-screen player, watching the scene from the side
+    let mut overview = gfx::Camera::new(
+        view,
+        projection,
+        TransformEuler::new(glam::vec3(2.0, 1.0, -2.0), glam::Vec3::ZERO),
+        glam::vec3(0.0, 1.0, 0.0),
+    );
+    overview.look_at(glam::Vec3::ZERO, 1.0);
+    overview.update_view();
+    let mut split_screen = false;
     
+    let mut surfaces = surface::PhysicalMaterials::new();
+    let stone = surfaces.load(&res, "materials/stone.ron").unwrap();
+    let weather = weather::Weather::from_res(&res, "weather/demo.ron").unwrap();
+
     // Just some testing here real quick
     let mut world = World::new();
-    #[derive(Debug)] struct Name(String);
+    world.spawn((
+        triangle_mesh,
+        triangle_material,
+        gfx::Mobility::Static,
+        Transform3::identity(),
+        surface::SurfaceMaterial(stone),
+        interact::Interactable::new("[F] Inspect triangle", 3.0),
+        gfx::MinimapMarker::new('^', glam::vec4(1.0, 0.8, 0.0, 1.0)),
+        gfx::WorldLabel::new("triangle"),
+    ));
+    world.spawn((
+        foliage_mesh,
+        foliage_material,
+        gfx::Mobility::Static,
+        Transform3::new(glam::vec3(1.0, -0.5, 0.5), glam::Quat::IDENTITY, glam::Vec3::ONE),
+    ));
+    let mut interaction = interact::Interaction::new();
+    world.spawn((
+        mirror_mesh,
+        mirror_material,
+        gfx::Mobility::Static,
+        Transform3::new(mirror_point, glam::Quat::IDENTITY, glam::Vec3::ONE),
+    ));
+    // Pointed along the sun by `time_of_day` every frame
+    world.spawn_single(gfx::Light::Directional {
+        direction: glam::Vec3::NEG_Y,
+        color: glam::vec3(1.0, 1.0, 1.0),
+        intensity: 1.0,
+    });
+
+    let mut lights = gfx::Lights::new(glam::vec3(0.15, 0.15, 0.15));
+    let mut frame_uniforms = gfx::FrameUniforms::new();
+    let time_of_day = gfx::TimeOfDay::new(9.0, std::time::Duration::from_secs(600));
+    let mut shadow = gfx::ShadowMap::new(&res, 2048).unwrap();
+    let mut profiler = gfx::GpuProfiler::new();
+    let mut debug_draw = gfx::DebugDraw::new(&res).unwrap();
+    let mut text = gfx::TextRenderer::new(&res, "fonts/mono.bmp").unwrap();
+    let mut labels = gfx::TextRenderer::new_sdf(&res, "fonts/mono_sdf.bmp").unwrap();
+    let world_labels = gfx::WorldLabels::new();
+    let mut particles = gfx::ParticleSystem::new(&res).unwrap();
+    // A small fountain beside the origin
+    particles.add_emitter(gfx::EmitterConfig {
+        rate: 60.0,
+        lifetime: (1.0, 1.6),
+        velocity: glam::vec3(0.0, 2.5, 0.0),
+        velocity_spread: 0.5,
+        acceleration: glam::vec3(0.0, -4.0, 0.0),
+        color: Curve::new(vec![
+            (0.0, glam::vec4(0.6, 0.8, 1.0, 0.9)),
+            (1.0, glam::vec4(0.2, 0.4, 1.0, 0.0)),
+        ]),
+        size: Curve::new(vec![(0.0, 0.03), (1.0, 0.08)]),
+        ..gfx::EmitterConfig::default()
+    }, glam::vec3(-1.0, 0.0, 0.5)).unwrap();
+    // Embers rising across the floor, simulated on the GPU where there are compute shaders
+    let embers = gfx::GpuParticles::new(&res, gfx::EmitterConfig {
+        rate: 2000.0,
+        lifetime: (2.0, 4.0),
+        velocity: glam::vec3(0.0, 0.4, 0.0),
+        velocity_spread: 0.3,
+        spawn_extents: glam::vec3(2.0, 0.0, 2.0),
+        color: Curve::new(vec![
+            (0.0, glam::vec4(1.0, 0.6, 0.2, 0.0)),
+            (0.2, glam::vec4(1.0, 0.5, 0.1, 0.8)),
+            (1.0, glam::vec4(0.8, 0.1, 0.0, 0.0)),
+        ]),
+        size: Curve::constant(0.02),
+        max_particles: 8192,
+        blend: gfx::BlendMode::Additive,
+        ..gfx::EmitterConfig::default()
+    }, glam::vec3(0.0, -0.5, 0.0));
+    let mut embers = match embers {
+        Ok(embers) => Some(embers),
+        Err(e) => {
+            LOGGER().a.warn(format!("no GPU particles: {}", e).as_str());
+            None
+        },
+    };
+    let mut frame: u64 = 0;
+
+    // Knob levels map to settings below, the highest level being what's set up above
+    let mut quality = gfx::AutoQuality::new(std::time::Duration::from_secs_f32(1.0 / 60.0));
+    let shadow_resolution = quality.add_knob("shadow resolution", "shadow pass", 3, 2);
+    let shadow_filtering = quality.add_knob("shadow filtering", "scene", 2, 1);
+    let post_anti_aliasing = quality.add_knob("anti-aliasing", "post-processing", 2, 1);
+    let mut last_frame = std::time::Instant::now();
+
+    let mut budgets = budget::Budgets::new();
+    budgets.set_limit(budget::Budget::Entities, Some(10_000));
+    budgets.set_limit(budget::Budget::Vram, Some(256 << 20));
+    budgets.set_limit(budget::Budget::AudioVoices, Some(32));
     #[derive(Debug)] struct Health(i32);
-    let ent0 = world.spawn((Name("Matsumoto".to_string()), Health(100)));
+    let ent0 = world.spawn((Name::new("Matsumoto"), Health(100)));
     let mut query = world.query::<(&Name, &Health)>().unwrap();
     for (name, health) in query.iter() {
         LOGGER().a.debug(
@@ -168,25 +332,158 @@ fn run() {
             ).as_str()
         );
     }
+    drop(query);
+
+    // Sounds the interface makes belong to no entity in particular
+    let ui = world.spawn_single(Name::new("ui"));
+    let mut audio = match audio::Audio::new(&sdl) {
+        Ok(mut audio) => {
+            if let Err(e) = audio.load_bank(&res, "sounds/ui.ron") {
+                LOGGER().a.error(format!("failed to load sound bank: {}", e).as_str());
+            }
+            Some(audio)
+        },
+        Err(e) => {
+            LOGGER().a.warn(format!("playing without sound: {}", e).as_str());
+            None
+        },
+    };
+
+    let mut windows = system::WindowManager::new(&window);
+    let mut profiler_window: Option<u32> = None;
+    let mut take_screenshot = false;
+    // Toggled with R, clips are saved to the working directory
+    let mut capture = gfx::FrameCapture::new(gfx::CaptureOutput::Ffmpeg { path: "clip.mp4".into(), fps: 60 });
+    let mut remount: Option<std::path::PathBuf> = None;
+
+    // Toggled with L, reported under the frame rate
+    let refresh_rate = window.display_mode().map_or(0, |mode| mode.refresh_rate);
+    let mut latency = system::LatencyTracker::new(&sdl, refresh_rate).expect("could not initialize SDL timer");
+
+    // Gameplay that only touches the world goes here instead of in the loop below, which keeps what needs GL
+    world.insert_resource(FrameTime::default());
+    world.insert_resource(interact::InteractionEvents::default());
+    world.insert_resource(time_of_day);
+    world.insert_resource(weather);
+    world.insert_resource(particles);
+    world.insert_resource(world_labels);
+    let mut schedule = Schedule::new();
+    schedule
+        .add_system(Stage::PreUpdate, "interaction feedback", show_interactions)
+        .add_system(Stage::Update, "time of day", gfx::advance_time_of_day)
+        .add_system(Stage::Update, "weather", weather::advance_weather)
+        .add_system(Stage::Update, "particles", gfx::advance_particles)
+        .add_system(Stage::Update, "world labels", gfx::advance_world_labels)
+        .add_exclusive_system(Stage::PostUpdate, "propagate transforms", hierarchy::propagate_transforms);
 
     let mut event_pump = sdl.event_pump()
         .expect("attempted to obtain SDL event pump when an EventPump instance already exists");
     'main_loop: loop {
         for event in event_pump.poll_iter() {
+            latency.input(&event);
+            let event = match windows.route(event) {
+                Some(event) => event,
+                None => continue,
+            };
+
             match event {
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F2), repeat: false, .. } => {
+                    match profiler_window.filter(|&id| windows.is_open(id)) {
+                        Some(id) => {
+                            windows.close(id);
+                            profiler_window = None;
+                        },
+                        None => match windows.open(&video_subsys, "GPU Profiler", 400, 200) {
+                            Ok(id) => profiler_window = Some(id),
+                            Err(e) => LOGGER().a.error(format!("failed to open profiler window: {}", e).as_str()),
+                        },
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F3), repeat: false, .. } => {
+                    LOGGER().a.info("capturing a profiler trace of the next 120 frames");
+                    profiler.capture(120);
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F4), repeat: false, .. } => {
+                    let mode = match camera.mode() {
+                        gfx::CameraMode::Euler => gfx::CameraMode::Quaternion,
+                        gfx::CameraMode::Quaternion => gfx::CameraMode::Euler,
+                    };
+                    camera.set_mode(mode);
+                    LOGGER().a.info(format!("camera mode: {:?}", mode).as_str());
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F5), repeat: false, .. } => {
+                    remount = Some(res.root().to_owned());
+                    break 'main_loop;
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F9), repeat: false, .. } => {
+                    sdl.mouse().set_relative_mouse_mode(false);
+                    let picked = system::dialog::open_file(&[]);
+                    sdl.mouse().set_relative_mouse_mode(true);
+
+                    match picked {
+                        Ok(Some(path)) => match resource::Resource::find_root(&path) {
+                            Some(root) => {
+                                remount = Some(root);
+                                break 'main_loop;
+                            },
+                            None => LOGGER().a.error(format!("{} isn't inside an asset root", path.display()).as_str()),
+                        },
+                        Ok(None) => {},
+                        Err(e) => LOGGER().a.error(format!("failed to show the asset root dialog: {}", e).as_str()),
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F8), repeat: false, .. } => {
+                    let written = std::fs::File::create("plots.csv")
+                        .and_then(|file| gfx::plot::write_csv(std::io::BufWriter::new(file)));
+                    match written {
+                        Ok(()) => LOGGER().a.info("wrote plots.csv"),
+                        Err(e) => LOGGER().a.error(format!("failed to write plots.csv: {}", e).as_str()),
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F10), repeat: false, .. } => {
+                    let reloaded = surfaces.reload(&res);
+                    LOGGER().a.info(format!("reloaded {} of {} physical materials", reloaded, surfaces.len()).as_str());
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F1), repeat: false, .. } => {
+                    let mut weather = world.resource_mut::<weather::Weather>().unwrap();
+                    let mut states: Vec<String> = weather.state_names().map(str::to_owned).collect();
+                    states.sort();
+                    let next = states.iter().position(|s| s == weather.target()).map_or(0, |i| (i + 1) % states.len());
+                    match weather.transition_to(&states[next], std::time::Duration::from_secs(10)) {
+                        Ok(()) => LOGGER().a.info(format!("weather: turning {}", states[next]).as_str()),
+                        Err(e) => LOGGER().a.error(format!("failed to change weather: {}", e).as_str()),
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F11), repeat: false, .. } => {
+                    let mut time_of_day = world.resource_mut::<gfx::TimeOfDay>().unwrap();
+                    time_of_day.set_hours(time_of_day.hours() + 3.0);
+                    LOGGER().a.info(format!("time of day: {:05.2}", time_of_day.hours()).as_str());
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F12), repeat: false, .. } => {
+                    take_screenshot = true;
+                    if let Some(audio) = &mut audio {
+                        let _ = audio.post_event("screenshot", ui);
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F7), repeat: false, .. } => {
+                    split_screen = !split_screen;
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(sdl2::keyboard::Keycode::F6), repeat: false, .. } => {
+                    quality.set_enabled(!quality.enabled());
+                    LOGGER().a.info(format!("auto quality: {}", if quality.enabled() { "on" } else { "off" }).as_str());
+                },
                 sdl2::event::Event::Quit {..} => {
                     break 'main_loop;
                 },
                 sdl2::event::Event::Window { win_event: sdl2::event::WindowEvent::Resized(w, h), .. } => {
-                    viewport.update_size(w, h);
-                    viewport.use_viewport();
-                    
-                    camera.projection = glam::Mat4::perspective_lh(
-                        90.0,
-                        viewport.width as f32 / viewport.height as f32,
-                        0.01,
-                        100.0
-                    );
+                    viewport.resize(w, h, scale_policy, &mut camera);
+
+                    match post.resize(viewport.width, viewport.height) {
+                        Err(e) => {
+                            LOGGER().a.error(format!("failed to resize post-processing target: {}", e).as_str());
+                        },
+                        _ => {}
+                    };
                 }
                 _ => {},
             }
@@ -198,17 +495,243 @@ fn run() {
         if input.is_key_down(&sdl2::keyboard::Keycode::Escape) {
             break 'main_loop;
         }
+        if input.is_key_pressed(&sdl2::keyboard::Keycode::L) {
+            latency.set_enabled(!latency.enabled());
+            LOGGER().a.info(format!("latency measurement: {}", if latency.enabled() { "on" } else { "off" }).as_str());
+        }
+        if input.is_key_pressed(&sdl2::keyboard::Keycode::R) {
+            if let Err(e) = capture.toggle() {
+                LOGGER().a.error(format!("failed to start recording: {}", e).as_str());
+            }
+        }
+
+        // Only the center of the view matters, which is the same for every region
+        let use_pressed = input.is_key_pressed(&sdl2::keyboard::Keycode::F);
+        interaction.update(&mut world, &extractor, &camera, &viewport, use_pressed);
+        world.insert_resource(interact::InteractionEvents(interaction.drain_events().collect()));
+        // Last frame's length, this one's is only known once it's done
+        world.insert_resource(FrameTime(last_frame.elapsed()));
 
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+        for stage in [Stage::PreUpdate, Stage::Update, Stage::PostUpdate] {
+            if let Err(e) = schedule.run_stage(stage, &mut world) {
+                LOGGER().a.error(format!("{}", e).as_str());
+            }
         }
 
-        program.use_program();
-        
-        program.set_mat4fv("View", camera.view, 0);
-        program.set_mat4fv("Projection", camera.projection, 0);
+        if let Err(e) = schedule.run_stage(Stage::Render, &mut world) {
+            LOGGER().a.error(format!("{}", e).as_str());
+        }
+        extractor.extract(&world);
+        latency.simulated();
+        let mut usage = budget::Usage::measure(&world);
+        usage.audio_voices = audio.as_mut().map_or(0, |audio| audio.voice_count());
+        budgets.check(&usage);
+
+        let sky = world.resource::<gfx::TimeOfDay>().unwrap().sample();
+        lights.set_ambient(sky.ambient);
+        if let Some(embers) = &mut embers {
+            embers.update(last_frame.elapsed());
+        }
+        let weather = world.resource::<weather::Weather>().unwrap();
+        if let Some(audio) = &mut audio {
+            weather.apply_audio(audio);
+        }
+        weather.set_uniforms(&program);
+        drop(weather);
+        frame_uniforms.set_fog(sky.fog());
+
+        profiler.begin_frame();
+        let frame_scope = profiler.scope("frame");
+
+        let shadow_scope = profiler.scope("shadow pass");
+        gfx::debug_group("shadow pass", || {
+            shadow.begin(sky.sun_direction, glam::Vec3::ZERO, 10.0);
+            extractor.draw_with_program(shadow.program_id(), &shadow.frustum());
+            shadow.end(&viewport);
+        });
+        drop(shadow_scope);
+
+        let scene_scope = profiler.scope("scene");
+
+        lights.collect(&world);
+        lights.bind();
+
+        gfx::debug_group("minimap", || {
+            minimap.render(camera.transform.position, |camera, viewport| {
+                draw_scene(&mut extractor, &lit_programs, &mirror_program, &shadow, &frame_uniforms, camera, viewport);
+            });
+        });
+
+        // The scene target is only as big as the viewport, so regions start from its corner, not the window's
+        let scene_viewport = gfx::Viewport::make_viewport(viewport.width, viewport.height);
+        let regions = scene_viewport.split(if split_screen { 2 } else { 1 });
+        camera.set_aspect_ratio(regions[0].aspect_ratio());
+        overview.set_aspect_ratio(regions[regions.len() - 1].aspect_ratio());
+
+        for (region, camera) in regions.iter().zip([&camera, &overview]) {
+            if let Err(e) = mirror.resize(region.width, region.height) {
+                LOGGER().a.error(format!("failed to resize mirror view: {}", e).as_str());
+            }
+            // Only visible from in front
+            let through_mirror = |camera: &gfx::Camera| {
+                let in_front = (camera.transform.position - mirror_point).dot(mirror_normal) > 0.0;
+                if in_front { Some(camera.reflected(mirror_point, mirror_normal)) } else { None }
+            };
+            gfx::debug_group("mirror", || {
+                mirror.render(camera, through_mirror, |camera, viewport| {
+                    draw_scene(
+                        &mut extractor, &lit_programs, &mirror_program, &shadow, &frame_uniforms, camera, viewport,
+                    );
+                });
+            });
+
+            post.begin();
+            region.begin_region(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            gfx::debug_group("scene", || {
+                draw_scene(&mut extractor, &lit_programs, &mirror_program, &shadow, &frame_uniforms, camera, region);
+            });
+            gfx::debug_group("particles", || {
+                world.resource_mut::<gfx::ParticleSystem>().unwrap().draw(camera);
+                if let Some(embers) = &embers {
+                    embers.draw(camera);
+                }
+            });
+
+            // Highlight whatever is under the crosshair, the cursor itself is captured for mouse look
+            let center = glam::vec2(region.width as f32, region.height as f32) * 0.5;
+            if let Some(hit) = gfx::pick::pick(&mut world, &extractor, camera, region, center) {
+                debug_draw.sphere(hit.point, 0.02, glam::vec4(1.0, 1.0, 0.0, 1.0));
+            }
+
+            // World axes at the origin
+            debug_draw.line(glam::Vec3::ZERO, glam::Vec3::X, glam::vec4(1.0, 0.0, 0.0, 1.0));
+            debug_draw.line(glam::Vec3::ZERO, glam::Vec3::Y, glam::vec4(0.0, 1.0, 0.0, 1.0));
+            debug_draw.line(glam::Vec3::ZERO, glam::Vec3::Z, glam::vec4(0.0, 0.0, 1.0, 1.0));
+            debug_draw.flush(camera);
+
+            labels.draw_world("origin", glam::Vec3::ZERO, 0.1, glam::vec4(1.0, 1.0, 0.6, 1.0), camera);
+            labels.flush_world(camera);
+        }
+        // Tested against the main camera's depth, still in the scene target
+        world.resource_mut::<gfx::WorldLabels>().unwrap().update(&world, last_frame.elapsed(), &camera, &regions[0]);
+        viewport.use_viewport();
+        drop(scene_scope);
+
+        let post_scope = profiler.scope("post-processing");
+        // Letterbox bars
+        gfx::RenderTarget::bind_default();
+        gfx::state::clear(gl::COLOR_BUFFER_BIT);
+        gfx::debug_group("post-processing", || post.end());
+        drop(post_scope);
+
+        minimap.draw(&viewport);
+        minimap.draw_markers(&world, &mut text, &viewport);
+        world.resource::<gfx::WorldLabels>().unwrap().draw(&world, &mut labels, &camera, &regions[0], &scene_viewport);
+        labels.flush(&viewport);
+
+        let now = std::time::Instant::now();
+        let frame_time = now - last_frame;
+        last_frame = now;
+        text.draw(
+            &format!("{:.0} FPS\n{:.2} ms", 1.0 / frame_time.as_secs_f32(), frame_time.as_secs_f32() * 1000.0),
+            glam::vec2(8.0, 8.0),
+            1.0,
+            glam::Vec4::ONE,
+        );
+
+        let mut plots_y = 64.0;
+        if let Some(report) = latency.report() {
+            let report = report.to_string();
+            text.draw(&report, glam::vec2(8.0, plots_y), 1.0, glam::vec4(0.8, 1.0, 0.8, 1.0));
+            plots_y += text.measure(&report, 1.0).y + 8.0;
+        }
+
+        debug_plot!("frame ms", frame_time.as_secs_f32() * 1000.0);
+        debug_plot!("camera height", camera.transform.position.y);
+        gfx::plot::draw(&mut debug_draw, &mut text, glam::vec2(8.0, plots_y));
+        debug_draw.flush_screen(&viewport);
+        interaction.draw_prompt(&mut world, &mut text, &viewport);
+        text.flush(&viewport);
+
+        drop(frame_scope);
+
+        if let Some(id) = profiler_window.filter(|&id| windows.is_open(id)) {
+            windows.drain_events(id);
+
+            let timings = profiler.results();
+            let rendered = windows.render(id, &window, &gl_context, |viewport| {
+                // One bar per scope, full width being a 60 Hz frame
+                const FRAME_BUDGET: f32 = 1.0 / 60.0;
+                const BAR_HEIGHT: i32 = 12;
+
+                unsafe { gl::ClearColor(0.1, 0.1, 0.1, 1.0); }
+                gfx::state::clear(gl::COLOR_BUFFER_BIT);
+
+                for (i, timing) in timings.iter().enumerate() {
+                    let width = (timing.elapsed.as_secs_f32() / FRAME_BUDGET * viewport.width as f32) as i32;
+                    let state = gfx::RenderState {
+                        scissor: Some(gfx::state::Scissor {
+                            x: timing.depth as i32 * BAR_HEIGHT,
+                            y: viewport.height - (i as i32 + 1) * (BAR_HEIGHT + 2),
+                            width: width.max(1),
+                            height: BAR_HEIGHT,
+                        }),
+                        ..gfx::RenderState::fullscreen()
+                    };
+                    state.apply();
+
+                    unsafe {
+                        gl::ClearColor(0.2 + 0.2 * timing.depth as f32, 0.8, 0.3, 1.0);
+                        gl::Clear(gl::COLOR_BUFFER_BIT);
+                    }
+                }
+
+                unsafe { gl::ClearColor(clear_color.x, clear_color.y, clear_color.z, clear_color.w); }
+            });
+
+            if let Err(e) = rendered {
+                LOGGER().a.error(format!("failed to render profiler window: {}", e).as_str());
+            }
+            viewport.use_viewport();
+        }
 
-        batch.draw();
+        if let Some(change) = quality.update(&profiler.results()) {
+            if change.knob == shadow_resolution {
+                match gfx::ShadowMap::new(&res, 512 << change.to) {
+                    Ok(mut resized) => {
+                        resized.set_bias(shadow.bias());
+                        resized.set_pcf_radius(shadow.pcf_radius());
+                        shadow = resized;
+                    },
+                    Err(e) => LOGGER().a.error(format!("failed to resize shadow map: {}", e).as_str()),
+                }
+            } else if change.knob == shadow_filtering {
+                shadow.set_pcf_radius(change.to as i32);
+            } else if change.knob == post_anti_aliasing {
+                let to = if change.to == 0 { gfx::AntiAliasing::None } else { anti_aliasing };
+                if let Err(e) = post.set_anti_aliasing(to) {
+                    LOGGER().a.error(format!("failed to change anti-aliasing: {}", e).as_str());
+                }
+            }
+        }
+
+        if let Some(trace) = profiler.take_trace() {
+            let written = std::fs::File::create("trace.json")
+                .and_then(|file| trace.write_chrome_json(std::io::BufWriter::new(file)));
+            match written {
+                Ok(()) => LOGGER().a.info(format!("wrote {} trace events to trace.json", trace.events.len()).as_str()),
+                Err(e) => LOGGER().a.error(format!("failed to write profiler trace: {}", e).as_str()),
+            }
+        }
+
+        frame += 1;
+        if frame % 1000 == 0 {
+            for timing in profiler.results() {
+                LOGGER().a.debug(
+                    format!("GPU {}{}: {:?}", "  ".repeat(timing.depth), timing.name, timing.elapsed).as_str()
+                );
+            }
+        }
 
         if input.is_key_down(&sdl2::keyboard::Keycode::W) {
             camera.translate_forward(0.0004);
@@ -223,36 +746,124 @@ fn run() {
             camera.translate_left(-0.0004);
         }
         if input.is_key_down(&sdl2::keyboard::Keycode::Q) {
-            camera.rotate(glam::vec3(0.0, 0.001, 0.0));
+            camera.rotate(Radians(0.0), Radians(0.001));
         }
         if input.is_key_down(&sdl2::keyboard::Keycode::E) {
-            camera.rotate(glam::vec3(0.0, -0.001, 0.0));
+            camera.rotate(Radians(0.0), Radians(-0.001));
         }
         if input.is_key_down(&sdl2::keyboard::Keycode::Z) {
-            camera.rotate(glam::vec3(0.001, 0.0, 0.0));
+            camera.rotate(Radians(0.001), Radians(0.0));
         }
         if input.is_key_down(&sdl2::keyboard::Keycode::X) {
-            camera.rotate(glam::vec3(-0.001, 0.0, 0.0));
+            camera.rotate(Radians(-0.001), Radians(0.0));
+        }
+        if input.is_key_down(&sdl2::keyboard::Keycode::C) {
+            camera.roll(Radians(0.001));
+        }
+        if input.is_key_down(&sdl2::keyboard::Keycode::V) {
+            camera.roll(Radians(-0.001));
         }
         
-        let moffset = input.mouse_rel_offset();
-        camera.rotate(glam::vec3(moffset.1 as f32 * -0.01, moffset.0 as f32 * -0.01, 0.0));
+        let (look_x, look_y) = input.mouse_look_delta();
+        camera.rotate(-look_y, -look_x);
 
         LOGGER().a.debug(format!("{}", camera.transform.euler_rotation).as_str());
 
         camera.update_view();
 
+        if std::mem::take(&mut take_screenshot) {
+            let seconds = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            if let Err(e) = gfx::capture_screenshot(format!("screenshot-{}.png", seconds)) {
+                LOGGER().a.error(format!("failed to capture screenshot: {}", e).as_str());
+            }
+        }
+        capture.capture();
+
         window.gl_swap_window();
+        world.advance_tick();
+        latency.swapped();
+    }
+
+    if let Some(root) = &remount {
+        LOGGER().a.info(format!("unloading {} to remount {}", res.root().display(), root.display()).as_str());
+    }
+    remount
+}
+
+/// Log what the player used this frame and pop a number up over it.
+fn show_interactions(
+    events: Res<interact::InteractionEvents>,
+    mut world_labels: ResMut<gfx::WorldLabels>,
+    mut query: Query<(Entity, &Transform3)>,
+) {
+    if events.0.is_empty() {
+        return;
+    }
+    for interact::Interacted(entity) in events.0.iter() {
+        LOGGER().a.info(format!("used entity {:?}", entity).as_str());
     }
+    for (entity, transform) in query.iter() {
+        if events.0.contains(&interact::Interacted(entity)) {
+            world_labels.damage_number(transform.position + glam::vec3(0.0, 0.3, 0.0), 10.0);
+        }
+    }
+}
+
+/// Draw every extracted entity from `camera` into `viewport` of the bound framebuffer.
+fn draw_scene(
+    extractor: &mut gfx::BatchExtractor,
+    lit_programs: &[&gfx::Program],
+    mirror_program: &gfx::Program,
+    shadow: &gfx::ShadowMap,
+    frame_uniforms: &gfx::FrameUniforms,
+    camera: &gfx::Camera,
+    viewport: &gfx::Viewport,
+) {
+    frame_uniforms.upload(camera);
+
+    for program in lit_programs {
+        program.use_program();
+        program.set_mat4fv("View", camera.view, 0);
+        program.set_mat4fv("Projection", camera.projection, 0);
+        shadow.apply(program, 1);
+    }
+
+    mirror_program.set_mat4fv("View", camera.view, 0);
+    mirror_program.set_mat4fv("Projection", camera.projection, 0);
+    mirror_program.set_vec2f("ViewOrigin", glam::vec2(viewport.x as f32, viewport.y as f32));
+
+    extractor.draw(camera);
 }
 
 fn main() -> Result<(), String> {
-    let _args: Vec<_> = std::env::args().collect();
+    let args: Vec<_> = std::env::args().collect();
+    let self_check = args.iter().any(|a| a == "--run-tests");
+    let assets = args.iter().position(|a| a == "--assets").and_then(|i| args.get(i + 1));
 
     let r = std::panic::catch_unwind(|| {
-        run();
+        if self_check {
+            return selfcheck::run();
+        }
+
+        match LOGGER().a.set_log_path("debug.log") {
+            Err(e) => LOGGER().a.error(&e),
+            _ => {}
+        }
+
+        let mut root = match assets {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => resource::Resource::from_relative_exe_path(std::path::Path::new("assets")).unwrap().root().to_owned(),
+        };
+        while let Some(next) = run(&root) {
+            root = next;
+        }
+        true
     });
 
+    let passed = *r.as_ref().unwrap_or(&false);
+
     let r_str: Option<String> = match r {
         Ok(_) => None,
         Err(e) => {
@@ -270,6 +881,10 @@ fn main() -> Result<(), String> {
 
     if r_str.is_some() {
         LOGGER().a.fatal(r_str.as_ref().unwrap());
+    }
+
+    // Self-check runs unattended, so don't block on a message box
+    if r_str.is_some() && !self_check {
         match system::windows::create_message_box("Engine Panic", &r_str.unwrap(), system::windows::IconType::None) {
             Err(e) => { LOGGER().a.error(format!("{}", &e).as_str()); },
             _ => {},
@@ -280,5 +895,9 @@ fn main() -> Result<(), String> {
     // if this point isn't reached on thread panic, you probably have bigger problems to worry about
     LOGGER().a.flush().unwrap();
 
+    if self_check {
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+//! Piecewise linear curves through keyframes, for values animated over some span like a day or a particle's life.
+//! ## Example
+//! ```ignore
+//! // Dark at midnight, bright at noon, wrapping around every 24 hours
+//! let brightness = Curve::looping(24.0, vec![(0.0, 0.1), (12.0, 1.0)]);
+//! assert_eq!(brightness.sample(18.0), 0.55);
+//! assert_eq!(brightness.sample(30.0), 0.55);
+//! ```
+
+/// Values a `Curve` can blend between.
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for glam::Vec3 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        glam::Vec3::lerp(self, other, t)
+    }
+}
+
+impl Lerp for glam::Vec4 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        glam::Vec4::lerp(self, other, t)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    /// `(x, value)` sorted by x.
+    keys: Vec<(f32, T)>,
+    /// Length of one loop, if the curve repeats.
+    period: Option<f32>,
+}
+
+impl<T: Lerp> Curve<T> {
+    /// A curve holding its first and last value past either end. Keys don't need to be sorted.
+    ///
+    /// Panics if `keys` is empty.
+    pub fn new(keys: Vec<(f32, T)>) -> Self {
+        Self::with_period(keys, None)
+    }
+
+    /// A curve repeating every `period`, blending from its last key back around to its first.
+    ///
+    /// Panics if `keys` is empty or `period` isn't positive.
+    pub fn looping(period: f32, keys: Vec<(f32, T)>) -> Self {
+        assert!(period > 0.0, "curve period must be positive");
+        let keys = keys.into_iter().map(|(x, value)| (x.rem_euclid(period), value)).collect();
+        Self::with_period(keys, Some(period))
+    }
+
+    /// A curve that's `value` everywhere.
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![(0.0, value)])
+    }
+
+    fn with_period(mut keys: Vec<(f32, T)>, period: Option<f32>) -> Self {
+        assert!(!keys.is_empty(), "curve needs at least one key");
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Curve { keys, period }
+    }
+
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    pub fn period(&self) -> Option<f32> {
+        self.period
+    }
+
+    pub fn sample(&self, x: f32) -> T {
+        let first = self.keys[0];
+        let last = self.keys[self.keys.len() - 1];
+
+        let period = match self.period {
+            Some(period) => period,
+            None => {
+                if x <= first.0 {
+                    return first.1;
+                }
+                if x >= last.0 {
+                    return last.1;
+                }
+                return self.sample_between(x);
+            },
+        };
+
+        let x = x.rem_euclid(period);
+        if x >= first.0 && x < last.0 {
+            return self.sample_between(x);
+        }
+
+        // In the gap wrapping from the last key around to the first
+        let gap = first.0 + period - last.0;
+        if gap <= 0.0 {
+            return first.1;
+        }
+        let since_last = if x >= last.0 { x - last.0 } else { x + period - last.0 };
+        last.1.lerp(first.1, since_last / gap)
+    }
+
+    /// Sample strictly inside the first and last key.
+    fn sample_between(&self, x: f32) -> T {
+        let next = self.keys.partition_point(|key| key.0 <= x);
+        let (x0, a) = self.keys[next - 1];
+        let (x1, b) = self.keys[next];
+
+        if x1 > x0 { a.lerp(b, (x - x0) / (x1 - x0)) } else { b }
+    }
+}
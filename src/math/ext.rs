@@ -0,0 +1,94 @@
+//! Convenience extensions over the `glam` types used across the engine.
+//! Bring the traits into scope with `use crate::math::ext::*;`.
+
+use std::f32::consts::PI;
+
+const TAU: f32 = PI * 2.0;
+
+pub trait QuatExt {
+    /// Rotation that turns the +Z axis towards `forward` while keeping +Y as close to `up` as possible.
+    /// Matches the left-handed conventions of `Mat4::look_at_lh`.
+    fn look_rotation(forward: glam::Vec3, up: glam::Vec3) -> glam::Quat;
+
+    /// Split a rotation into a twist around `axis` and the remaining swing, such that `self = swing * twist`.
+    /// `axis` must be normalized.
+    fn swing_twist(self, axis: glam::Vec3) -> (glam::Quat, glam::Quat);
+}
+
+impl QuatExt for glam::Quat {
+    fn look_rotation(forward: glam::Vec3, up: glam::Vec3) -> glam::Quat {
+        let z = forward.normalize();
+        let x = up.cross(z).normalize();
+        let y = z.cross(x);
+
+        glam::Quat::from_mat3(&glam::Mat3::from_cols(x, y, z))
+    }
+
+    fn swing_twist(self, axis: glam::Vec3) -> (glam::Quat, glam::Quat) {
+        let rotation_axis = glam::vec3(self.x, self.y, self.z);
+        let projected = axis * rotation_axis.dot(axis);
+        let twist = glam::Quat::from_xyzw(projected.x, projected.y, projected.z, self.w);
+
+        // Rotation of exactly 180° perpendicular to the axis, twist is undefined so treat it as none
+        if twist.length_squared() < f32::EPSILON {
+            return (self, glam::Quat::IDENTITY);
+        }
+
+        let twist = twist.normalize();
+        (self * twist.conjugate(), twist)
+    }
+}
+
+pub trait Mat4Ext {
+    /// Decompose an affine transform into `(position, rotation, scale)`.
+    fn decompose(&self) -> (glam::Vec3, glam::Quat, glam::Vec3);
+}
+
+impl Mat4Ext for glam::Mat4 {
+    fn decompose(&self) -> (glam::Vec3, glam::Quat, glam::Vec3) {
+        let (scale, rotation, position) = self.to_scale_rotation_translation();
+        (position, rotation, scale)
+    }
+}
+
+pub trait Vec3Ext {
+    /// Move towards `target` by at most `max_delta`, without overshooting it.
+    fn move_towards(self, target: glam::Vec3, max_delta: f32) -> glam::Vec3;
+}
+
+impl Vec3Ext for glam::Vec3 {
+    fn move_towards(self, target: glam::Vec3, max_delta: f32) -> glam::Vec3 {
+        let delta = target - self;
+        let distance = delta.length();
+
+        if distance <= max_delta || distance == 0.0 {
+            target
+        } else {
+            self + delta / distance * max_delta
+        }
+    }
+}
+
+/// Wrap an angle in radians to [0, 2π). Works for angles of any magnitude in constant time.
+#[inline]
+pub fn wrap_angle_positive(angle: f32) -> f32 {
+    (TAU + (angle % TAU)) % TAU
+}
+
+/// Wrap an angle in radians to [-π, π).
+#[inline]
+pub fn wrap_angle(angle: f32) -> f32 {
+    wrap_angle_positive(angle + PI) - PI
+}
+
+/// Shortest signed difference in radians to get from angle `from` to angle `to`.
+#[inline]
+pub fn delta_angle(from: f32, to: f32) -> f32 {
+    wrap_angle(to - from)
+}
+
+/// Interpolate between two angles in radians along the shortest path.
+#[inline]
+pub fn lerp_angle(from: f32, to: f32, t: f32) -> f32 {
+    from + delta_angle(from, to) * t
+}
@@ -0,0 +1,37 @@
+/// Six clip planes of a view-projection matrix, each stored as `(normal, distance)` with normals pointing inwards.
+///
+/// Planes follow OpenGL's `-w <= z <= w` clip volume, which also keeps everything a `[0, 1]` depth projection
+/// would draw.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [glam::Vec4; 6],
+}
+
+impl Frustum {
+    /// Extract the planes from `projection * view` (Gribb & Hartmann).
+    pub fn from_matrix(view_projection: glam::Mat4) -> Self {
+        let row0 = view_projection.row(0);
+        let row1 = view_projection.row(1);
+        let row2 = view_projection.row(2);
+        let row3 = view_projection.row(3);
+
+        let normalize = |plane: glam::Vec4| plane / plane.truncate().length();
+
+        Frustum {
+            planes: [
+                normalize(row3 + row0), // left
+                normalize(row3 - row0), // right
+                normalize(row3 + row1), // bottom
+                normalize(row3 - row1), // top
+                normalize(row3 + row2), // near
+                normalize(row3 - row2), // far
+            ],
+        }
+    }
+
+    /// Whether any part of the sphere at `center` with `radius` is inside the frustum.
+    /// Conservative: spheres just outside a corner may still count as inside.
+    pub fn intersects_sphere(&self, center: glam::Vec3, radius: f32) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
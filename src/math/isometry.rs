@@ -1,15 +1,11 @@
-use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use super::units::Radians;
 
+/// Position, rotation and scale. Relative to the parent's for entities with a `logic::Parent`.
 #[derive(Debug, Clone)]
 pub struct Transform3 {
     pub position: glam::Vec3,
     pub rotation: glam::Quat,
     pub scale: glam::Vec3,
-    // The link from child to parent must be downgraded from `Rc` to `Weak` to avoid `Rc<RefCell>` circular references.
-    // There are other ways of doing this, but `RefCell`s provide easier mutability.
-    //parent: Weak<RefCell<AffineTransform>>,
-    //children: Vec<Rc<RefCell<AffineTransform>>>
 }
 
 #[derive(Debug, Clone)]
@@ -25,33 +21,27 @@ impl Transform3 {
             position: position,
             rotation: rotation,
             scale: scale,
-
-            //parent: Weak::new(),
-            //children: Vec::new(),
         }
     }
 
+    pub fn identity() -> Self {
+        Transform3::new(glam::Vec3::ZERO, glam::Quat::IDENTITY, glam::Vec3::ONE)
+    }
+
+    /// Affine matrix applying scale, then rotation, then translation.
+    pub fn matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
     /// Multiply this `Transform3`'s rotation by a quaternion and then normalize the result.
     pub fn rotate(&mut self, other: glam::Quat) {
         self.rotation = self.rotation.mul_quat(other).normalize();
     }
 
-    // Adds `Self` as a child of `parent`, then sets parent of `Self` to `target`.
-    // If a parent already exists, removes `Self` from its children. This overwrites the current parent.
-    //pub fn parent_to(&mut self, target: &mut AffineTransform) {
-    //    if self.parent.weak_count() > 0 {
-    //        self.parent = Weak::new();
-    //    }
-    //    target.add_child(self);
-    //    // need to avoid circular references but keep mutability, so downgrade RefCell Rc
-    //    self.parent = Rc::downgrade(&Rc::new(RefCell::new(target)));
-    //}
-
-    //fn add_child(&mut self, child: &mut AffineTransform) {
-    //    self.children.push(
-    //        Rc::new(RefCell::new(child))
-    //    );
-    //}
+    /// Rotate by `angle` around a normalized `axis`.
+    pub fn rotate_axis(&mut self, axis: glam::Vec3, angle: Radians) {
+        self.rotate(glam::Quat::from_axis_angle(axis, angle.0));
+    }
 }
 
 impl Drop for Transform3 {
@@ -1 +1,6 @@
-pub mod isometry;
\ No newline at end of file
+pub mod isometry;
+pub mod ext;
+pub mod stable;
+pub mod units;
+pub mod curve;
+pub mod frustum;
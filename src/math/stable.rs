@@ -0,0 +1,106 @@
+//! Deterministic alternatives to the `f32` operations whose results can differ across platforms and compilers.
+//!
+//! `f32::sin` and friends call into whatever libm the target links against, so two machines running the same
+//! simulation can disagree in the last few bits and slowly drift apart, which is fatal for lockstep networking.
+//! The approximations here only use IEEE-754 basic arithmetic (add, sub, mul, div, rounding), never fused ops,
+//! so they produce bit-identical results everywhere.
+//!
+//! The functions only take the polynomial path while deterministic mode is enabled with `set_deterministic`,
+//! otherwise they defer to the (faster and more precise) standard library.
+
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+const TAU: f32 = PI * 2.0;
+const FRAC_1_TAU: f32 = 1.0 / TAU;
+
+/// Enable or disable deterministic simulation mode for every function in this module.
+pub fn set_deterministic(enabled: bool) {
+    DETERMINISTIC.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub fn sin(x: f32) -> f32 {
+    if is_deterministic() { sin_poly(x) } else { x.sin() }
+}
+
+#[inline]
+pub fn cos(x: f32) -> f32 {
+    if is_deterministic() { sin_poly(x + FRAC_PI_2) } else { x.cos() }
+}
+
+#[inline]
+pub fn sin_cos(x: f32) -> (f32, f32) {
+    (sin(x), cos(x))
+}
+
+#[inline]
+pub fn atan(x: f32) -> f32 {
+    if is_deterministic() { atan_poly(x) } else { x.atan() }
+}
+
+/// Four quadrant arctangent of `y / x`, in the range [-π, π].
+#[inline]
+pub fn atan2(y: f32, x: f32) -> f32 {
+    if is_deterministic() { atan2_poly(y, x) } else { y.atan2(x) }
+}
+
+/// Odd Taylor polynomial up to x¹¹ after reducing `x` to [-π/2, π/2]. Max error is around 1e-6.
+fn sin_poly(x: f32) -> f32 {
+    // Wrap to [-π, π]
+    let mut x = x - (x * FRAC_1_TAU).round() * TAU;
+
+    // sin(π - x) = sin(x), fold into [-π/2, π/2] where the polynomial converges quickly
+    if x > FRAC_PI_2 {
+        x = PI - x;
+    } else if x < -FRAC_PI_2 {
+        x = -PI - x;
+    }
+
+    let x2 = x * x;
+    x * (1.0
+        + x2 * (-1.0 / 6.0
+        + x2 * (1.0 / 120.0
+        + x2 * (-1.0 / 5040.0
+        + x2 * (1.0 / 362880.0
+        + x2 * (-1.0 / 39916800.0))))))
+}
+
+/// Minimax polynomial on [-1, 1], using atan(x) = ±π/2 - atan(1/x) outside of it. Max error is around 2e-6.
+fn atan_poly(x: f32) -> f32 {
+    let (t, invert) = if x.abs() > 1.0 { (1.0 / x, true) } else { (x, false) };
+
+    let t2 = t * t;
+    let r = t * (0.99997726
+        + t2 * (-0.33262347
+        + t2 * (0.19354346
+        + t2 * (-0.11643287
+        + t2 * (0.05265332
+        + t2 * (-0.01172120))))));
+
+    if invert {
+        if x > 0.0 { FRAC_PI_2 - r } else { -FRAC_PI_2 - r }
+    } else {
+        r
+    }
+}
+
+fn atan2_poly(y: f32, x: f32) -> f32 {
+    if x > 0.0 {
+        atan_poly(y / x)
+    } else if x < 0.0 {
+        if y >= 0.0 { atan_poly(y / x) + PI } else { atan_poly(y / x) - PI }
+    } else if y > 0.0 {
+        FRAC_PI_2
+    } else if y < 0.0 {
+        -FRAC_PI_2
+    } else {
+        0.0
+    }
+}
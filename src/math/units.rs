@@ -0,0 +1,91 @@
+//! Newtypes for angles so a value in degrees can't be passed where radians are expected (or vice versa)
+//! without an explicit conversion.
+//! ## Example
+//! ```ignore
+//! let fov: Radians = Degrees(90.0).into();
+//! let projection = glam::Mat4::perspective_lh(fov.0, aspect_ratio, 0.01, 100.0);
+//! ```
+
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Radians(pub f32);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Degrees(pub f32);
+
+impl Radians {
+    pub fn to_degrees(self) -> Degrees {
+        Degrees(self.0.to_degrees())
+    }
+}
+
+impl Degrees {
+    pub fn to_radians(self) -> Radians {
+        Radians(self.0.to_radians())
+    }
+}
+
+impl From<Degrees> for Radians {
+    fn from(other: Degrees) -> Self {
+        other.to_radians()
+    }
+}
+
+impl From<Radians> for Degrees {
+    fn from(other: Radians) -> Self {
+        other.to_degrees()
+    }
+}
+
+impl std::fmt::Display for Radians {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} rad", self.0)
+    }
+}
+
+impl std::fmt::Display for Degrees {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}°", self.0)
+    }
+}
+
+macro_rules! angle_ops_impl {
+    ($name: ident) => {
+        impl Add for $name {
+            type Output = Self;
+            fn add(self, other: Self) -> Self { $name(self.0 + other.0) }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) { self.0 += other.0; }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self { $name(self.0 - other.0) }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) { self.0 -= other.0; }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+            fn neg(self) -> Self { $name(-self.0) }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = Self;
+            fn mul(self, scalar: f32) -> Self { $name(self.0 * scalar) }
+        }
+
+        impl Div<f32> for $name {
+            type Output = Self;
+            fn div(self, scalar: f32) -> Self { $name(self.0 / scalar) }
+        }
+    };
+}
+
+angle_ops_impl! {Radians}
+angle_ops_impl! {Degrees}
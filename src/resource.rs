@@ -1,5 +1,12 @@
 use std::io::Read;
 
+use crate::log::LOGGER;
+
+/// Lists the mods to load, one directory name per line, later ones overriding earlier ones.
+/// `#` starts a comment and a leading `!` disables a mod. Mods it doesn't mention load after the listed ones,
+/// alphabetically.
+pub const LOAD_ORDER_FILE: &str = "load_order.txt";
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("IO error")]
@@ -12,8 +19,18 @@ pub enum Error {
     FailedToGetExePath,
 }
 
+/// A mod directory mounted over the asset root, laid out the same way as it.
+#[derive(Debug, Clone)]
+pub struct Mod {
+    pub name: String,
+    pub path: std::path::PathBuf,
+}
+
+/// The asset root, with any mods layered over it. Lookups go through the mods from the last loaded one down,
+/// then the root, so whichever layer provides a resource last wins.
 pub struct Resource {
     root_path: std::path::PathBuf,
+    mods: Vec<Mod>,
 }
 
 impl Resource {
@@ -23,6 +40,7 @@ impl Resource {
         
         Ok(Resource {
             root_path: exe_path.join(rel_path),
+            mods: Vec::new(),
         })
     }
 
@@ -30,8 +48,115 @@ impl Resource {
         Resource::from_relative_exe_path(std::path::Path::new(""))
     }
 
+    /// Mount `root` as is, e.g. a development or mod directory somewhere other than next to the executable.
+    pub fn from_path(root: &std::path::Path) -> Resource {
+        Resource {
+            root_path: root.to_owned(),
+            mods: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> &std::path::Path {
+        &self.root_path
+    }
+
+    /// The asset root `path` is in, as the closest directory up from it with a `shaders` directory,
+    /// since every root has to provide those.
+    pub fn find_root(path: &std::path::Path) -> Option<std::path::PathBuf> {
+        path.ancestors().find(|dir| dir.join("shaders").is_dir()).map(|dir| dir.to_owned())
+    }
+
+    /// Mount the mods in `mods_dir` in the order its load order file gives, logging every resource a mod overrides.
+    pub fn mount_mods(&mut self, mods_dir: &std::path::Path) -> Result<(), Error> {
+        let mut available = Vec::new();
+        for entry in std::fs::read_dir(mods_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                available.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        available.sort();
+
+        let load_order = match std::fs::read_to_string(mods_dir.join(LOAD_ORDER_FILE)) {
+            Ok(load_order) => load_order,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut order = Vec::new();
+        let mut disabled = Vec::new();
+        for line in load_order.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.strip_prefix('!') {
+                Some(name) => disabled.push(name.trim().to_owned()),
+                None if available.iter().any(|m| m == line) => order.push(line.to_owned()),
+                None => LOGGER().a.warn(format!("{} lists mod {}, which isn't installed", LOAD_ORDER_FILE, line).as_str()),
+            }
+        }
+        for name in available {
+            if !order.contains(&name) && !disabled.contains(&name) {
+                order.push(name);
+            }
+        }
+
+        for name in order {
+            let layer = Mod { path: mods_dir.join(&name), name };
+            self.mount_mod(layer)?;
+        }
+
+        Ok(())
+    }
+
+    fn mount_mod(&mut self, layer: Mod) -> Result<(), Error> {
+        let mut provided = Vec::new();
+        list_layer(&layer.path, "", &mut provided)?;
+
+        for name in &provided {
+            let name = name.trim_start_matches('/');
+            if let Some(owner) = self.owner(name) {
+                LOGGER().a.info(format!("mod {} overrides {} from {}", layer.name, name, owner).as_str());
+            }
+        }
+
+        LOGGER().a.info(format!("mounted mod {} ({} resources)", layer.name, provided.len()).as_str());
+        self.mods.push(layer);
+        Ok(())
+    }
+
+    /// Mods in load order, the last one taking priority.
+    pub fn mods(&self) -> &[Mod] {
+        &self.mods
+    }
+
+    /// Name of the layer `resource_name` currently comes from, if any layer has it.
+    fn owner(&self, resource_name: &str) -> Option<&str> {
+        for layer in self.mods.iter().rev() {
+            if resource_name_to_path(&layer.path, resource_name).exists() {
+                return Some(&layer.name);
+            }
+        }
+
+        if resource_name_to_path(&self.root_path, resource_name).exists() { Some("the asset root") } else { None }
+    }
+
+    /// Path of `resource_name` in the highest priority layer that has it, or in the root if none do.
+    fn resolve(&self, resource_name: &str) -> std::path::PathBuf {
+        for layer in self.mods.iter().rev() {
+            let path = resource_name_to_path(&layer.path, resource_name);
+            if path.exists() {
+                return path;
+            }
+        }
+
+        resource_name_to_path(&self.root_path, resource_name)
+    }
+
     pub fn load_cstring(&self, resource_name: &str) -> Result<std::ffi::CString, Error> {
-        let mut file: std::fs::File = std::fs::File::open(resource_name_to_path(&self.root_path, resource_name))?;
+        let mut file: std::fs::File = std::fs::File::open(self.resolve(resource_name))?;
 
         // Allocate buffer of the same size as FILE
         let mut buffer: Vec<u8> = Vec::with_capacity(file.metadata()?.len() as usize + 1);
@@ -44,6 +169,52 @@ impl Resource {
 
         Ok(unsafe { std::ffi::CString::from_vec_unchecked(buffer) })
     }
+
+    pub fn load_bytes(&self, resource_name: &str) -> Result<Vec<u8>, Error> {
+        Ok(std::fs::read(self.resolve(resource_name))?)
+    }
+
+    /// Names of every resource under the directory `dir`, recursively, in the same `a/b/c.ext` form
+    /// the `load_*` functions take. Covers the root and every mod.
+    pub fn list(&self, dir: &str) -> Result<Vec<String>, Error> {
+        let layers = std::iter::once(self.root_path.as_path()).chain(self.mods.iter().map(|m| m.path.as_path()));
+        let mut names = Vec::new();
+        let mut found = false;
+
+        for layer in layers {
+            if resource_name_to_path(layer, dir).is_dir() {
+                list_layer(layer, dir, &mut names)?;
+                found = true;
+            }
+        }
+
+        if !found {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("no layer has a directory {}", dir)).into());
+        }
+
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+fn list_layer(root: &std::path::Path, dir: &str, names: &mut Vec<String>) -> Result<(), Error> {
+    let mut pending = vec![dir.trim_end_matches('/').to_owned()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(resource_name_to_path(root, &dir))? {
+            let entry = entry?;
+            let name = format!("{}/{}", dir, entry.file_name().to_string_lossy());
+
+            if entry.file_type()?.is_dir() {
+                pending.push(name);
+            } else {
+                names.push(name);
+            }
+        }
+    }
+
+    Ok(())
 }
 
 fn resource_name_to_path(root_dir: &std::path::Path, location: &str) -> std::path::PathBuf {
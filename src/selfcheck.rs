@@ -0,0 +1,235 @@
+//! `--run-tests` self-check mode.
+//!
+//! Brings the engine up in a headless context, checks the OpenGL capabilities it relies on, compiles every shader,
+//! builds the renderer's passes, and draws a few frames offscreen. Every check is logged, and the process exit code
+//! tells whether all of them passed, so it works both in CI (with a software GL driver like llvmpipe) and as a
+//! first step when users report hardware issues.
+
+use crate::gfx;
+use crate::log::LOGGER;
+use crate::logic::World;
+use crate::math::isometry::{Transform3, TransformEuler};
+use crate::math::units::{Degrees, Radians};
+use crate::resource::Resource;
+
+const TARGET_SIZE: i32 = 256;
+const FRAMES: usize = 4;
+const SHADER_EXTENSIONS: [&str; 3] = [".vert", ".frag", ".comp"];
+
+#[derive(Default)]
+struct Report {
+    passed: usize,
+    failed: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.passed += 1;
+                LOGGER().a.info(format!("self-check: {} ... ok", name).as_str());
+            },
+            Err(e) => {
+                self.failed += 1;
+                LOGGER().a.error(format!("self-check: {} ... FAILED: {}", name, e).as_str());
+            },
+        }
+    }
+}
+
+/// Run every check, returning whether they all passed.
+pub fn run() -> bool {
+    let mut report = Report::default();
+
+    let res = match Resource::from_relative_exe_path(std::path::Path::new("assets")) {
+        Ok(res) => res,
+        Err(e) => {
+            report.check("locate assets", Err(e.to_string()));
+            return false;
+        },
+    };
+
+    let headless = match gfx::HeadlessContext::new(TARGET_SIZE, TARGET_SIZE) {
+        Ok(headless) => headless,
+        Err(e) => {
+            report.check("create OpenGL core context", Err(e.to_string()));
+            return false;
+        },
+    };
+
+    check_capabilities(&mut report);
+    check_shaders(&mut report, &res);
+    check_frames(&mut report, &res, &headless);
+
+    LOGGER().a.info(format!("self-check: {} passed, {} failed", report.passed, report.failed).as_str());
+    report.failed == 0
+}
+
+fn gl_integer(name: gl::types::GLenum) -> i32 {
+    let mut value: gl::types::GLint = 0;
+    unsafe { gl::GetIntegerv(name, &mut value); }
+    value
+}
+
+/// Drain the OpenGL error queue.
+fn gl_errors() -> Result<(), String> {
+    let errors: Vec<String> = gfx::gl_error::drain().into_iter().map(gfx::gl_error::error_name).collect();
+    if errors.is_empty() { Ok(()) } else { Err(format!("OpenGL errors {}", errors.join(", "))) }
+}
+
+fn check_capabilities(report: &mut Report) {
+    let capabilities = gfx::capabilities();
+    let (major, minor) = capabilities.version;
+    report.check(
+        &format!("OpenGL version {}.{} >= {}.{}", major, minor, gfx::caps::MIN_VERSION.0, gfx::caps::MIN_VERSION.1),
+        capabilities.unsupported_reason().map_or(Ok(()), Err),
+    );
+    capabilities.log();
+
+    // One binding each for transforms, lights, GPU culling commands, and the culling counter
+    let mut limits = vec![
+        ("shader storage buffer bindings", gl::MAX_SHADER_STORAGE_BUFFER_BINDINGS, 4),
+        ("texture image units", gl::MAX_TEXTURE_IMAGE_UNITS, 2),
+    ];
+    if capabilities.compute_shaders {
+        limits.push(("compute work group invocations", gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS, 64));
+    }
+    for (name, limit, required) in limits {
+        let value = gl_integer(limit);
+        report.check(
+            &format!("{} {} >= {}", name, value, required),
+            if value >= required { Ok(()) } else { Err("limit too low".to_owned()) },
+        );
+    }
+}
+
+fn check_shaders(report: &mut Report, res: &Resource) {
+    let names = match res.list("shaders") {
+        Ok(names) => names,
+        Err(e) => {
+            report.check("list shaders", Err(e.to_string()));
+            return;
+        },
+    };
+
+    // Compute shaders are never loaded without support for them
+    let compute_shaders = gfx::capabilities().compute_shaders;
+    let names = names.iter()
+        .filter(|n| SHADER_EXTENSIONS.iter().any(|e| n.ends_with(e)))
+        .filter(|n| compute_shaders || !n.ends_with(".comp"));
+
+    for name in names {
+        report.check(
+            &format!("compile {}", name),
+            gfx::Shader::from_res(res, name).map(|_| ()).map_err(|e| e.to_string()),
+        );
+    }
+}
+
+fn check_frames(report: &mut Report, res: &Resource, headless: &gfx::HeadlessContext) {
+    // Building every pass also links every program the renderer uses
+    let passes = (|| -> Result<_, String> {
+        let program = gfx::Program::from_res(res, "shaders/test").map_err(|e| e.to_string())?;
+        let post = gfx::PostProcess::new(res, TARGET_SIZE, TARGET_SIZE, gfx::AntiAliasing::Fxaa, gfx::ColorSpace::Linear)
+            .map_err(|e| e.to_string())?;
+        let outline = gfx::Outline::new(res, glam::vec4(1.0, 0.6, 0.0, 1.0), 1.05).map_err(|e| e.to_string())?;
+        let shadow = gfx::ShadowMap::new(res, 512).map_err(|e| e.to_string())?;
+        let gpu_culling = if gfx::capabilities().compute_shaders {
+            Some(gfx::GpuCulling::new(res, 1).map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+        let debug_draw = gfx::DebugDraw::new(res).map_err(|e| e.to_string())?;
+        let text = gfx::TextRenderer::new(res, "fonts/mono.bmp").map_err(|e| e.to_string())?;
+        gl_errors()?;
+
+        Ok((program, post, outline, shadow, gpu_culling, debug_draw, text))
+    })();
+
+    let (program, _post, outline, mut shadow, gpu_culling, mut debug_draw, mut text) = match passes {
+        Ok(passes) => {
+            report.check("build render passes", Ok(()));
+            passes
+        },
+        Err(e) => {
+            report.check("build render passes", Err(e));
+            return;
+        },
+    };
+
+    let vertices = vec![
+        gfx::Vertex { pos: (0.5, -0.5, 0.0).into(), color: (1.0, 0.0, 1.0).into(), normal: (0.0, 0.0, -1.0).into() },
+        gfx::Vertex { pos: (-0.5, -0.5, 0.0).into(), color: (0.0, 1.0, 1.0).into(), normal: (0.0, 0.0, -1.0).into() },
+        gfx::Vertex { pos: (0.0, 0.5, 0.0).into(), color: (1.0, 1.0, 0.0).into(), normal: (0.0, 0.0, -1.0).into() },
+    ];
+
+    let mut extractor = gfx::BatchExtractor::new();
+    let mesh = extractor.add_mesh(gfx::Mesh::new(vertices, vec![0, 1, 2]));
+    let material = extractor.add_material(gfx::Material::new(program.id(), gfx::RenderState::default()));
+    extractor.set_outline(Some(outline));
+    extractor.set_gpu_culling(gpu_culling);
+
+    let mut world = World::new();
+    world.spawn((mesh, material, gfx::Mobility::Dynamic, Transform3::identity()));
+    world.spawn((mesh, material, gfx::Mobility::Static, Transform3::identity(), gfx::Outlined));
+    world.spawn_single(gfx::Light::Directional {
+        direction: glam::vec3(0.3, -0.5, 1.0),
+        color: glam::Vec3::ONE,
+        intensity: 1.0,
+    });
+    let mut lights = gfx::Lights::new(glam::Vec3::splat(0.15));
+    let frame_uniforms = gfx::FrameUniforms::new();
+
+    let fov: Radians = Degrees(90.0).into();
+    let mut camera = gfx::Camera::new(
+        glam::Mat4::IDENTITY,
+        gfx::Camera::perspective(fov, 1.0, 0.01, 100.0),
+        TransformEuler::new(glam::vec3(0.0, 0.0, -1.0), glam::vec3(0.0, std::f32::consts::PI / 2.0, 0.0)),
+        glam::Vec3::Y,
+    );
+    camera.update_view();
+
+    let viewport = headless.viewport();
+    let clear_color = [0.0f32, 0.0, 0.0, 1.0];
+
+    for frame in 0..FRAMES {
+        extractor.extract(&world);
+
+        shadow.begin(glam::vec3(0.3, -0.5, 1.0), glam::Vec3::ZERO, 10.0);
+        extractor.draw_with_program(shadow.program_id(), &shadow.frustum());
+        shadow.end(&viewport);
+
+        headless.target().bind();
+        unsafe { gl::ClearColor(clear_color[0], clear_color[1], clear_color[2], clear_color[3]); }
+        gfx::state::clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+
+        program.set_mat4fv("View", camera.view, 0);
+        program.set_mat4fv("Projection", camera.projection, 0);
+        lights.collect(&world);
+        lights.bind();
+        frame_uniforms.upload(&camera);
+        shadow.apply(&program, 1);
+        extractor.draw(&camera);
+
+        debug_draw.aabb(glam::Vec3::splat(-0.5), glam::Vec3::splat(0.5), glam::Vec4::ONE);
+        debug_draw.flush(&camera);
+
+        text.draw(&format!("frame {}", frame), glam::vec2(4.0, 4.0), 1.0, glam::Vec4::ONE);
+        text.flush(&viewport);
+
+        unsafe { gl::Finish(); }
+        report.check(&format!("render frame {}", frame), gl_errors());
+    }
+
+    // The triangle covers the middle of the view, so the center pixel can't still be the clear color
+    gfx::RenderTarget::bind_default();
+    let pixels = headless.read_pixels();
+    let center = (TARGET_SIZE as usize / 2 * TARGET_SIZE as usize + TARGET_SIZE as usize / 2) * 4;
+    let pixel = &pixels[center..center + 4];
+    let clear_pixel = clear_color.map(|c| (c * 255.0) as u8);
+
+    report.check(
+        "scene reaches the render target",
+        if pixel != clear_pixel { Ok(()) } else { Err(format!("center pixel is the clear color {:?}", pixel)) },
+    );
+}
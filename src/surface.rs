@@ -0,0 +1,177 @@
+//! Physical materials, what a surface is made of as far as anything but rendering is concerned.
+//!
+//! A `PhysicalMaterial` holds how slippery and bouncy a surface is, the sound event played when something steps on
+//! it, and the decals left on it by impacts. Entities reference one through a `SurfaceMaterial` component, next to
+//! their mesh or collider. Materials are RON files:
+//! ```ron
+//! (
+//!     friction: 0.8,
+//!     restitution: 0.1,
+//!     footstep: "footstep_stone", // Sound event, none if left out
+//!     decals: ["decals/crack_1.bmp", "decals/crack_2.bmp"],
+//! )
+//! ```
+//! `PhysicalMaterials` loads each file once, however many entities use it, and hands out handles that stay valid
+//! through `reload()`, so edited files show up without touching the entities.
+//!
+//! There's no physics or decal system yet, `combine()` and `decals` are what they'll read from.
+//! ## Example
+//! ```ignore
+//! let mut materials = PhysicalMaterials::new();
+//! let stone = materials.load(&res, "materials/stone.ron")?;
+//! world.spawn((mesh, material, gfx::Mobility::Static, Transform3::identity(), SurfaceMaterial(stone)));
+//!
+//! // When a foot lands
+//! materials.play_footstep(stone, &mut audio, player)?;
+//! ```
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::audio::{self, Audio};
+use crate::log::LOGGER;
+use crate::logic::Entity;
+use crate::resource::{self, Resource};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load physical material: {0}")]
+    Load(#[from] resource::Error),
+    #[error("physical material isn't UTF-8")]
+    Encoding,
+    #[error("invalid physical material: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("physical material has {0}")]
+    Invalid(&'static str),
+}
+
+/// Fields left out of a material file keep their `Default` value.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PhysicalMaterial {
+    /// Coulomb friction coefficient, 0 being ice.
+    pub friction: f32,
+    /// Fraction of the speed along the contact normal kept after a bounce, in [0, 1].
+    pub restitution: f32,
+    /// Sound event posted for footsteps on the surface.
+    pub footstep: Option<String>,
+    /// Resource names of decal textures, one picked per impact.
+    pub decals: Vec<String>,
+}
+
+impl Default for PhysicalMaterial {
+    fn default() -> Self {
+        PhysicalMaterial { friction: 0.5, restitution: 0.0, footstep: None, decals: Vec::new() }
+    }
+}
+
+impl PhysicalMaterial {
+    pub fn from_res(res: &Resource, name: &str) -> Result<Self, Error> {
+        let bytes = res.load_bytes(name)?;
+        let text = std::str::from_utf8(&bytes).map_err(|_| Error::Encoding)?;
+        Self::parse(text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        // `footstep: "footstep_stone"` rather than `footstep: Some("footstep_stone")`
+        let material: PhysicalMaterial = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(text)?;
+
+        if material.friction.is_nan() || material.friction < 0.0 {
+            return Err(Error::Invalid("a negative friction"));
+        }
+        if !(0.0..=1.0).contains(&material.restitution) {
+            return Err(Error::Invalid("a restitution outside [0, 1]"));
+        }
+
+        Ok(material)
+    }
+
+    /// Friction and restitution of a contact between two materials, as `(friction, restitution)`. Friction is the
+    /// geometric mean, so ice stays slippery against anything, and the bouncier restitution wins.
+    pub fn combine(&self, other: &PhysicalMaterial) -> (f32, f32) {
+        ((self.friction * other.friction).sqrt(), self.restitution.max(other.restitution))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PhysicalMaterialHandle(usize);
+
+/// Component naming what an entity's surface is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SurfaceMaterial(pub PhysicalMaterialHandle);
+
+/// Every loaded physical material, by resource name.
+#[derive(Debug, Default)]
+pub struct PhysicalMaterials {
+    materials: Vec<(String, PhysicalMaterial)>,
+    by_name: HashMap<String, PhysicalMaterialHandle>,
+}
+
+impl PhysicalMaterials {
+    pub fn new() -> Self {
+        PhysicalMaterials::default()
+    }
+
+    /// Load the material in `name`, or return the handle it was loaded with before.
+    pub fn load(&mut self, res: &Resource, name: &str) -> Result<PhysicalMaterialHandle, Error> {
+        if let Some(handle) = self.by_name.get(name) {
+            return Ok(*handle);
+        }
+
+        let material = PhysicalMaterial::from_res(res, name)?;
+        let handle = PhysicalMaterialHandle(self.materials.len());
+        self.materials.push((name.to_owned(), material));
+        self.by_name.insert(name.to_owned(), handle);
+
+        Ok(handle)
+    }
+
+    pub fn get(&self, handle: PhysicalMaterialHandle) -> &PhysicalMaterial {
+        &self.materials[handle.0].1
+    }
+
+    pub fn handle(&self, name: &str) -> Option<PhysicalMaterialHandle> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Read every material's file again. A file that fails to load keeps its previous contents, and the error is
+    /// logged. Returns how many materials were reloaded.
+    pub fn reload(&mut self, res: &Resource) -> usize {
+        let mut reloaded = 0;
+        for (name, material) in self.materials.iter_mut() {
+            match PhysicalMaterial::from_res(res, name) {
+                Ok(loaded) => {
+                    *material = loaded;
+                    reloaded += 1;
+                },
+                Err(e) => LOGGER().a.error(format!("failed to reload {}: {}", name, e).as_str()),
+            }
+        }
+
+        reloaded
+    }
+
+    /// Post the footstep event of `handle`'s material for `entity`, if it has one.
+    pub fn play_footstep(
+        &self,
+        handle: PhysicalMaterialHandle,
+        audio: &mut Audio,
+        entity: Entity,
+    ) -> Result<(), audio::player::Error> {
+        match &self.get(handle).footstep {
+            Some(event) => audio.post_event(event, entity),
+            None => Ok(()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+}
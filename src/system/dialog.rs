@@ -0,0 +1,188 @@
+//! Native open/save file dialogs for editor workflows.
+//!
+//! Windows uses the shell's `IFileDialog`. Elsewhere there's no native API SDL can reach, so the dialogs are
+//! shown by `zenity` if it's installed.
+//! ## Example
+//! ```ignore
+//! let filters = [system::dialog::Filter { name: "Scenes", extensions: &["scene"] }];
+//! if let Some(path) = system::dialog::open_file(&filters)? {
+//!     // Load the scene at `path`
+//! }
+//! ```
+
+use std::path::PathBuf;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[cfg(target_os = "windows")]
+    #[error("file dialog failed with HRESULT {0:#010x}")]
+    Com(i32),
+    #[cfg(not(target_os = "windows"))]
+    #[error("failed to run the dialog helper: {0}")]
+    Helper(#[from] std::io::Error),
+}
+
+/// A named group of file extensions the user can pick from, e.g. `Filter { name: "Images", extensions: &["png"] }`.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter<'a> {
+    pub name: &'a str,
+    /// Extensions without the leading dot.
+    pub extensions: &'a [&'a str],
+}
+
+/// Ask the user for an existing file. `Ok(None)` means the dialog was cancelled.
+pub fn open_file(filters: &[Filter]) -> Result<Option<PathBuf>, Error> {
+    native::show(filters, None)
+}
+
+/// Ask the user where to save a file, suggesting `default_name`. `Ok(None)` means the dialog was cancelled.
+pub fn save_file(filters: &[Filter], default_name: &str) -> Result<Option<PathBuf>, Error> {
+    native::show(filters, Some(default_name))
+}
+
+#[cfg(target_os = "windows")]
+mod native {
+    use std::iter::once;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::PathBuf;
+
+    use winapi::Interface;
+    use winapi::shared::winerror::{ERROR_CANCELLED, HRESULT, HRESULT_FROM_WIN32, RPC_E_CHANGED_MODE};
+    use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+    use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize};
+    use winapi::um::objbase::COINIT_APARTMENTTHREADED;
+    use winapi::um::shobjidl::{
+        IFileDialog, IFileOpenDialog, IFileSaveDialog,
+        FOS_FILEMUSTEXIST, FOS_FORCEFILESYSTEM, FOS_OVERWRITEPROMPT, FOS_PATHMUSTEXIST,
+    };
+    use winapi::um::shobjidl_core::{CLSID_FileOpenDialog, CLSID_FileSaveDialog, IShellItem, SIGDN_FILESYSPATH};
+    use winapi::um::shtypes::COMDLG_FILTERSPEC;
+
+    use super::{Error, Filter};
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(once(0)).collect()
+    }
+
+    fn check(hr: HRESULT) -> Result<(), Error> {
+        if hr < 0 { Err(Error::Com(hr)) } else { Ok(()) }
+    }
+
+    pub fn show(filters: &[Filter], save_name: Option<&str>) -> Result<Option<PathBuf>, Error> {
+        unsafe {
+            // Already being initialized differently by someone else on this thread is fine, COM works either way
+            let init = CoInitializeEx(std::ptr::null_mut(), COINIT_APARTMENTTHREADED);
+            if init < 0 && init != RPC_E_CHANGED_MODE {
+                return Err(Error::Com(init));
+            }
+
+            let result = show_dialog(filters, save_name);
+
+            if init >= 0 {
+                CoUninitialize();
+            }
+
+            result
+        }
+    }
+
+    unsafe fn show_dialog(filters: &[Filter], save_name: Option<&str>) -> Result<Option<PathBuf>, Error> {
+        let mut dialog: *mut IFileDialog = std::ptr::null_mut();
+        let (clsid, iid, options) = match save_name {
+            Some(_) => (&CLSID_FileSaveDialog, IFileSaveDialog::uuidof(), FOS_OVERWRITEPROMPT | FOS_PATHMUSTEXIST),
+            None => (&CLSID_FileOpenDialog, IFileOpenDialog::uuidof(), FOS_FILEMUSTEXIST),
+        };
+        check(CoCreateInstance(
+            clsid,
+            std::ptr::null_mut(),
+            CLSCTX_INPROC_SERVER,
+            &iid,
+            &mut dialog as *mut *mut IFileDialog as *mut _,
+        ))?;
+        let dialog = &*dialog;
+
+        let result = (|| -> Result<Option<PathBuf>, Error> {
+            // Filter strings have to outlive the specs pointing at them
+            let names: Vec<Vec<u16>> = filters.iter().map(|f| wide(f.name)).collect();
+            let specs: Vec<Vec<u16>> = filters
+                .iter()
+                .map(|f| wide(&f.extensions.iter().map(|e| format!("*.{}", e)).collect::<Vec<_>>().join(";")))
+                .collect();
+            let filter_specs: Vec<COMDLG_FILTERSPEC> = names
+                .iter()
+                .zip(specs.iter())
+                .map(|(name, spec)| COMDLG_FILTERSPEC { pszName: name.as_ptr(), pszSpec: spec.as_ptr() })
+                .collect();
+
+            if !filter_specs.is_empty() {
+                check(dialog.SetFileTypes(filter_specs.len() as u32, filter_specs.as_ptr()))?;
+            }
+
+            let mut current = 0;
+            check(dialog.GetOptions(&mut current))?;
+            check(dialog.SetOptions(current | options | FOS_FORCEFILESYSTEM))?;
+
+            if let Some(name) = save_name {
+                check(dialog.SetFileName(wide(name).as_ptr()))?;
+                if let Some(extension) = filters.first().and_then(|f| f.extensions.first()) {
+                    check(dialog.SetDefaultExtension(wide(extension).as_ptr()))?;
+                }
+            }
+
+            let shown = dialog.Show(std::ptr::null_mut());
+            if shown == HRESULT_FROM_WIN32(ERROR_CANCELLED) {
+                return Ok(None);
+            }
+            check(shown)?;
+
+            let mut item: *mut IShellItem = std::ptr::null_mut();
+            check(dialog.GetResult(&mut item))?;
+
+            let mut path_ptr = std::ptr::null_mut();
+            let named = (*item).GetDisplayName(SIGDN_FILESYSPATH, &mut path_ptr);
+            (*item).Release();
+            check(named)?;
+
+            let len = (0..).take_while(|&i| *path_ptr.offset(i) != 0).count();
+            let path = std::ffi::OsString::from_wide(std::slice::from_raw_parts(path_ptr, len));
+            CoTaskMemFree(path_ptr as *mut _);
+
+            Ok(Some(PathBuf::from(path)))
+        })();
+
+        dialog.Release();
+        result
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+mod native {
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use super::{Error, Filter};
+
+    pub fn show(filters: &[Filter], save_name: Option<&str>) -> Result<Option<PathBuf>, Error> {
+        let mut command = Command::new("zenity");
+        command.arg("--file-selection");
+
+        if let Some(name) = save_name {
+            command.args(["--save", "--confirm-overwrite", &format!("--filename={}", name)]);
+        }
+
+        for filter in filters {
+            let patterns: Vec<String> = filter.extensions.iter().map(|e| format!("*.{}", e)).collect();
+            command.arg(format!("--file-filter={} | {}", filter.name, patterns.join(" ")));
+        }
+
+        let output = command.output()?;
+
+        // zenity exits with 1 when cancelled
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        let path = String::from_utf8_lossy(&output.stdout).trim_end_matches('\n').to_owned();
+        Ok(if path.is_empty() { None } else { Some(PathBuf::from(path)) })
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 
 use crate::log::LOGGER;
+use crate::math::units::Radians;
 
 /// Handler containing all SDL states needed to process inputs.
 pub struct InputDevice {
@@ -19,6 +20,8 @@ pub struct InputDevice {
 
     mouse_pos: (i32, i32),
     mouse_rel_offset: (i32, i32),
+    /// Angle turned per pixel of relative mouse motion.
+    mouse_sensitivity: Radians,
 }
 
 impl InputDevice {
@@ -38,6 +41,7 @@ impl InputDevice {
 
             mouse_pos: (0, 0),
             mouse_rel_offset: (0, 0),
+            mouse_sensitivity: Radians(0.01),
         }
     }
 
@@ -84,12 +88,36 @@ impl InputDevice {
         self.keys_prev.contains(keycode)
     }
 
+    /// Whether `keycode` went down since the last call to `process_keymap()`.
+    #[inline]
+    pub fn is_key_pressed(&mut self, keycode: &sdl2::keyboard::Keycode) -> bool {
+        self.keys_new.contains(keycode)
+    }
+
     /// Get mouse position change since the last call to `process_mousemap()`.
     #[inline]
     pub fn mouse_rel_offset(&mut self) -> (i32, i32) {
         self.mouse_rel_offset
     }
 
+    /// Get mouse position change since the last call to `process_mousemap()` as `(horizontal, vertical)` angles,
+    /// scaled by the mouse sensitivity.
+    #[inline]
+    pub fn mouse_look_delta(&self) -> (Radians, Radians) {
+        (
+            self.mouse_sensitivity * self.mouse_rel_offset.0 as f32,
+            self.mouse_sensitivity * self.mouse_rel_offset.1 as f32,
+        )
+    }
+
+    pub fn mouse_sensitivity(&self) -> Radians {
+        self.mouse_sensitivity
+    }
+
+    pub fn set_mouse_sensitivity(&mut self, sensitivity: Radians) {
+        self.mouse_sensitivity = sensitivity;
+    }
+
     fn init_controller(sdl_ctx: &sdl2::Sdl) -> Option<sdl2::controller::GameController> {
         let game_controller_subsys = sdl_ctx.game_controller().unwrap();
         let num_controllers_and_joysticks: u32 = match game_controller_subsys.num_joysticks() {
@@ -0,0 +1,277 @@
+//! Input-to-photon latency estimates, for comparing vsync modes and frame pacing.
+//!
+//! While enabled, `LatencyTracker` follows every frame that had input through:
+//! - input: when SDL queued the frame's earliest input event, from the event's timestamp
+//! - update: when the simulation consumed it, marked by `simulated()`
+//! - swap: when the frame was handed to the driver, marked by `swapped()`
+//! - GPU: when the GPU got through the swap, from a timestamp query issued right after it. Like the GPU profiler,
+//!   results are read back frames later so nothing waits on the GPU, and frames still not done by then are dropped.
+//!
+//! Photons are estimated as GPU plus half a refresh, the average time scanout takes to reach a pixel. Compositors,
+//! display processing and panel response aren't visible from here, so real latency is somewhat higher. Input applied
+//! after the frame is drawn only shows up a frame later than measured. SDL timestamps are in whole milliseconds.
+//! ## Example
+//! ```ignore
+//! let mut latency = LatencyTracker::new(&sdl, refresh_rate)?;
+//! latency.set_enabled(true);
+//!
+//! // Every frame
+//! for event in event_pump.poll_iter() {
+//!     latency.input(&event);
+//!     // ...
+//! }
+//! // Update the world from input
+//! latency.simulated();
+//! // Draw
+//! window.gl_swap_window();
+//! latency.swapped();
+//!
+//! if let Some(report) = latency.report() {
+//!     text.draw(&report.to_string(), glam::vec2(8.0, 40.0), 1.0, glam::Vec4::ONE);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Frames a query gets to finish on the GPU before its frame is dropped.
+const MAX_PENDING: usize = 8;
+/// Frames the report is computed over.
+const WINDOW: usize = 120;
+
+/// Fastest, average and slowest of some latency over the report's frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stat {
+    pub min: Duration,
+    pub average: Duration,
+    pub max: Duration,
+}
+
+impl Stat {
+    fn of(samples: impl Iterator<Item = Duration> + Clone) -> Self {
+        let count = samples.clone().count().max(1) as u32;
+        Stat {
+            min: samples.clone().min().unwrap_or_default(),
+            average: samples.clone().sum::<Duration>() / count,
+            max: samples.max().unwrap_or_default(),
+        }
+    }
+}
+
+/// Latency from input to each point of a frame, over the last few frames that had input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyReport {
+    pub frames: usize,
+    pub update: Stat,
+    pub swap: Stat,
+    pub gpu: Stat,
+    /// Estimated, see the module docs.
+    pub photon: Stat,
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f32() * 1000.0;
+        write!(f, "input latency, {} frames (min/avg/max ms)", self.frames)?;
+        for (name, stat) in [("update", self.update), ("swap", self.swap), ("gpu", self.gpu), ("photon", self.photon)] {
+            write!(f, "\n{:<7}{:5.1} {:5.1} {:5.1}", name, ms(stat.min), ms(stat.average), ms(stat.max))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sample {
+    update: Duration,
+    swap: Duration,
+    gpu: Duration,
+    photon: Duration,
+}
+
+/// A swapped frame waiting for its query.
+struct Pending {
+    input: Instant,
+    simulated: Instant,
+    swapped: Instant,
+    /// GPU time at `swapped`, to place the query result on the CPU timeline.
+    gpu_epoch: gl::types::GLint64,
+    query: gl::types::GLuint,
+    age: usize,
+}
+
+pub struct LatencyTracker {
+    timer: sdl2::TimerSubsystem,
+    enabled: bool,
+    /// Half the display's refresh interval, if it's known.
+    half_refresh: Duration,
+    /// Earliest input since the last `simulated()`.
+    input: Option<Instant>,
+    /// Input consumed by this frame's update, and when.
+    simulated: Option<(Instant, Instant)>,
+    pending: VecDeque<Pending>,
+    samples: VecDeque<Sample>,
+}
+
+impl LatencyTracker {
+    /// `refresh_rate` is the display's in Hz, 0 if unknown, as SDL reports it.
+    pub fn new(sdl: &sdl2::Sdl, refresh_rate: i32) -> Result<Self, String> {
+        let half_refresh = if refresh_rate > 0 {
+            Duration::from_secs_f64(0.5 / refresh_rate as f64)
+        } else {
+            Duration::ZERO
+        };
+
+        Ok(LatencyTracker {
+            timer: sdl.timer()?,
+            enabled: false,
+            half_refresh,
+            input: None,
+            simulated: None,
+            pending: VecDeque::new(),
+            samples: VecDeque::new(),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Start or stop measuring. Stopping forgets every measurement.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    /// Note `event` if it's input. Call on every event as it's polled.
+    pub fn input(&mut self, event: &sdl2::event::Event) {
+        use sdl2::event::Event;
+
+        if !self.enabled {
+            return;
+        }
+
+        let timestamp = match event {
+            Event::KeyDown { timestamp, .. }
+            | Event::KeyUp { timestamp, .. }
+            | Event::MouseMotion { timestamp, .. }
+            | Event::MouseButtonDown { timestamp, .. }
+            | Event::MouseButtonUp { timestamp, .. }
+            | Event::MouseWheel { timestamp, .. }
+            | Event::ControllerAxisMotion { timestamp, .. }
+            | Event::ControllerButtonDown { timestamp, .. }
+            | Event::ControllerButtonUp { timestamp, .. } => *timestamp,
+            _ => return,
+        };
+
+        // Both in milliseconds since SDL started
+        let age = Duration::from_millis(self.timer.ticks().wrapping_sub(timestamp) as u64);
+        let now = Instant::now();
+        let queued = now.checked_sub(age).unwrap_or(now);
+        self.input = Some(self.input.map_or(queued, |earliest| earliest.min(queued)));
+    }
+
+    /// Mark the simulation having consumed the input noted so far.
+    pub fn simulated(&mut self) {
+        if let Some(input) = self.input.take() {
+            self.simulated = Some((input, Instant::now()));
+        }
+    }
+
+    /// Mark the frame swapped. Call right after swapping buffers.
+    pub fn swapped(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        self.collect();
+
+        let (input, simulated) = match self.simulated.take() {
+            Some(simulated) => simulated,
+            None => return,
+        };
+
+        let mut query: gl::types::GLuint = 0;
+        let mut gpu_epoch: gl::types::GLint64 = 0;
+        let swapped = Instant::now();
+        unsafe {
+            gl::GenQueries(1, &mut query);
+            gl::GetInteger64v(gl::TIMESTAMP, &mut gpu_epoch);
+            gl::QueryCounter(query, gl::TIMESTAMP);
+        }
+
+        self.pending.push_back(Pending { input, simulated, swapped, gpu_epoch, query, age: 0 });
+    }
+
+    /// Latency over the last frames measured, `None` until a frame has been.
+    pub fn report(&self) -> Option<LatencyReport> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let samples = self.samples.iter();
+        Some(LatencyReport {
+            frames: self.samples.len(),
+            update: Stat::of(samples.clone().map(|s| s.update)),
+            swap: Stat::of(samples.clone().map(|s| s.swap)),
+            gpu: Stat::of(samples.clone().map(|s| s.gpu)),
+            photon: Stat::of(samples.map(|s| s.photon)),
+        })
+    }
+
+    /// Read back every finished query in order, dropping frames that took too long.
+    fn collect(&mut self) {
+        while let Some(pending) = self.pending.front_mut() {
+            let mut available: gl::types::GLint = 0;
+            unsafe { gl::GetQueryObjectiv(pending.query, gl::QUERY_RESULT_AVAILABLE, &mut available); }
+
+            if available == 0 {
+                pending.age += 1;
+                if pending.age < MAX_PENDING {
+                    break;
+                }
+            }
+
+            let pending = self.pending.pop_front().unwrap();
+            let mut gpu_done: gl::types::GLuint64 = 0;
+            unsafe {
+                if available != 0 {
+                    gl::GetQueryObjectui64v(pending.query, gl::QUERY_RESULT, &mut gpu_done);
+                }
+                gl::DeleteQueries(1, &pending.query);
+            }
+            if available == 0 {
+                continue;
+            }
+
+            let after_swap = Duration::from_nanos((gpu_done as i64 - pending.gpu_epoch).max(0) as u64);
+            let gpu = pending.swapped + after_swap - pending.input;
+            self.samples.push_back(Sample {
+                update: pending.simulated - pending.input,
+                swap: pending.swapped - pending.input,
+                gpu,
+                photon: gpu + self.half_refresh,
+            });
+            if self.samples.len() > WINDOW {
+                self.samples.pop_front();
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        for pending in self.pending.drain(..) {
+            unsafe { gl::DeleteQueries(1, &pending.query); }
+        }
+        self.samples.clear();
+        self.input = None;
+        self.simulated = None;
+    }
+}
+
+impl Drop for LatencyTracker {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -1,4 +1,10 @@
 pub mod input;
 pub mod windows;
+pub mod window;
+pub mod dialog;
+pub mod thread;
+pub mod latency;
 
-pub use input::InputDevice as InputDevice;
\ No newline at end of file
+pub use input::InputDevice as InputDevice;
+pub use window::WindowManager as WindowManager;
+pub use latency::LatencyTracker as LatencyTracker;
\ No newline at end of file
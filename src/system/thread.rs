@@ -0,0 +1,250 @@
+//! Named threads.
+//!
+//! Every engine thread should be started with `spawn_named()`, so its name shows up in debuggers and OS tools,
+//! and in log messages and profiler traces through `current_name()`.
+//!
+//! Work split up every frame, like systems and parallel queries, goes to the long-lived threads of `workers()`
+//! instead of starting threads of its own. `WorkerPool::scope()` hands them jobs that borrow from the caller, and
+//! returns once they're all done.
+//! ## Example
+//! ```ignore
+//! let loader = system::thread::spawn_named("asset loader", move || {
+//!     // ...
+//! }).unwrap();
+//!
+//! let mut halves = [0, 0];
+//! system::thread::workers().scope(|scope| {
+//!     for (i, half) in halves.iter_mut().enumerate() {
+//!         scope.execute(move || *half = sum(&numbers[i * 100..(i + 1) * 100]));
+//!     }
+//! });
+//! ```
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread::JoinHandle;
+
+/// Spawn a thread named `name`, registering the name with the OS as well.
+pub fn spawn_named<F, T>(name: &str, f: F) -> std::io::Result<JoinHandle<T>>
+    where F: FnOnce() -> T + Send + 'static,
+          T: Send + 'static
+{
+    let os_name = name.to_owned();
+
+    std::thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || {
+            register_os_name(&os_name);
+            f()
+        })
+}
+
+/// Name of the calling thread, `main` for the main thread, or its ID if it was started without a name.
+pub fn current_name() -> String {
+    let thread = std::thread::current();
+    match thread.name() {
+        Some(name) => name.to_owned(),
+        None => format!("{:?}", thread.id()),
+    }
+}
+
+/// Windows keeps thread descriptions separately from anything std sets up, and only has
+/// `SetThreadDescription` since Windows 10 1607, so it's looked up at runtime.
+#[cfg(target_os = "windows")]
+fn register_os_name(name: &str) {
+    use std::iter::once;
+
+    use winapi::shared::ntdef::{HANDLE, HRESULT, PCWSTR};
+    use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress};
+    use winapi::um::processthreadsapi::GetCurrentThread;
+
+    type SetThreadDescription = unsafe extern "system" fn(HANDLE, PCWSTR) -> HRESULT;
+
+    let module: Vec<u16> = "kernel32.dll".encode_utf16().chain(once(0)).collect();
+    let description: Vec<u16> = name.encode_utf16().chain(once(0)).collect();
+
+    unsafe {
+        let kernel32 = GetModuleHandleW(module.as_ptr());
+        if kernel32.is_null() {
+            return;
+        }
+
+        let function = GetProcAddress(kernel32, b"SetThreadDescription\0".as_ptr() as *const i8);
+        if !function.is_null() {
+            let set_thread_description: SetThreadDescription = std::mem::transmute(function);
+            set_thread_description(GetCurrentThread(), description.as_ptr());
+        }
+    }
+}
+
+/// std already names the OS thread on these platforms, truncated to what they allow.
+#[cfg(not(target_os = "windows"))]
+fn register_os_name(_name: &str) {}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+impl Shared {
+    fn try_pop(&self) -> Option<Job> {
+        self.queue.lock().unwrap().jobs.pop_front()
+    }
+}
+
+/// Named threads started once and handed jobs until the pool is dropped.
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Start `count` threads named `"{name} {i}"`.
+    pub fn new(name: &str, count: usize) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared::default());
+        let workers = (0..count)
+            .map(|i| {
+                let shared = shared.clone();
+                spawn_named(&format!("{} {}", name, i), move || work(&shared))
+            })
+            .collect::<std::io::Result<_>>()?;
+
+        Ok(WorkerPool { shared, workers })
+    }
+
+    /// How many threads the pool has, not counting the ones waiting on a `scope()`.
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Run `f`, then wait until every job it handed to the `Scope` has run, so they can borrow anything that outlives
+    /// the call. The calling thread runs queued jobs while it waits, so scopes can be nested in jobs. A panic in a
+    /// job is carried over to the caller once the rest are done.
+    pub fn scope<'env, R>(&self, f: impl FnOnce(&Scope<'_, 'env>) -> R) -> R {
+        let scope = Scope {
+            shared: &self.shared,
+            state: Arc::new(ScopeState::default()),
+            env: PhantomData,
+        };
+        let result = catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.wait();
+
+        if let Some(panic) = scope.state.panic.lock().unwrap().take() {
+            resume_unwind(panic);
+        }
+        result.unwrap_or_else(|panic| resume_unwind(panic))
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.jobs.pop_front() {
+                    break job;
+                }
+                if queue.closed {
+                    return;
+                }
+                queue = shared.available.wait(queue).unwrap();
+            }
+        };
+        job();
+    }
+}
+
+#[derive(Default)]
+struct ScopeState {
+    pending: Mutex<usize>,
+    done: Condvar,
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+/// Hands jobs borrowing from `'env` to a `WorkerPool`, see `WorkerPool::scope()`.
+pub struct Scope<'pool, 'env> {
+    shared: &'pool Shared,
+    state: Arc<ScopeState>,
+    /// Invariant, so jobs can't be handed something that doesn't live as long as the whole scope.
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'env> Scope<'_, 'env> {
+    /// Queue `f` to run on whichever thread of the pool gets to it first.
+    pub fn execute<F: FnOnce() + Send + 'env>(&self, f: F) {
+        let state = self.state.clone();
+        *state.pending.lock().unwrap() += 1;
+
+        let job: Box<dyn FnOnce() + Send + 'env> = Box::new(move || {
+            if let Err(panic) = catch_unwind(AssertUnwindSafe(f)) {
+                state.panic.lock().unwrap().get_or_insert(panic);
+            }
+            let mut pending = state.pending.lock().unwrap();
+            *pending -= 1;
+            if *pending == 0 {
+                state.done.notify_all();
+            }
+        });
+        // Safety: `WorkerPool::scope()` doesn't return before every job handed to the scope has run, so nothing a
+        // job borrows from `'env` is gone before it's done with it
+        let job: Job = unsafe { std::mem::transmute(job) };
+
+        self.shared.queue.lock().unwrap().jobs.push_back(job);
+        self.shared.available.notify_one();
+    }
+
+    /// Help with queued jobs until every job of this scope has run. Only this scope's own jobs are waited for, a job
+    /// that's queued after the queue is found empty is run by whoever queued it while they wait.
+    fn wait(&self) {
+        loop {
+            if *self.state.pending.lock().unwrap() == 0 {
+                return;
+            }
+            if let Some(job) = self.shared.try_pop() {
+                job();
+                continue;
+            }
+
+            let pending = self.state.pending.lock().unwrap();
+            if *pending > 0 {
+                drop(self.state.done.wait(pending).unwrap());
+            }
+        }
+    }
+}
+
+/// The engine's shared worker threads, started on first use. There's one fewer than the CPU has threads, since
+/// whoever waits on a `WorkerPool::scope()` works along.
+pub fn workers() -> &'static WorkerPool {
+    static WORKERS: OnceLock<WorkerPool> = OnceLock::new();
+    WORKERS.get_or_init(|| {
+        let count = std::thread::available_parallelism().map_or(1, |n| n.get()).saturating_sub(1);
+        WorkerPool::new("worker", count).expect("failed to start worker threads")
+    })
+}
@@ -0,0 +1,142 @@
+//! Auxiliary tool windows (profiler, asset browser, ...) next to the main game window.
+//!
+//! Every tool window is drawn with the main window's OpenGL context, so textures, buffers, programs and even
+//! VAOs are shared as is. Each window still has its own swap chain, and its own event queue filled by `route()`.
+//! ## Example
+//! ```ignore
+//! let mut windows = system::WindowManager::new(&window);
+//! let profiler_window = windows.open(&video_subsys, "Profiler", 400, 300).unwrap();
+//!
+//! for event in event_pump.poll_iter() {
+//!     let event = match windows.route(event) {
+//!         Some(event) => event,
+//!         None => continue,
+//!     };
+//!     // Main window and global events
+//! }
+//!
+//! windows.render(profiler_window, &window, &gl_context, |viewport| {
+//!     // Draw the profiler
+//! }).unwrap();
+//! ```
+
+use std::collections::HashMap;
+
+use crate::gfx::Viewport;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to build SDL window: {0}")]
+    Build(#[from] sdl2::video::WindowBuildError),
+    #[error("failed to make OpenGL context current: {0}")]
+    MakeCurrent(String),
+    #[error("no open tool window with ID {0}")]
+    NoSuchWindow(u32),
+}
+
+struct ToolWindow {
+    window: sdl2::video::Window,
+    viewport: Viewport,
+    events: Vec<sdl2::event::Event>,
+}
+
+pub struct WindowManager {
+    main_id: u32,
+    tools: HashMap<u32, ToolWindow>,
+}
+
+impl WindowManager {
+    pub fn new(main: &sdl2::video::Window) -> Self {
+        WindowManager {
+            main_id: main.id(),
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Open a tool window. The returned ID identifies it in every other call.
+    pub fn open(&mut self, video: &sdl2::VideoSubsystem, title: &str, width: u32, height: u32) -> Result<u32, Error> {
+        let window = video
+            .window(title, width, height)
+            .opengl()
+            .resizable()
+            .allow_highdpi()
+            .build()?;
+
+        let (drawable_width, drawable_height) = window.drawable_size();
+        let id = window.id();
+        self.tools.insert(id, ToolWindow {
+            window,
+            viewport: Viewport::make_viewport(drawable_width as i32, drawable_height as i32),
+            events: Vec::new(),
+        });
+
+        Ok(id)
+    }
+
+    /// Destroy a tool window. Its queued events are dropped.
+    pub fn close(&mut self, id: u32) {
+        self.tools.remove(&id);
+    }
+
+    pub fn is_open(&self, id: u32) -> bool {
+        self.tools.contains_key(&id)
+    }
+
+    /// Queue `event` on the tool window it belongs to, handling resizes and closes on the way.
+    /// Events for the main window, and ones not tied to any window like `Quit`, are handed back.
+    pub fn route(&mut self, event: sdl2::event::Event) -> Option<sdl2::event::Event> {
+        let id = match event.get_window_id() {
+            Some(id) if id != self.main_id => id,
+            _ => return Some(event),
+        };
+
+        let tool = match self.tools.get_mut(&id) {
+            Some(tool) => tool,
+            // Late events for a window that was already closed
+            None => return None,
+        };
+
+        match event {
+            sdl2::event::Event::Window { win_event: sdl2::event::WindowEvent::Close, .. } => {
+                self.tools.remove(&id);
+                return None;
+            },
+            sdl2::event::Event::Window { win_event: sdl2::event::WindowEvent::Resized(..), .. } => {
+                let (width, height) = tool.window.drawable_size();
+                tool.viewport.update_size(width as i32, height as i32);
+            },
+            _ => {},
+        }
+
+        tool.events.push(event);
+        None
+    }
+
+    /// Take every event queued for tool window `id` since the last call.
+    pub fn drain_events(&mut self, id: u32) -> Vec<sdl2::event::Event> {
+        match self.tools.get_mut(&id) {
+            Some(tool) => std::mem::take(&mut tool.events),
+            None => Vec::new(),
+        }
+    }
+
+    /// Draw into tool window `id` with `draw` and present it, then make `context` current on `main` again.
+    pub fn render<F>(
+        &self,
+        id: u32,
+        main: &sdl2::video::Window,
+        context: &sdl2::video::GLContext,
+        draw: F,
+    ) -> Result<(), Error>
+        where F: FnOnce(&Viewport)
+    {
+        let tool = self.tools.get(&id).ok_or(Error::NoSuchWindow(id))?;
+
+        tool.window.gl_make_current(context).map_err(Error::MakeCurrent)?;
+        tool.viewport.use_viewport();
+        draw(&tool.viewport);
+        tool.window.gl_swap_window();
+
+        main.gl_make_current(context).map_err(Error::MakeCurrent)
+    }
+}
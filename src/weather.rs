@@ -0,0 +1,289 @@
+//! Weather states and blending between them over time.
+//!
+//! A scene's weather file defines named `WeatherState`s and which one it starts in:
+//! ```ron
+//! (
+//!     initial: "clear",
+//!     states: {
+//!         "clear": (wind: (1.0, 0.0, 0.5), gustiness: 0.2, ambience: {"ambience_birds": 1.0}),
+//!         "storm": (
+//!             rain: 1.0,           // Precipitation intensities in [0, 1], 0 if left out
+//!             snow: 0.0,
+//!             wetness: 1.0,        // How soaked surfaces look, in [0, 1]
+//!             wind: (6.0, 0.0, 2.0),
+//!             gustiness: 0.8,      // Fraction of the wind speed gusts add or take away
+//!             ambience: {"ambience_rain": 1.0, "ambience_wind": 0.7}, // Audio bus volumes
+//!         ),
+//!     },
+//! )
+//! ```
+//! `Weather::transition_to()` blends every value from the current weather to another state over a given time, and
+//! ambience buses crossfade along, a bus missing from a state counting as silent. What the weather drives reads it
+//! from `Weather::current()`: `emitters()` places rain and snow volumes around the camera, `wind_at()` is what
+//! vegetation sways with, and `set_uniforms()` hands wetness and wind to shaders that declare `Wetness` and `Wind`.
+//!
+//! Precipitation is left to whoever owns the particles, e.g. moving `gfx::ParticleSystem` emitters to `emitters()`
+//! every frame. Sound events play once rather than loop, so ambience buses only carry whatever gets posted to them.
+//! ## Example
+//! ```ignore
+//! let mut weather = Weather::from_res(&res, "weather/demo.ron")?;
+//! weather.transition_to("storm", Duration::from_secs(20))?;
+//! world.insert_resource(weather);
+//! schedule.add_system(Stage::Update, "weather", advance_weather);
+//!
+//! // Every frame, after the schedule
+//! let weather = world.resource::<Weather>().unwrap();
+//! weather.apply_audio(&mut audio);
+//! weather.set_uniforms(&program);
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::audio::Audio;
+use crate::gfx::Program;
+use crate::logic::system::FrameTime;
+use crate::logic::{Res, ResMut};
+use crate::resource::{self, Resource};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to load weather: {0}")]
+    Load(#[from] resource::Error),
+    #[error("weather isn't UTF-8")]
+    Encoding,
+    #[error("invalid weather: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("weather state `{state}` {message}")]
+    Invalid {
+        state: String,
+        message: &'static str,
+    },
+    #[error("no weather state named `{0}`")]
+    UnknownState(String),
+}
+
+/// Fields left out of a weather file are 0, or silent for `ambience`.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct WeatherState {
+    pub rain: f32,
+    pub snow: f32,
+    pub wetness: f32,
+    /// Direction and speed in m/s.
+    pub wind: glam::Vec3,
+    pub gustiness: f32,
+    /// Audio bus volumes.
+    pub ambience: HashMap<String, f32>,
+}
+
+impl WeatherState {
+    fn validate(&self) -> Result<(), &'static str> {
+        let unit = |value: f32| (0.0..=1.0).contains(&value);
+        if !unit(self.rain) || !unit(self.snow) || !unit(self.wetness) {
+            return Err("has a rain, snow or wetness outside [0, 1]");
+        }
+        if self.gustiness.is_nan() || self.gustiness < 0.0 {
+            return Err("has a negative gustiness");
+        }
+        if self.ambience.values().any(|v| v.is_nan() || *v < 0.0) {
+            return Err("has a negative ambience volume");
+        }
+        Ok(())
+    }
+
+    /// `self` blended towards `other` by `t` in [0, 1].
+    pub fn lerp(&self, other: &WeatherState, t: f32) -> WeatherState {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+
+        let mut ambience = HashMap::new();
+        for bus in self.ambience.keys().chain(other.ambience.keys()) {
+            let from = self.ambience.get(bus).copied().unwrap_or(0.0);
+            let to = other.ambience.get(bus).copied().unwrap_or(0.0);
+            ambience.insert(bus.clone(), mix(from, to));
+        }
+
+        WeatherState {
+            rain: mix(self.rain, other.rain),
+            snow: mix(self.snow, other.snow),
+            wetness: mix(self.wetness, other.wetness),
+            wind: self.wind.lerp(other.wind, t),
+            gustiness: mix(self.gustiness, other.gustiness),
+            ambience,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+/// Box around the camera precipitation should be spawned in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emitter {
+    pub kind: Precipitation,
+    /// In [0, 1], scaling the emitter's spawn rate.
+    pub intensity: f32,
+    pub center: glam::Vec3,
+    pub half_extents: glam::Vec3,
+    /// Velocity particles start with, falling and carried by the wind.
+    pub velocity: glam::Vec3,
+}
+
+#[derive(Deserialize)]
+struct WeatherFile {
+    initial: String,
+    states: HashMap<String, WeatherState>,
+}
+
+struct Transition {
+    from: WeatherState,
+    to: String,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+pub struct Weather {
+    states: HashMap<String, WeatherState>,
+    current: WeatherState,
+    /// Name of the state the weather is in or heading to.
+    target: String,
+    transition: Option<Transition>,
+    /// Seconds since creation, for gusts.
+    time: f32,
+}
+
+impl Weather {
+    pub fn from_res(res: &Resource, name: &str) -> Result<Self, Error> {
+        let bytes = res.load_bytes(name)?;
+        let text = std::str::from_utf8(&bytes).map_err(|_| Error::Encoding)?;
+        Self::parse(text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let WeatherFile { initial, states } = ron::from_str(text)?;
+
+        for (name, state) in states.iter() {
+            if let Err(message) = state.validate() {
+                return Err(Error::Invalid { state: name.clone(), message });
+            }
+        }
+
+        let current = states.get(&initial).cloned().ok_or_else(|| Error::UnknownState(initial.clone()))?;
+
+        Ok(Weather { states, current, target: initial, transition: None, time: 0.0 })
+    }
+
+    /// Start blending from the current weather, mid-transition or not, to `state` over `duration`.
+    pub fn transition_to(&mut self, state: &str, duration: Duration) -> Result<(), Error> {
+        if !self.states.contains_key(state) {
+            return Err(Error::UnknownState(state.to_owned()));
+        }
+
+        self.target = state.to_owned();
+        self.transition = Some(Transition {
+            from: self.current.clone(),
+            to: state.to_owned(),
+            elapsed: Duration::ZERO,
+            duration,
+        });
+        self.update(Duration::ZERO);
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.time += dt.as_secs_f32();
+
+        let transition = match &mut self.transition {
+            Some(transition) => transition,
+            None => return,
+        };
+
+        transition.elapsed += dt;
+        let to = &self.states[&transition.to];
+        if transition.elapsed >= transition.duration {
+            self.current = to.clone();
+            self.transition = None;
+        } else {
+            let t = transition.elapsed.as_secs_f32() / transition.duration.as_secs_f32();
+            // Smoothstep, so changes ease in and out instead of starting and stopping abruptly
+            self.current = transition.from.lerp(to, t * t * (3.0 - 2.0 * t));
+        }
+    }
+
+    /// The weather right now, blended if transitioning.
+    pub fn current(&self) -> &WeatherState {
+        &self.current
+    }
+
+    /// Name of the state the weather is in, or is transitioning to.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    pub fn state_names(&self) -> impl Iterator<Item = &str> {
+        self.states.keys().map(String::as_str)
+    }
+
+    /// Wind at `position`, the steady wind plus gusts that vary over time and travel along it.
+    pub fn wind_at(&self, position: glam::Vec3) -> glam::Vec3 {
+        let wind = self.current.wind;
+        let speed = wind.length();
+        if speed <= f32::EPSILON {
+            return glam::Vec3::ZERO;
+        }
+
+        let phase = self.time * 0.7 - position.dot(wind / speed) * 0.15;
+        let gust = (phase.sin() * 0.6 + (phase * 2.3 + 1.7).sin() * 0.4) * self.current.gustiness;
+        wind * (1.0 + gust)
+    }
+
+    /// Precipitation volumes around `camera_position`, only those currently falling.
+    pub fn emitters(&self, camera_position: glam::Vec3) -> Vec<Emitter> {
+        let half_extents = glam::vec3(15.0, 10.0, 15.0);
+        let center = camera_position + glam::vec3(0.0, half_extents.y * 0.5, 0.0);
+        let wind = self.current.wind;
+
+        [
+            (Precipitation::Rain, self.current.rain, glam::vec3(0.0, -9.0, 0.0) + wind * 0.2),
+            (Precipitation::Snow, self.current.snow, glam::vec3(0.0, -1.2, 0.0) + wind * 0.8),
+        ]
+        .into_iter()
+        .filter(|(_, intensity, _)| *intensity > 0.0)
+        .map(|(kind, intensity, velocity)| Emitter { kind, intensity, center, half_extents, velocity })
+        .collect()
+    }
+
+    /// Set the volume of every ambience bus any state uses, silencing the ones the current weather doesn't.
+    pub fn apply_audio(&self, audio: &mut Audio) {
+        for state in self.states.values() {
+            for bus in state.ambience.keys() {
+                audio.set_bus_volume(bus, self.current.ambience.get(bus).copied().unwrap_or(0.0));
+            }
+        }
+    }
+
+    /// Set `Wetness` and `Wind` on `program`, if it uses them.
+    pub fn set_uniforms(&self, program: &Program) {
+        if program.has_uniform("Wetness") {
+            program.set_f32("Wetness", self.current.wetness);
+        }
+        if program.has_uniform("Wind") {
+            program.set_vec3f("Wind", self.wind_at(glam::Vec3::ZERO));
+        }
+    }
+}
+
+/// System moving the `Weather` resource along by the frame's length.
+pub fn advance_weather(frame: Res<FrameTime>, mut weather: ResMut<Weather>) {
+    weather.update(frame.0);
+}